@@ -0,0 +1,136 @@
+use std::{ io::Write, path::{ Path, PathBuf } };
+
+use crate::{ checksum::sha256_file, filesystem::RealFilesystem, trash_file };
+
+/// The checksum file written inside each archive, in `sha256sum` format.
+pub const CHECKSUM_FILE: &str = "SHA256SUMS";
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Zip,
+    Tar,
+}
+
+impl ArchiveFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ArchiveFormat::Zip => "zip",
+            ArchiveFormat::Tar => "tar",
+        }
+    }
+}
+
+/// Returns the path of the archive created for a directory, e.g. `0_1944-1952.zip` next to `0_1944-1952/`.
+pub fn archive_path(directory: &Path, format: ArchiveFormat) -> PathBuf {
+    let name = directory.file_name().expect("Failed to get directory name.").to_string_lossy();
+    directory.with_file_name(format!("{}.{}", name, format.extension()))
+}
+
+/// Packs the files of a directory into an archive next to it, under a top-level folder of the same name.
+/// A `SHA256SUMS` file listing every packed file is added so the transfer can be verified after extraction.
+pub fn archive_directory(directory: &Path, format: ArchiveFormat) -> PathBuf {
+    let mut files = std::fs
+        ::read_dir(directory)
+        .expect("Failed to read group directory.")
+        .map(|entry| entry.expect("Failed to read directory entry.").path())
+        .filter(|path| path.is_file())
+        .collect::<Vec<_>>();
+    files.sort();
+
+    let checksums = files
+        .iter()
+        .map(|file| {
+            let (sha256, _) = sha256_file(&RealFilesystem, file);
+            let name = file.file_name().expect("Failed to get file name.").to_string_lossy();
+            format!("{}  {}\n", sha256, name)
+        })
+        .collect::<String>();
+
+    let target = archive_path(directory, format);
+    if target.exists() {
+        trash_file(&RealFilesystem, &target);
+    }
+    let root = directory.file_name().expect("Failed to get directory name.").to_string_lossy();
+    let output = std::fs::File::create(&target).expect("Failed to create archive file.");
+    match format {
+        ArchiveFormat::Zip => {
+            let mut zip = zip::ZipWriter::new(output);
+            let options = zip::write::SimpleFileOptions
+                ::default()
+                .compression_method(zip::CompressionMethod::Deflated)
+                .large_file(true);
+            for file in &files {
+                let name = file.file_name().expect("Failed to get file name.").to_string_lossy();
+                zip.start_file(format!("{}/{}", root, name), options).expect("Failed to add file to archive.");
+                let mut input = std::fs::File::open(file).expect("Failed to open file.");
+                std::io::copy(&mut input, &mut zip).expect("Failed to write file to archive.");
+            }
+            zip.start_file(format!("{}/{}", root, CHECKSUM_FILE), options).expect(
+                "Failed to add checksums to archive."
+            );
+            zip.write_all(checksums.as_bytes()).expect("Failed to write checksums to archive.");
+            zip.finish().expect("Failed to finish archive.");
+        }
+        ArchiveFormat::Tar => {
+            let mut tar = tar::Builder::new(output);
+            for file in &files {
+                let name = file.file_name().expect("Failed to get file name.").to_string_lossy();
+                tar.append_path_with_name(file, format!("{}/{}", root, name)).expect(
+                    "Failed to add file to archive."
+                );
+            }
+            let mut header = tar::Header::new_gnu();
+            header.set_size(checksums.len() as u64);
+            header.set_mode(0o644);
+            header.set_mtime(chrono::Utc::now().timestamp() as u64);
+            header.set_cksum();
+            tar.append_data(&mut header, format!("{}/{}", root, CHECKSUM_FILE), checksums.as_bytes()).expect(
+                "Failed to add checksums to archive."
+            );
+            tar.finish().expect("Failed to finish archive.");
+        }
+    }
+    target
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Read;
+    use super::*;
+
+    #[test]
+    fn archive_with_checksums() {
+        let dir = crate::test_util::TestDir::new("archive_group");
+        dir.write("0_1944-1944/Arizona_Sun_1944-05-12.pdf", "abc");
+        let group = dir.join("0_1944-1944");
+        let sums = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad  Arizona_Sun_1944-05-12.pdf\n";
+
+        let zip = archive_directory(&group, ArchiveFormat::Zip);
+        assert_eq!(zip, dir.join("0_1944-1944.zip"));
+        let mut zip = zip::ZipArchive::new(std::fs::File::open(zip).unwrap()).unwrap();
+        assert_eq!(zip.file_names().collect::<std::collections::BTreeSet<_>>(), [
+            "0_1944-1944/Arizona_Sun_1944-05-12.pdf",
+            "0_1944-1944/SHA256SUMS",
+        ].into());
+        let mut checksums = String::new();
+        zip.by_name("0_1944-1944/SHA256SUMS").unwrap().read_to_string(&mut checksums).unwrap();
+        assert_eq!(checksums, sums);
+
+        let tar = archive_directory(&group, ArchiveFormat::Tar);
+        let mut tar = tar::Archive::new(std::fs::File::open(tar).unwrap());
+        let entries = tar
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                let mut entry = entry.unwrap();
+                let mut contents = String::new();
+                entry.read_to_string(&mut contents).unwrap();
+                (entry.path().unwrap().to_string_lossy().to_string(), contents)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(entries, [
+            ("0_1944-1944/Arizona_Sun_1944-05-12.pdf".to_string(), "abc".to_string()),
+            ("0_1944-1944/SHA256SUMS".to_string(), sums.to_string()),
+        ]);
+    }
+}
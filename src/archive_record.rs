@@ -1,5 +1,8 @@
+use std::collections::BTreeMap;
 use serde::{ Serialize, Deserialize };
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// A row of a target CSV file. Missing columns are read as empty, so projected CSVs (see [`ColumnOptions`]) can be read back.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ArchiveRecord {
     #[serde(rename = "NODE_TITLE")]
     pub node_title: String,
@@ -168,4 +171,122 @@ pub struct ArchiveRecord {
 
     #[serde(rename = "Admin Notes")]
     pub admin_notes: String,
+
+    /// Any other (site-specific) columns, carried through unchanged.
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, String>,
+}
+
+/// Which columns of a target CSV file are written to the output CSV.
+#[derive(clap::Args, Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct ColumnOptions {
+    /// Write only these columns, in this order, e.g. `--keep-columns NODE_TITLE "Part Of" Volume Issue`.
+    #[arg(long, num_args = 1.., conflicts_with = "drop_columns")]
+    pub keep_columns: Vec<String>,
+
+    /// Write every column except these.
+    #[arg(long, num_args = 1..)]
+    pub drop_columns: Vec<String>,
+}
+
+impl ColumnOptions {
+    /// Returns the output headers for a CSV file with `headers`. Panics on a column the file does not have.
+    pub fn project(&self, headers: &csv::StringRecord) -> csv::StringRecord {
+        for column in self.keep_columns.iter().chain(&self.drop_columns) {
+            if !headers.iter().any(|header| header == column) {
+                panic!("Column \"{}\" not found in the target CSV file.", column);
+            }
+        }
+        if !self.keep_columns.is_empty() {
+            return self.keep_columns.iter().collect();
+        }
+        headers
+            .iter()
+            .filter(|header| !self.drop_columns.iter().any(|column| column == header))
+            .collect()
+    }
+}
+
+impl ArchiveRecord {
+    /// Returns `(column, old, new)` for every column whose value differs from `other`, in column order.
+    pub fn changes(&self, other: &ArchiveRecord) -> Vec<(String, String, String)> {
+        let old = serde_json::to_value(self).expect("Failed to serialize record.");
+        let new = serde_json::to_value(other).expect("Failed to serialize record.");
+        let (Some(old), Some(new)) = (old.as_object(), new.as_object()) else {
+            return vec![];
+        };
+        old.iter()
+            .filter_map(|(column, old_value)| {
+                let old_value = old_value.as_str().unwrap_or_default();
+                let new_value = new.get(column).and_then(|v| v.as_str()).unwrap_or_default();
+                (old_value != new_value).then(|| {
+                    (column.clone(), old_value.to_string(), new_value.to_string())
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the record's values in the order of `headers`, for writing with `csv::Writer::write_record`.
+    /// (The csv crate cannot serialize the flattened `extra` columns itself.)
+    pub fn to_row(&self, headers: &csv::StringRecord) -> csv::StringRecord {
+        let value = serde_json::to_value(self).expect("Failed to serialize record.");
+        headers
+            .iter()
+            .map(|header| value.get(header).and_then(|v| v.as_str()).unwrap_or_default())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn round_trip_extra_columns() {
+        let mut headers = serde_json::to_value(ArchiveRecord::default())
+            .unwrap()
+            .as_object()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>();
+        headers.insert(1, "Local ID".to_string());
+        let headers = csv::StringRecord::from(headers);
+        let row = headers
+            .iter()
+            .map(|header| if header == "Local ID" { "A-17" } else { "" })
+            .collect::<csv::StringRecord>();
+
+        let record: ArchiveRecord = row.deserialize(Some(&headers)).unwrap();
+        assert_eq!(record.extra.get("Local ID").map(String::as_str), Some("A-17"));
+        assert_eq!(record.to_row(&headers), row);
+    }
+
+    #[test]
+    fn project_columns() {
+        let headers = csv::StringRecord::from(vec!["NODE_TITLE", "Volume", "Issue", "Admin Notes"]);
+        let keep = ColumnOptions {
+            keep_columns: vec!["Issue".to_string(), "NODE_TITLE".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(keep.project(&headers), csv::StringRecord::from(vec!["Issue", "NODE_TITLE"]));
+        let drop = ColumnOptions {
+            drop_columns: vec!["Admin Notes".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(drop.project(&headers), csv::StringRecord::from(vec!["NODE_TITLE", "Volume", "Issue"]));
+        assert_eq!(ColumnOptions::default().project(&headers), headers);
+    }
+
+    #[test]
+    fn list_changed_columns() {
+        let record = ArchiveRecord { node_title: "Arizona Sun, 1944-05-12".to_string(), ..Default::default() };
+        let populated = ArchiveRecord { volume: "3".to_string(), issue: "12".to_string(), ..record.clone() };
+        assert_eq!(record.changes(&populated), [
+            ("Volume".to_string(), String::new(), "3".to_string()),
+            ("Issue".to_string(), String::new(), "12".to_string()),
+        ]);
+        assert!(populated.changes(&populated).is_empty());
+    }
 }
@@ -0,0 +1,267 @@
+use std::{
+    collections::BTreeMap,
+    io::Read,
+    path::{ Path, PathBuf },
+    sync::{ atomic::{ AtomicUsize, Ordering }, Mutex },
+};
+use owo_colors::OwoColorize;
+use serde::{ Deserialize, Serialize };
+use sha2::{ Digest, Sha256 };
+
+use crate::{
+    filesystem::{ Filesystem, RealFilesystem },
+    incremental::FileCache,
+    print_status,
+    run_timestamp,
+    safely_target_file,
+    summary,
+    SNAPSHOT_DIR,
+};
+
+/// One row of a checksum manifest. Paths are relative to the manifest's directory and use `/` separators.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub path: String,
+    pub sha256: String,
+    pub bytes: u64,
+}
+
+/// Returns the default number of hashing threads: one per available CPU.
+pub fn default_jobs() -> usize {
+    std::thread::available_parallelism().map_or(1, |jobs| jobs.get())
+}
+
+/// Hashes files on `jobs` threads, returning their entries in the order of `files`.
+/// Each thread reads one file at a time through a 1 MiB buffer, so memory use is bounded by `jobs`, not file size.
+/// Files unchanged since they were cached reuse their cached digest instead of being read.
+fn hash_entries(
+    fs: &dyn Filesystem,
+    directory: &str,
+    files: &[PathBuf],
+    jobs: usize,
+    cache: Option<&FileCache>
+) -> Vec<ManifestEntry> {
+    let next = AtomicUsize::new(0);
+    let entries = Mutex::new((0..files.len()).map(|_| None).collect::<Vec<_>>());
+    std::thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, files.len().max(1)) {
+            scope.spawn(|| {
+                loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(file) = files.get(i) else {
+                        break;
+                    };
+                    let cached = cache.and_then(|cache| cache.get("checksum", file));
+                    let entry = match cached {
+                        Some(sha256) => ManifestEntry {
+                            path: relative_path(directory, file),
+                            sha256: sha256.to_string(),
+                            bytes: fs.len(file).unwrap_or(0),
+                        },
+                        None => hash_entry(fs, directory, file),
+                    };
+                    entries.lock().expect("Failed to lock hashed entries.")[i] = Some(entry);
+                }
+            });
+        }
+    });
+    entries
+        .into_inner()
+        .expect("Failed to collect hashed entries.")
+        .into_iter()
+        .map(|entry| entry.expect("Failed to hash file."))
+        .collect()
+}
+
+/// Hashes every file on `jobs` threads and writes a `path,sha256,bytes` manifest.
+/// With `incremental`, files unchanged since the last incremental run reuse their digest from the directory's cache.
+pub fn write_manifest(directory: &str, files: &[PathBuf], output: &str, jobs: usize, incremental: bool) {
+    let output = safely_target_file(output);
+    let mut writer = csv::Writer::from_path(&output).expect("Failed to create manifest file.");
+    let mut cache = incremental.then(|| FileCache::open(directory));
+    let entries = hash_entries(&RealFilesystem, directory, files, jobs, cache.as_ref());
+    if let Some(cache) = &mut cache {
+        let unchanged = files
+            .iter()
+            .zip(&entries)
+            .filter(|(file, entry)| cache.get("checksum", file) == Some(entry.sha256.as_str()))
+            .count();
+        print_status!("Reused {} unchanged checksums.", unchanged);
+        summary::record_count("unchanged", unchanged);
+        for (file, entry) in files.iter().zip(&entries) {
+            cache.insert("checksum", file, &entry.sha256);
+        }
+        cache.save();
+    }
+    for entry in entries {
+        summary::record_file(entry.bytes);
+        print_status!("{}  {}", entry.sha256.dimmed(), entry.path);
+        writer.serialize(entry).expect("Failed to write manifest entry.");
+    }
+    writer.flush().expect("Failed to write manifest file.");
+    print_status!("Wrote {} checksums to \"{}\"", files.len(), output);
+    summary::record_output(&output);
+}
+
+/// Hashes files before they are moved into `<directory>/.bulk_format/snapshots/<run timestamp>/<name>.csv`, a checksum
+/// manifest that `checksum --verify` can check them against after a bad run. Returns the snapshot's path.
+pub fn snapshot_files(fs: &dyn Filesystem, directory: &str, files: &[PathBuf], name: &str) -> PathBuf {
+    let snapshot_dir = Path::new(directory).join(SNAPSHOT_DIR).join(run_timestamp());
+    fs.create_dir_all(&snapshot_dir).expect("Failed to create snapshot directory.");
+    let output = snapshot_dir.join(format!("{}.csv", name));
+    let mut writer = csv::Writer::from_writer(vec![]);
+    for entry in hash_entries(fs, directory, files, default_jobs(), None) {
+        writer.serialize(entry).expect("Failed to write snapshot entry.");
+    }
+    let contents = writer.into_inner().expect("Failed to write snapshot file.");
+    fs.write(&output, &contents).expect("Failed to write snapshot file.");
+    print_status!("Snapshotted {} files to \"{}\"", files.len(), output.to_string_lossy());
+    summary::record_output(output.to_string_lossy());
+    output
+}
+
+/// Re-hashes the files listed in a manifest on `jobs` threads and reports changed, missing, and new files.
+/// Returns false if anything differs from the manifest.
+pub fn verify_manifest(directory: &str, files: &[PathBuf], manifest: &str, jobs: usize) -> bool {
+    let mut reader = csv::Reader::from_path(manifest).expect("Failed to read manifest file.");
+    let expected = reader
+        .deserialize()
+        .map(|entry| {
+            let entry: ManifestEntry = entry.expect("Failed to parse manifest entry.");
+            (entry.path.clone(), entry)
+        })
+        .collect::<BTreeMap<_, _>>();
+    let found = files
+        .iter()
+        .map(|file| (relative_path(directory, file), file))
+        .collect::<BTreeMap<_, _>>();
+
+    let (mut ok, mut changed, mut missing, mut new) = (0, 0, 0, 0);
+    let listed = expected
+        .keys()
+        .filter_map(|path| found.get(path).map(|file| file.to_path_buf()))
+        .collect::<Vec<_>>();
+    let actual = hash_entries(&RealFilesystem, directory, &listed, jobs, None)
+        .into_iter()
+        .inspect(|entry| summary::record_file(entry.bytes))
+        .map(|entry| (entry.path.clone(), entry))
+        .collect::<BTreeMap<_, _>>();
+    for (path, entry) in &expected {
+        let Some(actual) = actual.get(path) else {
+            print_status!("{} {}", "MISSING".red().bold(), path);
+            missing += 1;
+            continue;
+        };
+        if actual.sha256.eq_ignore_ascii_case(&entry.sha256) {
+            ok += 1;
+        } else {
+            print_status!("{} {}", "CHANGED".red().bold(), path);
+            changed += 1;
+        }
+    }
+    for path in found.keys().filter(|path| !expected.contains_key(*path)) {
+        print_status!("{} {}", "NEW".yellow().bold(), path);
+        new += 1;
+    }
+
+    print_status!(
+        "{} ok, {} changed, {} missing, {} new",
+        ok.green(),
+        changed.red(),
+        missing.red(),
+        new.yellow()
+    );
+    for (key, count) in [("ok", ok), ("changed", changed), ("missing", missing), ("new", new)] {
+        summary::record_count(key, count);
+    }
+    changed == 0 && missing == 0 && new == 0
+}
+
+fn hash_entry(fs: &dyn Filesystem, directory: &str, file: &Path) -> ManifestEntry {
+    let (sha256, bytes) = sha256_file(fs, file);
+    ManifestEntry { path: relative_path(directory, file), sha256, bytes }
+}
+
+/// Returns the hex SHA-256 digest and size of a file, reading it in chunks so large scans are not loaded into memory.
+pub fn sha256_file(fs: &dyn Filesystem, path: &Path) -> (String, u64) {
+    let mut file = fs.open(path).expect("Failed to open file.");
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 1 << 20];
+    let mut bytes = 0;
+    loop {
+        let read = file.read(&mut buffer).expect("Failed to read file.");
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        bytes += read as u64;
+    }
+    (hex::encode(hasher.finalize()), bytes)
+}
+
+pub fn relative_path(directory: &str, file: &Path) -> String {
+    file.strip_prefix(directory)
+        .unwrap_or(file)
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn manifest_paths_are_relative() {
+        assert_eq!(relative_path("scans", Path::new("scans/0_1944-1944/tn1.pdf")), "0_1944-1944/tn1.pdf");
+        assert_eq!(relative_path("scans/", Path::new("scans/tn1.pdf")), "tn1.pdf");
+    }
+
+    #[test]
+    fn hash_on_threads_in_file_order() {
+        use crate::filesystem::MemoryFilesystem;
+
+        let fs = MemoryFilesystem::new();
+        let files = (0..40)
+            .map(|i| {
+                let file = PathBuf::from(format!("scans/tn{}.pdf", i));
+                fs.add_file(&file, i);
+                file
+            })
+            .collect::<Vec<_>>();
+        let hashed = |jobs| {
+            hash_entries(&fs, "scans", &files, jobs, None)
+                .into_iter()
+                .map(|entry| (entry.path, entry.sha256, entry.bytes))
+                .collect::<Vec<_>>()
+        };
+        let serial = hashed(1);
+        assert_eq!(serial[7].0, "tn7.pdf");
+        assert_eq!(serial[7].2, 7);
+        assert_eq!(hashed(8), serial);
+        assert_eq!(hashed(0), serial);
+    }
+
+    #[test]
+    fn snapshot_files_before_moving_them() {
+        use std::io::Read;
+        use crate::filesystem::MemoryFilesystem;
+
+        let fs = MemoryFilesystem::new();
+        fs.create_dir_all(Path::new("scans")).unwrap();
+        fs.write(Path::new("scans/tn1.pdf"), b"abc").unwrap();
+        fs.add_file("scans/tn2.pdf", 2);
+        let files = [PathBuf::from("scans/tn1.pdf"), PathBuf::from("scans/tn2.pdf")];
+        let snapshot = snapshot_files(&fs, "scans", &files, "group-files");
+        assert_eq!(snapshot, Path::new("scans").join(SNAPSHOT_DIR).join(run_timestamp()).join("group-files.csv"));
+        let mut contents = String::new();
+        fs.open(&snapshot).unwrap().read_to_string(&mut contents).unwrap();
+        assert_eq!(
+            contents,
+            "path,sha256,bytes\n\
+             tn1.pdf,ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad,3\n\
+             tn2.pdf,96a296d224f285c67bee93c30f8a309157f0daa35dc5b87e410b78630a09cfc7,2\n"
+        );
+    }
+}
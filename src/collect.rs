@@ -0,0 +1,352 @@
+use std::{ path::{ Path, PathBuf }, time::SystemTime };
+use owo_colors::OwoColorize;
+
+use crate::{
+    checksum,
+    csv_writer,
+    fetch,
+    filesystem::Filesystem,
+    glob_match,
+    messages,
+    parse_size,
+    parse_time,
+    print_status,
+    print_warn,
+    safely_target_file,
+    sample_indices,
+    summary,
+    warnings::Warning,
+    SNAPSHOT_DIR,
+    STDIO,
+    TRASH_DIR,
+};
+
+/// Reads a list of file paths, one per line, from a file or stdin (`-`), in order.
+/// Blank lines and `#` comments are skipped, and paths that are not files or fall outside the `--min-size`,
+/// `--max-size` or `--newer-than` filters are reported and skipped.
+pub fn read_file_list(fs: &dyn Filesystem, path: &str, filters: &FileFilters) -> Vec<PathBuf> {
+    let list = if path == STDIO {
+        std::io::read_to_string(std::io::stdin()).expect("Failed to read file list from stdin.")
+    } else {
+        std::fs::read_to_string(path).expect("Failed to read file list.")
+    };
+    let files = listed_files(fs, &list, filters);
+    print_status!("Found {} listed files.", files.len());
+    files
+}
+
+/// Returns the listed files that exist and pass the size and age filters.
+pub(crate) fn listed_files(fs: &dyn Filesystem, list: &str, filters: &FileFilters) -> Vec<PathBuf> {
+    let mut excluded = 0;
+    let files = list
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(PathBuf::from)
+        .filter(|file| {
+            if !fs.is_file(file) {
+                print_warn!(Warning::SkippedFile, "{}", messages::listed_file_missing(&file.to_string_lossy()));
+                return false;
+            }
+            if filters.too_old(fs, file) {
+                excluded += 1;
+                return false;
+            }
+            if let Some(reason) = filters.size_excludes(fs, file) {
+                print_warn!(Warning::SkippedFile, "{}", messages::skipped_because(&file.to_string_lossy(), &reason));
+                excluded += 1;
+                return false;
+            }
+            true
+        })
+        .collect::<Vec<_>>();
+    if excluded > 0 {
+        print_status!("Excluded {} listed files.", excluded);
+        summary::record_count("excluded", excluded);
+    }
+    files
+}
+
+/// Collects the files of every directory, sorted by file name, warning about file names found in more than one.
+pub fn collect_directories(
+    fs: &dyn Filesystem,
+    directories: &[String],
+    extensions: &[String],
+    recursive: bool,
+    filters: &FileFilters
+) -> Vec<PathBuf> {
+    let mut files = directories
+        .iter()
+        .flat_map(|directory| collect_files(fs, directory, extensions, recursive, filters))
+        .collect::<Vec<_>>();
+    if directories.len() > 1 {
+        files.sort_by(|a, b| a.file_name().cmp(&b.file_name()));
+        for pair in files.windows(2).filter(|pair| pair[0].file_name() == pair[1].file_name()) {
+            print_warn!(
+                Warning::NameCollision,
+                "{}",
+                messages::same_file_name(&pair[0].to_string_lossy(), &pair[1].to_string_lossy())
+            );
+        }
+    }
+    files
+}
+
+/// Which files found in a directory are skipped.
+#[derive(clap::Args, Debug, Default, serde::Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct FileFilters {
+    /// Skip files and directories matching a glob, e.g. `--exclude "*_draft*" --exclude "backup/**"`.
+    /// Patterns without a `/` match names at any depth, and patterns with one match paths relative to the directory.
+    /// `*` matches within a name, `**` across directories, and `?` any one character.
+    #[arg(long)]
+    pub exclude: Vec<String>,
+
+    /// Skip (and report) files smaller than this size, e.g. `1KB` to leave out zero-byte failed scans.
+    #[arg(long, value_parser = parse_size)]
+    #[serde(deserialize_with = "deserialize_size")]
+    pub min_size: Option<u64>,
+
+    /// Skip (and report) files larger than this size, e.g. `2GB` to leave out uncompressed TIFFs.
+    #[arg(long, value_parser = parse_size)]
+    #[serde(deserialize_with = "deserialize_size")]
+    pub max_size: Option<u64>,
+
+    /// Only collect files modified after a local date (`2024-05-12`, `2024-05-12 09:30`) or within a duration
+    /// (`12h`, `7d`, `2w`), e.g. the files scanned since the last weekly batch.
+    #[arg(long, value_parser = parse_time)]
+    #[serde(deserialize_with = "deserialize_time")]
+    pub newer_than: Option<SystemTime>,
+}
+
+impl FileFilters {
+    /// Returns true if a path (relative to the searched directory, with `/` separators) matches an `--exclude` glob.
+    fn excludes(&self, relative: &str) -> bool {
+        let name = relative.rsplit('/').next().unwrap_or(relative);
+        self.exclude.iter().any(|pattern| {
+            if pattern.contains('/') {
+                glob_match(pattern.trim_start_matches("./"), relative)
+            } else {
+                glob_match(pattern, name)
+            }
+        })
+    }
+
+    /// Returns why a file is outside the `--min-size`/`--max-size` bounds, if it is.
+    fn size_excludes(&self, fs: &dyn Filesystem, path: &Path) -> Option<String> {
+        let bytes = fs.len(path).ok()?;
+        match (self.min_size, self.max_size) {
+            (Some(min), _) if bytes < min => {
+                Some(messages::under_min_size(&summary::format_size(bytes), &summary::format_size(min)))
+            }
+            (_, Some(max)) if bytes > max => {
+                Some(messages::over_max_size(&summary::format_size(bytes), &summary::format_size(max)))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns true if a file was last modified before `--newer-than`.
+    fn too_old(&self, fs: &dyn Filesystem, path: &Path) -> bool {
+        let Some(newer_than) = self.newer_than else {
+            return false;
+        };
+        fs.modified(path).is_ok_and(|modified| modified <= newer_than)
+    }
+}
+
+/// Which of the collected files (or read records) a run processes, e.g. a pilot of the first 50.
+#[derive(clap::Args, Debug, Default, Clone, serde::Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Selection {
+    /// Skip this many files (or records) before processing any.
+    #[arg(long, default_value_t = 0)]
+    pub offset: usize,
+
+    /// Process at most this many files (or records), e.g. `--limit 50` for a pilot run of a new lookup table.
+    #[arg(long)]
+    pub limit: Option<usize>,
+
+    /// Process a random sample of this many of the files (or records) in the `--offset`/`--limit` range, e.g.
+    /// `--sample 25` to spot-check a batch. The sample is listed in `--sample-report`.
+    #[arg(long)]
+    pub sample: Option<usize>,
+
+    /// The seed of `--sample`, to draw the same sample again. Defaults to a seed from the clock, printed with the sample.
+    #[arg(long, requires = "sample")]
+    pub seed: Option<u64>,
+
+    /// A path to the `position,item` CSV listing the sampled files (or records). Defaults to `sample_report.csv`.
+    #[arg(long, requires = "sample")]
+    pub sample_report: Option<String>,
+}
+
+impl Selection {
+    /// Returns the items in the `--offset`/`--limit` range, or a `--sample` of them, reporting the selection if it
+    /// leaves any out. `name` names an item in the sample report.
+    pub fn select<T>(&self, items: Vec<T>, noun: &str, name: impl Fn(&T) -> String) -> Vec<T> {
+        let total = items.len();
+        let mut selected = items
+            .into_iter()
+            .skip(self.offset)
+            .take(self.limit.unwrap_or(usize::MAX))
+            .collect::<Vec<_>>();
+        if selected.len() < total {
+            let first = self.offset.min(total);
+            print_status!("Selected {} {}-{} of {}.", noun, first + 1, first + selected.len(), total);
+        }
+        if let Some(n) = self.sample {
+            selected = self.sample(selected, n, noun, name);
+        }
+        if selected.len() < total {
+            summary::record_count("not selected", total - selected.len());
+        }
+        selected
+    }
+
+    /// Returns a random sample of `n` items and writes the sample report.
+    fn sample<T>(&self, items: Vec<T>, n: usize, noun: &str, name: impl Fn(&T) -> String) -> Vec<T> {
+        let seed = self.seed.unwrap_or_else(|| {
+            let since_epoch = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default();
+            since_epoch.as_nanos() as u64
+        });
+        let total = items.len();
+        let indices = sample_indices(total, n, seed);
+        let sample = items
+            .into_iter()
+            .enumerate()
+            .filter(|(i, _)| indices.binary_search(i).is_ok())
+            .collect::<Vec<_>>();
+
+        let output = safely_target_file(self.sample_report.as_deref().unwrap_or("sample_report.csv"));
+        let mut writer = csv_writer(&output);
+        writer.write_record(["position", "item"]).expect("Failed to write sample report header.");
+        for (i, item) in &sample {
+            writer.write_record([(self.offset + i + 1).to_string(), name(item)]).expect("Failed to write sample row.");
+        }
+        writer.flush().expect("Failed to write sample report.");
+        print_status!(
+            "Sampled {} of {} {} with --seed {}. Listed them in \"{}\".",
+            sample.len(),
+            total,
+            noun,
+            seed,
+            output
+        );
+        summary::record_output(&output);
+        summary::record_count("sampled", sample.len());
+        sample.into_iter().map(|(_, item)| item).collect()
+    }
+}
+
+/// Reads a size in a plan file, either a number of bytes or a string like `50GB`.
+fn deserialize_size<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    enum Size {
+        Bytes(u64),
+        Text(String),
+    }
+    match serde::Deserialize::deserialize(deserializer)? {
+        Some(Size::Bytes(bytes)) => Ok(Some(bytes)),
+        Some(Size::Text(size)) => parse_size(&size).map(Some).map_err(serde::de::Error::custom),
+        None => Ok(None),
+    }
+}
+
+/// Reads a point in time in a plan file, either a local date or a duration before now.
+fn deserialize_time<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<SystemTime>, D::Error> {
+    let time: Option<String> = serde::Deserialize::deserialize(deserializer)?;
+    time.map(|time| parse_time(&time)).transpose().map_err(serde::de::Error::custom)
+}
+
+pub fn collect_files(
+    fs: &dyn Filesystem,
+    directory: &str,
+    extensions: &[String],
+    recursive: bool,
+    filters: &FileFilters
+) -> Vec<PathBuf> {
+    let mut files = vec![];
+    let root = Path::new(directory);
+    let excluded = collect_into(fs, root, root, extensions, recursive, filters, &mut files);
+    print_status!("Found {} files.", files.len());
+    if excluded > 0 {
+        print_status!("Excluded {} files and directories.", excluded);
+        summary::record_count("excluded", excluded);
+    }
+
+    files.sort_by(|a, b| a.file_name().cmp(&b.file_name()));
+
+    files
+}
+
+/// Adds the matching files of `directory` (inside the searched `root`) to `files`. Returns the number of files and
+/// directories skipped by `filters`.
+fn collect_into(
+    fs: &dyn Filesystem,
+    root: &Path,
+    directory: &Path,
+    extensions: &[String],
+    recursive: bool,
+    filters: &FileFilters,
+    files: &mut Vec<PathBuf>
+) -> usize {
+    let mut excluded = 0;
+    for path in fs
+        .read_dir(directory)
+        .expect("Failed to read directory. Path does not exist or is not a directory.") {
+        let relative = checksum::relative_path(&root.to_string_lossy(), &path);
+
+        if fs.is_file(&path) {
+            if let Some(ext) = path.extension() {
+                if extensions.contains(&ext.to_string_lossy().to_string()) {
+                    if filters.excludes(&relative) || filters.too_old(fs, &path) {
+                        excluded += 1;
+                    } else if let Some(reason) = filters.size_excludes(fs, &path) {
+                        print_warn!(
+                            Warning::SkippedFile,
+                            "{}",
+                            messages::skipped_because(&path.to_string_lossy(), &reason)
+                        );
+                        excluded += 1;
+                    } else {
+                        files.push(path.to_path_buf());
+                    }
+                }
+            }
+        } else if
+            fs.is_dir(&path) &&
+            recursive &&
+            !path.ends_with(TRASH_DIR) &&
+            !Path::new(SNAPSHOT_DIR).parent().is_some_and(|state_dir| path.ends_with(state_dir)) &&
+            !path.ends_with(fetch::CACHE_DIR)
+        {
+            // `backup/**` excludes the `backup` directory itself.
+            if filters.excludes(&relative) || filters.excludes(&format!("{}/", relative)) {
+                excluded += 1;
+                continue;
+            }
+            excluded += collect_into(fs, root, &path, extensions, recursive, filters, files);
+        }
+    }
+    excluded
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn select_by_offset_and_limit() {
+        let select = |offset: usize, limit: Option<usize>| {
+            let selection = Selection { offset, limit, ..Default::default() };
+            selection.select((1..=5).collect(), "files", |i: &i32| i.to_string())
+        };
+        assert_eq!(select(0, None), [1, 2, 3, 4, 5]);
+        assert_eq!(select(0, Some(2)), [1, 2]);
+        assert_eq!(select(1, Some(3)), [2, 3, 4]);
+        assert_eq!(select(3, Some(10)), [4, 5]);
+        assert!(select(6, None).is_empty());
+    }
+}
@@ -0,0 +1,276 @@
+use std::collections::BTreeMap;
+use owo_colors::OwoColorize;
+use serde::Deserialize;
+
+use crate::{
+    date::Date,
+    issue_data::{ IssueData, DEFAULT_RECORD_TITLE_TEMPLATE },
+    messages,
+    print_status,
+    print_warn,
+    summary,
+    warnings::Warning,
+};
+
+#[derive(clap::Args, Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct CompareOptions {
+    /// The template used to build record titles from the lookup table, e.g. `{title} ({date})`.
+    /// Supported placeholders: `{title}`, `{date}`, `{volume}`, `{issue}`, and `{tn}`.
+    #[arg(long, default_value = DEFAULT_RECORD_TITLE_TEMPLATE)]
+    pub title_template: String,
+
+    /// Match lookup issues to generated names by their full record `title`, or by their publication and issue `date`
+    /// (read from the end of each generated name), for when titles were reworded on the platform.
+    /// Publications are matched ignoring case, punctuation, and spacing.
+    #[arg(long, value_enum, default_value_t = MatchOn::Title)]
+    pub match_on: MatchOn,
+
+    /// Ignore differences when matching record titles to generated names: `case`, `punct` (punctuation), and/or `whitespace` (repeated spaces).
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub normalize: Vec<Normalization>,
+
+    /// The lowest percentage of lookup entries that must match a generated name, e.g. `99.5`.
+    /// Below it, the comparison fails and exits with an error.
+    #[arg(long)]
+    pub min_coverage: Option<f64>,
+}
+
+impl Default for CompareOptions {
+    fn default() -> Self {
+        Self {
+            title_template: DEFAULT_RECORD_TITLE_TEMPLATE.to_string(),
+            match_on: MatchOn::Title,
+            normalize: vec![],
+            min_coverage: None,
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MatchOn {
+    Title,
+    Date,
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Normalization {
+    Case,
+    Punct,
+    Whitespace,
+}
+
+/// Splits a generated name into its publication and the issue date at its end, e.g. `Arizona Sun, 1944-08-06` or
+/// `Arizona Sun 1944-08`.
+fn generated_date(name: &str) -> Option<(&str, Date)> {
+    let (publication, date) = name.trim().rsplit_once([',', ' '])?;
+    let date = Date::try_from(date.trim()).ok()?;
+    Some((publication.trim_end_matches([',', ' ']), date))
+}
+
+/// Returns the key of an issue for `--match-on date`: its publication, loosely normalized, and date.
+fn date_key(publication: &str, date: &Date) -> String {
+    let all = [Normalization::Case, Normalization::Punct, Normalization::Whitespace];
+    format!("{} {}", normalize(publication, &all), date)
+}
+
+/// Applies the normalizations to a title before matching.
+fn normalize(title: &str, normalizations: &[Normalization]) -> String {
+    let mut title = title.to_string();
+    if normalizations.contains(&Normalization::Case) {
+        title = title.to_lowercase();
+    }
+    if normalizations.contains(&Normalization::Punct) {
+        title.retain(|c| c.is_alphanumeric() || c.is_whitespace());
+    }
+    if normalizations.contains(&Normalization::Whitespace) {
+        title = title.split_whitespace().collect::<Vec<_>>().join(" ");
+    }
+    title
+}
+
+pub fn parse_generated_names(generated: &str) -> Vec<String> {
+    let mut names = vec![];
+
+    // assert the lookup is a csv file.
+    assert!(generated.ends_with(".csv"), "Generated table must be a CSV file.");
+
+    let mut reader = csv::Reader::from_path(generated).expect("Failed to read generated table.");
+    for result in reader.records() {
+        let record = result.expect("Failed to parse record.");
+        // Arizona Catering Employees, 1944-05-12
+        let node_title = record.get(0).expect("Failed to get node title.");
+        // // split on comma, extract the date.
+        // let date = node_title.split(", ").last().expect("Failed to split date from node title.");
+        names.push(node_title.to_string());
+    }
+
+    print_status!(
+        "{} {} {}",
+        "Parsed".italic().white(),
+        names.len().bold().white(),
+        "records from generated table.".italic().white()
+    );
+
+    names
+}
+
+/// Returns the percentage of `total` that `matched` is, or 100% of nothing.
+fn coverage(matched: usize, total: usize) -> f64 {
+    if total == 0 { 100.0 } else { ((matched as f64) * 100.0) / (total as f64) }
+}
+
+/// Compares the lookup table to the generated names, printing duplicates, missing entries, and coverage statistics.
+/// Returns false if the coverage is below `--min-coverage`.
+pub fn compare_tables(
+    lookup_table: BTreeMap<String, IssueData>,
+    generated_names: Vec<String>,
+    options: &CompareOptions
+) -> bool {
+    let total = lookup_table.len();
+    // check if any of the dates in the lookup table are the same.
+    {
+        let mut duplicate_dates = vec![];
+        for (tn, issue) in &lookup_table {
+            if
+                !issue.is_undated() &&
+                lookup_table
+                    .values()
+                    .filter(|i| i.date == issue.date)
+                    .count() > 1
+            {
+                duplicate_dates.push((tn, issue.date.clone()));
+            }
+        }
+        summary::record_count("duplicate_dates", duplicate_dates.len());
+        if duplicate_dates.is_empty() {
+            print_status!("{}", "No duplicate dates found.".green().bold());
+        } else {
+            print_status!("{}", "Duplicate dates:".red().bold());
+            for (tn, date) in duplicate_dates {
+                print_status!("{}: {}", tn, date);
+            }
+        }
+    }
+
+    let mut missing = vec![];
+    let mut indexes = vec![];
+    // for each name in the lookup table, check if it exists in the generated names, and if it does not, add it to the missing list.
+    let generated_keys = generated_names
+        .iter()
+        .map(|name| {
+            match options.match_on {
+                MatchOn::Title => Some(normalize(name, &options.normalize)),
+                MatchOn::Date => generated_date(name).map(|(publication, date)| date_key(publication, &date)),
+            }
+        })
+        .collect::<Vec<_>>();
+    // generated names that share a key with another, e.g. the same issue ingested twice.
+    let duplicate_generated = generated_keys
+        .iter()
+        .filter(|key| key.is_some() && generated_keys.iter().filter(|other| other == key).count() > 1)
+        .count();
+    for (tn, issue) in lookup_table {
+        let record_title = issue.record_title_with(&options.title_template);
+        let key = match options.match_on {
+            MatchOn::Title => Some(normalize(&record_title, &options.normalize)),
+            MatchOn::Date => {
+                let publication = issue.title.replace("_", " ");
+                Date::try_from(issue.date.as_str()).ok().map(|date| date_key(&publication, &date))
+            }
+        };
+        // get the index of the generated name.
+        match generated_keys.iter().position(|generated| key.is_some() && *generated == key) {
+            Some(index) => indexes.push(index),
+            None => missing.push((tn, record_title)),
+        }
+    }
+
+    let coverage = coverage(indexes.len(), total);
+    let meets_min_coverage = options.min_coverage.is_none_or(|min_coverage| coverage >= min_coverage);
+
+    // sort the indexes, verify they are sequential.
+    indexes.sort();
+    let mut last = -1;
+    let mut is_sequential = true;
+    for index in indexes.iter() {
+        let index = *index as i32;
+        if index != last + 1 {
+            print_warn!(Warning::Other, "{}", messages::index_not_sequential(index));
+            is_sequential = false;
+        }
+        last = index;
+    }
+    if let (true, Some(first), Some(last)) = (is_sequential, indexes.first(), indexes.last()) {
+        print_status!("{} {}..{}", "Indexes are sequential.".green().bold(), first, last);
+    } else if indexes.is_empty() {
+        print_status!("{}", "No generated names matched.".red().bold());
+    } else {
+        print_status!("{}", "Indexes are not sequential.".red().bold());
+    }
+    print_status!("{} Total verified files.", indexes.len());
+    summary::record_count("verified", indexes.len());
+    summary::record_count("missing", missing.len());
+
+    if missing.is_empty() {
+        print_status!("{}", "No missing entries found.".green().bold());
+    } else {
+        print_status!("{}", "Missing entries:".red().bold());
+        for (tn, title) in &missing {
+            print_status!("{}: {}", tn, title);
+        }
+    }
+
+    print_status!(
+        "Coverage: {:.2}% ({} of {} lookup entries), {} duplicate generated names.",
+        coverage,
+        indexes.len(),
+        total,
+        duplicate_generated
+    );
+    summary::record_count("duplicate_generated", duplicate_generated);
+    if let Some(min_coverage) = options.min_coverage {
+        if meets_min_coverage {
+            print_status!("{}", format!("Coverage meets {}%.", min_coverage).green().bold());
+        } else {
+            print_status!("{}", format!("Coverage is below {}%.", min_coverage).red().bold());
+        }
+    }
+    meets_min_coverage
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn compute_coverage() {
+        assert_eq!(coverage(199, 200), 99.5);
+        assert_eq!(coverage(0, 0), 100.0);
+    }
+
+    #[test]
+    fn normalize_titles() {
+        let all = [Normalization::Case, Normalization::Punct, Normalization::Whitespace];
+        assert_eq!(
+            normalize("Arizona Catering  Employees, 1944-08-06", &all),
+            normalize("ARIZONA CATERING EMPLOYEES 1944-08-06 ", &all)
+        );
+        assert_eq!(normalize("The  Sun", &[Normalization::Whitespace]), "The Sun");
+        assert_eq!(normalize("The Sun", &[]), "The Sun");
+        assert_ne!(normalize("The Sun", &[Normalization::Punct]), normalize("the sun", &[]));
+    }
+
+    #[test]
+    fn parse_generated_dates() {
+        let parse = |name| generated_date(name).map(|(publication, date)| (publication, date.to_string()));
+        assert_eq!(parse("Arizona Sun, 1944-08-06"), Some(("Arizona Sun", "1944-08-06".to_string())));
+        assert_eq!(parse("Arizona Sun 1944-08"), Some(("Arizona Sun", "1944-08".to_string())));
+        assert_eq!(parse("Arizona Sun, n.d."), None);
+        let date = Date::try_from("1944-08-06").unwrap();
+        assert_eq!(date_key("Arizona Sun", &date), date_key("ARIZONA  SUN.", &date));
+        assert_ne!(date_key("Arizona Sun", &date), date_key("Arizona Star", &date));
+    }
+}
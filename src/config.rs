@@ -0,0 +1,250 @@
+use std::{ collections::BTreeMap, sync::OnceLock };
+use serde::Deserialize;
+
+use crate::hooks::Hooks;
+
+/// The config file read from the working directory when `--config` is not given.
+pub const DEFAULT_CONFIG_FILE: &str = "bulk_format.yaml";
+
+/// Site-specific settings, read from a YAML config file.
+///
+/// Example:
+/// ```yaml
+/// months:
+///   Sett: 9
+///   Setembro: 9
+/// two-digit-year-pivot: 30
+/// circa-suffix: _ca
+/// reserved-name-suffix: _file
+/// word-separator: hyphen
+/// bracketed-annotations: strip
+/// tn:
+///   strip-prefix: azu_
+///   strip-leading-zeros: true
+///   pattern: "#*"
+/// derivative-command: magick {input}[0] -thumbnail 400x400 {output}
+/// google-credentials: secrets/cataloging-service-account.json
+/// vocabularies:
+///   Type: [Text]
+///   Language: [English, Spanish]
+/// collection-defaults:
+///   part-of: Arizona Catering Employees
+///   contributing-institution: Arizona State Library, Archives and Public Records
+///   rights-statement: http://rightsstatements.org/vocab/NoC-US/1.0/
+///   collection: Arizona Newspapers
+/// hooks:
+///   after-file: ocrmypdf --skip-text "$BULK_FORMAT_PATH" "$BULK_FORMAT_PATH"
+/// extensions: [pdf]
+/// profiles:
+///   arizona-labor-journal:
+///     extensions: [pdf, tif]
+///     name-template: "{Call Number}_{date}"
+///     vocabularies:
+///       Language: [English]
+///     collection-defaults:
+///       part-of: Arizona Labor Journal
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Config {
+    /// Additional month names, mapped to their month number (1-12).
+    pub months: BTreeMap<String, u32>,
+
+    /// Two-digit years below the pivot are read as 20xx, and the rest as 19xx.
+    pub two_digit_year_pivot: u32,
+
+    /// Appended to approximate (`ca. 1910`) dates, e.g. `1910_ca`.
+    pub circa_suffix: String,
+
+    /// Appended to `--name-template` file names that are reserved device names on Windows (`CON`, `NUL`, `COM1`, ...).
+    pub reserved_name_suffix: String,
+
+    /// How the words of a title are joined in formatted file names.
+    pub word_separator: WordSeparator,
+
+    /// Whether bracketed notes in raw titles, e.g. `[microfilm]`, are stripped or kept.
+    pub bracketed_annotations: AnnotationPolicy,
+
+    /// How tns from the lookup table and from file names are normalized before they are matched.
+    pub tn: TnOptions,
+
+    /// The command run by `derivatives` for each file. See [`crate::derivative`] for its placeholders.
+    pub derivative_command: String,
+
+    /// A path to the Google service account key used for `gsheet:` lookups. Defaults to `GOOGLE_APPLICATION_CREDENTIALS`.
+    pub google_credentials: Option<String>,
+
+    /// The allowed values of target CSV columns, checked by `validate`. Values separated by `;` are checked separately.
+    pub vocabularies: BTreeMap<String, Vec<String>>,
+
+    /// Values that Populate writes into blank fields of every record in the collection.
+    pub collection_defaults: CollectionDefaults,
+
+    /// Shell commands run before and after each run and each formatted file. See [`crate::hooks::Hooks`].
+    pub hooks: Hooks,
+
+    /// The file extensions searched for when `--ext` is not given. Defaults to `pdf`.
+    pub extensions: Vec<String>,
+
+    /// The `--name-template` used by `format` when none is given.
+    pub name_template: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct CollectionDefaults {
+    pub part_of: Option<String>,
+    pub contributing_institution: Option<String>,
+    pub rights_statement: Option<String>,
+    pub collection: Option<String>,
+}
+
+/// tns are always trimmed. See [`crate::issue_data::normalize_tn`].
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct TnOptions {
+    /// Compare tns case-insensitively, so that e.g. `TN12` and `tn12` are the same tn. Off unless set, as tns that
+    /// differ only in case would otherwise merge.
+    pub case_fold: bool,
+
+    /// A prefix removed from tns, e.g. `azu_`.
+    pub strip_prefix: Option<String>,
+
+    /// Remove leading zeros (after the prefix), e.g. `00123` -> `123`.
+    pub strip_leading_zeros: bool,
+
+    /// A pattern every normalized tn should match, where `#` is a digit, `@` a letter, and `*` any characters.
+    /// tns that don't match are reported.
+    pub pattern: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AnnotationPolicy {
+    Strip,
+    Keep,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WordSeparator {
+    /// `Arizona_Catering_Employees_1944-08-06`
+    Underscore,
+    /// `Arizona-Catering-Employees-1944-08-06`
+    Hyphen,
+    /// `ArizonaCateringEmployees_1944-08-06`
+    CamelCase,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            months: BTreeMap::new(),
+            two_digit_year_pivot: 30,
+            circa_suffix: "_ca".to_string(),
+            reserved_name_suffix: "_file".to_string(),
+            word_separator: WordSeparator::Underscore,
+            bracketed_annotations: AnnotationPolicy::Strip,
+            tn: TnOptions::default(),
+            derivative_command: crate::derivative::DEFAULT_COMMAND.to_string(),
+            google_credentials: None,
+            vocabularies: BTreeMap::new(),
+            collection_defaults: CollectionDefaults::default(),
+            hooks: Hooks::default(),
+            extensions: vec![],
+            name_template: None,
+        }
+    }
+}
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// Loads the config file at `path`, or `bulk_format.yaml` if it exists, with the settings of `profile` applied.
+/// Must be called before the config is first read.
+pub fn load_config(path: Option<&str>, profile: Option<&str>) {
+    let config = match path {
+        Some(path) => parse_config(path, profile),
+        None if std::path::Path::new(DEFAULT_CONFIG_FILE).exists() =>
+            parse_config(DEFAULT_CONFIG_FILE, profile),
+        None => {
+            assert!(profile.is_none(), "--profile requires a config file with a \"profiles\" section.");
+            Config::default()
+        }
+    };
+    CONFIG.set(config).expect("Config was already loaded.");
+}
+
+/// Overlays a profile on the config: mappings are merged key by key, and any other value replaces the config's.
+fn merge(config: &mut serde_yaml::Value, profile: serde_yaml::Value) {
+    match (config, profile) {
+        (serde_yaml::Value::Mapping(config), serde_yaml::Value::Mapping(profile)) => {
+            for (key, value) in profile {
+                match config.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        config.insert(key, value);
+                    }
+                }
+            }
+        }
+        (config, profile) => {
+            *config = profile;
+        }
+    }
+}
+
+fn parse_config(path: &str, profile: Option<&str>) -> Config {
+    let file = std::fs::File::open(path).expect("Failed to read config file.");
+    let mut value: serde_yaml::Value = serde_yaml::from_reader(file).expect("Failed to parse config file.");
+    let profiles = value.as_mapping_mut().and_then(|config| config.remove("profiles"));
+    if let Some(profile) = profile {
+        let settings = profiles
+            .as_ref()
+            .and_then(|profiles| profiles.get(profile))
+            .unwrap_or_else(|| panic!("Profile \"{}\" not found in \"{}\".", profile, path));
+        merge(&mut value, settings.clone());
+    }
+    let config: Config = serde_yaml::from_value(value).expect("Failed to parse config file.");
+    for (name, month) in &config.months {
+        assert!((1..=12).contains(month), "Invalid month number for \"{}\": {}", name, month);
+    }
+    assert!(!config.reserved_name_suffix.is_empty(), "The reserved name suffix must not be empty.");
+    assert!(config.two_digit_year_pivot <= 100, "The two-digit year pivot must be at most 100.");
+    config
+}
+
+/// Returns the loaded config, or the defaults if no config was loaded.
+pub fn config() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+/// Returns `extensions`, or the config's `extensions` if none were given, or `pdf`.
+pub fn extensions_or_default(extensions: &[String]) -> Vec<String> {
+    if !extensions.is_empty() {
+        extensions.to_vec()
+    } else if !config().extensions.is_empty() {
+        config().extensions.clone()
+    } else {
+        vec!["pdf".to_string()]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn merge_profile_settings() {
+        let mut config: serde_yaml::Value = serde_yaml
+            ::from_str("circa-suffix: _ca\nextensions: [pdf]\nvocabularies:\n  Type: [Text]\n")
+            .unwrap();
+        let profile = serde_yaml
+            ::from_str("extensions: [pdf, tif]\nvocabularies:\n  Language: [English]\n")
+            .unwrap();
+        merge(&mut config, profile);
+        let config: Config = serde_yaml::from_value(config).unwrap();
+        assert_eq!(config.circa_suffix, "_ca");
+        assert_eq!(config.extensions, ["pdf", "tif"]);
+        assert_eq!(config.vocabularies.keys().collect::<Vec<_>>(), ["Language", "Type"]);
+    }
+}
@@ -0,0 +1,237 @@
+use std::collections::BTreeMap;
+use owo_colors::OwoColorize;
+
+use crate::{
+    archive_record::ArchiveRecord,
+    csv_reader,
+    csv_writer,
+    date::Date,
+    issue_data::IssueData,
+    links,
+    lookup::{ parse_lookup_table, LookupError },
+    messages,
+    print_status,
+    print_warn,
+    safely_target_file,
+    summary,
+    warnings::Warning,
+};
+
+/// Where to read issue dates from: a lookup table, or a target CSV's NODE_TITLEs.
+#[derive(clap::Args, Debug)]
+pub struct DateSource {
+    /// A path (or URL) to a lookup CSV or JSON file to read issue dates from.
+    #[arg(short = 'L', long, required_unless_present = "target", conflicts_with = "target")]
+    pub lookup: Option<String>,
+
+    /// A path to a target CSV file to read issue dates from (the date at the end of each NODE_TITLE).
+    #[arg(short, long)]
+    pub target: Option<String>,
+}
+
+const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+impl DateSource {
+    /// Returns the sorted issue dates of each publication, and the number of issues without a date.
+    pub fn issue_dates(&self) -> Result<(BTreeMap<String, Vec<Date>>, usize), LookupError> {
+        let mut publications: BTreeMap<String, Vec<Date>> = BTreeMap::new();
+        let mut undated = 0;
+        if let Some(lookup) = &self.lookup {
+            for issue in parse_lookup_table(lookup)?.values() {
+                match Date::try_from(issue.date.as_str()) {
+                    Ok(date) => publications.entry(issue.title.replace("_", " ")).or_default().push(date),
+                    Err(_) => {
+                        undated += 1;
+                    }
+                }
+            }
+        } else if let Some(target) = &self.target {
+            for result in csv_reader(target).deserialize() {
+                let record: ArchiveRecord = result.expect("Failed to parse record.");
+                match links::title_parts(&record.node_title) {
+                    Some((publication, date)) => {
+                        publications.entry(publication.to_string()).or_default().push(date);
+                    }
+                    None => {
+                        print_warn!(Warning::UndatedIssue, "{}", messages::no_date_found(&record.node_title));
+                        undated += 1;
+                    }
+                }
+            }
+        }
+        for dates in publications.values_mut() {
+            dates.sort();
+        }
+        Ok((publications, undated))
+    }
+}
+
+/// Counts issues per year from the first to the last year (so empty years are included), with a count per month.
+/// Month index 12 counts issues dated only to the year.
+fn count_by_year(dates: &[Date]) -> BTreeMap<i32, [usize; 13]> {
+    let mut counts = BTreeMap::new();
+    let years = dates.iter().map(|date| date.year);
+    let (Some(first), Some(last)) = (years.clone().min(), years.max()) else {
+        return counts;
+    };
+    for year in first..=last {
+        counts.insert(year, [0; 13]);
+    }
+    for date in dates {
+        let month = date.month.map_or(12, |month| (month.clamp(1, 12) - 1) as usize);
+        if let Some(months) = counts.get_mut(&date.year) {
+            months[month] += 1;
+        }
+    }
+    counts
+}
+
+/// Returns the `Date Range` value spanning a publication's issue dates, e.g. `1944/1952`, or `1944` within one year.
+pub fn date_range<'a>(dates: impl IntoIterator<Item = &'a Date>) -> Option<String> {
+    let years = dates
+        .into_iter()
+        .map(|date| date.year)
+        .collect::<Vec<_>>();
+    let (first, last) = (years.iter().min()?, years.iter().max()?);
+    Some(if first == last { first.to_string() } else { format!("{}/{}", first, last) })
+}
+
+/// Returns the `Date Range` of each publication (e.g. `Arizona Sun`) from the dated issues in a lookup table.
+pub fn publication_date_ranges<'a>(issues: impl IntoIterator<Item = &'a IssueData>) -> BTreeMap<String, String> {
+    let mut publications: BTreeMap<String, Vec<Date>> = BTreeMap::new();
+    for issue in issues {
+        if let Ok(date) = Date::try_from(issue.date.as_str()) {
+            publications.entry(issue.title.replace("_", " ")).or_default().push(date);
+        }
+    }
+    publications
+        .into_iter()
+        .filter_map(|(publication, dates)| Some((publication, date_range(&dates)?)))
+        .collect()
+}
+
+/// Prints the first and last issue dates and the `Date Range` of each publication,
+/// and optionally exports them as a CSV of `publication, first_issue, last_issue, date_range`.
+pub fn date_range_report(source: &DateSource, output: Option<&str>) -> Result<(), LookupError> {
+    let (publications, undated) = source.issue_dates()?;
+
+    let mut rows = vec![];
+    for (publication, dates) in &publications {
+        let (Some(first), Some(last), Some(range)) = (dates.first(), dates.last(), date_range(dates)) else {
+            continue;
+        };
+        print_status!(
+            "{} {} {}",
+            publication.bold(),
+            range,
+            format!("({} to {}, {} issues)", first, last, dates.len()).dimmed()
+        );
+        rows.push([publication.clone(), first.to_string(), last.to_string(), range]);
+    }
+    if undated > 0 {
+        print_warn!(Warning::UndatedIssue, "{}", messages::undated_not_counted(undated));
+    }
+    summary::record_count("publications", rows.len());
+
+    if let Some(output) = output {
+        let output = safely_target_file(output);
+        let mut writer = csv_writer(&output);
+        writer
+            .write_record(["publication", "first_issue", "last_issue", "date_range"])
+            .expect("Failed to write headers.");
+        for row in &rows {
+            writer.write_record(row).expect("Failed to write record.");
+        }
+        writer.flush().expect("Failed to write date range CSV file.");
+        print_status!("Saved {} date ranges to \"{}\".", rows.len(), output);
+        summary::record_output(&output);
+    }
+    Ok(())
+}
+
+/// Prints a per-year (and, with `months`, per-month) issue count table for each publication,
+/// and optionally exports it as a CSV of `publication, year, issues, Jan, ..., Dec`.
+pub fn coverage_report(source: &DateSource, months: bool, output: Option<&str>) -> Result<(), LookupError> {
+    let (publications, undated) = source.issue_dates()?;
+
+    let mut rows = vec![];
+    for (publication, dates) in &publications {
+        let counts = count_by_year(dates);
+        let max = counts.values().map(|months| months.iter().sum::<usize>()).max().unwrap_or_default();
+        let average = (dates.len() as f64) / (counts.len().max(1) as f64);
+        print_status!("{} ({} issues, {} years)", publication.bold(), dates.len(), counts.len());
+        for (year, by_month) in &counts {
+            let total = by_month.iter().sum::<usize>();
+            let bar = "#".repeat((total * 40).div_ceil(max.max(1)));
+            // years with under half the average issue count are likely gaps in the collection.
+            let count = if total == 0 {
+                format!("{:>4}", total).red().to_string()
+            } else if (total as f64) < average / 2.0 {
+                format!("{:>4}", total).yellow().to_string()
+            } else {
+                format!("{:>4}", total)
+            };
+            print_status!("{}", format!("  {} {} {}", year, count, bar.dimmed()).trim_end());
+            if months {
+                let by_month_text = MONTHS
+                    .iter()
+                    .zip(by_month)
+                    .map(|(name, count)| {
+                        let text = format!("{} {}", name, count);
+                        if *count == 0 { text.dimmed().to_string() } else { text }
+                    })
+                    .collect::<Vec<_>>()
+                    .join("  ");
+                print_status!("       {}", by_month_text);
+            }
+            rows.push((publication, *year, total, *by_month));
+        }
+    }
+    if undated > 0 {
+        print_warn!(Warning::UndatedIssue, "{}", messages::undated_not_counted(undated));
+    }
+    summary::record_count("publications", publications.len());
+    summary::record_count("undated", undated);
+
+    if let Some(output) = output {
+        let output = safely_target_file(output);
+        let mut writer = csv_writer(&output);
+        let mut header = vec!["publication", "year", "issues"];
+        header.extend(MONTHS);
+        writer.write_record(&header).expect("Failed to write headers.");
+        for (publication, year, total, by_month) in rows {
+            let mut record = vec![publication.clone(), year.to_string(), total.to_string()];
+            record.extend(by_month[..12].iter().map(|count| count.to_string()));
+            writer.write_record(&record).expect("Failed to write record.");
+        }
+        writer.flush().expect("Failed to write coverage CSV file.");
+        print_status!("Saved coverage to \"{}\".", output);
+        summary::record_output(&output);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn count_issues_by_year() {
+        let dates = ["1944-08-06", "1944-09-21", "1944", "1947-01"].map(|date| Date::try_from(date).unwrap());
+        let counts = count_by_year(&dates);
+        assert_eq!(counts.keys().copied().collect::<Vec<_>>(), vec![1944, 1945, 1946, 1947]);
+        assert_eq!(counts[&1944][7], 1);
+        assert_eq!(counts[&1944][8], 1);
+        assert_eq!(counts[&1944][12], 1);
+        assert_eq!(counts[&1945].iter().sum::<usize>(), 0);
+        assert_eq!(counts[&1947][0], 1);
+    }
+
+    #[test]
+    fn compute_date_ranges() {
+        let dates = ["1952-07-11", "1944-08-06", "1944_ca"].map(|date| Date::try_from(date).unwrap());
+        assert_eq!(date_range(&dates), Some("1944/1952".to_string()));
+        assert_eq!(date_range(&dates[1..]), Some("1944".to_string()));
+        assert_eq!(date_range(&[]), None);
+    }
+}
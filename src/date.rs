@@ -1,10 +1,84 @@
 use std::fmt::Display;
+use chrono::{ Datelike, NaiveDate };
+use crate::config::config;
 
-#[derive(PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq)]
 pub struct Date {
     pub year: i32,
     pub month: Option<i32>,
     pub day: Option<i32>,
+    /// An approximate date, written with the configured circa suffix, e.g. `1910_ca`.
+    pub circa: bool,
+}
+
+impl Date {
+    /// Parses the date at the end of a formatted title or file stem, e.g. `Arizona_Sun_1944-05-12`, `Arizona-Sun-1944-05-12`, or `Arizona_Sun_1910_ca`.
+    pub fn from_formatted_title(title: &str) -> Result<Self, String> {
+        let suffix = &config().circa_suffix;
+        let (title, circa) = match title.strip_suffix(suffix.as_str()) {
+            Some(title) if !suffix.is_empty() => (title, true),
+            _ => (title, false),
+        };
+        // the date is the longest part after an underscore or hyphen that parses, since hyphens also separate its parts.
+        let mut date = title
+            .match_indices(['_', '-'])
+            .find_map(|(i, _)| Date::try_from(&title[i + 1..]).ok())
+            .ok_or_else(|| format!("No date found in \"{}\".", title))?;
+        date.circa = circa;
+        Ok(date)
+    }
+
+    /// Returns the first day of the date, e.g. `1944-05` -> `1944-05-01`.
+    pub fn first_day(&self) -> NaiveDate {
+        NaiveDate::from_ymd_opt(self.year, self.month.unwrap_or(1) as u32, self.day.unwrap_or(1) as u32).expect(
+            "Invalid date."
+        )
+    }
+
+    /// Returns the last day of the date, e.g. `1944-02` -> `1944-02-29`.
+    pub fn last_day(&self) -> NaiveDate {
+        match (self.month, self.day) {
+            (Some(_), Some(_)) => self.first_day(),
+            (Some(month), None) => {
+                let (year, month) = if month == 12 { (self.year + 1, 1) } else { (self.year, month + 1) };
+                NaiveDate::from_ymd_opt(year, month as u32, 1).and_then(|date| date.pred_opt()).expect("Invalid date.")
+            }
+            _ => NaiveDate::from_ymd_opt(self.year, 12, 31).expect("Invalid date."),
+        }
+    }
+
+    /// Checks the month is 1-12 and the day exists in that month (including February 29 in leap years).
+    fn validate(&self) -> Result<(), String> {
+        if let Some(month) = self.month {
+            if !(1..=12).contains(&month) {
+                return Err(format!("Invalid month: {}", month));
+            }
+        }
+        if let (Some(month), Some(day)) = (self.month, self.day) {
+            if day < 1 || NaiveDate::from_ymd_opt(self.year, month as u32, day as u32).is_none() {
+                return Err(format!("Invalid day: {}", day));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl From<NaiveDate> for Date {
+    fn from(date: NaiveDate) -> Self {
+        Date { year: date.year(), month: Some(date.month() as i32), day: Some(date.day() as i32), circa: false }
+    }
+}
+
+impl TryFrom<&Date> for NaiveDate {
+    type Error = String;
+
+    /// Only full dates convert; see [`Date::first_day`] for partial dates.
+    fn try_from(date: &Date) -> Result<Self, Self::Error> {
+        let (Some(month), Some(day)) = (date.month, date.day) else {
+            return Err(format!("\"{}\" is not a full date.", date));
+        };
+        NaiveDate::from_ymd_opt(date.year, month as u32, day as u32).ok_or_else(|| "Invalid date".to_string())
+    }
 }
 
 impl Ord for Date {
@@ -12,7 +86,8 @@ impl Ord for Date {
         let year_cmp = self.year.cmp(&other.year);
         let month_cmp = self.month.cmp(&other.month);
         let day_cmp = self.day.cmp(&other.day);
-        year_cmp.then(month_cmp).then(day_cmp)
+        let circa_cmp = self.circa.cmp(&other.circa);
+        year_cmp.then(month_cmp).then(day_cmp).then(circa_cmp)
     }
 }
 
@@ -22,31 +97,20 @@ impl PartialOrd for Date {
     }
 }
 
-#[cfg(test)]
-mod test {
-    use super::*;
-
-    #[test]
-    fn cmp_dates() {
-        let date1 = Date { year: 2020, month: Some(1), day: Some(1) };
-        let date2 = Date { year: 2020, month: Some(1), day: Some(2) };
-        let date3 = Date { year: 2020, month: Some(2), day: Some(1) };
-        let date4 = Date { year: 2021, month: Some(1), day: Some(1) };
-        assert!(date1 < date2);
-        assert!(date2 < date3);
-        assert!(date3 < date4);
-    }
-}
-
 impl Display for Date {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Date { year, month: None, day: None } => write!(f, "{}", year),
-            Date { year, month: Some(month), day: None } => write!(f, "{}-{}", year, month),
-            Date { year, month: Some(month), day: Some(day) } =>
-                write!(f, "{}-{}-{}", year, month, day),
+            Date { year, month: None, day: None, .. } => write!(f, "{}", year)?,
+            Date { year, month: Some(month), day: None, .. } =>
+                write!(f, "{}-{:02}", year, month)?,
+            Date { year, month: Some(month), day: Some(day), .. } =>
+                write!(f, "{}-{:02}-{:02}", year, month, day)?,
             _ => panic!("Invalid date. Has a year and day, but no month"),
         }
+        if self.circa {
+            write!(f, "{}", config().circa_suffix)?;
+        }
+        Ok(())
     }
 }
 
@@ -54,29 +118,92 @@ impl TryFrom<&str> for Date {
     type Error = String;
 
     fn try_from(string: &str) -> Result<Self, Self::Error> {
+        let suffix = &config().circa_suffix;
+        let (string, circa) = match string.strip_suffix(suffix.as_str()) {
+            Some(string) if !suffix.is_empty() => (string, true),
+            _ => (string, false),
+        };
         let parts = string.split('-').collect::<Vec<&str>>();
-        match parts.len() {
+        let date = match parts.len() {
             1 =>
-                Ok(Date {
+                Date {
                     year: parts[0].parse().map_err(|_| "Invalid year")?,
                     month: None,
                     day: None,
-                }),
+                    circa,
+                },
             2 => {
-                Ok(Date {
+                Date {
                     year: parts[0].parse().map_err(|_| "Invalid year")?,
                     month: Some(parts[1].parse().map_err(|_| "Invalid month")?),
                     day: None,
-                })
+                    circa,
+                }
             }
             3 => {
-                Ok(Date {
+                Date {
                     year: parts[0].parse().map_err(|_| "Invalid year")?,
                     month: Some(parts[1].parse().map_err(|_| "Invalid month")?),
                     day: Some(parts[2].parse().map_err(|_| "Invalid day")?),
-                })
+                    circa,
+                }
             }
-            _ => Err("Invalid date".to_string()),
-        }
+            _ => {
+                return Err("Invalid date".to_string());
+            }
+        };
+        date.validate()?;
+        Ok(date)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn cmp_dates() {
+        let date1 = Date { year: 2020, month: Some(1), day: Some(1), circa: false };
+        let date2 = Date { year: 2020, month: Some(1), day: Some(2), circa: false };
+        let date3 = Date { year: 2020, month: Some(2), day: Some(1), circa: false };
+        let date4 = Date { year: 2021, month: Some(1), day: Some(1), circa: false };
+        assert!(date1 < date2);
+        assert!(date2 < date3);
+        assert!(date3 < date4);
+    }
+
+    #[test]
+    fn parse_circa_dates() {
+        let date = Date::from_formatted_title("Arizona_Sun_1910_ca").unwrap();
+        assert_eq!(date, Date { year: 1910, month: None, day: None, circa: true });
+        assert_eq!(date.to_string(), "1910_ca");
+        let date = Date::from_formatted_title("Arizona_Sun_1944-05-12").unwrap();
+        assert!(!date.circa);
+        assert_eq!(date.to_string(), "1944-05-12");
+        assert!(Date::try_from("1910").unwrap() < Date::try_from("1910_ca").unwrap());
+        let date = Date::from_formatted_title("Arizona-Sun-1944-05").unwrap();
+        assert_eq!(date.to_string(), "1944-05");
+        assert!(Date::from_formatted_title("Arizona_Sun").is_err());
+        let date = Date::from_formatted_title("Local-226-1944").unwrap();
+        assert_eq!(date.to_string(), "1944");
+    }
+
+    #[test]
+    fn validate_calendar_dates() {
+        assert!(Date::try_from("1944-13").is_err());
+        assert!(Date::try_from("1944-00-10").is_err());
+        assert!(Date::try_from("1944-05-32").is_err());
+        assert!(Date::try_from("1943-02-29").is_err());
+        assert!(Date::try_from("1944-02-29").is_ok());
+        assert!(Date::try_from("1900-02-29").is_err());
+
+        let naive = NaiveDate::from_ymd_opt(1944, 2, 29).unwrap();
+        let date = Date::from(naive);
+        assert_eq!(date.to_string(), "1944-02-29");
+        assert_eq!(NaiveDate::try_from(&date), Ok(naive));
+        assert!(NaiveDate::try_from(&Date::try_from("1944-02").unwrap()).is_err());
+        assert_eq!(Date::try_from("1944-02").unwrap().first_day(), NaiveDate::from_ymd_opt(1944, 2, 1).unwrap());
+        assert_eq!(Date::try_from("1944-02").unwrap().last_day(), NaiveDate::from_ymd_opt(1944, 2, 29).unwrap());
+        assert_eq!(Date::try_from("1944-12").unwrap().last_day(), NaiveDate::from_ymd_opt(1944, 12, 31).unwrap());
     }
 }
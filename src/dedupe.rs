@@ -0,0 +1,116 @@
+use std::collections::{ BTreeMap, BTreeSet };
+use owo_colors::OwoColorize;
+
+use crate::{
+    csv_reader,
+    csv_writer,
+    derived_path,
+    messages,
+    print_status,
+    print_warn,
+    safely_target_file,
+    summary,
+    warnings::Warning,
+};
+
+/// Splits row indexes into the rows to keep and the duplicates to set aside for review.
+/// Of the rows sharing a key, the one with the most non-empty values (`completeness`) is kept, the first on a tie,
+/// in the position of the key's first row. Rows with an empty key are always kept.
+fn pick_rows(keys: &[&str], completeness: &[usize]) -> (Vec<usize>, Vec<usize>) {
+    let mut best: BTreeMap<&str, usize> = BTreeMap::new();
+    for (i, key) in keys.iter().enumerate().filter(|(_, key)| !key.is_empty()) {
+        let best = best.entry(key).or_insert(i);
+        if completeness[i] > completeness[*best] {
+            *best = i;
+        }
+    }
+
+    let mut keep = vec![];
+    let mut losers = vec![];
+    let mut seen = BTreeSet::new();
+    for (i, key) in keys.iter().enumerate() {
+        match best.get(key) {
+            None => keep.push(i),
+            Some(&winner) => {
+                // the winner takes the place of the key's first row.
+                if seen.insert(key) {
+                    keep.push(winner);
+                }
+                if i != winner {
+                    losers.push(i);
+                }
+            }
+        }
+    }
+    (keep, losers)
+}
+
+/// Writes `<target>_deduped.csv` with one row per NODE_TITLE, and the dropped duplicates to `review`
+/// (default `<target>_duplicates.csv`). Returns the path of the deduped CSV.
+pub fn dedupe_rows(target: &str, review: Option<&str>) -> String {
+    let mut reader = csv_reader(target);
+    let headers = reader.headers().expect("Failed to read target CSV headers.").clone();
+    let key_column = headers
+        .iter()
+        .position(|header| header == "NODE_TITLE")
+        .expect("Column \"NODE_TITLE\" not found in the target CSV file.");
+    let rows = reader
+        .records()
+        .map(|result| result.expect("Failed to parse record."))
+        .collect::<Vec<_>>();
+
+    let keys = rows
+        .iter()
+        .map(|row| row.get(key_column).unwrap_or_default().trim())
+        .collect::<Vec<_>>();
+    let completeness = rows
+        .iter()
+        .map(|row| row.iter().filter(|value| !value.trim().is_empty()).count())
+        .collect::<Vec<_>>();
+    let (keep, losers) = pick_rows(&keys, &completeness);
+
+    for i in &losers {
+        print_warn!(Warning::Duplicate, "{}", messages::duplicate_set_aside(keys[*i], i + 2, completeness[*i]));
+    }
+
+    let output = safely_target_file(&derived_path(target, "_deduped", "csv"));
+    let mut writer = csv_writer(&output);
+    writer.write_record(&headers).expect("Failed to write headers.");
+    for i in &keep {
+        writer.write_record(&rows[*i]).expect("Failed to write record.");
+    }
+    writer.flush().expect("Failed to write deduped CSV file.");
+    summary::record_output(&output);
+    summary::record_count("kept", keep.len());
+    summary::record_count("duplicates", losers.len());
+
+    if !losers.is_empty() {
+        let review = safely_target_file(
+            &review.map_or_else(|| derived_path(target, "_duplicates", "csv"), str::to_string)
+        );
+        let mut writer = csv_writer(&review);
+        writer.write_record(&headers).expect("Failed to write headers.");
+        for i in &losers {
+            writer.write_record(&rows[*i]).expect("Failed to write record.");
+        }
+        writer.flush().expect("Failed to write duplicates CSV file.");
+        print_status!("Saved {} duplicate rows to \"{}\" for review.", losers.len(), review);
+        summary::record_output(&review);
+    }
+    print_status!("Kept {} rows and saved to \"{}\".", keep.len(), output);
+    output
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn keep_most_complete_duplicate() {
+        let keys = ["a", "b", "a", "", "b", ""];
+        let completeness = [3, 5, 4, 1, 5, 1];
+        let (keep, losers) = pick_rows(&keys, &completeness);
+        assert_eq!(keep, vec![2, 1, 3, 5]);
+        assert_eq!(losers, vec![0, 4]);
+    }
+}
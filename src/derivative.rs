@@ -0,0 +1,129 @@
+use std::{ path::{ Path, PathBuf }, process::Command };
+use owo_colors::OwoColorize;
+
+use crate::{ config::config, messages, preview, print_status, print_warn, summary, warnings::Warning };
+
+/// Renders the first page of each file as a 1024px access JPEG with poppler.
+pub const DEFAULT_COMMAND: &str = "pdftoppm -jpeg -singlefile -scale-to 1024 {input} {output_stem}";
+
+#[derive(clap::Args, Debug)]
+pub struct DerivativeOptions {
+    /// The command to run for each file. `{input}` is replaced with the master file, `{output}` with the derivative file,
+    /// and `{output_stem}` with the derivative file without its extension (for tools such as pdftoppm that add it themselves).
+    /// Defaults to `derivative-command` from the config file, or pdftoppm.
+    #[arg(long)]
+    pub command: Option<String>,
+
+    /// Appended to each master's formatted title to name its derivative.
+    #[arg(long, default_value = "_access")]
+    pub suffix: String,
+
+    /// The extension of the derivative files the command produces.
+    #[arg(long = "derivative-ext", default_value = "jpg")]
+    pub derivative_ext: String,
+
+    /// The directory to write derivatives to. Defaults to the directory of each master file.
+    #[arg(short, long)]
+    pub output: Option<String>,
+
+    /// Replace derivatives that already exist instead of skipping them.
+    #[arg(long)]
+    pub overwrite: bool,
+
+    /// Print the commands without running them.
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+/// Runs the derivative command for each formatted master file, e.g. `Title_1944-08-06.pdf -> Title_1944-08-06_access.jpg`.
+pub fn create_derivatives(files: Vec<PathBuf>, options: &DerivativeOptions) {
+    let template = options.command.as_deref().unwrap_or(&config().derivative_command);
+    if let Some(output) = &options.output {
+        if !options.dry_run {
+            std::fs::create_dir_all(output).expect("Failed to create output directory.");
+        }
+    }
+
+    let mut failed = 0;
+    for file in files {
+        // never derive from a derivative made by an earlier run.
+        if file.file_stem().is_some_and(|stem| stem.to_string_lossy().ends_with(&options.suffix)) {
+            continue;
+        }
+        let target = derivative_path(&file, options);
+        if target.exists() && !options.overwrite {
+            preview::print_unchanged(&file.to_string_lossy(), "derivative already exists");
+            continue;
+        }
+
+        let args = command_args(template, &file, &target);
+        let Some((program, args)) = args.split_first() else {
+            panic!("The derivative command is empty.");
+        };
+        if options.dry_run {
+            preview::print_rename(&file.to_string_lossy(), &target.to_string_lossy());
+            print_status!("    {} {}", program, args.join(" "));
+            continue;
+        }
+
+        print_status!("Creating derivative \"{}\"", target.to_string_lossy());
+        let status = Command::new(program)
+            .args(args)
+            .status()
+            .unwrap_or_else(|e| panic!("Failed to run \"{}\": {}", program, e));
+        if !status.success() || !target.exists() {
+            print_warn!(
+                Warning::Failure,
+                "{}",
+                messages::derivative_failed(&file.to_string_lossy(), &status.to_string())
+            );
+            failed += 1;
+        } else {
+            summary::record_output(target.to_string_lossy());
+            summary::record_file(std::fs::metadata(&file).map_or(0, |metadata| metadata.len()));
+        }
+    }
+    if failed > 0 {
+        print_warn!(Warning::Failure, "{}", messages::derivatives_failed(failed));
+    }
+}
+
+/// Returns the derivative path of a master file: its stem plus the suffix, in the output directory if one is given.
+fn derivative_path(file: &Path, options: &DerivativeOptions) -> PathBuf {
+    let stem = file.file_stem().expect("Failed to get file name.").to_string_lossy();
+    let name = format!("{}{}.{}", stem, options.suffix, options.derivative_ext);
+    match &options.output {
+        Some(output) => Path::new(output).join(name),
+        None => file.with_file_name(name),
+    }
+}
+
+/// Splits the command template on whitespace and fills in the placeholders of each argument, so paths with spaces stay one argument.
+fn command_args(template: &str, input: &Path, output: &Path) -> Vec<String> {
+    let output_stem = output.with_extension("");
+    template
+        .split_whitespace()
+        .map(|arg| {
+            arg.replace("{input}", &input.to_string_lossy())
+                .replace("{output_stem}", &output_stem.to_string_lossy())
+                .replace("{output}", &output.to_string_lossy())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn fill_command_placeholders() {
+        let args = command_args(
+            DEFAULT_COMMAND,
+            Path::new("scans/Arizona Sun_1944-08-06.pdf"),
+            Path::new("scans/Arizona Sun_1944-08-06_access.jpg")
+        );
+        assert_eq!(args.first().map(String::as_str), Some("pdftoppm"));
+        assert_eq!(args[args.len() - 2], "scans/Arizona Sun_1944-08-06.pdf");
+        assert_eq!(args[args.len() - 1], "scans/Arizona Sun_1944-08-06_access");
+    }
+}
@@ -0,0 +1,162 @@
+use std::collections::{ BTreeMap, BTreeSet };
+use owo_colors::OwoColorize;
+
+use crate::{ csv_reader, messages, preview, print_status, print_warn, summary, warnings::Warning };
+
+/// A CSV file's headers and rows, with each row's values by column.
+struct Table {
+    headers: Vec<String>,
+    rows: Vec<(String, BTreeMap<String, String>)>,
+}
+
+/// How a row differs between two CSV files.
+#[derive(Debug, PartialEq, Eq)]
+enum RowChange {
+    Added(String),
+    Removed(String),
+    /// `(column, old, new)` for each changed value.
+    Changed(String, Vec<(String, String, String)>),
+}
+
+/// Reads a CSV file keyed by `key_column`. Rows with a key already seen are skipped with a warning.
+fn read_table(path: &str, key_column: &str) -> Table {
+    let mut reader = csv_reader(path);
+    let headers = reader
+        .headers()
+        .expect("Failed to read CSV headers.")
+        .iter()
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    let key = headers
+        .iter()
+        .position(|header| header == key_column)
+        .unwrap_or_else(|| panic!("Column \"{}\" not found in \"{}\".", key_column, path));
+    let mut rows: Vec<(String, BTreeMap<String, String>)> = vec![];
+    for result in reader.records() {
+        let record = result.expect("Failed to parse record.");
+        let key = record.get(key).unwrap_or_default().to_string();
+        if rows.iter().any(|(existing, _)| *existing == key) {
+            print_warn!(Warning::Duplicate, "{}", messages::duplicate_key(key_column, &key, path));
+            continue;
+        }
+        let values = headers
+            .iter()
+            .cloned()
+            .zip(record.iter().map(str::to_string))
+            .collect();
+        rows.push((key, values));
+    }
+    Table { headers, rows }
+}
+
+/// Compares two tables row by row: changed and removed rows in the old table's order, then added rows.
+/// A column missing from one table reads as empty.
+fn diff_tables(old: &Table, new: &Table) -> Vec<RowChange> {
+    let mut columns = old.headers.clone();
+    columns.extend(new.headers.iter().filter(|header| !old.headers.contains(header)).cloned());
+    let new_rows = new.rows
+        .iter()
+        .map(|(key, values)| (key, values))
+        .collect::<BTreeMap<_, _>>();
+
+    let mut changes = vec![];
+    for (key, old_values) in &old.rows {
+        let Some(new_values) = new_rows.get(key) else {
+            changes.push(RowChange::Removed(key.clone()));
+            continue;
+        };
+        let value = |values: &BTreeMap<String, String>, column: &str| values.get(column).cloned().unwrap_or_default();
+        let changed = columns
+            .iter()
+            .map(|column| (column.clone(), value(old_values, column), value(new_values, column)))
+            .filter(|(_, old, new)| old != new)
+            .collect::<Vec<_>>();
+        if !changed.is_empty() {
+            changes.push(RowChange::Changed(key.clone(), changed));
+        }
+    }
+    let old_keys = old.rows
+        .iter()
+        .map(|(key, _)| key)
+        .collect::<BTreeSet<_>>();
+    for (key, _) in new.rows.iter().filter(|(key, _)| !old_keys.contains(key)) {
+        changes.push(RowChange::Added(key.clone()));
+    }
+    changes
+}
+
+/// Prints the added, removed, and changed rows (keyed by NODE_TITLE) and columns between two CSV files.
+/// Returns the number of changed rows.
+pub fn diff_csv(old: &str, new: &str) -> usize {
+    let old_table = read_table(old, "NODE_TITLE");
+    let new_table = read_table(new, "NODE_TITLE");
+    for header in old_table.headers.iter().filter(|header| !new_table.headers.contains(header)) {
+        print_status!("{} {}", "- column".red(), header.red());
+    }
+    for header in new_table.headers.iter().filter(|header| !old_table.headers.contains(header)) {
+        print_status!("{} {}", "+ column".green(), header.green());
+    }
+
+    let changes = diff_tables(&old_table, &new_table);
+    let (mut added, mut removed, mut changed) = (0, 0, 0);
+    for change in &changes {
+        match change {
+            RowChange::Added(key) => {
+                added += 1;
+                print_status!("{} {}", "+".green(), key.green());
+            }
+            RowChange::Removed(key) => {
+                removed += 1;
+                print_status!("{} {}", "-".red(), key.red());
+            }
+            RowChange::Changed(key, values) => {
+                changed += 1;
+                print_status!("{} {}", "~".yellow(), key);
+                for (column, old, new) in values {
+                    preview::print_field_change(column, old, new);
+                }
+            }
+        }
+    }
+    summary::record_count("added", added);
+    summary::record_count("removed", removed);
+    summary::record_count("changed", changed);
+    print_status!(
+        "{}",
+        format!("{} added, {} removed, {} changed rows.", added, removed, changed).bold()
+    );
+    changes.len()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn table(headers: &[&str], rows: &[&[&str]]) -> Table {
+        let headers = headers.iter().map(|header| header.to_string()).collect::<Vec<_>>();
+        let rows = rows
+            .iter()
+            .map(|row| {
+                let values = headers
+                    .iter()
+                    .cloned()
+                    .zip(row.iter().map(|value| value.to_string()))
+                    .collect();
+                (row[0].to_string(), values)
+            })
+            .collect();
+        Table { headers, rows }
+    }
+
+    #[test]
+    fn diff_rows_by_key() {
+        let old = table(&["NODE_TITLE", "Volume"], &[&["a", "1"], &["b", ""], &["c", "3"]]);
+        let new = table(&["NODE_TITLE", "Volume", "Issue"], &[&["b", "2", ""], &["a", "1", "4"], &["d", "", ""]]);
+        assert_eq!(diff_tables(&old, &new), vec![
+            RowChange::Changed("a".to_string(), vec![("Issue".to_string(), String::new(), "4".to_string())]),
+            RowChange::Changed("b".to_string(), vec![("Volume".to_string(), String::new(), "2".to_string())]),
+            RowChange::Removed("c".to_string()),
+            RowChange::Added("d".to_string())
+        ]);
+    }
+}
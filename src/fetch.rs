@@ -0,0 +1,112 @@
+use std::path::{ Path, PathBuf };
+use owo_colors::OwoColorize;
+use sha2::{ Digest, Sha256 };
+
+use crate::{ gsheet, messages, print_status, print_warn, print_warn_ok, warnings::Warning };
+
+/// The directory, in the working directory, that holds downloaded lookup tables and their ETags.
+pub const CACHE_DIR: &str = ".bulk_format_cache";
+
+/// Returns a local path for a lookup table. `http(s)://` URLs are downloaded into the cache, and revalidated with their
+/// ETag on later runs so an unchanged table is not downloaded again. `gsheet:` lookups are downloaded on every run.
+/// Other paths are returned as is.
+pub fn resolve_lookup(lookup: &str) -> String {
+    if let Some(sheet) = lookup.strip_prefix(gsheet::PREFIX) {
+        std::fs::create_dir_all(CACHE_DIR).expect("Failed to create cache directory.");
+        let cached = gsheet
+            ::download_sheet(sheet, Path::new(CACHE_DIR))
+            .unwrap_or_else(|e| panic!("Failed to fetch Google Sheet \"{}\": {}", sheet, e));
+        print_status!("Downloaded lookup table from Google Sheet \"{}\"", sheet);
+        return cached.to_string_lossy().to_string();
+    }
+    if !lookup.starts_with("http://") && !lookup.starts_with("https://") {
+        return lookup.to_string();
+    }
+    let cached = cache_path(Path::new(CACHE_DIR), lookup);
+    let etag_path = cached.with_extension(format!(
+        "{}.etag",
+        cached.extension().expect("Failed to get file extension.").to_string_lossy()
+    ));
+    std::fs::create_dir_all(CACHE_DIR).expect("Failed to create cache directory.");
+
+    let etag = std::fs::read_to_string(&etag_path).ok().filter(|_| cached.exists());
+    match download(lookup, etag.as_deref(), &cached) {
+        Ok(Some(new_etag)) => {
+            print_status!("Downloaded lookup table from \"{}\"", lookup);
+            match new_etag {
+                Some(etag) => std::fs::write(&etag_path, etag).expect("Failed to write ETag file."),
+                None => {
+                    let _ = std::fs::remove_file(&etag_path);
+                }
+            }
+        }
+        Ok(None) => {
+            print_status!("{}", "Cached lookup table is up to date.".dimmed());
+        }
+        Err(e) if cached.exists() => {
+            print_warn!(Warning::Failure, "{}", messages::fetch_failed(lookup, &e.to_string()));
+            print_warn_ok!("Using the cached copy \"{}\".", cached.to_string_lossy());
+        }
+        Err(e) => panic!("Failed to fetch \"{}\": {}", lookup, e),
+    }
+    cached.to_string_lossy().to_string()
+}
+
+/// Downloads `url` to `target` unless the server reports that `etag` is still current.
+/// Returns `None` if the cached copy is current, or the new ETag (if any) after a download.
+fn download(url: &str, etag: Option<&str>, target: &Path) -> Result<Option<Option<String>>, String> {
+    let mut request = ureq::get(url);
+    if let Some(etag) = etag {
+        request = request.header("If-None-Match", etag);
+    }
+    let response = request.call().map_err(|e| e.to_string())?;
+    if response.status() == 304 {
+        return Ok(None);
+    }
+    let new_etag = response
+        .headers()
+        .get("etag")
+        .and_then(|etag| etag.to_str().ok())
+        .map(str::to_string);
+
+    // write to a partial file first, so a dropped connection never leaves a truncated table in the cache.
+    let partial = target.with_extension("partial");
+    let mut file = std::fs::File::create(&partial).map_err(|e| e.to_string())?;
+    std::io::copy(&mut response.into_body().into_reader(), &mut file).map_err(|e| e.to_string())?;
+    std::fs::rename(&partial, target).map_err(|e| e.to_string())?;
+    Ok(Some(new_etag))
+}
+
+/// Returns the cache path of a URL: a short hash of the URL plus its file name, ending in `.json` or `.csv`.
+fn cache_path(cache_dir: &Path, url: &str) -> PathBuf {
+    let hash = hex::encode(Sha256::digest(url.as_bytes()));
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    let name = path
+        .rsplit('/')
+        .next()
+        .filter(|name| !name.is_empty())
+        .unwrap_or("lookup");
+    let name = if name.ends_with(".json") || name.ends_with(".csv") {
+        name.to_string()
+    } else {
+        format!("{}.csv", name)
+    };
+    cache_dir.join(format!("{}_{}", &hash[..12], name))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn cache_paths_keep_lookup_extension() {
+        let dir = Path::new(CACHE_DIR);
+        let csv = cache_path(dir, "https://example.org/catalog/lookup.csv?token=abc");
+        assert!(csv.to_string_lossy().ends_with("_lookup.csv"));
+        let json = cache_path(dir, "https://example.org/lookup.json");
+        assert!(json.to_string_lossy().ends_with("_lookup.json"));
+        let export = cache_path(dir, "https://example.org/sheets/export");
+        assert!(export.to_string_lossy().ends_with("_export.csv"));
+        assert_ne!(csv, cache_path(dir, "https://example.org/other/lookup.csv"));
+    }
+}
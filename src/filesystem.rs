@@ -0,0 +1,379 @@
+use std::{
+    collections::{ BTreeMap, BTreeSet },
+    io::{ self, Read },
+    path::{ Path, PathBuf },
+    sync::{ Arc, Mutex },
+    time::SystemTime,
+};
+
+/// The file operations that collecting, grouping, and formatting files go through, so the same code can run against
+/// the disk or against an in-memory filesystem in tests and `--simulate` runs.
+pub trait Filesystem: Sync {
+    /// Returns the paths of the entries of a directory.
+    fn read_dir(&self, directory: &Path) -> io::Result<Vec<PathBuf>>;
+    fn is_file(&self, path: &Path) -> bool;
+    fn is_dir(&self, path: &Path) -> bool;
+    /// Returns a file's size in bytes.
+    fn len(&self, path: &Path) -> io::Result<u64>;
+    fn modified(&self, path: &Path) -> io::Result<SystemTime>;
+    fn create_dir_all(&self, directory: &Path) -> io::Result<()>;
+    /// Copies a file, returning the number of bytes copied.
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<u64>;
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+    fn remove_file(&self, path: &Path) -> io::Result<()>;
+    /// Opens a file to read its contents.
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read>>;
+    /// Creates (or replaces) a file with `contents`.
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()>;
+    fn set_modified(&self, path: &Path, modified: SystemTime) -> io::Result<()>;
+    /// Copies the modification time (and on disk, the access time and permissions) of one file onto another.
+    fn copy_metadata(&self, from: &Path, to: &Path) -> io::Result<()>;
+
+    fn exists(&self, path: &Path) -> bool {
+        self.is_file(path) || self.is_dir(path)
+    }
+
+    /// Returns true if changes are only simulated, so work outside these operations (archives, state, manifests) must
+    /// be skipped too.
+    fn is_simulated(&self) -> bool {
+        false
+    }
+}
+
+/// The disk.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RealFilesystem;
+
+impl Filesystem for RealFilesystem {
+    fn read_dir(&self, directory: &Path) -> io::Result<Vec<PathBuf>> {
+        std::fs::read_dir(directory)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect()
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        path.is_file()
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        path.is_dir()
+    }
+
+    fn len(&self, path: &Path) -> io::Result<u64> {
+        Ok(path.metadata()?.len())
+    }
+
+    fn modified(&self, path: &Path) -> io::Result<SystemTime> {
+        path.metadata()?.modified()
+    }
+
+    fn create_dir_all(&self, directory: &Path) -> io::Result<()> {
+        std::fs::create_dir_all(directory)
+    }
+
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<u64> {
+        crate::copy_file(from, to)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        std::fs::rename(from, to)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        std::fs::remove_file(path)
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read>> {
+        Ok(Box::new(std::fs::File::open(path)?))
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        std::fs::write(path, contents)
+    }
+
+    fn set_modified(&self, path: &Path, modified: SystemTime) -> io::Result<()> {
+        std::fs::File::options().write(true).open(path)?.set_modified(modified)
+    }
+
+    fn copy_metadata(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let metadata = from.metadata()?;
+        let mut times = std::fs::FileTimes::new();
+        if let Ok(modified) = metadata.modified() {
+            times = times.set_modified(modified);
+        }
+        if let Ok(accessed) = metadata.accessed() {
+            times = times.set_accessed(accessed);
+        }
+        std::fs::File::options().write(true).open(to)?.set_times(times)?;
+        std::fs::set_permissions(to, metadata.permissions())
+    }
+}
+
+/// A file of a `MemoryFilesystem`: its size, modification time, and where its contents are read from. Only written
+/// files are held in memory, so simulating a run over large scans does not load them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryFile {
+    pub bytes: u64,
+    pub modified: SystemTime,
+    contents: MemoryContents,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum MemoryContents {
+    /// A file added by its size alone, which reads as that many zero bytes.
+    #[cfg(test)]
+    Zeros,
+    /// A disk file seen through an overlay, or copied from one.
+    Disk(PathBuf),
+    Written(Arc<[u8]>),
+}
+
+#[derive(Debug, Default)]
+struct MemoryState {
+    files: BTreeMap<PathBuf, MemoryFile>,
+    directories: BTreeSet<PathBuf>,
+    /// Disk paths moved away or replaced in memory, hidden from an overlay.
+    removed: BTreeSet<PathBuf>,
+}
+
+/// An in-memory filesystem. As an `overlay`, it reads through to the disk and keeps every change in memory, so a
+/// `--simulate` run sees the real files but changes none of them.
+#[derive(Debug, Default)]
+pub struct MemoryFilesystem {
+    state: Mutex<MemoryState>,
+    overlay: bool,
+}
+
+impl MemoryFilesystem {
+    /// Returns an empty in-memory filesystem.
+    #[cfg(test)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a filesystem that reads the disk and keeps its changes in memory.
+    pub fn overlay() -> Self {
+        MemoryFilesystem { overlay: true, ..Self::default() }
+    }
+
+    /// Adds a file (and its parent directories) of `bytes` bytes, modified now.
+    #[cfg(test)]
+    pub fn add_file(&self, path: impl AsRef<Path>, bytes: u64) {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            self.create_dir_all(parent).expect("Failed to create in-memory directory.");
+        }
+        let file = MemoryFile { bytes, modified: SystemTime::now(), contents: MemoryContents::Zeros };
+        self.lock().files.insert(path.to_path_buf(), file);
+    }
+
+    /// Returns every file in memory, by path.
+    #[cfg(test)]
+    pub fn files(&self) -> BTreeMap<PathBuf, MemoryFile> {
+        self.lock().files.clone()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, MemoryState> {
+        self.state.lock().expect("Failed to lock the in-memory filesystem.")
+    }
+
+    fn on_disk(&self, state: &MemoryState, path: &Path) -> bool {
+        self.overlay && !state.removed.contains(path)
+    }
+
+    fn file(&self, path: &Path) -> io::Result<MemoryFile> {
+        let state = self.lock();
+        if let Some(file) = state.files.get(path) {
+            return Ok(file.clone());
+        }
+        if self.on_disk(&state, path) && path.is_file() {
+            let metadata = path.metadata()?;
+            let contents = MemoryContents::Disk(path.to_path_buf());
+            return Ok(MemoryFile { bytes: metadata.len(), modified: metadata.modified()?, contents });
+        }
+        Err(not_found(path))
+    }
+
+    /// Returns an error if `path` is not in an existing directory.
+    fn check_parent(&self, path: &Path) -> io::Result<()> {
+        if path.parent().is_none_or(|parent| parent.as_os_str().is_empty() || self.is_dir(parent)) {
+            Ok(())
+        } else {
+            Err(not_found(path))
+        }
+    }
+
+    fn insert(&self, path: &Path, file: MemoryFile) {
+        let mut state = self.lock();
+        state.removed.insert(path.to_path_buf());
+        state.files.insert(path.to_path_buf(), file);
+    }
+}
+
+fn not_found(path: &Path) -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, format!("\"{}\" does not exist.", path.to_string_lossy()))
+}
+
+impl Filesystem for MemoryFilesystem {
+    fn read_dir(&self, directory: &Path) -> io::Result<Vec<PathBuf>> {
+        if !self.is_dir(directory) {
+            return Err(not_found(directory));
+        }
+        let state = self.lock();
+        let mut entries = BTreeSet::new();
+        if self.on_disk(&state, directory) && directory.is_dir() {
+            for entry in std::fs::read_dir(directory)? {
+                let path = entry?.path();
+                if !state.removed.contains(&path) {
+                    entries.insert(path);
+                }
+            }
+        }
+        let children = state.files
+            .keys()
+            .chain(&state.directories)
+            .filter(|path| path.parent() == Some(directory))
+            .cloned();
+        entries.extend(children);
+        Ok(entries.into_iter().collect())
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        let state = self.lock();
+        state.files.contains_key(path) || (self.on_disk(&state, path) && path.is_file())
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        let state = self.lock();
+        state.directories.contains(path) || (self.on_disk(&state, path) && path.is_dir())
+    }
+
+    fn len(&self, path: &Path) -> io::Result<u64> {
+        Ok(self.file(path)?.bytes)
+    }
+
+    fn modified(&self, path: &Path) -> io::Result<SystemTime> {
+        Ok(self.file(path)?.modified)
+    }
+
+    fn create_dir_all(&self, directory: &Path) -> io::Result<()> {
+        let mut state = self.lock();
+        for ancestor in directory.ancestors().filter(|ancestor| !ancestor.as_os_str().is_empty()) {
+            state.directories.insert(ancestor.to_path_buf());
+        }
+        Ok(())
+    }
+
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<u64> {
+        let file = self.file(from)?;
+        self.check_parent(to)?;
+        let bytes = file.bytes;
+        self.insert(to, file);
+        Ok(bytes)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.copy(from, to)?;
+        let mut state = self.lock();
+        state.files.remove(from);
+        state.removed.insert(from.to_path_buf());
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        self.file(path)?;
+        let mut state = self.lock();
+        state.files.remove(path);
+        state.removed.insert(path.to_path_buf());
+        Ok(())
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read>> {
+        let file = self.file(path)?;
+        Ok(match file.contents {
+            #[cfg(test)]
+            MemoryContents::Zeros => Box::new(io::repeat(0).take(file.bytes)),
+            MemoryContents::Disk(path) => Box::new(std::fs::File::open(path)?),
+            MemoryContents::Written(contents) => Box::new(io::Cursor::new(contents)),
+        })
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        self.check_parent(path)?;
+        let bytes = contents.len() as u64;
+        let file = MemoryFile { bytes, modified: SystemTime::now(), contents: MemoryContents::Written(contents.into()) };
+        self.insert(path, file);
+        Ok(())
+    }
+
+    fn set_modified(&self, path: &Path, modified: SystemTime) -> io::Result<()> {
+        let file = self.file(path)?;
+        self.insert(path, MemoryFile { modified, ..file });
+        Ok(())
+    }
+
+    fn copy_metadata(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.set_modified(to, self.modified(from)?)
+    }
+
+    fn is_simulated(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::collect::{ collect_files, listed_files, FileFilters };
+
+    #[test]
+    fn move_files_in_memory() {
+        let fs = MemoryFilesystem::new();
+        fs.add_file("scans/tn1.pdf", 10);
+        fs.rename(Path::new("scans/tn1.pdf"), Path::new("scans/tn2.pdf")).unwrap();
+        assert!(!fs.exists(Path::new("scans/tn1.pdf")));
+        assert_eq!(fs.len(Path::new("scans/tn2.pdf")).unwrap(), 10);
+        assert!(fs.copy(Path::new("scans/tn2.pdf"), Path::new("missing/tn2.pdf")).is_err());
+        assert_eq!(fs.read_dir(Path::new("scans")).unwrap(), vec![PathBuf::from("scans/tn2.pdf")]);
+    }
+
+    #[test]
+    fn collect_files_in_memory() {
+        let fs = MemoryFilesystem::new();
+        fs.add_file("scans/tn2.pdf", 10);
+        fs.add_file("scans/tn1.pdf", 0);
+        fs.add_file("scans/tn3.txt", 10);
+        fs.add_file("scans/backup/tn4.pdf", 10);
+        fs.add_file("scans/nested/tn5.pdf", 10);
+        let filters = FileFilters { exclude: vec!["backup/**".to_string()], min_size: Some(1), ..Default::default() };
+        let files = collect_files(&fs, "scans", &["pdf".to_string()], true, &filters);
+        assert_eq!(files, vec![PathBuf::from("scans/tn2.pdf"), PathBuf::from("scans/nested/tn5.pdf")]);
+    }
+
+    #[test]
+    fn skip_files_outside_size_bounds() {
+        let fs = MemoryFilesystem::new();
+        fs.add_file("scans/tn1.pdf", 0);
+        fs.add_file("scans/tn2.pdf", 1_000);
+        fs.add_file("scans/tn3.pdf", 2_000_000_000);
+        let filters = FileFilters { min_size: Some(1_000), max_size: Some(1_000_000_000), ..Default::default() };
+        let files = collect_files(&fs, "scans", &["pdf".to_string()], false, &filters);
+        assert_eq!(files, vec![PathBuf::from("scans/tn2.pdf")]);
+    }
+
+    #[test]
+    fn filter_listed_files_in_memory() {
+        let fs = MemoryFilesystem::new();
+        fs.add_file("scans/tn1.pdf", 0);
+        fs.add_file("scans/tn2.pdf", 10);
+        fs.add_file("other/tn3.pdf", 10);
+        let filters = FileFilters { min_size: Some(1), ..Default::default() };
+        let list = "# batch 3\nscans/tn1.pdf\nscans/tn2.pdf\n\nscans/missing.pdf\nother/tn3.pdf\n";
+        let files = listed_files(&fs, list, &filters);
+        assert_eq!(files, vec![PathBuf::from("scans/tn2.pdf"), PathBuf::from("other/tn3.pdf")]);
+
+        let newer_than = SystemTime::now() + std::time::Duration::from_secs(60);
+        let filters = FileFilters { newer_than: Some(newer_than), ..Default::default() };
+        assert!(listed_files(&fs, list, &filters).is_empty());
+    }
+}
@@ -0,0 +1,336 @@
+use std::cmp::Ordering;
+
+use crate::{ csv_reader, csv_writer, derived_path, print_status, safely_target_file, summary, STDIO };
+
+/// A predicate over the columns of a CSV row, e.g. `Collection == "Arizona Labor Journal" && Volume != ""`.
+///
+/// Operands are `"strings"` or `'strings'`, numbers, and column names (bare, or in backticks when they contain spaces).
+/// Comparisons are `==` (or `=`), `!=` (or `<>`), `<`, `<=`, `>`, and `>=`, and are numeric when both sides are numbers.
+/// Predicates combine with `&&`/`AND`, `||`/`OR`, `!`/`NOT`, and parentheses.
+#[derive(Debug, PartialEq)]
+pub enum Expr {
+    Compare(Operand, CompareOp, Operand),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Operand {
+    Column(String),
+    Literal(String),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum Token {
+    Str(String),
+    Word(String),
+    Column(String),
+    Op(CompareOp),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut chars = input.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '"' | '\'' | '`' => {
+                chars.next();
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some(next) if next == c => break,
+                        Some(next) => value.push(next),
+                        None => {
+                            return Err(format!("Unterminated {} in \"{}\".", c, input));
+                        }
+                    }
+                }
+                tokens.push(if c == '`' { Token::Column(value) } else { Token::Str(value) });
+            }
+            '(' | ')' => {
+                chars.next();
+                tokens.push(if c == '(' { Token::Open } else { Token::Close });
+            }
+            '=' | '!' | '<' | '>' | '&' | '|' => {
+                chars.next();
+                let pair = chars.peek().map(|next| format!("{}{}", c, next));
+                let token = match pair.as_deref() {
+                    Some("==") => Some(Token::Op(CompareOp::Eq)),
+                    Some("!=") => Some(Token::Op(CompareOp::Ne)),
+                    Some("<>") => Some(Token::Op(CompareOp::Ne)),
+                    Some("<=") => Some(Token::Op(CompareOp::Le)),
+                    Some(">=") => Some(Token::Op(CompareOp::Ge)),
+                    Some("&&") => Some(Token::And),
+                    Some("||") => Some(Token::Or),
+                    _ => None,
+                };
+                match token {
+                    Some(token) => {
+                        chars.next();
+                        tokens.push(token);
+                    }
+                    None =>
+                        tokens.push(match c {
+                            '=' => Token::Op(CompareOp::Eq),
+                            '<' => Token::Op(CompareOp::Lt),
+                            '>' => Token::Op(CompareOp::Gt),
+                            '!' => Token::Not,
+                            _ => {
+                                return Err(format!("Unexpected \"{}\" in \"{}\".", c, input));
+                            }
+                        }),
+                }
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&next) = chars.peek() {
+                    if next.is_whitespace() || "\"'`()=!<>&|".contains(next) {
+                        break;
+                    }
+                    word.push(next);
+                    chars.next();
+                }
+                tokens.push(match word.to_uppercase().as_str() {
+                    "AND" => Token::And,
+                    "OR" => Token::Or,
+                    "NOT" => Token::Not,
+                    _ => Token::Word(word),
+                });
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Parses a predicate such as `Collection == "Arizona Labor Journal" && Volume != ""`.
+pub fn parse_predicate(input: &str) -> Result<Expr, String> {
+    let tokens = tokenize(input)?;
+    let mut parser = Parser { tokens: &tokens, pos: 0 };
+    let expr = parser.or()?;
+    match parser.tokens.get(parser.pos) {
+        None => Ok(expr),
+        Some(token) => Err(format!("Unexpected {:?} in \"{}\".", token, input)),
+    }
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        let found = self.tokens.get(self.pos) == Some(token);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.eat(&Token::Or) {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.not()?;
+        while self.eat(&Token::And) {
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> Result<Expr, String> {
+        if self.eat(&Token::Not) {
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        if self.eat(&Token::Open) {
+            let expr = self.or()?;
+            if !self.eat(&Token::Close) {
+                return Err("Missing \")\".".to_string());
+            }
+            return Ok(expr);
+        }
+        let left = self.operand()?;
+        let op = match self.next() {
+            Some(Token::Op(op)) => op,
+            other => {
+                return Err(format!("Expected a comparison after {:?}, found {:?}.", left, other));
+            }
+        };
+        Ok(Expr::Compare(left, op, self.operand()?))
+    }
+
+    fn operand(&mut self) -> Result<Operand, String> {
+        match self.next() {
+            Some(Token::Str(value)) => Ok(Operand::Literal(value)),
+            Some(Token::Word(word)) if word.parse::<f64>().is_ok() => Ok(Operand::Literal(word)),
+            Some(Token::Word(column) | Token::Column(column)) => Ok(Operand::Column(column)),
+            other => Err(format!("Expected a column or value, found {:?}.", other)),
+        }
+    }
+}
+
+impl Expr {
+    /// Returns every column name the predicate refers to.
+    pub fn columns(&self) -> Vec<&str> {
+        match self {
+            Expr::Compare(left, _, right) =>
+                [left, right]
+                    .into_iter()
+                    .filter_map(|operand| {
+                        match operand {
+                            Operand::Column(column) => Some(column.as_str()),
+                            Operand::Literal(_) => None,
+                        }
+                    })
+                    .collect(),
+            Expr::And(left, right) | Expr::Or(left, right) => {
+                let mut columns = left.columns();
+                columns.extend(right.columns());
+                columns
+            }
+            Expr::Not(expr) => expr.columns(),
+        }
+    }
+
+    /// Returns true if the row (with `headers`) satisfies the predicate. Missing columns read as empty.
+    pub fn matches(&self, headers: &csv::StringRecord, row: &csv::StringRecord) -> bool {
+        match self {
+            Expr::Compare(left, op, right) => {
+                let value = |operand: &Operand| -> String {
+                    match operand {
+                        Operand::Literal(value) => value.clone(),
+                        Operand::Column(column) =>
+                            headers
+                                .iter()
+                                .position(|header| header == column)
+                                .and_then(|i| row.get(i))
+                                .unwrap_or_default()
+                                .to_string(),
+                    }
+                };
+                compare_values(&value(left), *op, &value(right))
+            }
+            Expr::And(left, right) => left.matches(headers, row) && right.matches(headers, row),
+            Expr::Or(left, right) => left.matches(headers, row) || right.matches(headers, row),
+            Expr::Not(expr) => !expr.matches(headers, row),
+        }
+    }
+}
+
+/// Orders two values numerically if both are numbers, otherwise as strings.
+pub fn order_values(left: &str, right: &str) -> Option<Ordering> {
+    match (left.trim().parse::<f64>(), right.trim().parse::<f64>()) {
+        (Ok(left), Ok(right)) => left.partial_cmp(&right),
+        _ => Some(left.cmp(right)),
+    }
+}
+
+fn compare_values(left: &str, op: CompareOp, right: &str) -> bool {
+    let Some(ordering) = order_values(left, right) else {
+        return false;
+    };
+    match op {
+        CompareOp::Eq => ordering.is_eq(),
+        CompareOp::Ne => ordering.is_ne(),
+        CompareOp::Lt => ordering.is_lt(),
+        CompareOp::Le => ordering.is_le(),
+        CompareOp::Gt => ordering.is_gt(),
+        CompareOp::Ge => ordering.is_ge(),
+    }
+}
+
+/// Writes the rows of a CSV file that match `predicate` to `output` (default `<csv>_filtered.csv`, or `-` for stdout).
+/// Returns the path written.
+pub fn filter_csv(path: &str, predicate: &str, output: Option<&str>) -> String {
+    let expr = parse_predicate(predicate).unwrap_or_else(|e| panic!("Invalid --where predicate: {}", e));
+    let mut reader = csv_reader(path);
+    let headers = reader.headers().expect("Failed to read CSV headers.").clone();
+    for column in expr.columns() {
+        if !headers.iter().any(|header| header == column) {
+            panic!("Column \"{}\" not found in \"{}\".", column, path);
+        }
+    }
+
+    let output = match output {
+        Some(STDIO) => STDIO.to_string(),
+        Some(output) => safely_target_file(output),
+        None if path == STDIO => STDIO.to_string(),
+        None => safely_target_file(&derived_path(path, "_filtered", "csv")),
+    };
+    let mut writer = csv_writer(&output);
+    writer.write_record(&headers).expect("Failed to write headers.");
+    let (mut rows, mut matched) = (0, 0);
+    for result in reader.records() {
+        let row = result.expect("Failed to parse record.");
+        rows += 1;
+        if expr.matches(&headers, &row) {
+            matched += 1;
+            writer.write_record(&row).expect("Failed to write record.");
+        }
+    }
+    writer.flush().expect("Failed to write filtered CSV file.");
+
+    summary::record_count("rows", rows);
+    summary::record_count("matched", matched);
+    print_status!("{} of {} rows matched and were saved to \"{}\".", matched, rows, output);
+    if output != STDIO {
+        summary::record_output(&output);
+    }
+    output
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_and_match_predicates() {
+        let headers = csv::StringRecord::from(vec!["Collection", "Volume", "Part Of"]);
+        let row = csv::StringRecord::from(vec!["Arizona Labor Journal", "12", ""]);
+        let matches = |predicate: &str| parse_predicate(predicate).unwrap().matches(&headers, &row);
+
+        assert!(matches(r#"Collection == "Arizona Labor Journal" && Volume != """#));
+        assert!(matches("Volume > 9 AND `Part Of` = ''"));
+        assert!(matches("!(Volume < 10) || Collection == 'x'"));
+        assert!(!matches("NOT Volume >= 12"));
+        assert!(matches(r#"Collection < "B""#));
+
+        assert_eq!(
+            parse_predicate("a == 1 || b == 2 && c == 3").unwrap().columns(),
+            vec!["a", "b", "c"]
+        );
+        assert!(parse_predicate("Volume ==").is_err());
+        assert!(parse_predicate("(Volume == 1").is_err());
+        assert!(parse_predicate("Volume == 'open").is_err());
+    }
+}
@@ -0,0 +1,1102 @@
+use std::{ collections::{ BTreeMap, BTreeSet }, path::{ Path, PathBuf } };
+use serde::{ Deserialize, Serialize };
+use owo_colors::OwoColorize;
+
+use crate::{
+    archive_record::ArchiveRecord,
+    checksum,
+    collect::Selection,
+    companion_files,
+    config::config,
+    csv_writer,
+    date::Date,
+    filesystem::{ Filesystem, RealFilesystem },
+    hooks,
+    incremental::FileCache,
+    interrupted,
+    issue_data::{ is_valid_tn, normalize_tn, FileNaming, IssueData },
+    messages,
+    pdf,
+    preview,
+    print_status,
+    print_warn,
+    print_warn_ok,
+    remote::RemoteTarget,
+    safely_target_file,
+    state::{ self, Stage },
+    summary,
+    trash_file,
+    warnings::Warning,
+    xmp,
+    ConflictResolver,
+};
+
+#[derive(clap::Args, Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct FormatOptions {
+    /// The output directory to save the newly named files. If not provided, the formatted files will be saved in the same directory as the input files.
+    /// If the directory does not exist, it will be created. `s3://bucket/prefix` and `sftp://host/path` upload the files instead (with the `aws` CLI and `scp`).
+    /// An `sftp://` path is relative to the login directory, and `sftp://host//path` is absolute.
+    #[arg(short, long, env = "BULK_FORMAT_OUTPUT_DIR")]
+    pub output: Option<String>,
+
+    /// Print the planned renames without copying any files.
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Carry over each input file's modification and access times (and permissions) to its output file.
+    #[arg(long)]
+    pub preserve_metadata: bool,
+
+    /// Set each output file's modification time to its issue date, so files sort chronologically by date modified.
+    #[arg(long)]
+    pub touch_issue_date: bool,
+
+    /// Embed the record title, issue date, and rights statement as XMP metadata in TIFF and JPEG outputs.
+    #[arg(long)]
+    pub embed_xmp: bool,
+
+    /// Set the PDF Title, Subject, and CreationDate of PDF outputs to the record title, publication title, and issue date.
+    #[arg(long)]
+    pub embed_pdf_metadata: bool,
+
+    /// Companion file extensions, e.g. `txt,xml,jp2`. Same-stem companions of each input (OCR text, page images) are copied to the same formatted name.
+    #[arg(long, value_delimiter = ',')]
+    pub sidecar_ext: Vec<String>,
+
+    /// Transliterate diacritics in output file names (`á` -> `a`, `ñ` -> `n`) and drop other non-ASCII characters.
+    /// Embedded metadata keeps the original titles.
+    #[arg(long)]
+    pub ascii_filenames: bool,
+
+    /// Insert a zero-padded `v03n012` volume/issue segment before the date, so names sort in issue order.
+    /// Numbers are padded to the widest volume and issue in the lookup table.
+    #[arg(long)]
+    pub volume_issue_segment: bool,
+
+    /// The rights statement to embed when a record has none.
+    #[arg(long)]
+    pub rights: Option<String>,
+
+    /// A path to a target (ArchiveRecord) CSV file. Records are matched to issues by `--join-on`, and their fields are
+    /// used in embedded metadata and `--name-template`.
+    #[arg(long)]
+    pub records: Option<String>,
+
+    /// How `--records` are matched to issues: by NODE_TITLE, or by Call Number against the tn.
+    #[arg(long, value_enum, default_value_t)]
+    pub join_on: RecordJoin,
+
+    /// A template for output file names, e.g. `{Call Number}_{date}`. `{title}`, `{date}`, `{volume}`, `{issue}`, and
+    /// `{tn}` come from the lookup table, and any other placeholder is the value of that column in the issue's
+    /// `--records` row. Files whose placeholders have no value keep the formatted title.
+    /// Defaults to `name-template` from the config file.
+    #[arg(long)]
+    pub name_template: Option<String>,
+
+    /// Skip files unchanged (same size and modification time) since the last incremental run whose output is still
+    /// in place, as cached in the input directory's `.bulk_format/cache.csv`.
+    #[arg(long)]
+    pub incremental: bool,
+
+    /// A path to write a CSV mapping each `tn` to its formatted file name, with the parsed volume, issue, and date
+    /// as separate columns.
+    #[arg(long)]
+    pub mapping: Option<String>,
+
+    /// A path to write a `source,target,action,reason` CSV of every file that was skipped, overwritten, or renamed
+    /// with a suffix. Defaults to `format_log.csv`, written only when there is something to log.
+    #[arg(long)]
+    pub log: Option<String>,
+
+    /// List the files of the `--output` directory that no lookup entry is formatted to, e.g. leftovers of an older run
+    /// named after a since-corrected title. Only files with the extension of an input file or sidecar are checked.
+    #[arg(long)]
+    pub check_orphans: bool,
+
+    #[command(flatten)]
+    #[serde(flatten)]
+    pub selection: Selection,
+}
+
+/// The column matching `--records` rows to issues.
+#[derive(clap::ValueEnum, Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RecordJoin {
+    /// The issue's record title, e.g. `Arizona Sun, 1944-05-12`, matches NODE_TITLE.
+    #[default]
+    Title,
+    /// The issue's tn matches Call Number (both normalized).
+    Tn,
+}
+
+/// One row of the `--mapping` CSV. `date` is the ISO date, without a circa suffix; undated issues have an empty `date`.
+#[derive(Debug, Serialize)]
+struct MappingRow {
+    tn: String,
+    source: String,
+    target: String,
+    volume: Option<u32>,
+    issue: Option<u32>,
+    date: String,
+}
+
+/// One row of the `--log` CSV: a file that was skipped, overwritten, or renamed, and why.
+#[derive(Debug, Serialize)]
+struct LogRow {
+    source: String,
+    target: String,
+    action: &'static str,
+    reason: &'static str,
+}
+
+impl LogRow {
+    fn new(source: &Path, target: &str, action: &'static str, reason: &'static str) -> Self {
+        LogRow { source: source.to_string_lossy().to_string(), target: target.to_string(), action, reason }
+    }
+}
+
+impl MappingRow {
+    fn new(issue: &IssueData, source: &str, target: &str) -> Self {
+        MappingRow {
+            tn: issue.tn.clone(),
+            source: source.to_string(),
+            target: target.to_string(),
+            volume: issue.volume,
+            issue: issue.issue,
+            date: iso_date(issue).unwrap_or_default(),
+        }
+    }
+}
+
+/// Copies the files that have a lookup entry to the output, renamed by their formatted titles, or uploads them to a
+/// remote output. Returns false if any upload failed.
+pub fn copy_and_rename_files(
+    fs: &dyn Filesystem,
+    directories: &[String],
+    files: Vec<PathBuf>,
+    lookup_table: &BTreeMap<String, IssueData>,
+    options: &FormatOptions
+) -> bool {
+    let files = options.selection.select(files, "files", |file| file.to_string_lossy().to_string());
+    let remote = options.output.as_deref().and_then(RemoteTarget::parse);
+    let output_dir = match &options.output {
+        Some(_) if remote.is_some() => String::new(),
+        Some(dir) => {
+            if !options.dry_run {
+                fs.create_dir_all(Path::new(dir)).expect("Failed to create output directory.");
+            }
+            dir.clone()
+        }
+        None => String::new(),
+    };
+
+    let records = options.records
+        .as_deref()
+        .map(|path| parse_records(path, options.join_on))
+        .unwrap_or_default();
+    let naming = file_naming(lookup_table, options);
+    let extensions = output_extensions(&files, options);
+    let mut resolver = ConflictResolver::default();
+    let mut case_folded = CaseFoldedNames::default();
+    let mut mapping = vec![];
+    let mut log = vec![];
+    let mut failed_uploads = 0;
+    let (mut formatted, mut delivered) = (vec![], vec![]);
+    // one cache per input directory (or, for listed files, per file directory), as each keeps its own.
+    let mut caches = if options.incremental && remote.is_none() && !options.dry_run {
+        let mut cache_dirs = directories.iter().map(PathBuf::from).collect::<Vec<_>>();
+        if cache_dirs.is_empty() {
+            cache_dirs = files
+                .iter()
+                .filter_map(|file| file.parent())
+                .map(Path::to_path_buf)
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .collect();
+        }
+        cache_dirs
+            .into_iter()
+            .map(|directory| {
+                let cache = FileCache::open(&directory.to_string_lossy());
+                (directory, cache)
+            })
+            .collect::<Vec<_>>()
+    } else {
+        vec![]
+    };
+
+    for file in files {
+        if interrupted() {
+            break;
+        }
+        let file_name = file.file_name().expect("Failed to get file name.").to_string_lossy();
+        let (tn, ext) = tn_and_extension(&file);
+
+        let normalized_tn = normalize_tn(&tn);
+        if !is_valid_tn(&normalized_tn) {
+            print_warn!(Warning::TnPattern, "{}", messages::file_tn_pattern(&file_name));
+        }
+        if let Some(issue) = lookup_table.get(&normalized_tn) {
+            if !options.dry_run && !hooks::before_file(&file, issue) {
+                print_warn_ok!("{}", messages::skipping(&file_name));
+                summary::record_count("skipped", 1);
+                log.push(LogRow::new(&file, "", "skipped", "the before-file hook failed"));
+                continue;
+            }
+            let target_file = with_extension(target_name(issue, &naming, &records, options), &ext);
+            if let Some(remote) = &remote {
+                if upload_file(fs, &file, issue, &target_file, remote, &records, options) {
+                    let stem = Path::new(&target_file).file_stem().expect("Failed to get file stem.");
+                    formatted.push((issue.tn.clone(), stem.to_string_lossy().to_string()));
+                    delivered.push((issue.tn.clone(), String::new()));
+                    mapping.push(MappingRow::new(issue, &file_name, &target_file));
+                } else if !options.dry_run {
+                    log.push(LogRow::new(&file, &remote.url(&target_file), "skipped", "the upload failed"));
+                    failed_uploads += 1;
+                }
+                continue;
+            }
+            let target_path = output_path(&file, &output_dir, &target_file);
+            if let Some(other) = case_folded.insert(fs, &target_path) {
+                let name = target_path.file_name().expect("Failed to get file name.").to_string_lossy();
+                print_warn!(Warning::NameCollision, "{}", messages::case_collision(&name, &other));
+                summary::record_count("case collisions", 1);
+            }
+            if options.dry_run {
+                preview::print_rename(&file_name, &target_path.to_string_lossy());
+                for (sidecar, sidecar_target) in sidecars(fs, &file, &target_path, options) {
+                    preview::print_rename(
+                        &sidecar.to_string_lossy(),
+                        &sidecar_target.to_string_lossy()
+                    );
+                }
+                continue;
+            }
+            let target = target_path.to_string_lossy();
+            let cache = caches
+                .iter_mut()
+                .find(|(directory, _)| file.starts_with(directory))
+                .map(|(_, cache)| cache);
+            let cached = cache.as_ref().and_then(|cache| cache.get("format", &file));
+            if cached == Some(target.as_ref()) && fs.exists(&target_path) {
+                print_status!("{}", messages::unchanged(&file_name));
+                summary::record_count("unchanged", 1);
+                mapping.push(MappingRow::new(issue, &file_name, &target));
+                log.push(LogRow::new(&file, &target, "skipped", "unchanged since the last incremental run"));
+                continue;
+            }
+            let existed = fs.exists(&target_path);
+            let Some(resolved) = resolver.resolve(fs, &target_path) else {
+                print_warn_ok!("{}", messages::skipping(&file_name));
+                summary::record_count("skipped", 1);
+                log.push(LogRow::new(&file, &target, "skipped", "the target already exists"));
+                continue;
+            };
+            if resolved != target_path {
+                log.push(LogRow::new(&file, &resolved.to_string_lossy(), "renamed", "the target already exists"));
+            } else if existed {
+                log.push(LogRow::new(&file, &target, "overwritten", "the target already existed and was trashed"));
+            }
+            let target_path = resolved;
+            print_status!("{}", messages::copying_file(&file_name, &target_path.to_string_lossy()));
+            let bytes = match fs.copy(&file, &target_path) {
+                Ok(bytes) => bytes,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {
+                    print_warn!(Warning::Failure, "{}", messages::interrupted_removed(&target_path.to_string_lossy()));
+                    log.push(LogRow::new(&file, &target_path.to_string_lossy(), "skipped", "the copy was interrupted"));
+                    break;
+                }
+                Err(e) => panic!("Failed to copy file \"{}\": {}", file.to_string_lossy(), e),
+            };
+            summary::record_output(target_path.to_string_lossy());
+            summary::record_file(bytes);
+            mapping.push(MappingRow::new(issue, &file_name, &target_path.to_string_lossy()));
+            let stem = target_path.file_stem().expect("Failed to get file stem.");
+            formatted.push((issue.tn.clone(), stem.to_string_lossy().to_string()));
+            finish_output(fs, &file, issue, &target_path, &records, options);
+            hooks::after_file(&file, &target_path, issue);
+            if let Some(cache) = cache {
+                cache.insert("format", &file, &target_path.to_string_lossy());
+            }
+            for (sidecar, sidecar_target) in sidecars(fs, &file, &target_path, options) {
+                // sidecars follow their primary file, so a stale sidecar is replaced rather than prompted for.
+                if fs.exists(&sidecar_target) {
+                    trash_file(fs, &sidecar_target);
+                    let target = sidecar_target.to_string_lossy();
+                    log.push(LogRow::new(&sidecar, &target, "overwritten", "a stale sidecar was trashed"));
+                }
+                print_status!(
+                    "{}",
+                    messages::copying_sidecar(&sidecar.to_string_lossy(), &sidecar_target.to_string_lossy())
+                );
+                let bytes = match fs.copy(&sidecar, &sidecar_target) {
+                    Ok(bytes) => bytes,
+                    Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {
+                        print_warn!(
+                            Warning::Failure,
+                            "{}",
+                            messages::interrupted_removed(&sidecar_target.to_string_lossy())
+                        );
+                        let target = sidecar_target.to_string_lossy();
+                        log.push(LogRow::new(&sidecar, &target, "skipped", "the copy was interrupted"));
+                        break;
+                    }
+                    Err(e) => panic!("Failed to copy sidecar file \"{}\": {}", sidecar.to_string_lossy(), e),
+                };
+                summary::record_output(sidecar_target.to_string_lossy());
+                summary::record_file(bytes);
+            }
+        } else if options.dry_run {
+            preview::print_unchanged(&file_name, "no lookup entry");
+        } else {
+            print_warn!(Warning::UnmatchedRecord, "{}", messages::no_lookup_entry(&file_name));
+            summary::record_count("unmatched", 1);
+            log.push(LogRow::new(&file, "", "skipped", "no lookup entry"));
+        }
+    }
+
+    for (_, cache) in &caches {
+        cache.save();
+    }
+    state::mark_tns(Stage::Formatted, &formatted);
+    state::mark_tns(Stage::Delivered, &delivered);
+    if let Some(output) = options.mapping.as_deref().filter(|_| !options.dry_run) {
+        write_mapping(output, &mapping);
+    }
+    if !log.is_empty() {
+        write_log(options.log.as_deref().unwrap_or("format_log.csv"), &log);
+    }
+    if options.check_orphans && remote.is_none() {
+        for orphan in find_orphans(fs, &output_dir, &extensions, lookup_table, &naming, &records, options) {
+            print_warn!(Warning::OrphanFile, "{}", messages::orphan_file(&orphan.to_string_lossy()));
+        }
+    }
+    if interrupted() {
+        // the files copied so far are cached and recorded above, so an `--incremental` rerun picks up from here.
+        std::process::exit(130);
+    }
+    if failed_uploads > 0 {
+        summary::record_count("failed uploads", failed_uploads);
+    }
+    failed_uploads == 0
+}
+
+/// Splits an input file name into its tn and extension, e.g. `v.1.pdf` into `v.1` and `pdf`. A file without an
+/// extension has an empty one.
+fn tn_and_extension(file: &Path) -> (String, String) {
+    let tn = file.file_stem().expect("Failed to get file name.").to_string_lossy().to_string();
+    let ext = file.extension().map(|ext| ext.to_string_lossy().to_string()).unwrap_or_default();
+    (tn, ext)
+}
+
+/// Appends an extension to a target name, unless the input had none.
+fn with_extension(name: String, ext: &str) -> String {
+    if ext.is_empty() { name } else { format!("{}.{}", name, ext) }
+}
+
+/// Returns where an input file's output goes: in `output_dir`, or beside the input if there is none.
+fn output_path(file: &Path, output_dir: &str, target_file: &str) -> PathBuf {
+    if output_dir.is_empty() { file.with_file_name(target_file) } else { Path::new(output_dir).join(target_file) }
+}
+
+/// Checks that every input file with a lookup entry (and each of its sidecars) has an output named as
+/// `copy_and_rename_files` would name it with the same options, equal in size and SHA-256 digest. Outputs with embedded
+/// metadata differ from their inputs, so they are only checked to exist. Returns false on any missing or mismatched output.
+pub fn verify_output(
+    fs: &dyn Filesystem,
+    files: &[PathBuf],
+    lookup_table: &BTreeMap<String, IssueData>,
+    options: &FormatOptions
+) -> bool {
+    assert!(
+        options.output.as_deref().and_then(RemoteTarget::parse).is_none(),
+        "Remote outputs cannot be verified. Download them and pass their directory as --output."
+    );
+    let files = options.selection.select(files.to_vec(), "files", |file| file.to_string_lossy().to_string());
+    let output_dir = options.output.clone().unwrap_or_default();
+    let records = options.records
+        .as_deref()
+        .map(|path| parse_records(path, options.join_on))
+        .unwrap_or_default();
+    let naming = file_naming(lookup_table, options);
+    let embedded = options.embed_xmp || options.embed_pdf_metadata;
+
+    let (mut ok, mut missing, mut mismatched, mut unmatched) = (0, 0, 0, 0);
+    let mut orphans = 0;
+    if options.check_orphans {
+        let extensions = output_extensions(&files, options);
+        for orphan in find_orphans(fs, &output_dir, &extensions, lookup_table, &naming, &records, options) {
+            print_status!("{} {}", "ORPHAN".red().bold(), orphan.to_string_lossy());
+            orphans += 1;
+        }
+    }
+    for file in &files {
+        let file_name = file.file_name().expect("Failed to get file name.").to_string_lossy();
+        let (tn, ext) = tn_and_extension(file);
+        let Some(issue) = lookup_table.get(&normalize_tn(&tn)) else {
+            print_status!("{} {}", "UNMATCHED".yellow().bold(), file_name);
+            unmatched += 1;
+            continue;
+        };
+        let target_file = with_extension(target_name(issue, &naming, &records, options), &ext);
+        let target_path = output_path(file, &output_dir, &target_file);
+        let mut outputs = vec![(file.clone(), target_path.clone())];
+        outputs.extend(sidecars(fs, file, &target_path, options));
+        for (source, target) in outputs {
+            let source_name = source.file_name().expect("Failed to get file name.").to_string_lossy().to_string();
+            let Ok(target_bytes) = fs.len(&target) else {
+                let target = target.to_string_lossy();
+                print_status!("{} {} -> {}", "MISSING".red().bold(), source_name, target);
+                missing += 1;
+                continue;
+            };
+            // only the primary file has metadata embedded; sidecars are plain copies.
+            if embedded && source == *file {
+                ok += 1;
+                continue;
+            }
+            let (source_sha256, source_bytes) = checksum::sha256_file(fs, &source);
+            summary::record_file(source_bytes);
+            if target_bytes != source_bytes {
+                print_status!(
+                    "{} {} -> {} ({} bytes, expected {})",
+                    "MISMATCHED".red().bold(),
+                    source_name,
+                    target.to_string_lossy(),
+                    target_bytes,
+                    source_bytes
+                );
+                mismatched += 1;
+            } else if checksum::sha256_file(fs, &target).0 != source_sha256 {
+                let target = target.to_string_lossy();
+                print_status!("{} {} -> {} (different SHA-256)", "MISMATCHED".red().bold(), source_name, target);
+                mismatched += 1;
+            } else {
+                ok += 1;
+            }
+        }
+    }
+
+    print_status!(
+        "{} ok, {} missing, {} mismatched, {} unmatched{}",
+        ok.green(),
+        missing.red(),
+        mismatched.red(),
+        unmatched.yellow(),
+        if options.check_orphans { format!(", {} orphaned", orphans.red()) } else { String::new() }
+    );
+    let counts = [
+        ("ok", ok),
+        ("missing", missing),
+        ("mismatched", mismatched),
+        ("unmatched", unmatched),
+        ("orphaned", orphans),
+    ];
+    for (key, count) in counts {
+        summary::record_count(key, count);
+    }
+    missing == 0 && mismatched == 0 && orphans == 0
+}
+
+/// Returns the lowercased extensions of the input files and `--sidecar-ext`, the extensions outputs can have.
+fn output_extensions(files: &[PathBuf], options: &FormatOptions) -> BTreeSet<String> {
+    files
+        .iter()
+        .filter_map(|file| Some(file.extension()?.to_string_lossy().to_string()))
+        .chain(options.sidecar_ext.iter().cloned())
+        .map(|ext| ext.to_lowercase())
+        .collect()
+}
+
+/// Returns the files of `output_dir` with one of `extensions` that no issue of the lookup table is named to.
+fn find_orphans(
+    fs: &dyn Filesystem,
+    output_dir: &str,
+    extensions: &BTreeSet<String>,
+    lookup_table: &BTreeMap<String, IssueData>,
+    naming: &FileNaming,
+    records: &BTreeMap<String, ArchiveRecord>,
+    options: &FormatOptions
+) -> Vec<PathBuf> {
+    if output_dir.is_empty() {
+        print_warn!(Warning::Other, "{}", messages::orphans_need_output());
+        return vec![];
+    }
+    let Ok(entries) = fs.read_dir(Path::new(output_dir)) else {
+        return vec![];
+    };
+    let expected = lookup_table
+        .values()
+        .map(|issue| target_name(issue, naming, records, options))
+        .collect::<BTreeSet<_>>();
+    let mut orphans = entries
+        .into_iter()
+        .filter(|path| fs.is_file(path))
+        .filter(|path| {
+            let name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+            match name.split_once('.') {
+                Some((stem, ext)) => extensions.contains(&ext.to_lowercase()) && !expected.contains(stem),
+                None => false,
+            }
+        })
+        .collect::<Vec<_>>();
+    orphans.sort();
+    orphans
+}
+
+/// The existing and planned file names of each output directory, by their lowercased names, to catch names that
+/// differ only in case (`Arizona_Sun_1944-05-12.pdf` and `ARIZONA_SUN_1944-05-12.pdf`).
+#[derive(Debug, Default)]
+struct CaseFoldedNames {
+    directories: BTreeMap<PathBuf, BTreeMap<String, String>>,
+}
+
+impl CaseFoldedNames {
+    /// Adds a planned target, and returns the existing or planned name it differs from only in case, if any.
+    fn insert(&mut self, fs: &dyn Filesystem, target: &Path) -> Option<String> {
+        let directory = target.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        let names = self.directories.entry(directory.to_path_buf()).or_insert_with(|| {
+            fs.read_dir(directory)
+                .map(|entries| {
+                    entries
+                        .iter()
+                        .filter_map(|entry| entry.file_name())
+                        .map(|name| name.to_string_lossy().to_string())
+                        .map(|name| (name.to_lowercase(), name))
+                        .collect()
+                })
+                .unwrap_or_default()
+        });
+        let name = target.file_name().expect("Failed to get file name.").to_string_lossy().to_string();
+        names.insert(name.to_lowercase(), name.clone()).filter(|other| *other != name)
+    }
+}
+
+/// Writes a `tn,source,target,volume,issue,date` CSV of the formatted files.
+fn write_mapping(output: &str, mapping: &[MappingRow]) {
+    let output = safely_target_file(output);
+    let mut writer = csv_writer(&output);
+    for row in mapping {
+        writer.serialize(row).expect("Failed to write mapping row.");
+    }
+    writer.flush().expect("Failed to write mapping file.");
+    print_status!("{}", messages::saved_mappings(mapping.len(), &output));
+    summary::record_output(&output);
+}
+
+/// Writes the `source,target,action,reason` log of the run, replacing (and trashing) the previous run's log.
+fn write_log(output: &str, log: &[LogRow]) {
+    let output_path = Path::new(output);
+    if output_path.exists() {
+        trash_file(&RealFilesystem, output_path);
+    }
+    let mut writer = csv_writer(output);
+    for row in log {
+        writer.serialize(row).expect("Failed to write log row.");
+    }
+    writer.flush().expect("Failed to write log file.");
+    print_status!("{}", messages::logged_files(log.len(), output));
+    summary::record_output(output);
+}
+
+/// Returns the output file stem of an issue: rendered from the `--name-template` if one is given and all of its
+/// placeholders have values, or the formatted title.
+fn target_name(
+    issue: &IssueData,
+    naming: &FileNaming,
+    records: &BTreeMap<String, ArchiveRecord>,
+    options: &FormatOptions
+) -> String {
+    let Some(template) = options.name_template.as_ref().or(config().name_template.as_ref()) else {
+        return issue.formatted_title_with(naming);
+    };
+    let record = records
+        .get(&record_key(issue, options.join_on))
+        .map(|record| serde_json::to_value(record).expect("Failed to serialize record."));
+    let column = |column: &str| {
+        record.as_ref().and_then(|record| record.get(column)?.as_str().map(str::to_string))
+    };
+    match issue.file_name_from_template(template, naming, column) {
+        Ok(name) => name,
+        Err(placeholder) => {
+            print_warn!(Warning::Other, "{}", messages::missing_name_value(&placeholder, &issue.record_title()));
+            issue.formatted_title_with(naming)
+        }
+    }
+}
+
+/// Returns the key of an issue's `--records` row.
+fn record_key(issue: &IssueData, join: RecordJoin) -> String {
+    match join {
+        RecordJoin::Title => issue.record_title(),
+        RecordJoin::Tn => issue.tn.clone(),
+    }
+}
+
+/// Returns the file naming options. Volume and issue numbers are padded to the widest in the lookup table (at least 2).
+fn file_naming(lookup_table: &BTreeMap<String, IssueData>, options: &FormatOptions) -> FileNaming {
+    let width = |numbers: Vec<Option<u32>>| {
+        numbers
+            .into_iter()
+            .flatten()
+            .map(|number| number.to_string().len())
+            .max()
+            .unwrap_or_default()
+            .max(2)
+    };
+    let volume_issue_widths = options.volume_issue_segment.then(|| {
+        (
+            width(lookup_table.values().map(|issue| issue.volume).collect()),
+            width(lookup_table.values().map(|issue| issue.issue).collect()),
+        )
+    });
+    FileNaming { ascii: options.ascii_filenames, volume_issue_widths }
+}
+
+/// Applies the requested metadata changes to a freshly copied output file. Embedding XMP or PDF metadata rewrites the
+/// file's contents, so it is done on the disk.
+fn finish_output(
+    fs: &dyn Filesystem,
+    file: &Path,
+    issue: &IssueData,
+    target: &Path,
+    records: &BTreeMap<String, ArchiveRecord>,
+    options: &FormatOptions
+) {
+    if options.embed_xmp && xmp::supports(target) {
+        let record = records.get(&record_key(issue, options.join_on));
+        embed_issue_xmp(issue, record, options, target);
+    }
+    let is_pdf = target.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("pdf"));
+    if options.embed_pdf_metadata && is_pdf {
+        embed_issue_pdf_info(issue, target);
+    }
+    if options.preserve_metadata {
+        fs.copy_metadata(file, target).expect("Failed to copy file metadata.");
+    }
+    if options.touch_issue_date {
+        touch_issue_date(fs, issue, target);
+    }
+}
+
+/// Uploads a file (and its sidecars) to a remote output as `target_file`.
+/// Files are uploaded straight from the input unless metadata has to be embedded, in which case a temporary copy is made.
+/// Returns true if the file itself was uploaded.
+fn upload_file(
+    fs: &dyn Filesystem,
+    file: &Path,
+    issue: &IssueData,
+    target_file: &str,
+    remote: &RemoteTarget,
+    records: &BTreeMap<String, ArchiveRecord>,
+    options: &FormatOptions
+) -> bool {
+    let uploads = std::iter
+        ::once((file.to_path_buf(), PathBuf::from(target_file)))
+        .chain(sidecars(fs, file, Path::new(target_file), options))
+        .collect::<Vec<_>>();
+    if options.dry_run {
+        for (source, target) in &uploads {
+            preview::print_rename(&source.to_string_lossy(), &remote.url(&target.to_string_lossy()));
+        }
+        return false;
+    }
+
+    let staged = options.embed_xmp || options.embed_pdf_metadata || options.touch_issue_date;
+    let mut uploaded = false;
+    for (i, (source, target)) in uploads.iter().enumerate() {
+        let target = target.to_string_lossy();
+        print_status!("Uploading file \"{}\" to \"{}\"", source.to_string_lossy(), remote.url(&target));
+        let source = if i == 0 && staged {
+            let staging = std::env::temp_dir().join(format!("bulk_format_{}", target));
+            fs.copy(source, &staging).expect("Failed to copy file.");
+            finish_output(fs, file, issue, &staging, records, options);
+            staging
+        } else {
+            source.clone()
+        };
+        let bytes = fs.len(&source).unwrap_or(0);
+        let result = remote.upload(&source, &target);
+        if i == 0 && staged {
+            fs.remove_file(&source).expect("Failed to remove temporary file.");
+        }
+        match result {
+            Ok(()) => {
+                summary::record_output(remote.url(&target));
+                summary::record_file(bytes);
+                uploaded |= i == 0;
+            }
+            Err(e) => {
+                print_warn!(Warning::Failure, "{}", e);
+            }
+        }
+    }
+    uploaded
+}
+
+/// Returns `(sidecar, target)` pairs for every existing sidecar of `file`, targeting the same stem as `target`.
+fn sidecars(fs: &dyn Filesystem, file: &Path, target: &Path, options: &FormatOptions) -> Vec<(PathBuf, PathBuf)> {
+    companion_files(fs, file, &options.sidecar_ext)
+        .into_iter()
+        .map(|sidecar| {
+            let ext = sidecar.extension().expect("Failed to get file extension.").to_owned();
+            (sidecar, target.with_extension(ext))
+        })
+        .collect()
+}
+
+/// Sets the modification time of `target` to local midnight on the issue's date.
+/// Partial dates use the first day of the month or year.
+fn touch_issue_date(fs: &dyn Filesystem, issue: &IssueData, target: &Path) {
+    use chrono::{ Local, NaiveTime, TimeZone };
+
+    let date = match Date::try_from(issue.date.as_str()) {
+        Ok(date) => date,
+        Err(_) => {
+            print_warn!(Warning::OddDate, "{}", messages::modified_time_not_set(&issue.tn));
+            return;
+        }
+    };
+    let modified = Local.from_local_datetime(&date.first_day().and_time(NaiveTime::MIN))
+        .earliest()
+        .expect("Failed to convert issue date to a timestamp.");
+    fs.set_modified(target, modified.into()).expect("Failed to set file modification time.");
+}
+
+/// Reads a target CSV file into a map of node title (or normalized Call Number, joining on tn) -> record.
+pub fn parse_records(path: &str, join: RecordJoin) -> BTreeMap<String, ArchiveRecord> {
+    let mut reader = csv::Reader::from_path(path).expect("Failed to read records CSV file.");
+    reader
+        .deserialize()
+        .map(|r| {
+            let record: ArchiveRecord = r.expect("Failed to parse record.");
+            let key = match join {
+                RecordJoin::Title => record.node_title.clone(),
+                RecordJoin::Tn => normalize_tn(&record.call_number),
+            };
+            (key, record)
+        })
+        .collect()
+}
+
+/// Returns the issue's date as an ISO 8601 date (without any circa suffix), or `None` if it is undated.
+fn iso_date(issue: &IssueData) -> Option<String> {
+    let date = Date::try_from(issue.date.as_str()).ok()?;
+    Some((Date { circa: false, ..date }).to_string())
+}
+
+fn embed_issue_xmp(
+    issue: &IssueData,
+    record: Option<&ArchiveRecord>,
+    options: &FormatOptions,
+    target: &Path
+) {
+    let rights = record
+        .map(|record| record.rights_statement.as_str())
+        .filter(|rights| !rights.is_empty())
+        .or(options.rights.as_deref());
+    let fields = xmp::XmpFields {
+        title: &issue.record_title(),
+        date: iso_date(issue),
+        rights,
+    };
+    if let Err(e) = xmp::embed_xmp(target, &fields) {
+        print_warn!(Warning::Failure, "{}", messages::xmp_failed(&target.to_string_lossy(), &e.to_string()));
+    }
+}
+
+fn embed_issue_pdf_info(issue: &IssueData, target: &Path) {
+    let date = Date::try_from(issue.date.as_str()).ok();
+    let info = pdf::PdfInfo {
+        title: &issue.record_title(),
+        subject: &issue.title.replace("_", " "),
+        creation_date: date.map(|date| (date.year, date.month, date.day)),
+    };
+    if let Err(e) = pdf::embed_pdf_info(target, &info) {
+        print_warn!(Warning::Failure, "{}", messages::pdf_info_failed(&target.to_string_lossy(), &e.to_string()));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn split_tn_and_extension() {
+        let split = |name: &str| tn_and_extension(Path::new(name));
+        assert_eq!(split("scans/tn1.tif"), ("tn1".to_string(), "tif".to_string()));
+        assert_eq!(split("v.1.pdf"), ("v.1".to_string(), "pdf".to_string()));
+        assert_eq!(split("tn1"), ("tn1".to_string(), String::new()));
+        assert_eq!(with_extension("Arizona_Sun_1944-05-12".to_string(), "pdf"), "Arizona_Sun_1944-05-12.pdf");
+        assert_eq!(with_extension("Arizona_Sun_1944-05-12".to_string(), ""), "Arizona_Sun_1944-05-12");
+    }
+
+    #[test]
+    fn format_files_in_memory() {
+        use crate::filesystem::MemoryFilesystem;
+
+        let dir = crate::test_util::TestDir::new("format_in_memory");
+        let fs = MemoryFilesystem::new();
+        fs.add_file("scans/tn1.pdf", 10);
+        fs.add_file("scans/tn1.txt", 3);
+        fs.add_file("scans/tn2.pdf", 20);
+        fs.add_file("out/Arizona_Sun_1944-05-12.txt", 1);
+        let lookup_table = [
+            ("tn1", "Arizona Sun. v. 3, no. 12 (May 12, 1944)"),
+            ("tn2", "Arizona Sun. v. 3, no. 13 (May 19, 1944)"),
+        ]
+            .map(|(tn, title)| (tn.to_string(), IssueData::new(tn.to_string(), title.to_string(), String::new())))
+            .into_iter()
+            .collect::<BTreeMap<_, _>>();
+        let options = FormatOptions {
+            output: Some("out".to_string()),
+            sidecar_ext: vec!["txt".to_string()],
+            touch_issue_date: true,
+            log: Some(dir.join("format_log.csv").to_string_lossy().to_string()),
+            ..Default::default()
+        };
+        let files = vec![PathBuf::from("scans/tn1.pdf"), PathBuf::from("scans/tn2.pdf")];
+        assert!(copy_and_rename_files(&fs, &[], files.clone(), &lookup_table, &options));
+
+        let outputs = fs
+            .files()
+            .into_iter()
+            .filter(|(path, _)| path.starts_with("out") && !path.starts_with(Path::new("out").join(crate::TRASH_DIR)))
+            .map(|(path, file)| (path, file.bytes))
+            .collect::<Vec<_>>();
+        assert_eq!(outputs, [
+            ("out/Arizona_Sun_1944-05-12.pdf", 10),
+            ("out/Arizona_Sun_1944-05-12.txt", 3),
+            ("out/Arizona_Sun_1944-05-19.pdf", 20),
+        ].map(|(path, bytes)| (PathBuf::from(path), bytes)));
+        // the stale sidecar was trashed in memory, and nothing was written next to the test.
+        let trashed = Path::new("out").join(crate::TRASH_DIR).join(crate::run_timestamp()).join("Arizona_Sun_1944-05-12.txt");
+        assert_eq!(fs.len(&trashed).unwrap(), 1);
+        assert!(!Path::new("out").exists());
+        let modified = chrono::DateTime::<chrono::Local>::from(fs.modified(Path::new("out/Arizona_Sun_1944-05-19.pdf")).unwrap());
+        assert_eq!(modified.date_naive(), chrono::NaiveDate::from_ymd_opt(1944, 5, 19).unwrap());
+
+        assert!(verify_output(&fs, &files, &lookup_table, &options));
+        fs.remove_file(Path::new("out/Arizona_Sun_1944-05-12.txt")).unwrap();
+        assert!(!verify_output(&fs, &files, &lookup_table, &options));
+    }
+
+    #[test]
+    fn dry_run_changes_nothing() {
+        use crate::filesystem::MemoryFilesystem;
+
+        let fs = MemoryFilesystem::new();
+        fs.add_file("scans/tn1.pdf", 10);
+        fs.add_file("scans/tn1.txt", 3);
+        fs.add_file("scans/tn9.pdf", 10);
+        let issue = IssueData::new("tn1".to_string(), "Arizona Sun. v. 3, no. 12 (May 12, 1944)".to_string(), String::new());
+        let lookup_table = BTreeMap::from([("tn1".to_string(), issue)]);
+        let options = FormatOptions {
+            output: Some("out".to_string()),
+            sidecar_ext: vec!["txt".to_string()],
+            dry_run: true,
+            ..Default::default()
+        };
+        let before = fs.files().into_keys().collect::<Vec<_>>();
+        let files = vec![PathBuf::from("scans/tn1.pdf"), PathBuf::from("scans/tn9.pdf")];
+        assert!(copy_and_rename_files(&fs, &[], files, &lookup_table, &options));
+        assert_eq!(fs.files().into_keys().collect::<Vec<_>>(), before);
+        assert!(!fs.exists(Path::new("out")));
+    }
+
+    #[test]
+    fn preserve_times_and_permissions() {
+        use crate::filesystem::RealFilesystem;
+
+        let dir = crate::test_util::TestDir::new("preserve_metadata");
+        let file = dir.write("scans/tn1.pdf", "pdf");
+        let modified = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000_000);
+        std::fs::File::options().write(true).open(&file).unwrap().set_modified(modified).unwrap();
+        let mut permissions = std::fs::metadata(&file).unwrap().permissions();
+        permissions.set_readonly(true);
+        std::fs::set_permissions(&file, permissions).unwrap();
+        let issue = IssueData::new("tn1".to_string(), "Arizona Sun. v. 3, no. 12 (May 12, 1944)".to_string(), String::new());
+        let lookup_table = BTreeMap::from([("tn1".to_string(), issue)]);
+        let options = FormatOptions {
+            output: Some(dir.join("out").to_string_lossy().to_string()),
+            preserve_metadata: true,
+            ..Default::default()
+        };
+        assert!(copy_and_rename_files(&RealFilesystem, &[], vec![file], &lookup_table, &options));
+
+        let metadata = std::fs::metadata(dir.join("out/Arizona_Sun_1944-05-12.pdf")).unwrap();
+        assert_eq!(metadata.modified().unwrap(), modified);
+        assert!(metadata.permissions().readonly());
+    }
+
+    #[test]
+    fn touch_issue_dates() {
+        use chrono::{ DateTime, Local, NaiveDate };
+        use crate::filesystem::MemoryFilesystem;
+
+        let fs = MemoryFilesystem::new();
+        let touched = |title: &str| {
+            fs.add_file("out/issue.pdf", 1);
+            let before = fs.modified(Path::new("out/issue.pdf")).unwrap();
+            let issue = IssueData::new("tn1".to_string(), title.to_string(), String::new());
+            touch_issue_date(&fs, &issue, Path::new("out/issue.pdf"));
+            let modified = fs.modified(Path::new("out/issue.pdf")).unwrap();
+            (modified != before).then(|| DateTime::<Local>::from(modified).naive_local())
+        };
+        let midnight = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap().and_hms_opt(0, 0, 0).unwrap();
+        assert_eq!(touched("Arizona Sun. v. 3, no. 12 (May 12, 1944)"), Some(midnight(1944, 5, 12)));
+        assert_eq!(touched("Arizona Sun. v. 3, no. 12 (May 1944)"), Some(midnight(1944, 5, 1)));
+        assert_eq!(touched("Arizona Sun. v. 3, no. 12 (n.d.)"), None);
+    }
+
+    #[test]
+    fn pair_sidecars_with_targets() {
+        use crate::filesystem::MemoryFilesystem;
+
+        let fs = MemoryFilesystem::new();
+        fs.add_file("scans/tn1.pdf", 10);
+        fs.add_file("scans/tn1.txt", 1);
+        fs.add_file("scans/tn1.hocr", 1);
+        let options = FormatOptions { sidecar_ext: ["txt", ".hocr", "xml", "pdf"].map(String::from).to_vec(), ..Default::default() };
+        let target = Path::new("out/Arizona_Sun_1944-05-12.pdf");
+        assert_eq!(sidecars(&fs, Path::new("scans/tn1.pdf"), target, &options), [
+            ("scans/tn1.txt", "out/Arizona_Sun_1944-05-12.txt"),
+            ("scans/tn1.hocr", "out/Arizona_Sun_1944-05-12.hocr"),
+        ].map(|(sidecar, target)| (PathBuf::from(sidecar), PathBuf::from(target))));
+    }
+
+    #[test]
+    fn map_formatted_files() {
+        use crate::filesystem::MemoryFilesystem;
+
+        let dir = crate::test_util::TestDir::new("format_mapping");
+        let fs = MemoryFilesystem::new();
+        let lookup_table = [
+            ("tn1", "Arizona Sun. v. 3, no. 12 (May 12, 1944)"),
+            ("tn2", "Arizona Sun. (ca. Aug. 1910)"),
+            ("tn3", "Arizona Sun. (n.d.)"),
+        ]
+            .map(|(tn, title)| {
+                fs.add_file(format!("scans/{}.pdf", tn), 1);
+                (tn.to_string(), IssueData::new(tn.to_string(), title.to_string(), String::new()))
+            })
+            .into_iter()
+            .collect::<BTreeMap<_, _>>();
+        let mapping = dir.join("mapping.csv");
+        let options = FormatOptions {
+            output: Some("out".to_string()),
+            mapping: Some(mapping.to_string_lossy().to_string()),
+            ..Default::default()
+        };
+        let files = ["tn1", "tn2", "tn3"].map(|tn| PathBuf::from(format!("scans/{}.pdf", tn))).to_vec();
+        assert!(copy_and_rename_files(&fs, &[], files, &lookup_table, &options));
+        assert_eq!(
+            std::fs::read_to_string(mapping).unwrap(),
+            "tn,source,target,volume,issue,date\n\
+             tn1,tn1.pdf,out/Arizona_Sun_1944-05-12.pdf,3,12,1944-05-12\n\
+             tn2,tn2.pdf,out/Arizona_Sun_1910-08_ca.pdf,,,1910-08\n\
+             tn3,tn3.pdf,out/Arizona_Sun_nd_tn3.pdf,,,\n"
+        );
+    }
+
+    #[test]
+    fn catch_names_differing_only_in_case() {
+        use crate::filesystem::MemoryFilesystem;
+
+        let fs = MemoryFilesystem::new();
+        fs.add_file("out/ARIZONA_SUN_1944-05-12.pdf", 1);
+        let mut names = CaseFoldedNames::default();
+        assert_eq!(
+            names.insert(&fs, Path::new("out/Arizona_Sun_1944-05-12.pdf")),
+            Some("ARIZONA_SUN_1944-05-12.pdf".to_string())
+        );
+        assert_eq!(names.insert(&fs, Path::new("out/Arizona_Sun_1944-05-19.pdf")), None);
+        assert_eq!(
+            names.insert(&fs, Path::new("out/arizona_sun_1944-05-19.pdf")),
+            Some("Arizona_Sun_1944-05-19.pdf".to_string())
+        );
+        // the same name planned again is not a case collision, and other directories are separate.
+        assert_eq!(names.insert(&fs, Path::new("out/arizona_sun_1944-05-19.pdf")), None);
+        assert_eq!(names.insert(&fs, Path::new("other/ARIZONA_SUN_1944-05-19.pdf")), None);
+    }
+
+    #[test]
+    fn verify_outputs_by_size_and_digest() {
+        use crate::filesystem::MemoryFilesystem;
+
+        let fs = MemoryFilesystem::new();
+        fs.create_dir_all(Path::new("scans")).unwrap();
+        fs.create_dir_all(Path::new("out")).unwrap();
+        fs.write(Path::new("scans/tn1.pdf"), b"abc").unwrap();
+        let issue = IssueData::new("tn1".to_string(), "Arizona Sun. v. 3, no. 12 (May 12, 1944)".to_string(), String::new());
+        let lookup_table = BTreeMap::from([("tn1".to_string(), issue)]);
+        let options = FormatOptions { output: Some("out".to_string()), ..Default::default() };
+        let files = [PathBuf::from("scans/tn1.pdf")];
+        let output = Path::new("out/Arizona_Sun_1944-05-12.pdf");
+        for (contents, verified) in [(&b"abc"[..], true), (b"abd", false), (b"ab", false)] {
+            fs.write(output, contents).unwrap();
+            assert_eq!(verify_output(&fs, &files, &lookup_table, &options), verified);
+        }
+        // embedded metadata changes the output, so it only has to exist.
+        let embedded = FormatOptions { embed_pdf_metadata: true, ..options };
+        assert!(verify_output(&fs, &files, &lookup_table, &embedded));
+    }
+
+    #[test]
+    fn log_skipped_and_overwritten_files() {
+        use crate::filesystem::MemoryFilesystem;
+
+        let dir = crate::test_util::TestDir::new("format_log");
+        let fs = MemoryFilesystem::new();
+        fs.add_file("scans/tn1.pdf", 10);
+        fs.add_file("scans/tn1.txt", 3);
+        fs.add_file("scans/tn9.pdf", 10);
+        fs.add_file("out/Arizona_Sun_1944-05-12.txt", 1);
+        let issue = IssueData::new("tn1".to_string(), "Arizona Sun. v. 3, no. 12 (May 12, 1944)".to_string(), String::new());
+        let lookup_table = BTreeMap::from([("tn1".to_string(), issue)]);
+        let log = dir.join("format_log.csv");
+        let options = FormatOptions {
+            output: Some("out".to_string()),
+            sidecar_ext: vec!["txt".to_string()],
+            log: Some(log.to_string_lossy().to_string()),
+            ..Default::default()
+        };
+        let files = vec![PathBuf::from("scans/tn1.pdf"), PathBuf::from("scans/tn9.pdf")];
+        assert!(copy_and_rename_files(&fs, &[], files.clone(), &lookup_table, &options));
+        let expected = "source,target,action,reason\n\
+                        scans/tn1.txt,out/Arizona_Sun_1944-05-12.txt,overwritten,a stale sidecar was trashed\n\
+                        scans/tn9.pdf,,skipped,no lookup entry\n";
+        assert_eq!(std::fs::read_to_string(&log).unwrap(), expected);
+
+        // the next run's log replaces this one, which is trashed.
+        assert!(copy_and_rename_files(&fs, &[], vec![files[1].clone()], &lookup_table, &options));
+        assert_eq!(std::fs::read_to_string(&log).unwrap(), "source,target,action,reason\nscans/tn9.pdf,,skipped,no lookup entry\n");
+        let trashed = dir.join(crate::TRASH_DIR).join(crate::run_timestamp()).join("format_log.csv");
+        assert_eq!(std::fs::read_to_string(trashed).unwrap(), expected);
+    }
+
+    #[test]
+    fn find_orphaned_outputs() {
+        use crate::filesystem::MemoryFilesystem;
+
+        let fs = MemoryFilesystem::new();
+        fs.add_file("scans/tn1.pdf", 3);
+        fs.add_file("out/Arizona_Sun_1944-05-12.pdf", 3);
+        fs.add_file("out/Arizona_Sun_1944-05-13.pdf", 3);
+        fs.add_file("out/notes.md", 3);
+        let issue = IssueData::new("tn1".to_string(), "Arizona Sun. v. 3, no. 12 (May 12, 1944)".to_string(), String::new());
+        let lookup_table = BTreeMap::from([("tn1".to_string(), issue)]);
+        let options = FormatOptions { output: Some("out".to_string()), check_orphans: true, ..Default::default() };
+        let files = [PathBuf::from("scans/tn1.pdf")];
+        let naming = file_naming(&lookup_table, &options);
+        let extensions = output_extensions(&files, &options);
+        // only files with the extension of an output can be orphans.
+        let orphans = find_orphans(&fs, "out", &extensions, &lookup_table, &naming, &BTreeMap::new(), &options);
+        assert_eq!(orphans, [PathBuf::from("out/Arizona_Sun_1944-05-13.pdf")]);
+        assert!(!verify_output(&fs, &files, &lookup_table, &options));
+
+        fs.remove_file(Path::new("out/Arizona_Sun_1944-05-13.pdf")).unwrap();
+        assert!(verify_output(&fs, &files, &lookup_table, &options));
+    }
+}
@@ -0,0 +1,697 @@
+use std::path::{ Path, PathBuf };
+use owo_colors::OwoColorize;
+
+use crate::{
+    archive::{ self, ArchiveFormat },
+    checksum,
+    collect::{ collect_directories, FileFilters },
+    companion_files,
+    config::config,
+    date::Date,
+    filesystem::Filesystem,
+    messages,
+    parse_size,
+    preview,
+    print_status,
+    print_warn,
+    state::{ self, Stage },
+    summary,
+    trash_file,
+    warnings::Warning,
+};
+
+#[derive(clap::Args, Debug)]
+pub struct GroupOptions {
+    /// The number of files to include in each group. If the number of files in the directory is not divisible by `n`, the last group will contain the remainder.
+    #[arg(short, required_unless_present = "max_bytes")]
+    pub n: Option<usize>,
+
+    /// The maximum total size of each group, e.g. `50GB` or `500MiB`. A group closes when either this or `n` is reached.
+    #[arg(long, value_parser = parse_size)]
+    pub max_bytes: Option<u64>,
+
+    /// Name group directories with their full min and max dates (`0_1944-05-12_1944-09-30`) instead of only the years (`0_1944-1944`).
+    /// Groups without dated files are named `0_nd` either way.
+    #[arg(long)]
+    pub full_dates: bool,
+
+    /// The index of the first new group, e.g. `17` to continue from previously delivered batches.
+    /// Defaults to one past the highest existing group in the directory, or `0`.
+    #[arg(long)]
+    pub start_index: Option<usize>,
+
+    /// Zero-pad group indices to this many digits, e.g. `3` for `017_1944-1952`.
+    #[arg(long, default_value_t = 1)]
+    pub index_width: usize,
+
+    /// Prefix group directory names, e.g. `batch_` for `batch_017_1944-1952`. Only groups with this prefix are
+    /// counted when continuing from existing groups.
+    #[arg(long, default_value = "")]
+    pub index_prefix: String,
+
+    /// Keep each publication's files together: a group closes early at a title boundary when the next publication's
+    /// files would not fit in it but fit in a group of their own. Publications larger than a group are still split.
+    #[arg(long)]
+    pub partition_by_title: bool,
+
+    /// Companion file extensions, e.g. `txt,xml,jp2`. Same-stem companions move into the same group as their primary file and count toward `--max-bytes`.
+    #[arg(long, value_delimiter = ',')]
+    pub sidecar_ext: Vec<String>,
+
+    /// Also pack each completed group into a `zip` or `tar` archive next to its directory, with a `SHA256SUMS` file inside.
+    #[arg(long, value_enum)]
+    pub archive: Option<ArchiveFormat>,
+
+    /// Print the planned groups and moves without moving any files.
+    #[arg(long)]
+    pub plan: bool,
+
+    /// Run the grouping against an in-memory copy of the directory, printing every move without changing any files.
+    /// Archives, `groups.csv`, and the processing state are skipped.
+    #[arg(long, conflicts_with = "plan")]
+    pub simulate: bool,
+}
+
+/// Groups the files of `directories` into numbered directories created in the first one.
+pub fn group_files(
+    fs: &dyn Filesystem,
+    directories: &[String],
+    extensions: &[String],
+    recursive: bool,
+    filters: &FileFilters,
+    options: &GroupOptions
+) {
+    let directory = directories.first().expect("No directory to group.").as_str();
+
+    // leave files that were already grouped by a previous run where they are, and let companions follow their primary file.
+    let grouped = directories
+        .iter()
+        .flat_map(|directory| existing_groups(fs, directory, &options.index_prefix))
+        .map(|(_, dir)| dir)
+        .collect::<Vec<_>>();
+    let files = collect_directories(fs, directories, extensions, recursive, filters)
+        .into_iter()
+        .filter(|file| !grouped.iter().any(|dir| file.starts_with(dir)))
+        .filter(|file| !is_companion(fs, file, &options.sidecar_ext))
+        .collect::<Vec<_>>();
+    group_into(fs, directory, files, options);
+}
+
+/// Groups listed files, in order, into numbered directories created in `directory`, or else in the first file's directory.
+pub fn group_listed_files(
+    fs: &dyn Filesystem,
+    directory: Option<&String>,
+    files: Vec<PathBuf>,
+    options: &GroupOptions
+) {
+    let Some(first) = files.first() else {
+        print_status!("No files to group.");
+        return;
+    };
+    let directory = match directory {
+        Some(directory) => directory.clone(),
+        None =>
+            match first.parent().map(|parent| parent.to_string_lossy().to_string()) {
+                Some(parent) if !parent.is_empty() => parent,
+                _ => ".".to_string(),
+            }
+    };
+    let files = files
+        .into_iter()
+        .filter(|file| !is_companion(fs, file, &options.sidecar_ext))
+        .collect();
+    group_into(fs, &directory, files, options);
+}
+
+/// Moves files into numbered group directories created in `directory`, numbered after any existing groups.
+fn group_into(fs: &dyn Filesystem, directory: &str, files: Vec<PathBuf>, options: &GroupOptions) {
+    let existing = existing_groups(fs, directory, &options.index_prefix);
+    let next_index = existing.iter().map(|(i, _)| i + 1).max().unwrap_or(0);
+    let first_index = options.start_index.unwrap_or(next_index);
+    if !existing.is_empty() {
+        print_status!(
+            "Found {} existing groups. New groups will start at index {}.",
+            existing.len(),
+            first_index
+        );
+    }
+    if first_index < next_index {
+        print_warn!(Warning::Other, "{}", messages::start_index_reused(first_index));
+    }
+
+    let groups = plan_groups(fs, files, options);
+    let mut manifest = vec![];
+    if !options.plan && !groups.is_empty() {
+        let moved = groups
+            .iter()
+            .flatten()
+            .flat_map(|file| with_companions(fs, file, options))
+            .collect::<Vec<_>>();
+        checksum::snapshot_files(fs, directory, &moved, "group-files");
+    }
+
+    for (i, group) in groups.iter().enumerate() {
+        let i = format!("{}{:0width$}", options.index_prefix, first_index + i, width = options.index_width);
+        // if the files have dates at the end, find the min and max dates.
+        let mut dates: Vec<Date> = vec![];
+        for file in group {
+            let stem = file.file_stem().expect("Failed to get file name.").to_string_lossy();
+            match Date::from_formatted_title(&stem) {
+                Ok(date) => dates.push(date),
+                Err(_) => {
+                    print_warn!(Warning::UndatedIssue, "{}", messages::excluded_from_group_name(&stem));
+                }
+            }
+        }
+
+        #[allow(unused_parens)]
+        let group_name = if
+            let Some((min_date, max_date)) = ({
+                dates
+                    .iter()
+                    .min()
+                    .and_then(|min_date| {
+                        dates
+                            .iter()
+                            .max()
+                            .map(|max_date| (min_date, max_date))
+                    })
+            })
+        {
+            if options.full_dates {
+                format!("{i}_{min_date}_{max_date}")
+            } else {
+                let min_date = min_date.year;
+                let max_date = max_date.year;
+                format!("{i}_{min_date}-{max_date}")
+            }
+        } else {
+            format!("{i}_{UNDATED_GROUP}")
+        };
+        let group_dir = Path::new(directory).join(&group_name);
+        if options.plan {
+            print_status!("{} ({} files)", group_dir.to_string_lossy().bold(), group.len());
+            for file in group.iter().flat_map(|file| with_companions(fs, file, options)) {
+                let target = group_dir.join(file.file_name().expect("Failed to get file name."));
+                preview::print_rename(&file.to_string_lossy(), &target.to_string_lossy());
+            }
+            if let Some(format) = options.archive {
+                let archive = archive::archive_path(&group_dir, format);
+                print_status!("{} {}", "Archive:".dimmed(), archive.to_string_lossy());
+            }
+            continue;
+        }
+
+        fs.create_dir_all(&group_dir).expect("Failed to create group directory.");
+
+        for file in group.iter().flat_map(|file| with_companions(fs, file, options)) {
+            let target = group_dir.join(file.file_name().expect("Failed to get file name."));
+            if fs.exists(&target) {
+                print_warn!(Warning::NameCollision, "{}", messages::will_be_replaced(&target.to_string_lossy()));
+                trash_file(fs, &target);
+            }
+            print_status!(
+                "Moving file \"{}\" to \"{}\"",
+                file.to_string_lossy(),
+                target.to_string_lossy()
+            );
+            let bytes = fs.len(&file).unwrap_or(0);
+            fs.rename(&file, &target).expect("Failed to move file.");
+            summary::record_output(target.to_string_lossy());
+            summary::record_file(bytes);
+            manifest.push((group_name.clone(), target));
+        }
+
+        if let Some(format) = options.archive.filter(|_| !fs.is_simulated()) {
+            let archive = archive::archive_directory(&group_dir, format);
+            print_status!("Archived \"{}\" to \"{}\"", group_dir.to_string_lossy(), archive.to_string_lossy());
+            summary::record_output(archive.to_string_lossy());
+        }
+    }
+
+    if fs.is_simulated() {
+        print_status!("{}", "Simulated. No files were changed.".bold());
+        return;
+    }
+    if !manifest.is_empty() {
+        write_group_manifest(fs, directory, &manifest);
+    }
+    if !options.plan {
+        let stems = groups
+            .iter()
+            .flatten()
+            .map(|file| file.file_stem().expect("Failed to get file stem.").to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        state::mark_files(Stage::Grouped, &stems);
+    }
+}
+
+/// One row of `groups.csv`: a file moved into a group. `tn` is empty for files the state file has no tn for, and `date`
+/// for files without a date.
+#[derive(Debug, serde::Serialize)]
+struct GroupManifestRow {
+    group: String,
+    file: String,
+    tn: String,
+    date: String,
+    bytes: u64,
+    sha256: String,
+}
+
+/// Appends the `group,file,tn,date,bytes,sha256` packing list of the moved files to `groups.csv` in `directory`,
+/// so it lists every group made there.
+fn write_group_manifest(fs: &dyn Filesystem, directory: &str, moved: &[(String, PathBuf)]) {
+    let path = Path::new(directory).join(GROUP_MANIFEST);
+    let exists = path.exists();
+    let file = std::fs::OpenOptions
+        ::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .expect("Failed to open group manifest.");
+    let mut writer = csv::WriterBuilder::new().has_headers(!exists).from_writer(file);
+    let tns = state::tns_by_file();
+    for (group, file) in moved {
+        let stem = file.file_stem().expect("Failed to get file stem.").to_string_lossy();
+        let (sha256, bytes) = checksum::sha256_file(fs, file);
+        let row = GroupManifestRow {
+            group: group.clone(),
+            file: file.file_name().expect("Failed to get file name.").to_string_lossy().to_string(),
+            tn: tns.get(stem.as_ref()).cloned().unwrap_or_default(),
+            date: Date::from_formatted_title(&stem).map(|date| date.to_string()).unwrap_or_default(),
+            bytes,
+            sha256,
+        };
+        writer.serialize(row).expect("Failed to write group manifest row.");
+    }
+    writer.flush().expect("Failed to write group manifest.");
+    print_status!("Listed {} files in \"{}\".", moved.len(), path.to_string_lossy());
+    summary::record_output(path.to_string_lossy());
+}
+
+/// The packing list of every group made in a directory.
+const GROUP_MANIFEST: &str = "groups.csv";
+
+/// The date range of a group without dated files, e.g. `3_nd`.
+const UNDATED_GROUP: &str = "nd";
+
+/// Returns the index and path of every group directory created by a previous run, e.g. `3_1944-1952`.
+fn existing_groups(fs: &dyn Filesystem, directory: &str, prefix: &str) -> Vec<(usize, PathBuf)> {
+    fs.read_dir(Path::new(directory))
+        .expect("Failed to read directory. Path does not exist or is not a directory.")
+        .into_iter()
+        .filter(|path| fs.is_dir(path))
+        .filter_map(|path| group_index(&path, prefix).map(|i| (i, path)))
+        .collect()
+}
+
+/// Parses the index from a group directory name as `group_into` writes it, after `prefix`: `3_1944-1952`,
+/// `3_1944-05-12_1952-07-11` (with the circa suffix on approximate dates, e.g. `3_1910_ca_1944-05-12`), or `3_nd`.
+fn group_index(path: &Path, prefix: &str) -> Option<usize> {
+    let name = path.file_name()?.to_string_lossy();
+    let (index, dates) = name.strip_prefix(prefix)?.split_once('_')?;
+    if index.is_empty() || !index.chars().all(|c| c.is_ascii_digit()) || !is_group_date_range(dates) {
+        return None;
+    }
+    index.parse().ok()
+}
+
+/// Returns true if `dates` is the date range of a group name: `1944-1952`, `1944-05-12_1952-07-11`, or `nd`.
+fn is_group_date_range(dates: &str) -> bool {
+    // only dates that are written back the same way, so `1944-5-12` or `01944` are not mistaken for group dates.
+    let is_date = |date: &str| Date::try_from(date).is_ok_and(|parsed| parsed.to_string() == date);
+    let is_year = |year: &str| year.parse::<i32>().is_ok_and(|parsed| parsed.to_string() == year);
+    dates == UNDATED_GROUP ||
+        dates.split_once('-').is_some_and(|(min, max)| is_year(min) && is_year(max)) ||
+        dates.match_indices('_').any(|(i, _)| is_date(&dates[..i]) && is_date(&dates[i + 1..]))
+}
+
+/// Returns true if `file` has a companion extension and a primary file with the same stem exists.
+fn is_companion(fs: &dyn Filesystem, file: &Path, sidecar_ext: &[String]) -> bool {
+    is_sidecar_ext(file, sidecar_ext) &&
+        fs
+            .read_dir(file.parent().expect("Failed to get parent directory."))
+            .expect("Failed to read directory.")
+            .into_iter()
+            .any(|other| {
+                other != file &&
+                    other.file_stem() == file.file_stem() &&
+                    !is_sidecar_ext(&other, sidecar_ext)
+            })
+}
+
+/// Returns true if the file's extension is one of `sidecar_ext`.
+fn is_sidecar_ext(file: &Path, sidecar_ext: &[String]) -> bool {
+    file.extension().is_some_and(|ext| {
+        sidecar_ext
+            .iter()
+            .any(|sidecar| sidecar.trim_start_matches('.').eq_ignore_ascii_case(&ext.to_string_lossy()))
+    })
+}
+
+/// Returns the file followed by its companion files.
+fn with_companions(fs: &dyn Filesystem, file: &Path, options: &GroupOptions) -> Vec<PathBuf> {
+    let mut files = vec![file.to_path_buf()];
+    files.extend(companion_files(fs, file, &options.sidecar_ext));
+    files
+}
+
+/// Splits files into groups, closing a group when it holds `n` files or adding the next file would exceed `max_bytes`.
+fn plan_groups(fs: &dyn Filesystem, files: Vec<PathBuf>, options: &GroupOptions) -> Vec<Vec<PathBuf>> {
+    let sizes = files
+        .iter()
+        .map(|file| {
+            match options.max_bytes {
+                Some(_) =>
+                    with_companions(fs, file, options)
+                        .iter()
+                        .map(|file| fs.len(file).expect("Failed to read file metadata."))
+                        .sum(),
+                None => 0,
+            }
+        })
+        .collect::<Vec<_>>();
+    let titles = options.partition_by_title.then(|| {
+        files
+            .iter()
+            .map(|file| publication_title(&file.file_stem().expect("Failed to get file stem.").to_string_lossy()))
+            .collect::<Vec<_>>()
+    });
+    split_groups(files, &sizes, titles.as_deref(), options.n, options.max_bytes)
+}
+
+/// Returns the publication part of a formatted file stem, without its date, undated `nd` suffix, or volume/issue
+/// segment, e.g. `Arizona_Sun` for `Arizona_Sun_v03n012_1944-05-12`.
+fn publication_title(stem: &str) -> String {
+    let suffix = &config().circa_suffix;
+    let stem = match stem.strip_suffix(suffix.as_str()) {
+        Some(stem) if !suffix.is_empty() => stem,
+        _ => stem,
+    };
+    let title = stem
+        .match_indices(['_', '-'])
+        .find(|(i, _)| Date::try_from(&stem[i + 1..]).is_ok() || stem[i + 1..].starts_with("nd"))
+        .map_or(stem, |(i, _)| &stem[..i]);
+    // drop a `v03n012` segment.
+    match title.rfind(['_', '-']) {
+        Some(i) if is_volume_issue_segment(&title[i + 1..]) => title[..i].to_string(),
+        _ => title.to_string(),
+    }
+}
+
+fn is_volume_issue_segment(segment: &str) -> bool {
+    let Some((volume, issue)) = segment.strip_prefix('v').and_then(|segment| segment.split_once('n')) else {
+        return false;
+    };
+    [volume, issue].iter().all(|number| !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Splits items into groups of at most `n` items and `max_bytes` bytes. With `titles`, a group closes early before a
+/// run of same-titled items that would not fit in what is left of it, but would fit in an empty group.
+fn split_groups<T>(
+    items: Vec<T>,
+    sizes: &[u64],
+    titles: Option<&[String]>,
+    n: Option<usize>,
+    max_bytes: Option<u64>
+) -> Vec<Vec<T>>
+    where T: std::fmt::Debug
+{
+    let mut groups = vec![];
+    let mut group = vec![];
+    let mut group_bytes = 0;
+
+    for (i, (item, size)) in items.into_iter().zip(sizes.iter().copied()).enumerate() {
+        let full = n.is_some_and(|n| group.len() >= n);
+        let overflows = max_bytes.is_some_and(|max| group_bytes + size > max);
+        let starts_title = titles.is_some_and(|titles| i == 0 || titles[i] != titles[i - 1]);
+        let title_moves = starts_title && {
+            let titles = titles.expect("Titles are required to partition by title.");
+            let count = titles[i..]
+                .iter()
+                .take_while(|title| **title == titles[i])
+                .count();
+            let bytes = sizes[i..i + count].iter().sum::<u64>();
+            let fits_alone = n.is_none_or(|n| count <= n) && max_bytes.is_none_or(|max| bytes <= max);
+            let fits_here =
+                n.is_none_or(|n| group.len() + count <= n) && max_bytes.is_none_or(|max| group_bytes + bytes <= max);
+            fits_alone && !fits_here
+        };
+        if !group.is_empty() && (full || overflows || title_moves) {
+            groups.push(std::mem::take(&mut group));
+            group_bytes = 0;
+        }
+        if max_bytes.is_some_and(|max| size > max) {
+            print_warn!(Warning::Other, "{}", messages::grouped_alone(&format!("{:?}", item)));
+        }
+        group.push(item);
+        group_bytes += size;
+    }
+    if !group.is_empty() {
+        groups.push(group);
+    }
+    groups
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::filesystem::MemoryFilesystem;
+
+    #[test]
+    fn parse_group_index() {
+        assert_eq!(group_index(Path::new("dir/3_nd"), ""), Some(3));
+        assert_eq!(group_index(Path::new("dir/12_1944-1952"), ""), Some(12));
+        assert_eq!(group_index(Path::new("dir/0_1944-05-12_1952-07-11"), ""), Some(0));
+        assert_eq!(group_index(Path::new("dir/0_1910_ca_1944-05-12"), ""), Some(0));
+        assert_eq!(group_index(Path::new("dir/1_1944-05_1944-06_ca"), ""), Some(1));
+        assert_eq!(group_index(Path::new("dir/017_1944-1952"), ""), Some(17));
+        assert_eq!(group_index(Path::new("dir/scans"), ""), None);
+        assert_eq!(group_index(Path::new("dir/2024"), ""), None);
+        assert_eq!(group_index(Path::new("dir/2024_drafts"), ""), None);
+        assert_eq!(group_index(Path::new("dir/2024_05-12"), ""), None);
+        assert_eq!(group_index(Path::new("dir/batch_017_1944-1952"), "batch_"), Some(17));
+        assert_eq!(group_index(Path::new("dir/batch_018_nd"), "batch_"), Some(18));
+        assert_eq!(group_index(Path::new("dir/017_1944-1952"), "batch_"), None);
+    }
+
+    #[test]
+    fn group_by_count_and_bytes() {
+        let items = vec!['a', 'b', 'c', 'd', 'e'];
+        let sizes = [10, 10, 30, 5, 5];
+        assert_eq!(split_groups(items.clone(), &sizes, None, Some(2), None), vec![
+            vec!['a', 'b'],
+            vec!['c', 'd'],
+            vec!['e']
+        ]);
+        assert_eq!(split_groups(items.clone(), &sizes, None, None, Some(25)), vec![
+            vec!['a', 'b'],
+            vec!['c'],
+            vec!['d', 'e']
+        ]);
+        assert_eq!(split_groups(items, &sizes, None, Some(1), Some(25)).len(), 5);
+    }
+
+    #[test]
+    fn keep_titles_together() {
+        let items = vec!['a', 'b', 'c', 'd', 'e', 'f', 'g'];
+        let titles = ["sun", "sun", "star", "star", "star", "labor", "labor"].map(str::to_string);
+        assert_eq!(split_groups(items.clone(), &[0; 7], Some(&titles), Some(4), None), vec![
+            vec!['a', 'b'],
+            vec!['c', 'd', 'e'],
+            vec!['f', 'g']
+        ]);
+        // a title larger than a group is split anyway, and the next one still starts a group of its own.
+        assert_eq!(split_groups(items, &[0; 7], Some(&titles), Some(2), None), vec![
+            vec!['a', 'b'],
+            vec!['c', 'd'],
+            vec!['e'],
+            vec!['f', 'g']
+        ]);
+    }
+
+    fn options(n: usize) -> GroupOptions {
+        GroupOptions {
+            n: Some(n),
+            max_bytes: None,
+            full_dates: false,
+            start_index: None,
+            index_width: 1,
+            index_prefix: String::new(),
+            partition_by_title: false,
+            sidecar_ext: vec![],
+            archive: None,
+            plan: false,
+            simulate: true,
+        }
+    }
+
+    #[test]
+    fn group_files_in_memory() {
+        let fs = MemoryFilesystem::new();
+        for (stem, bytes) in [("Arizona_Sun_1944-05-12", 10), ("Arizona_Sun_1944-05-19", 10), ("Arizona_Sun_1952-07-11", 10)] {
+            fs.add_file(format!("scans/{}.pdf", stem), bytes);
+        }
+        fs.add_file("scans/Arizona_Sun_1944-05-12.txt", 5);
+        fs.add_file("scans/0_1940-1941/Arizona_Sun_1940-01-01.pdf", 10);
+        let options = GroupOptions { sidecar_ext: vec!["txt".to_string()], ..options(2) };
+        group_files(&fs, &["scans".to_string()], &["pdf".to_string()], true, &FileFilters::default(), &options);
+        let snapshot = Path::new("scans").join(crate::SNAPSHOT_DIR).join(crate::run_timestamp()).join("group-files.csv");
+        assert!(fs.exists(&snapshot));
+        fs.remove_file(&snapshot).unwrap();
+        let files = fs.files().into_keys().collect::<Vec<_>>();
+        assert_eq!(files, [
+            "scans/0_1940-1941/Arizona_Sun_1940-01-01.pdf",
+            "scans/1_1944-1944/Arizona_Sun_1944-05-12.pdf",
+            "scans/1_1944-1944/Arizona_Sun_1944-05-12.txt",
+            "scans/1_1944-1944/Arizona_Sun_1944-05-19.pdf",
+            "scans/2_1952-1952/Arizona_Sun_1952-07-11.pdf",
+        ].map(PathBuf::from));
+    }
+
+    #[test]
+    fn name_groups_by_full_dates() {
+        let fs = MemoryFilesystem::new();
+        for stem in ["Arizona_Sun_1944-05-19", "Arizona_Sun_1944-05-12", "Arizona_Sun_1945-01"] {
+            fs.add_file(format!("scans/{}.pdf", stem), 10);
+        }
+        let options = GroupOptions { full_dates: true, ..options(2) };
+        group_files(&fs, &["scans".to_string()], &["pdf".to_string()], true, &FileFilters::default(), &options);
+        assert!(fs.exists(Path::new("scans/0_1944-05-12_1944-05-19/Arizona_Sun_1944-05-12.pdf")));
+        assert!(fs.exists(Path::new("scans/1_1945-01_1945-01/Arizona_Sun_1945-01.pdf")));
+    }
+
+    #[test]
+    fn companions_follow_their_primary_file() {
+        let fs = MemoryFilesystem::new();
+        fs.add_file("scans/Arizona_Sun_1944-05-12.pdf", 10);
+        fs.add_file("scans/Arizona_Sun_1944-05-12.txt", 5);
+        fs.add_file("scans/Arizona_Sun_1944-05-19.pdf", 10);
+        // a sidecar without a primary file is grouped on its own.
+        fs.add_file("scans/Arizona_Sun_1944-05-26.txt", 3);
+        let options = GroupOptions { n: None, max_bytes: Some(20), sidecar_ext: vec!["txt".to_string()], ..options(0) };
+        let extensions = ["pdf", "txt"].map(String::from);
+        group_files(&fs, &["scans".to_string()], &extensions, true, &FileFilters::default(), &options);
+        let files = fs.files().into_keys().filter(|path| !path.starts_with("scans/.bulk_format")).collect::<Vec<_>>();
+        // the companion counts towards its primary file's group size.
+        assert_eq!(files, [
+            "scans/0_1944-1944/Arizona_Sun_1944-05-12.pdf",
+            "scans/0_1944-1944/Arizona_Sun_1944-05-12.txt",
+            "scans/1_1944-1944/Arizona_Sun_1944-05-19.pdf",
+            "scans/1_1944-1944/Arizona_Sun_1944-05-26.txt",
+        ].map(PathBuf::from));
+    }
+
+    #[test]
+    fn group_files_of_several_directories() {
+        let fs = MemoryFilesystem::new();
+        fs.add_file("scans/Arizona_Sun_1944-05-19.pdf", 10);
+        fs.add_file("reel2/Arizona_Sun_1944-05-12.pdf", 10);
+        fs.add_file("reel2/Arizona_Sun_1944-05-26.pdf", 10);
+        let directories = ["scans", "reel2"].map(String::from);
+        let files = collect_directories(&fs, &directories, &["pdf".to_string()], true, &FileFilters::default());
+        assert_eq!(files, [
+            "reel2/Arizona_Sun_1944-05-12.pdf",
+            "scans/Arizona_Sun_1944-05-19.pdf",
+            "reel2/Arizona_Sun_1944-05-26.pdf",
+        ].map(PathBuf::from));
+
+        // the groups are made in the first directory.
+        group_files(&fs, &directories, &["pdf".to_string()], true, &FileFilters::default(), &options(2));
+        let files = fs.files().into_keys().filter(|path| !path.starts_with("scans/.bulk_format")).collect::<Vec<_>>();
+        assert_eq!(files, [
+            "scans/0_1944-1944/Arizona_Sun_1944-05-12.pdf",
+            "scans/0_1944-1944/Arizona_Sun_1944-05-19.pdf",
+            "scans/1_1944-1944/Arizona_Sun_1944-05-26.pdf",
+        ].map(PathBuf::from));
+    }
+
+    #[test]
+    fn group_listed_files_in_order() {
+        let fs = MemoryFilesystem::new();
+        for stem in ["Arizona_Sun_1952-07-11", "Arizona_Sun_1944-05-12", "Arizona_Sun_1944-05-19"] {
+            fs.add_file(format!("scans/{}.pdf", stem), 10);
+        }
+        let files = ["scans/Arizona_Sun_1952-07-11.pdf", "scans/Arizona_Sun_1944-05-12.pdf"].map(PathBuf::from).to_vec();
+        group_listed_files(&fs, None, files, &options(1));
+        let files = fs.files().into_keys().filter(|path| !path.starts_with("scans/.bulk_format")).collect::<Vec<_>>();
+        // listed files keep their order, and unlisted files stay where they are.
+        assert_eq!(files, [
+            "scans/0_1952-1952/Arizona_Sun_1952-07-11.pdf",
+            "scans/1_1944-1944/Arizona_Sun_1944-05-12.pdf",
+            "scans/Arizona_Sun_1944-05-19.pdf",
+        ].map(PathBuf::from));
+    }
+
+    #[test]
+    fn append_moved_files_to_packing_list() {
+        use crate::filesystem::RealFilesystem;
+
+        let dir = crate::test_util::TestDir::new("group_manifest");
+        dir.write("scans/Arizona_Sun_1944-05-12.pdf", "abc");
+        dir.write("scans/Local_226_nd_tn9.pdf", "");
+        state::mark_tns(Stage::Formatted, &[("tn1".to_string(), "Arizona_Sun_1944-05-12".to_string())]);
+        let directories = [dir.join("scans").to_string_lossy().to_string()];
+        let options = GroupOptions { simulate: false, ..options(5) };
+        group_files(&RealFilesystem, &directories, &["pdf".to_string()], true, &FileFilters::default(), &options);
+        dir.write("scans/Arizona_Sun_1952-07-11.pdf", "abc");
+        group_files(&RealFilesystem, &directories, &["pdf".to_string()], true, &FileFilters::default(), &options);
+
+        let abc = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        let empty = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+        assert_eq!(
+            std::fs::read_to_string(dir.join("scans").join(GROUP_MANIFEST)).unwrap(),
+            format!(
+                "group,file,tn,date,bytes,sha256\n\
+                 0_1944-1944,Arizona_Sun_1944-05-12.pdf,tn1,1944-05-12,3,{abc}\n\
+                 0_1944-1944,Local_226_nd_tn9.pdf,,,0,{empty}\n\
+                 1_1952-1952,Arizona_Sun_1952-07-11.pdf,,1952-07-11,3,{abc}\n"
+            )
+        );
+    }
+
+    #[test]
+    fn number_groups_with_prefix_width_and_start() {
+        let fs = MemoryFilesystem::new();
+        fs.add_file("scans/batch007_1940-1940/Arizona_Sun_1940-01-01.pdf", 10);
+        fs.add_file("scans/Arizona_Sun_1944-05-12.pdf", 10);
+        let options = GroupOptions { index_width: 3, index_prefix: "batch".to_string(), ..options(1) };
+        group_files(&fs, &["scans".to_string()], &["pdf".to_string()], true, &FileFilters::default(), &options);
+        // numbering continues after the existing group with the same prefix.
+        assert!(fs.exists(Path::new("scans/batch008_1944-1944/Arizona_Sun_1944-05-12.pdf")));
+
+        fs.add_file("scans/Arizona_Sun_1952-07-11.pdf", 10);
+        let options = GroupOptions { start_index: Some(20), ..options };
+        group_files(&fs, &["scans".to_string()], &["pdf".to_string()], true, &FileFilters::default(), &options);
+        assert!(fs.exists(Path::new("scans/batch020_1952-1952/Arizona_Sun_1952-07-11.pdf")));
+    }
+
+    #[test]
+    fn rerun_with_circa_groups_and_year_directories() {
+        let fs = MemoryFilesystem::new();
+        fs.add_file("scans/0_1910_ca_1944-05-12/Arizona_Sun_1910_ca.pdf", 10);
+        fs.add_file("scans/2024/Arizona_Sun_1952-07-11.pdf", 10);
+        fs.add_file("scans/Local_226_nd_tn9.pdf", 10);
+        let options = GroupOptions { full_dates: true, ..options(5) };
+        group_files(&fs, &["scans".to_string()], &["pdf".to_string()], true, &FileFilters::default(), &options);
+        let files = fs.files().into_keys().filter(|path| !path.starts_with("scans/.bulk_format")).collect::<Vec<_>>();
+        // `2024` is not a group, so its file is grouped, and the new group continues after the circa group.
+        assert_eq!(files, [
+            "scans/0_1910_ca_1944-05-12/Arizona_Sun_1910_ca.pdf",
+            "scans/1_1952-07-11_1952-07-11/Arizona_Sun_1952-07-11.pdf",
+            "scans/1_1952-07-11_1952-07-11/Local_226_nd_tn9.pdf",
+        ].map(PathBuf::from));
+
+        fs.add_file("scans/Local_226_nd_tn10.pdf", 10);
+        group_files(&fs, &["scans".to_string()], &["pdf".to_string()], true, &FileFilters::default(), &options);
+        assert!(fs.exists(Path::new("scans/2_nd/Local_226_nd_tn10.pdf")));
+    }
+
+    #[test]
+    fn parse_publication_titles() {
+        assert_eq!(publication_title("Arizona_Sun_1944-05-12"), "Arizona_Sun");
+        assert_eq!(publication_title("Arizona_Sun_v03n012_1944-05-12"), "Arizona_Sun");
+        assert_eq!(publication_title("Arizona-Sun-1944-05"), "Arizona-Sun");
+        assert_eq!(publication_title("Arizona_Sun_nd_tn123"), "Arizona_Sun");
+    }
+}
@@ -0,0 +1,158 @@
+use std::path::{ Path, PathBuf };
+use serde::{ Deserialize, Serialize };
+
+use crate::config::config;
+
+/// The prefix of a Google Sheets lookup: `gsheet:<spreadsheet-id>/<tab>`.
+pub const PREFIX: &str = "gsheet:";
+
+const SCOPE: &str = "https://www.googleapis.com/auth/spreadsheets.readonly";
+
+/// The fields of a Google service account key file that are needed to request an access token.
+#[derive(Deserialize)]
+struct ServiceAccount {
+    client_email: String,
+    private_key: String,
+    token_uri: String,
+}
+
+#[derive(Serialize)]
+struct Claims<'a> {
+    iss: &'a str,
+    scope: &'a str,
+    aud: &'a str,
+    iat: i64,
+    exp: i64,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+}
+
+#[derive(Deserialize)]
+struct ValueRange {
+    #[serde(default)]
+    values: Vec<Vec<String>>,
+}
+
+/// Downloads a sheet (`<spreadsheet-id>/<tab>`, or the first tab if none is given) into a CSV file in `cache_dir`,
+/// authenticating with the service account key in `google-credentials` (config) or `GOOGLE_APPLICATION_CREDENTIALS`.
+pub fn download_sheet(sheet: &str, cache_dir: &Path) -> Result<PathBuf, String> {
+    let (id, tab) = sheet.split_once('/').unwrap_or((sheet, ""));
+    if id.is_empty() {
+        return Err("Missing spreadsheet id.".to_string());
+    }
+    let token = access_token()?;
+
+    // a bare cell range reads from the first tab.
+    let range = if tab.is_empty() { "A:ZZ" } else { tab };
+    let url = format!(
+        "https://sheets.googleapis.com/v4/spreadsheets/{}/values/{}?majorDimension=ROWS",
+        id,
+        percent_encode(range)
+    );
+    let body = ureq
+        ::get(&url)
+        .header("Authorization", &format!("Bearer {}", token))
+        .call()
+        .and_then(|mut response| response.body_mut().read_to_string())
+        .map_err(|e| e.to_string())?;
+    let values: ValueRange = serde_json::from_str(&body).map_err(|e| e.to_string())?;
+
+    let name = if tab.is_empty() { id.to_string() } else { format!("{}_{}", id, tab) };
+    let target = cache_dir.join(format!("gsheet_{}.csv", name.replace(['/', '\\', ' '], "_")));
+    let file = std::fs::File::create(&target).map_err(|e| e.to_string())?;
+    write_rows(file, &values.values)?;
+    Ok(target)
+}
+
+/// Writes the rows of a sheet as CSV. The Sheets API leaves out trailing empty cells, so every row is padded to the
+/// width of the widest row (the header, usually) for the lookup table's non-flexible reader.
+fn write_rows(writer: impl std::io::Write, rows: &[Vec<String>]) -> Result<(), String> {
+    let width = rows.iter().map(Vec::len).max().unwrap_or_default();
+    let mut writer = csv::Writer::from_writer(writer);
+    for row in rows {
+        let padding = std::iter::repeat_n("", width - row.len());
+        writer.write_record(row.iter().map(String::as_str).chain(padding)).map_err(|e| e.to_string())?;
+    }
+    writer.flush().map_err(|e| e.to_string())
+}
+
+/// Exchanges a signed service account JWT for an OAuth access token.
+fn access_token() -> Result<String, String> {
+    let credentials = config()
+        .google_credentials
+        .clone()
+        .or_else(|| std::env::var("GOOGLE_APPLICATION_CREDENTIALS").ok())
+        .ok_or("No service account key. Set `google-credentials` in the config or GOOGLE_APPLICATION_CREDENTIALS.")?;
+    let account: ServiceAccount = serde_json
+        ::from_str(&std::fs::read_to_string(&credentials).map_err(|e| e.to_string())?)
+        .map_err(|e| format!("Invalid service account key \"{}\": {}", credentials, e))?;
+
+    let now = chrono::Utc::now().timestamp();
+    let claims = Claims {
+        iss: &account.client_email,
+        scope: SCOPE,
+        aud: &account.token_uri,
+        iat: now,
+        exp: now + 3600,
+    };
+    let key = jsonwebtoken::EncodingKey
+        ::from_rsa_pem(account.private_key.as_bytes())
+        .map_err(|e| e.to_string())?;
+    let assertion = jsonwebtoken
+        ::encode(&jsonwebtoken::Header::new(jsonwebtoken::Algorithm::RS256), &claims, &key)
+        .map_err(|e| e.to_string())?;
+
+    let body = ureq
+        ::post(&account.token_uri)
+        .send_form([
+            ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
+            ("assertion", assertion.as_str()),
+        ])
+        .and_then(|mut response| response.body_mut().read_to_string())
+        .map_err(|e| e.to_string())?;
+    let token: TokenResponse = serde_json::from_str(&body).map_err(|e| e.to_string())?;
+    Ok(token.access_token)
+}
+
+/// Percent-encodes everything but unreserved URL characters, e.g. a tab name with spaces.
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| {
+            if b.is_ascii_alphanumeric() || b"-_.~".contains(&b) {
+                (b as char).to_string()
+            } else {
+                format!("%{:02X}", b)
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn encode_tab_names() {
+        assert_eq!(percent_encode("Master List"), "Master%20List");
+        assert_eq!(percent_encode("A:ZZ"), "A%3AZZ");
+        assert_eq!(percent_encode("tn_titles"), "tn_titles");
+    }
+
+    #[test]
+    fn pad_short_rows() {
+        let row = |cells: &[&str]| cells.iter().map(|cell| cell.to_string()).collect::<Vec<_>>();
+        let rows = [
+            row(&["tn", "title", "a", "b", "c", "date_loaded"]),
+            row(&["tn1", "Arizona Sun. (May 12, 1944)"]),
+        ];
+        let mut csv = vec![];
+        write_rows(&mut csv, &rows).unwrap();
+        let mut reader = csv::Reader::from_reader(csv.as_slice());
+        let record = reader.records().next().unwrap().unwrap();
+        assert_eq!(record.get(5), Some(""));
+    }
+}
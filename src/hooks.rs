@@ -0,0 +1,139 @@
+use std::{ path::Path, process::Command };
+use owo_colors::OwoColorize;
+use serde::Deserialize;
+
+use crate::{ config::config, issue_data::IssueData, messages, print_status, print_warn, summary, warnings::Warning };
+
+/// Shell commands run around a command and around each formatted file, set in the config's `hooks`.
+/// Each hook runs through `sh -c` (`cmd /C` on Windows) with its context in `BULK_FORMAT_*` environment variables:
+///
+/// - `before-run`, `after-run`: `BULK_FORMAT_COMMAND`, and after the run `BULK_FORMAT_STATUS` (`success` or `failure`)
+///   and `BULK_FORMAT_SUMMARY` (the `--quiet` JSON summary). A failed `before-run` hook stops the run.
+/// - `before-file`, `after-file`: `BULK_FORMAT_PATH` (the input file before it is copied, the output file after),
+///   `BULK_FORMAT_SOURCE`, `BULK_FORMAT_TN`, `BULK_FORMAT_TITLE`, `BULK_FORMAT_DATE`, `BULK_FORMAT_VOLUME`, and
+///   `BULK_FORMAT_ISSUE`. A failed `before-file` hook skips the file.
+/// - `after-populate`: `BULK_FORMAT_PATH`, the populated CSV file.
+///
+/// Example:
+/// ```yaml
+/// hooks:
+///   after-file: ocrmypdf --skip-text "$BULK_FORMAT_PATH" "$BULK_FORMAT_PATH"
+///   after-populate: python validate_records.py "$BULK_FORMAT_PATH"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Hooks {
+    pub before_run: Option<String>,
+    pub after_run: Option<String>,
+    pub before_file: Option<String>,
+    pub after_file: Option<String>,
+    pub after_populate: Option<String>,
+}
+
+/// Runs a hook command with `env` set. Returns false, with a warning, if it could not run or exited with an error.
+fn run_hook(name: &str, command: &str, env: &[(&str, String)]) -> bool {
+    print_status!("{} {}", format!("[{}]", name).dimmed(), command);
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    if crate::stdout_is_data() {
+        shell.stdout(std::io::stderr());
+    }
+    match shell.arg(command).envs(env.iter().map(|(key, value)| (key, value))).status() {
+        Ok(status) if status.success() => true,
+        Ok(status) => {
+            print_warn!(Warning::Failure, "{}", messages::hook_failed(name, &status.to_string()));
+            false
+        }
+        Err(e) => {
+            print_warn!(Warning::Failure, "{}", messages::hook_not_run(name, &e.to_string()));
+            false
+        }
+    }
+}
+
+/// Returns the environment describing a file and its issue.
+fn file_env(path: &Path, source: &Path, issue: &IssueData) -> Vec<(&'static str, String)> {
+    let number = |number: Option<u32>| number.map(|number| number.to_string()).unwrap_or_default();
+    vec![
+        ("BULK_FORMAT_PATH", path.to_string_lossy().to_string()),
+        ("BULK_FORMAT_SOURCE", source.to_string_lossy().to_string()),
+        ("BULK_FORMAT_TN", issue.tn.clone()),
+        ("BULK_FORMAT_TITLE", issue.record_title()),
+        ("BULK_FORMAT_DATE", if issue.is_undated() { String::new() } else { issue.date.clone() }),
+        ("BULK_FORMAT_VOLUME", number(issue.volume)),
+        ("BULK_FORMAT_ISSUE", number(issue.issue)),
+    ]
+}
+
+/// Runs the `before-run` hook, panicking if it fails.
+pub fn before_run(command: &str) {
+    if let Some(hook) = &config().hooks.before_run {
+        let env = [("BULK_FORMAT_COMMAND", command.to_string())];
+        assert!(run_hook("before-run", hook, &env), "The before-run hook failed. Nothing was done.");
+    }
+}
+
+/// Runs the `after-run` hook with the run's status and summary.
+pub fn after_run(command: &str, success: bool) {
+    if let Some(hook) = &config().hooks.after_run {
+        let env = [
+            ("BULK_FORMAT_COMMAND", command.to_string()),
+            ("BULK_FORMAT_STATUS", (if success { "success" } else { "failure" }).to_string()),
+            ("BULK_FORMAT_SUMMARY", summary::summary_json(command)),
+        ];
+        run_hook("after-run", hook, &env);
+    }
+}
+
+/// Runs the `before-file` hook on an input file. Returns false if the file should be skipped.
+pub fn before_file(source: &Path, issue: &IssueData) -> bool {
+    match &config().hooks.before_file {
+        Some(hook) => run_hook("before-file", hook, &file_env(source, source, issue)),
+        None => true,
+    }
+}
+
+/// Runs the `after-file` hook on a copied output file.
+pub fn after_file(source: &Path, target: &Path, issue: &IssueData) {
+    if let Some(hook) = &config().hooks.after_file {
+        run_hook("after-file", hook, &file_env(target, source, issue));
+    }
+}
+
+/// Runs the `after-populate` hook on a populated CSV file.
+pub fn after_populate(target: &str) {
+    if let Some(hook) = &config().hooks.after_populate {
+        run_hook("after-populate", hook, &[("BULK_FORMAT_PATH", target.to_string())]);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    #[cfg(unix)]
+    fn run_hooks_with_file_environment() {
+        let dir = crate::test_util::TestDir::new("hooks");
+        let output = dir.join("env.txt");
+        let issue = IssueData::new("tn1".to_string(), "Arizona Sun. v. 3, no. 12 (May 12, 1944)".to_string(), String::new());
+        let env = file_env(Path::new("out/Arizona_Sun_1944-05-12.pdf"), Path::new("scans/tn1.pdf"), &issue);
+        let command = format!(
+            "echo \"$BULK_FORMAT_PATH|$BULK_FORMAT_SOURCE|$BULK_FORMAT_TN|$BULK_FORMAT_TITLE|$BULK_FORMAT_VOLUME|$BULK_FORMAT_ISSUE\" > '{}'",
+            output.to_string_lossy()
+        );
+        assert!(run_hook("after-file", &command, &env));
+        assert_eq!(
+            std::fs::read_to_string(output).unwrap(),
+            "out/Arizona_Sun_1944-05-12.pdf|scans/tn1.pdf|tn1|Arizona Sun, 1944-05-12|3|12\n"
+        );
+        assert!(!run_hook("before-file", "exit 3", &env));
+    }
+}
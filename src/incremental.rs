@@ -0,0 +1,112 @@
+use std::{ collections::BTreeMap, path::{ Path, PathBuf }, time::UNIX_EPOCH };
+use serde::{ Deserialize, Serialize };
+
+use crate::checksum::relative_path;
+
+/// The cache, in a directory a command reads files from, of the files it processed on earlier `--incremental` runs.
+pub const CACHE_FILE: &str = ".bulk_format/cache.csv";
+
+/// One cached result: a file's size and modification time when `command` last processed it,
+/// and what it produced (a SHA-256 digest for `checksum`, an output path for `format`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct CacheEntry {
+    command: String,
+    path: String,
+    bytes: u64,
+    modified: u128,
+    result: String,
+}
+
+/// A per-directory cache of processed files, keyed by command and path relative to the directory.
+/// A file counts as unchanged while its size and modification time match the cached ones.
+#[derive(Debug)]
+pub struct FileCache {
+    directory: String,
+    entries: BTreeMap<(String, String), CacheEntry>,
+}
+
+impl FileCache {
+    /// Reads the cache of `directory`, or starts an empty one if it has none.
+    pub fn open(directory: &str) -> Self {
+        let path = Path::new(directory).join(CACHE_FILE);
+        let entries = match csv::Reader::from_path(&path) {
+            Ok(mut reader) =>
+                reader
+                    .deserialize()
+                    .map(|result| {
+                        let entry: CacheEntry = result.expect("Failed to parse cache entry.");
+                        ((entry.command.clone(), entry.path.clone()), entry)
+                    })
+                    .collect(),
+            Err(_) => BTreeMap::new(),
+        };
+        FileCache { directory: directory.to_string(), entries }
+    }
+
+    /// Returns the cached result of `command` for `file`, if the file is unchanged since it was cached.
+    pub fn get(&self, command: &str, file: &Path) -> Option<&str> {
+        let entry = self.entries.get(&(command.to_string(), relative_path(&self.directory, file)))?;
+        (stamp(file)? == (entry.bytes, entry.modified)).then_some(entry.result.as_str())
+    }
+
+    /// Caches the result of `command` for `file` at its current size and modification time.
+    pub fn insert(&mut self, command: &str, file: &Path, result: &str) {
+        let Some((bytes, modified)) = stamp(file) else {
+            return;
+        };
+        let path = relative_path(&self.directory, file);
+        let entry = CacheEntry {
+            command: command.to_string(),
+            path: path.clone(),
+            bytes,
+            modified,
+            result: result.to_string(),
+        };
+        self.entries.insert((command.to_string(), path), entry);
+    }
+
+    /// Writes the cache back to its directory, replacing the previous one.
+    pub fn save(&self) {
+        let path = Path::new(&self.directory).join(CACHE_FILE);
+        std::fs::create_dir_all(path.parent().expect("Failed to get cache directory.")).expect(
+            "Failed to create cache directory."
+        );
+        // write beside the cache and rename over it, so an interrupted run never leaves a truncated cache.
+        let partial = PathBuf::from(format!("{}.partial", path.to_string_lossy()));
+        let mut writer = csv::Writer::from_path(&partial).expect("Failed to create cache file.");
+        for entry in self.entries.values() {
+            writer.serialize(entry).expect("Failed to write cache entry.");
+        }
+        writer.flush().expect("Failed to write cache file.");
+        std::fs::rename(&partial, &path).expect("Failed to replace cache file.");
+    }
+}
+
+/// Returns a file's size and modification time in nanoseconds since the Unix epoch.
+fn stamp(file: &Path) -> Option<(u64, u128)> {
+    let metadata = file.metadata().ok()?;
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some((metadata.len(), modified.as_nanos()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reuse_results_of_unchanged_files() {
+        let dir = crate::test_util::TestDir::new("incremental_cache");
+        let directory = dir.join("scans").to_string_lossy().to_string();
+        let file = dir.write("scans/tn1.pdf", "abc");
+        let mut cache = FileCache::open(&directory);
+        assert_eq!(cache.get("format", &file), None);
+        cache.insert("format", &file, "out/Arizona_Sun_1944-05-12.pdf");
+        cache.save();
+
+        let cache = FileCache::open(&directory);
+        assert_eq!(cache.get("format", &file), Some("out/Arizona_Sun_1944-05-12.pdf"));
+        assert_eq!(cache.get("checksum", &file), None);
+        std::fs::write(&file, "abcd").unwrap();
+        assert_eq!(cache.get("format", &file), None);
+    }
+}
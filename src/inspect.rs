@@ -0,0 +1,94 @@
+use std::collections::BTreeSet;
+use owo_colors::OwoColorize;
+
+use crate::{ print_status, summary };
+
+/// Per-column statistics gathered while scanning a CSV file.
+struct ColumnProfile {
+    name: String,
+    empty: usize,
+    distinct: BTreeSet<String>,
+}
+
+/// Prints the headers, row count, empty-value percentages, and distinct value samples of a CSV file.
+pub fn inspect_csv(path: &str, samples: usize) {
+    let mut reader = csv::Reader::from_path(path).expect("Failed to read CSV file.");
+    let (columns, rows) = profile_columns(&mut reader);
+
+    summary::record_count("columns", columns.len());
+    summary::record_count("rows", rows);
+    print_status!("{} {}", "File:".bold(), path);
+    print_status!("{} {}", "Columns:".bold(), columns.len());
+    print_status!("{} {}", "Rows:".bold(), rows);
+    print_status!();
+
+    for (i, column) in columns.iter().enumerate() {
+        let empty_pct = if rows == 0 { 0.0 } else { ((column.empty as f64) / (rows as f64)) * 100.0 };
+        let empty = format!("{:.1}% empty", empty_pct);
+        let empty = if empty_pct >= 100.0 {
+            empty.dimmed().to_string()
+        } else if empty_pct > 0.0 {
+            empty.yellow().to_string()
+        } else {
+            empty.green().to_string()
+        };
+        print_status!(
+            "{} {} ({}, {} distinct)",
+            format!("[{}]", i).dimmed(),
+            column.name.bold(),
+            empty,
+            column.distinct.len()
+        );
+        for value in column.distinct.iter().take(samples) {
+            print_status!("    {}", value);
+        }
+        if column.distinct.len() > samples {
+            print_status!("    {}", format!("... and {} more", column.distinct.len() - samples).dimmed());
+        }
+    }
+}
+
+/// Profiles each column of the records of `reader`, and returns the profiles with the number of rows.
+fn profile_columns<R: std::io::Read>(reader: &mut csv::Reader<R>) -> (Vec<ColumnProfile>, usize) {
+    let headers = reader.headers().expect("Failed to read CSV headers.").clone();
+
+    let mut columns = headers
+        .iter()
+        .map(|name| ColumnProfile {
+            name: name.to_string(),
+            empty: 0,
+            distinct: BTreeSet::new(),
+        })
+        .collect::<Vec<_>>();
+
+    let mut rows = 0;
+    for result in reader.records() {
+        let record = result.expect("Failed to parse record.");
+        rows += 1;
+        for (column, value) in columns.iter_mut().zip(record.iter()) {
+            if value.trim().is_empty() {
+                column.empty += 1;
+            } else {
+                column.distinct.insert(value.to_string());
+            }
+        }
+    }
+    (columns, rows)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn profile_empty_and_distinct_values() {
+        let mut reader = csv::Reader::from_reader("tn,title,notes\ntn1,Arizona Sun,\ntn2,Arizona Sun, \ntn3,Catering News,x\n".as_bytes());
+        let (columns, rows) = profile_columns(&mut reader);
+        assert_eq!(rows, 3);
+        let profiles = columns
+            .iter()
+            .map(|column| (column.name.as_str(), column.empty, column.distinct.len()))
+            .collect::<Vec<_>>();
+        assert_eq!(profiles, [("tn", 0, 3), ("title", 0, 2), ("notes", 2, 1)]);
+    }
+}
@@ -1,7 +1,15 @@
+use chrono::{ NaiveDate, NaiveDateTime };
 use owo_colors::OwoColorize;
-use crate::{ print_warn, print_warn_ok };
+use serde::{ Serialize, Deserialize };
+use crate::{
+    config::{ config, AnnotationPolicy, TnOptions, WordSeparator },
+    messages,
+    print_warn,
+    print_warn_ok,
+    warnings::Warning,
+};
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct IssueData {
     pub tn: String,
     pub title: String,
@@ -12,28 +20,73 @@ pub struct IssueData {
     pub date_loaded: String,
 }
 
-impl IssueData {
-    pub fn new(tn: String, raw_title: String, date_loaded: String) -> Self {
+/// Why a raw lookup title cannot be parsed into an [`IssueData`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TitleParseError {
+    /// No period separates the publication title from the volume/issue, e.g. `Arizona Sun (May 12, 1944)`.
+    MissingTitlePeriod { raw: String },
+    /// The volume and issue are neither comma nor space separated, e.g. `v.3no.12`.
+    AmbiguousSplit { raw: String },
+    UnparseableVolume { raw: String },
+    UnparseableIssue { raw: String },
+    /// The date has no month where one is expected, e.g. `12, 1944`.
+    MissingDate { raw: String },
+    InvalidMonth { raw: String },
+    /// The date has too many parts to be read as `Month day, year`, `Month year`, or `year`.
+    InvalidDate { raw: String },
+}
+
+impl std::fmt::Display for TitleParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TitleParseError::MissingTitlePeriod { raw } => {
+                write!(f, "Failed to split title from volume/issue for title: \"{}\"", raw)
+            }
+            TitleParseError::AmbiguousSplit { raw } => write!(f, "Failed to split volume/issue: \"{}\"", raw),
+            TitleParseError::UnparseableVolume { raw } => write!(f, "Failed to parse volume: \"{}\"", raw),
+            TitleParseError::UnparseableIssue { raw } => write!(f, "Failed to parse issue: \"{}\"", raw),
+            TitleParseError::MissingDate { raw } => write!(f, "Failed to find month in date: \"{}\"", raw),
+            TitleParseError::InvalidMonth { raw } => write!(f, "Invalid month: \"{}\"", raw),
+            TitleParseError::InvalidDate { raw } => write!(f, "Invalid date: \"{}\"", raw),
+        }
+    }
+}
+
+impl std::error::Error for TitleParseError {}
+
+/// A row of a lookup table, before its raw title is parsed.
+#[derive(Debug, Clone)]
+pub struct LookupRow {
+    pub tn: String,
+    pub raw_title: String,
+    pub date_loaded: String,
+}
+
+impl TryFrom<LookupRow> for IssueData {
+    type Error = TitleParseError;
+
+    fn try_from(row: LookupRow) -> Result<Self, Self::Error> {
+        let LookupRow { tn, raw_title, date_loaded } = row;
         // Example: Arizona Catering Employees. (Aug. 6, 1944)
         // Example: Arizona Catering Employees. v. 1 no 11 Sep. 21, 1944)
         // Example: Arizona Catering Employees. v. 9, no. 9 (Jul. 11, 1952)
+        // Example: Arizona Labor Journal [microfilm]. v. 2, no. 4 (Jan. 5, 1945)
+
+        let raw_title = match config().bracketed_annotations {
+            AnnotationPolicy::Strip => strip_bracketed(&raw_title),
+            AnnotationPolicy::Keep => raw_title,
+        };
 
-        // First, split off the date.
+        // First, split off the date. Splitting always yields at least one part.
         let mut parts = raw_title.split(" (");
-        let date = parts
-            .clone()
-            .last()
-            .expect("Failed to split date from raw title")
-            .trim_end_matches(')');
-        let date = convert_date(date, &tn);
+        let date = parts.clone().last().unwrap_or_default().trim_end_matches(')');
+        let date = convert_date(date, &tn)?;
 
         // Next, split off the volume and issue, if they exist.
-        let parts = parts.next().expect("Failed to split title from raw title");
+        let parts = parts.next().unwrap_or_default();
         let (title, v_n) = parts
             .split_once(".")
-            .expect(
-                format!("Failed to split title from volume/issue for title: \"{}\"", parts).as_str()
-            );
+            .ok_or_else(|| TitleParseError::MissingTitlePeriod { raw: parts.to_string() })?;
 
         // Replace spaces in the title with underscores.
         let title = title.replace(" ", "_");
@@ -52,9 +105,11 @@ impl IssueData {
                         .replace("v. ", "v.")
                         .replace("v ", "v");
 
-                    print_warn!("VOLUME AND ISSUE WERE NOT COMMA SEPARATED: \"{}\"", v_n);
+                    print_warn!(Warning::UnparsedVolume, "{}", messages::volume_not_comma_separated(&v_n));
 
-                    let (v, n) = v_n.split_once(" ").expect("Failed to split volume/issue.");
+                    let (v, n) = v_n
+                        .split_once(" ")
+                        .ok_or_else(|| TitleParseError::AmbiguousSplit { raw: v_n.clone() })?;
                     print_warn_ok!(
                         "Split volume/issue on the first space instead: \"{}\" and \"{}\"",
                         v,
@@ -70,7 +125,7 @@ impl IssueData {
                 .trim_start_matches("v.")
                 .trim_start_matches("v ")
                 .trim_start_matches("v")
-                .trim_end_matches(|c: char| (c == ',' || c == '.' || c.is_whitespace()));
+                .trim_end_matches(|c: char| c == ',' || c == '.' || c.is_whitespace());
 
             let issue = issue
                 .trim()
@@ -78,91 +133,357 @@ impl IssueData {
                 .trim_start_matches("no.")
                 .trim_start_matches("no ")
                 .trim_start_matches("no")
-                .trim_end_matches(|c: char| (c == ',' || c == '.' || c.is_whitespace()));
+                .trim_end_matches(|c: char| c == ',' || c == '.' || c.is_whitespace());
 
             let volume = volume
                 .parse::<u32>()
-                .expect(format!("Failed to parse volume: {}", volume).as_str());
-
-            let issue = (
-                match issue.parse::<u32>() {
-                    Ok(i) => Some(i),
-                    Err(_) => {
-                        // The date is probably missing its opening parenthesis and is being parsed as the issue. Try splitting on the first space, and taking the first part.
-                        issue.split_once(" ").expect("Failed to split issue.").0.parse::<u32>().ok()
-                    }
+                .map_err(|_| TitleParseError::UnparseableVolume { raw: volume.to_string() })?;
+
+            let issue = match issue.parse::<u32>() {
+                Ok(i) => Some(i),
+                Err(_) => {
+                    // The date is probably missing its opening parenthesis and is being parsed as the issue. Try splitting on the first space, and taking the first part.
+                    issue.split_once(" ").and_then(|(issue, _)| issue.parse::<u32>().ok())
                 }
-            ).expect("Failed to parse issue.");
+            }.ok_or_else(|| TitleParseError::UnparseableIssue { raw: issue.to_string() })?;
 
             (Some(volume), Some(issue))
         };
 
-        Self {
+        Ok(Self {
             tn,
             title,
             volume,
             issue,
             date,
             date_loaded,
-        }
+        })
+    }
+}
+
+impl IssueData {
+    /// Parses a lookup row, panicking if its title cannot be parsed. Use `IssueData::try_from` to handle the error.
+    pub fn new(tn: String, raw_title: String, date_loaded: String) -> Self {
+        let row = LookupRow { tn, raw_title, date_loaded };
+        let tn = row.tn.clone();
+        IssueData::try_from(row).unwrap_or_else(|e| panic!("Failed to parse the title of tn \"{}\": {}", tn, e))
     }
 
     /// Returns a formatted title for the issue in the format: `title_date`.
+    /// Undated issues use the tn in place of the date: `title_nd_tn`.
+    /// Titles never contain a `.` and always end in the date or tn, so they are never a name reserved on Windows.
+    /// Words are joined with the configured `word-separator`.
     pub fn formatted_title(&self) -> String {
-        format!("{}_{}", self.title, self.date)
+        self.formatted_title_with(&FileNaming::default())
+    }
+
+    /// Returns the formatted title with the optional parts of [`FileNaming`] applied, e.g. `Arizona_Sun_v03n012_1944-05-12`.
+    pub fn formatted_title_with(&self, naming: &FileNaming) -> String {
+        let (title, separator) = match config().word_separator {
+            WordSeparator::Underscore => (self.title.clone(), "_"),
+            WordSeparator::Hyphen => (self.title.replace("_", "-"), "-"),
+            WordSeparator::CamelCase => (camel_case(&self.title), "_"),
+        };
+        let title = match naming.volume_issue_widths.and_then(|widths| self.volume_issue_segment(widths)) {
+            Some(segment) => format!("{title}{separator}{segment}"),
+            None => title,
+        };
+        let title = if self.is_undated() {
+            format!("{title}{separator}nd{separator}{}", self.tn)
+        } else {
+            format!("{title}{separator}{}", self.date)
+        };
+        if naming.ascii { ascii_file_name(&title) } else { title }
+    }
+
+    /// Returns a file name rendered from a `--name-template`, e.g. `{Call Number}_{date}` -> `AZ-0042_1944-05-12`.
+    /// `{title}`, `{date}` (`nd` if undated), `{volume}`, `{issue}`, and `{tn}` come from the issue, and any other
+    /// placeholder is looked up with `column`. Characters not allowed in file names are replaced with `_`, and the
+    /// `ascii` option applies as in [`IssueData::formatted_title_with`]. Names whose stem before the first `.` is
+    /// reserved on Windows get the configured `reserved-name-suffix`, e.g. `CON` -> `CON_file`, `nul.v2` -> `nul_file.v2`.
+    /// Returns the first placeholder that has no value as an error.
+    pub fn file_name_from_template(
+        &self,
+        template: &str,
+        naming: &FileNaming,
+        column: impl Fn(&str) -> Option<String>
+    ) -> Result<String, String> {
+        let mut name = String::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            let Some(end) = rest[start..].find('}').map(|end| start + end) else {
+                break;
+            };
+            name.push_str(&rest[..start]);
+            let placeholder = &rest[start + 1..end];
+            let value = match placeholder {
+                "title" => Some(self.title.clone()),
+                "date" => Some(if self.is_undated() { "nd".to_string() } else { self.date.clone() }),
+                "volume" => self.volume.map(|volume| volume.to_string()),
+                "issue" => self.issue.map(|issue| issue.to_string()),
+                "tn" => Some(self.tn.clone()),
+                column_name => column(column_name),
+            };
+            let value = value
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+                .ok_or_else(|| placeholder.to_string())?;
+            name.extend(value.chars().map(|c| if INVALID_FILE_NAME_CHARS.contains(&c) { '_' } else { c }));
+            rest = &rest[end + 1..];
+        }
+        name.push_str(rest);
+        let name = if naming.ascii { ascii_file_name(&name) } else { name };
+        Ok(avoid_reserved_name(name))
+    }
+
+    /// Returns a zero-padded `v03n012` segment, or just `v03`/`n012` if the issue has only one of them.
+    fn volume_issue_segment(&self, (volume_width, issue_width): (usize, usize)) -> Option<String> {
+        let volume = self.volume.map(|volume| format!("v{:0width$}", volume, width = volume_width));
+        let issue = self.issue.map(|issue| format!("n{:0width$}", issue, width = issue_width));
+        match (volume, issue) {
+            (None, None) => None,
+            (volume, issue) => Some(format!("{}{}", volume.unwrap_or_default(), issue.unwrap_or_default())),
+        }
+    }
+
+    /// Parses `date_loaded`, e.g. `6/8/2020 10:00`. See [`parse_date_loaded`].
+    pub fn parsed_date_loaded(&self) -> Option<NaiveDateTime> {
+        parse_date_loaded(&self.date_loaded)
+    }
+
+    /// Returns true if the raw title's date was `n.d.` (no date).
+    pub fn is_undated(&self) -> bool {
+        self.date == UNDATED
     }
 
     /// Returns a formatted title for the issue in the format: `title, date`.
     pub fn record_title(&self) -> String {
+        self.record_title_with(DEFAULT_RECORD_TITLE_TEMPLATE)
+    }
+
+    /// Returns a record title rendered from a template.
+    /// Supported placeholders: `{title}`, `{date}`, `{volume}`, `{issue}`, and `{tn}`.
+    ///
+    /// Example: `{title} ({date})` -> `Arizona Catering Employees (1944-08-06)`
+    pub fn record_title_with(&self, template: &str) -> String {
         // replace underscores with spaces.
         let title = self.title.replace("_", " ");
-        format!("{}, {}", title, self.date)
-    }
-}
-
-const MONTHS: [&str; 12] = [
-    "Jan",
-    "Feb",
-    "Mar",
-    "Apr",
-    "May",
-    "Jun",
-    "Jul",
-    "Aug",
-    "Sep",
-    "Oct",
-    "Nov",
-    "Dec",
+        let volume = self.volume.map(|v| v.to_string()).unwrap_or_default();
+        let issue = self.issue.map(|n| n.to_string()).unwrap_or_default();
+        template
+            .replace("{title}", &title)
+            .replace("{date}", &self.date)
+            .replace("{volume}", &volume)
+            .replace("{issue}", &issue)
+            .replace("{tn}", &self.tn)
+    }
+}
+
+/// The date of an issue published without one.
+pub const UNDATED: &str = "n.d.";
+
+/// Normalizes a tn as set by `tn` in the config, so that e.g. `" azu_00123 "` and `azu_123` match.
+pub fn normalize_tn(tn: &str) -> String {
+    normalize_tn_with(tn, &config().tn)
+}
+
+fn normalize_tn_with(tn: &str, options: &TnOptions) -> String {
+    let mut tn = tn.trim().to_string();
+    if options.case_fold {
+        tn = tn.to_lowercase();
+    }
+    if let Some(prefix) = &options.strip_prefix {
+        let prefix = if options.case_fold { prefix.to_lowercase() } else { prefix.clone() };
+        if let Some(rest) = tn.strip_prefix(&prefix) {
+            tn = rest.to_string();
+        }
+    }
+    if options.strip_leading_zeros && tn.starts_with('0') {
+        let rest = tn.trim_start_matches('0');
+        tn = if rest.is_empty() { "0".to_string() } else { rest.to_string() };
+    }
+    tn
+}
+
+/// Returns true if a normalized tn matches the configured tn `pattern`, or no pattern is set.
+pub fn is_valid_tn(tn: &str) -> bool {
+    let options = &config().tn;
+    options.pattern.as_ref().is_none_or(|pattern| {
+        let pattern = if options.case_fold { pattern.to_lowercase() } else { pattern.clone() };
+        matches_tn_pattern(&pattern.chars().collect::<Vec<_>>(), &tn.chars().collect::<Vec<_>>())
+    })
+}
+
+/// Matches `#` to a digit, `@` to a letter, `*` to any run of characters, and anything else to itself.
+fn matches_tn_pattern(pattern: &[char], tn: &[char]) -> bool {
+    match pattern.split_first() {
+        None => tn.is_empty(),
+        Some(('*', rest)) => (0..=tn.len()).any(|i| matches_tn_pattern(rest, &tn[i..])),
+        Some((p, rest)) =>
+            tn.split_first().is_some_and(|(c, tn)| {
+                let matches = match p {
+                    '#' => c.is_ascii_digit(),
+                    '@' => c.is_alphabetic(),
+                    p => p == c,
+                };
+                matches && matches_tn_pattern(rest, tn)
+            }),
+    }
+}
+
+/// Date-time formats accepted for `date_loaded`, tried in order. Two-digit years are tried first,
+/// since `%Y` would read `20` as the year 20.
+const DATE_LOADED_FORMATS: [&str; 9] = [
+    "%d/%m/%y %H:%M",
+    "%d/%m/%Y %H:%M",
+    "%d/%m/%y %H:%M:%S",
+    "%d/%m/%Y %H:%M:%S",
+    "%d.%m.%Y %H:%M",
+    "%d-%m-%Y %H:%M",
+    "%Y-%m-%d %H:%M",
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%dT%H:%M:%S",
+];
+
+/// Date formats accepted for `date_loaded` without a time, read as midnight.
+const DATE_LOADED_DATE_FORMATS: [&str; 5] = ["%d/%m/%y", "%d/%m/%Y", "%d.%m.%Y", "%d-%m-%Y", "%Y-%m-%d"];
+
+/// Parses a date loaded in the lookup table's `d/m/y h:m` format, or a common variant
+/// (seconds, `.` or `-` separators, ISO 8601, or no time).
+pub fn parse_date_loaded(date_loaded: &str) -> Option<NaiveDateTime> {
+    let date_loaded = date_loaded.trim();
+    DATE_LOADED_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(date_loaded, format).ok())
+        .or_else(|| {
+            DATE_LOADED_DATE_FORMATS
+                .iter()
+                .find_map(|format| NaiveDate::parse_from_str(date_loaded, format).ok())
+                .map(|date| date.and_time(chrono::NaiveTime::MIN))
+        })
+}
+
+/// The template used by [`IssueData::record_title`].
+pub const DEFAULT_RECORD_TITLE_TEMPLATE: &str = "{title}, {date}";
+
+/// Removes bracketed notes from a raw title.
+///
+/// Example: Arizona Labor Journal [microfilm]. v. 2 -> Arizona Labor Journal. v. 2
+fn strip_bracketed(raw_title: &str) -> String {
+    let mut stripped = String::with_capacity(raw_title.len());
+    let mut depth = 0;
+    for c in raw_title.chars() {
+        match c {
+            '[' => {
+                depth += 1;
+            }
+            ']' if depth > 0 => {
+                depth -= 1;
+            }
+            _ if depth == 0 => stripped.push(c),
+            _ => {}
+        }
+    }
+    stripped.split_whitespace().collect::<Vec<_>>().join(" ").replace(" .", ".").replace(" ,", ",")
+}
+
+/// Full month names per locale, in calendar order. Abbreviations such as `Aug.` or `Sept.` are matched by prefix.
+const MONTH_TABLES: [[&str; 12]; 3] = [
+    [
+        "january",
+        "february",
+        "march",
+        "april",
+        "may",
+        "june",
+        "july",
+        "august",
+        "september",
+        "october",
+        "november",
+        "december",
+    ],
+    [
+        "enero",
+        "febrero",
+        "marzo",
+        "abril",
+        "mayo",
+        "junio",
+        "julio",
+        "agosto",
+        "septiembre",
+        "octubre",
+        "noviembre",
+        "diciembre",
+    ],
+    [
+        "janvier",
+        "fevrier",
+        "mars",
+        "avril",
+        "mai",
+        "juin",
+        "juillet",
+        "aout",
+        "septembre",
+        "octobre",
+        "novembre",
+        "decembre",
+    ],
 ];
 
 /// Converts a date string to a partial yyyy-mm-dd format.
 ///
 /// Example: Aug. 6, 1944 -> 1944-08-06
-fn convert_date(date: &str, tn: &str) -> String {
+/// Example: Jan. 5, '44 -> 1944-01-05
+/// Example: 1-5-44 -> 1944-01-05
+/// Example: ca. 1910 -> 1910_ca
+/// Example: n.d. -> n.d.
+fn convert_date(date: &str, tn: &str) -> Result<String, TitleParseError> {
+    if ["n.d.", "n.d", "nd", "no date"].iter().any(|nd| date.trim().eq_ignore_ascii_case(nd)) {
+        print_warn!(Warning::UndatedIssue, "{}", messages::undated_issue(tn));
+        return Ok(UNDATED.to_string());
+    }
+
+    match strip_circa(date) {
+        Some(date) => Ok(format!("{}{}", convert_exact_date(date)?, config().circa_suffix)),
+        None => convert_exact_date(date),
+    }
+}
+
+/// Strips a leading `ca.`, `ca`, `c.`, or `circa` from an approximate date.
+fn strip_circa(date: &str) -> Option<&str> {
+    let date = date.trim();
+    ["circa ", "ca. ", "ca ", "c. "].iter().find_map(|prefix| {
+        match date.get(..prefix.len()) {
+            Some(head) if head.eq_ignore_ascii_case(prefix) => Some(date[prefix.len()..].trim()),
+            _ => None,
+        }
+    })
+}
+
+fn convert_exact_date(date: &str) -> Result<String, TitleParseError> {
+    if let Some(date) = convert_numeric_date(date) {
+        return Ok(date);
+    }
+
     let mut parts = date.split(" ").collect::<Vec<&str>>();
 
-    if parts.len() != 3 {
-        print_warn!("Parts is not 3 in length. Date: \"{}\"", date);
+    // A lone year has no month to search for.
+    if parts.len() != 3 && !(parts.len() == 1 && parts[0].chars().all(|c| c.is_ascii_digit())) {
+        print_warn!(Warning::OddDate, "{}", messages::date_parts(date));
         let mut month_i = None;
         for (i, part) in parts.iter().enumerate() {
             if month_i.is_some() {
                 break;
             }
-            for month in MONTHS.iter() {
-                if part.starts_with(month) {
-                    print_warn_ok!("Found month: \"{}\" at part {}", month, i);
-                    month_i = Some(i);
-                    break;
-                }
+            if try_parse_month(part).is_some() {
+                print_warn_ok!("Found month: \"{}\" at part {}", part, i);
+                month_i = Some(i);
             }
         }
-        let month_i = month_i.expect(
-            format!(
-                "Failed to find month in date. Check the date formatting for: \"{}\", tn: {}",
-                date,
-                tn
-            ).as_str()
-        );
+        let month_i = month_i.ok_or_else(|| TitleParseError::MissingDate { raw: date.to_string() })?;
         // replace parts with month_i..end.
         parts = parts[month_i..].to_vec();
     }
@@ -171,45 +492,386 @@ fn convert_date(date: &str, tn: &str) -> String {
         3 => {
             let month = parts[0].trim_end_matches('.');
             let day = parts[1].trim_end_matches(',');
-            let year = parts[2];
+            let year = expand_year(parts[2]);
 
-            let month = try_parse_month(month);
+            let month = expect_month(month)?;
             let day = if day.len() == 1 { format!("0{}", day) } else { day.to_string() };
 
-            format!("{}-{}-{}", year, month, day)
+            Ok(format!("{}-{}-{}", year, month, day))
         }
         2 => {
             let month = parts[0].trim_end_matches('.');
-            let year = parts[1];
+            let year = expand_year(parts[1]);
 
-            let month = try_parse_month(month);
-            format!("{}-{}", year, month)
+            let month = expect_month(month)?;
+            Ok(format!("{}-{}", year, month))
         }
-        1 => {
-            let year = parts[0];
-            format!("{}", year)
-        }
-        _ => panic!("Invalid date."),
+        1 => Ok(expand_year(parts[0])),
+        _ => Err(TitleParseError::InvalidDate { raw: date.to_string() }),
+    }
+}
+
+/// Converts a numeric `m-d-y`, `m/d/y`, or `y-m-d` date to a yyyy-mm-dd format.
+fn convert_numeric_date(date: &str) -> Option<String> {
+    let parts = date.trim().split(['-', '/']).collect::<Vec<&str>>();
+    if
+        parts.len() != 3 ||
+        parts.iter().any(|p| p.is_empty() || !p.chars().all(|c| c.is_ascii_digit()))
+    {
+        return None;
+    }
+
+    let (year, month, day) = if parts[0].len() == 4 {
+        (parts[0], parts[1], parts[2])
+    } else {
+        (parts[2], parts[0], parts[1])
+    };
+    let month = month.parse::<u32>().ok().filter(|m| (1..=12).contains(m))?;
+    let day = day.parse::<u32>().ok().filter(|d| (1..=31).contains(d))?;
+
+    Some(format!("{}-{:02}-{:02}", expand_year(year), month, day))
+}
+
+/// Expands a two-digit year (`44` or `'44`) to four digits using the configured century pivot.
+fn expand_year(year: &str) -> String {
+    let digits = year.trim_start_matches(['\'', '\u{2019}']);
+    if digits.len() != 2 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return year.to_string();
+    }
+
+    let yy = digits.parse::<u32>().expect("Failed to parse two-digit year.");
+    let century = if yy < config().two_digit_year_pivot { 2000 } else { 1900 };
+    let expanded = (century + yy).to_string();
+    print_warn!(Warning::OddDate, "{}", messages::expanded_year(year, &expanded));
+    expanded
+}
+
+fn expect_month(maybe_month: &str) -> Result<String, TitleParseError> {
+    match try_parse_month(maybe_month) {
+        Some(month) => Ok(format!("{:02}", month)),
+        None => Err(TitleParseError::InvalidMonth { raw: maybe_month.to_string() }),
     }
 }
 
-fn try_parse_month(maybe_month: &str) -> &str {
+/// Parses an English, Spanish, or French month name or abbreviation, or any month name listed in the config.
+fn try_parse_month(maybe_month: &str) -> Option<u32> {
     // remove any leading/trailing periods or whitespace.
-    let maybe_month = maybe_month.trim_matches(|c: char| (c == '.' || c.is_whitespace()));
-
-    match maybe_month {
-        "Jan" => "01",
-        "Feb" => "02",
-        "Mar" => "03",
-        "Apr" => "04",
-        "May" => "05",
-        "Jun" => "06",
-        "Jul" => "07",
-        "Aug" => "08",
-        "Sep" => "09",
-        "Oct" => "10",
-        "Nov" => "11",
-        "Dec" => "12",
-        _ => panic!("Invalid month. {}", maybe_month),
+    let maybe_month = maybe_month.trim_matches(|c: char| c == '.' || c.is_whitespace());
+    let maybe_month = fold_diacritics(&maybe_month.to_lowercase());
+
+    for (name, month) in &config().months {
+        if fold_diacritics(&name.to_lowercase()) == maybe_month {
+            return Some(*month);
+        }
+    }
+
+    // Abbreviations shorter than three letters are too ambiguous to match.
+    if maybe_month.chars().count() < 3 {
+        return None;
+    }
+
+    let mut found = None;
+    for table in MONTH_TABLES.iter() {
+        for (i, name) in table.iter().enumerate() {
+            if name.starts_with(maybe_month.as_str()) {
+                let month = (i as u32) + 1;
+                match found {
+                    None => {
+                        found = Some(month);
+                    }
+                    // e.g. "jui" could be "juin" or "juillet".
+                    Some(other) if other != month => {
+                        return None;
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+    }
+    found
+}
+
+/// Optional parts of a formatted file name, set by `format` options.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FileNaming {
+    /// Transliterate diacritics and drop other non-ASCII characters, e.g. `El_Año` -> `El_Ano`.
+    pub ascii: bool,
+    /// Insert a `v03n012` segment before the date, with the volume and issue zero-padded to these widths.
+    pub volume_issue_widths: Option<(usize, usize)>,
+}
+
+/// Characters replaced with `_` in values filled into a `--name-template`.
+const INVALID_FILE_NAME_CHARS: [char; 9] = ['/', '\\', ':', '*', '?', '"', '<', '>', '|'];
+
+/// Transliterates diacritics in a file name and drops any other non-ASCII characters.
+fn ascii_file_name(name: &str) -> String {
+    fold_diacritics(name)
+        .chars()
+        .flat_map(|c| {
+            match c {
+                'ß' => vec!['s', 's'],
+                'æ' => vec!['a', 'e'],
+                'Æ' => vec!['A', 'E'],
+                'ø' => vec!['o'],
+                'Ø' => vec!['O'],
+                c if c.is_ascii() => vec![c],
+                _ => vec![],
+            }
+        })
+        .collect()
+}
+
+/// Joins the words of an underscored title, capitalizing the first letter of each, e.g. `Arizona_labor_Journal` -> `ArizonaLaborJournal`.
+fn camel_case(title: &str) -> String {
+    title
+        .split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+/// Device names that Windows reserves, with or without an extension.
+const WINDOWS_RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL",
+    "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9",
+    "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Appends the configured suffix to the name of a file stem that Windows treats as a device name.
+/// Windows ignores everything after the first `.` and trailing spaces when matching, so `nul.v2` is reserved too.
+fn avoid_reserved_name(stem: String) -> String {
+    let end = stem.find('.').unwrap_or(stem.len());
+    let name = stem[..end].trim_end();
+    if WINDOWS_RESERVED_NAMES.iter().any(|reserved| reserved.eq_ignore_ascii_case(name)) {
+        format!("{}{}{}", name, config().reserved_name_suffix, &stem[end..])
+    } else {
+        stem
+    }
+}
+
+/// Replaces accented Latin letters with their unaccented counterparts, e.g. `août` -> `aout`.
+pub fn fold_diacritics(s: &str) -> String {
+    s.chars()
+        .map(|c| {
+            match c {
+                'á' | 'à' | 'â' | 'ä' | 'ã' | 'å' => 'a',
+                'Á' | 'À' | 'Â' | 'Ä' | 'Ã' | 'Å' => 'A',
+                'é' | 'è' | 'ê' | 'ë' => 'e',
+                'É' | 'È' | 'Ê' | 'Ë' => 'E',
+                'í' | 'ì' | 'î' | 'ï' => 'i',
+                'Í' | 'Ì' | 'Î' | 'Ï' => 'I',
+                'ó' | 'ò' | 'ô' | 'ö' | 'õ' => 'o',
+                'Ó' | 'Ò' | 'Ô' | 'Ö' | 'Õ' => 'O',
+                'ú' | 'ù' | 'û' | 'ü' => 'u',
+                'Ú' | 'Ù' | 'Û' | 'Ü' => 'U',
+                'ñ' => 'n',
+                'Ñ' => 'N',
+                'ç' => 'c',
+                'Ç' => 'C',
+                _ => c,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn record_title_templates() {
+        let issue = IssueData::new(
+            "tn1".to_string(),
+            "Arizona Catering Employees. v. 9, no. 9 (Jul. 11, 1952)".to_string(),
+            "7/11/1952 14:02".to_string()
+        );
+        assert_eq!(issue.record_title(), "Arizona Catering Employees, 1952-07-11");
+        assert_eq!(
+            issue.record_title_with("{title} ({date})"),
+            "Arizona Catering Employees (1952-07-11)"
+        );
+        assert_eq!(
+            issue.record_title_with("{title} v{volume} n{issue}"),
+            "Arizona Catering Employees v9 n9"
+        );
+    }
+
+    #[test]
+    fn parse_localized_months() {
+        assert_eq!(try_parse_month("Aug."), Some(8));
+        assert_eq!(try_parse_month("Sept."), Some(9));
+        assert_eq!(try_parse_month("Agosto"), Some(8));
+        assert_eq!(try_parse_month("Dic."), Some(12));
+        assert_eq!(try_parse_month("août"), Some(8));
+        assert_eq!(try_parse_month("Févr."), Some(2));
+        assert_eq!(try_parse_month("jui"), None);
+        assert_eq!(try_parse_month("no"), None);
+        assert_eq!(convert_date("Agosto 6, 1944", "tn1").unwrap(), "1944-08-06");
+    }
+
+    #[test]
+    fn expand_two_digit_years() {
+        assert_eq!(convert_date("Jan. 5, '44", "tn1").unwrap(), "1944-01-05");
+        assert_eq!(convert_date("1-5-44", "tn1").unwrap(), "1944-01-05");
+        assert_eq!(convert_date("12/25/05", "tn1").unwrap(), "2005-12-25");
+        assert_eq!(convert_date("1944-08-06", "tn1").unwrap(), "1944-08-06");
+    }
+
+    #[test]
+    fn convert_circa_dates() {
+        assert_eq!(convert_date("ca. 1910", "tn1").unwrap(), "1910_ca");
+        assert_eq!(convert_date("Circa 1910", "tn1").unwrap(), "1910_ca");
+        assert_eq!(convert_date("ca. Aug. 1910", "tn1").unwrap(), "1910-08_ca");
+        assert_eq!(convert_date("1910", "tn1").unwrap(), "1910");
+    }
+
+    #[test]
+    fn title_parse_errors() {
+        let parse = |raw_title: &str| {
+            let row = LookupRow { tn: "tn1".to_string(), raw_title: raw_title.to_string(), date_loaded: String::new() };
+            IssueData::try_from(row).map(|issue| issue.formatted_title())
+        };
+        assert_eq!(parse("Arizona Sun. (May 12, 1944)"), Ok("Arizona_Sun_1944-05-12".to_string()));
+        assert_eq!(
+            parse("Arizona Sun (May 12, 1944)"),
+            Err(TitleParseError::MissingTitlePeriod { raw: "Arizona Sun".to_string() })
+        );
+        assert_eq!(parse("Arizona Sun. (Foo 12, 1944)"), Err(TitleParseError::InvalidMonth { raw: "Foo".to_string() }));
+        assert_eq!(
+            parse("Arizona Sun. v. x, no. 2 (May 12, 1944)"),
+            Err(TitleParseError::UnparseableVolume { raw: "x".to_string() })
+        );
+        assert_eq!(
+            parse("Arizona Sun. v.3no.12 (May 12, 1944)"),
+            Err(TitleParseError::AmbiguousSplit { raw: "v.3no.12".to_string() })
+        );
+        assert!(matches!(parse("Arizona Sun. (Late summer of 1944)"), Err(TitleParseError::MissingDate { .. })));
+    }
+
+    #[test]
+    fn strip_bracketed_annotations() {
+        let issue = IssueData::new(
+            "tn8".to_string(),
+            "Arizona Labor Journal [microfilm]. v. 2, no. 4 (Jan. 5, 1945)".to_string(),
+            "7/11/1952 14:02".to_string()
+        );
+        assert_eq!(issue.formatted_title(), "Arizona_Labor_Journal_1945-01-05");
+        assert_eq!(issue.volume, Some(2));
+        assert_eq!(issue.issue, Some(4));
+    }
+
+    #[test]
+    fn undated_issues() {
+        let issue = IssueData::new(
+            "tn7".to_string(),
+            "Arizona Sun. (n.d.)".to_string(),
+            "7/11/1952 14:02".to_string()
+        );
+        assert!(issue.is_undated());
+        assert_eq!(issue.formatted_title(), "Arizona_Sun_nd_tn7");
+        assert_eq!(issue.record_title(), "Arizona Sun, n.d.");
+    }
+
+    #[test]
+    fn ascii_formatted_titles() {
+        let issue = IssueData::new(
+            "tn9".to_string(),
+            "¡El Año de la Niña!. (Jan. 5, 1945)".to_string(),
+            "7/11/1952 14:02".to_string()
+        );
+        assert_eq!(issue.formatted_title(), "¡El_Año_de_la_Niña!_1945-01-05");
+        let naming = FileNaming { ascii: true, ..Default::default() };
+        assert_eq!(issue.formatted_title_with(&naming), "El_Ano_de_la_Nina!_1945-01-05");
+    }
+
+    #[test]
+    fn parse_dates_loaded() {
+        let parse = |date_loaded: &str| parse_date_loaded(date_loaded).map(|date| date.to_string());
+        assert_eq!(parse("6/8/2020 10:00"), Some("2020-08-06 10:00:00".to_string()));
+        assert_eq!(parse(" 06/08/20 10:00:30"), Some("2020-08-06 10:00:30".to_string()));
+        assert_eq!(parse("2020-08-06T10:00:00"), Some("2020-08-06 10:00:00".to_string()));
+        assert_eq!(parse("6.8.2020"), Some("2020-08-06 00:00:00".to_string()));
+        assert_eq!(parse("32/8/2020 10:00"), None);
+        assert_eq!(parse("8/13/2020 10:00"), None);
+        assert_eq!(parse(""), None);
+    }
+
+    #[test]
+    fn normalize_tns() {
+        let options = TnOptions {
+            case_fold: true,
+            strip_prefix: Some("AZU_".to_string()),
+            strip_leading_zeros: true,
+            ..Default::default()
+        };
+        assert_eq!(normalize_tn_with(" azu_00123 ", &options), "123");
+        assert_eq!(normalize_tn_with("AZU_000", &options), "0");
+        assert_eq!(normalize_tn_with(" TN12 ", &options), "tn12");
+        // without case-fold, tns that differ only in case stay apart.
+        assert_eq!(normalize_tn_with(" TN12 ", &TnOptions::default()), "TN12");
+        assert_ne!(normalize_tn_with("TN12", &TnOptions::default()), normalize_tn_with("tn12", &TnOptions::default()));
+
+        let chars = |s: &str| s.chars().collect::<Vec<_>>();
+        assert!(matches_tn_pattern(&chars("tn#*"), &chars("tn12345")));
+        assert!(matches_tn_pattern(&chars("@@_###"), &chars("az_012")));
+        assert!(!matches_tn_pattern(&chars("tn#*"), &chars("tnx1")));
+        assert!(!matches_tn_pattern(&chars("###"), &chars("1234")));
+    }
+
+    #[test]
+    fn volume_issue_segments() {
+        let issue = IssueData::new(
+            "tn1".to_string(),
+            "Arizona Catering Employees. v. 9, no. 9 (Jul. 11, 1952)".to_string(),
+            "7/11/1952 14:02".to_string()
+        );
+        let naming = FileNaming { volume_issue_widths: Some((2, 3)), ..Default::default() };
+        assert_eq!(issue.formatted_title_with(&naming), "Arizona_Catering_Employees_v09n009_1952-07-11");
+    }
+
+    #[test]
+    fn camel_case_titles() {
+        assert_eq!(camel_case("Arizona_labor_Journal"), "ArizonaLaborJournal");
+        assert_eq!(camel_case("El_Año"), "ElAño");
+    }
+
+    #[test]
+    fn file_names_from_templates() {
+        let issue = IssueData::new(
+            "tn1".to_string(),
+            "Arizona Catering Employees. v. 9, no. 9 (Jul. 11, 1952)".to_string(),
+            "7/11/1952 14:02".to_string()
+        );
+        let column = |column: &str| (column == "Call Number").then(|| "AZ/0042 ".to_string());
+        let naming = FileNaming::default();
+        let render = |template: &str| issue.file_name_from_template(template, &naming, column);
+        assert_eq!(render("{Call Number}_{date}"), Ok("AZ_0042_1952-07-11".to_string()));
+        assert_eq!(render("{title}_v{volume}"), Ok("Arizona_Catering_Employees_v9".to_string()));
+        assert_eq!(render("{Local ID}_{tn}"), Err("Local ID".to_string()));
+    }
+
+    #[test]
+    fn avoid_reserved_names() {
+        assert_eq!(avoid_reserved_name("CON".to_string()), "CON_file");
+        assert_eq!(avoid_reserved_name("com1.v2".to_string()), "com1_file.v2");
+        assert_eq!(avoid_reserved_name("CON_1944-08-06".to_string()), "CON_1944-08-06");
+        assert_eq!(avoid_reserved_name("CONTACT".to_string()), "CONTACT");
+
+        let issue = IssueData::new(
+            "tn1".to_string(),
+            "Con. v. 2, no. 4 (Jan. 5, 1945)".to_string(),
+            "1/5/1945 14:02".to_string()
+        );
+        assert_eq!(issue.formatted_title(), "Con_1945-01-05");
+        let column = |column: &str| (column == "Call Number").then(|| "nul".to_string());
+        let render = |template: &str| issue.file_name_from_template(template, &FileNaming::default(), column);
+        assert_eq!(render("{Call Number}"), Ok("nul_file".to_string()));
+        assert_eq!(render("{Call Number}.{date}"), Ok("nul_file.1945-01-05".to_string()));
+        assert_eq!(render("{Call Number}_{date}"), Ok("nul_1945-01-05".to_string()));
     }
 }
@@ -1,18 +1,165 @@
-use std::path::PathBuf;
+pub mod archive;
+pub mod archive_record;
+pub mod checksum;
+pub mod collect;
+pub mod compare;
+pub mod config;
+pub mod coverage;
+pub mod date;
+pub mod dedupe;
+pub mod derivative;
+pub mod diff;
+pub mod fetch;
+pub mod filesystem;
+pub mod filter;
+pub mod format;
+pub mod group;
+pub mod gsheet;
+pub mod hooks;
+pub mod incremental;
+pub mod inspect;
+pub mod issue_data;
+pub mod links;
+pub mod lookup;
+pub mod messages;
+pub mod missing;
+pub mod notify;
+pub mod pdf;
+pub mod pipeline;
+pub mod plan;
+pub mod populate;
+pub mod preview;
+pub mod query;
+pub mod remote;
+pub mod reverse;
+pub mod state;
+pub mod summary;
+#[cfg(test)]
+mod test_util;
+pub mod titles;
+pub mod validate;
+pub mod warnings;
+pub mod xmp;
 
+use std::{
+    io::{ IsTerminal, Read, Write },
+    path::{ Path, PathBuf },
+    sync::{ atomic::{ AtomicBool, Ordering }, OnceLock },
+    time::{ Duration, SystemTime },
+};
+
+use crate::filesystem::{ Filesystem, RealFilesystem };
+
+/// Reports a warning of a `Warning` category. With `--group-warnings`, it is only counted toward the end-of-run
+/// summary.
+#[macro_export]
+macro_rules! print_warn {
+    ($category:expr, $($arg:tt)*) => {
+            let label = match $crate::summary::record_warning($category, format!($($arg)*)) {
+                $crate::warnings::Severity::Allow => None,
+                $crate::warnings::Severity::Warn => Some("[WARN]".yellow().to_string()),
+                $crate::warnings::Severity::Deny => Some("[DENY]".red().bold().to_string()),
+            };
+            let shown = !$crate::summary::is_quiet() && !$crate::summary::groups_warnings();
+            if let Some(label) = label.filter(|_| shown) {
+                use std::io::Write as _;
+                // output goes through anstream, which strips colors when they are disabled or the stream is not a terminal.
+                let _ = writeln!(anstream::stderr(), "{} {}", label, format_args!($($arg)*));
+            }
+            if $crate::summary::severity($category) == $crate::warnings::Severity::Deny {
+                $crate::summary::deny($category);
+            }
+    };
+}
+
+/// Reports an error that stops the run, such as an unparseable lookup row. It is printed even with `--quiet`.
+#[macro_export]
+macro_rules! print_error {
+    ($($arg:tt)*) => {
+        {
+            use std::io::Write as _;
+            let _ = writeln!(anstream::stderr(), "{} {}", "[ERROR]".red().bold(), format_args!($($arg)*));
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! print_warn_ok {
+    ($($arg:tt)*) => {
+            if !$crate::summary::is_quiet() {
+                use std::io::Write as _;
+                let _ = writeln!(
+                    anstream::stderr(),
+                    "{} {}",
+                    "[OK]".yellow().dimmed().italic(),
+                    format_args!($($arg)*).dimmed()
+                );
+            }
+    };
+}
+
+/// Prints a status message to stdout, or to stderr when stdout carries CSV data (`-t -`). Nothing is printed with `--quiet`.
+#[macro_export]
+macro_rules! print_status {
+    ($($arg:tt)*) => {
+        {
+            use std::io::Write as _;
+            if $crate::summary::is_quiet() {
+                // suppressed.
+            } else if $crate::stdout_is_data() {
+                let _ = writeln!(anstream::stderr(), $($arg)*);
+            } else {
+                let _ = writeln!(anstream::stdout(), $($arg)*);
+            }
+        }
+    };
+}
+
+/// The answer given as "all" or "none" to a `prompt_bool`, repeated for every later prompt of the session.
+static REMEMBERED_ANSWER: OnceLock<bool> = OnceLock::new();
+
+/// Set by `--yes` (or `BULK_FORMAT_ASSUME_YES`) to answer every prompt with yes, or overwrite, without asking.
+static ASSUME_YES: AtomicBool = AtomicBool::new(false);
+
+/// Answers every later prompt with yes, and every conflict with overwrite, for unattended runs.
+pub fn assume_yes() {
+    ASSUME_YES.store(true, Ordering::Relaxed);
+}
+
+/// Asks a yes/no question. Answering `a` (all) or `none` answers this and every later question of the session.
 pub fn prompt_bool(prompt: &str) -> bool {
+    if ASSUME_YES.load(Ordering::Relaxed) {
+        println!("{} {}", prompt, messages::answered_assumed());
+        return true;
+    }
+    ask_bool(prompt, &mut std::io::stdin().lock(), &REMEMBERED_ANSWER)
+}
+
+/// Asks a yes/no question, reading answers from `input` until one is valid, and remembering an all/none answer.
+fn ask_bool(prompt: &str, input: &mut impl std::io::BufRead, remembered: &OnceLock<bool>) -> bool {
+    if let Some(answer) = remembered.get() {
+        println!("{} {}", prompt, if *answer { messages::answered_all() } else { messages::answered_none() });
+        return *answer;
+    }
     loop {
         println!("{}", prompt);
-        println!("[y/N]");
-        let mut input = String::new();
-        std::io::stdin().read_line(&mut input).expect("Failed to read input.");
-        match input.trim().to_lowercase().as_str() {
-            "y" => {
+        println!("{}", messages::yes_no_choices());
+        let mut answer = String::new();
+        input.read_line(&mut answer).expect("Failed to read input.");
+        match answer.trim().to_lowercase().as_str() {
+            // a bare `s` is left out, as it means skip in a conflict prompt.
+            "y" | "yes" | "si" | "sí" => {
                 return true;
             }
-            "n" => {
+            "n" | "no" => {
                 return false;
             }
+            "a" | "all" | "t" | "todos" => {
+                return *remembered.get_or_init(|| true);
+            }
+            "none" | "ninguno" => {
+                return *remembered.get_or_init(|| false);
+            }
             _ => {
                 continue;
             }
@@ -20,41 +167,589 @@ pub fn prompt_bool(prompt: &str) -> bool {
     }
 }
 
+/// Parses a byte size such as `500`, `50GB`, or `1.5 GiB`. Decimal units (KB, MB, GB, TB) are powers of 1000, binary units (KiB, MiB, GiB, TiB) are powers of 1024.
+pub fn parse_size(size: &str) -> Result<u64, String> {
+    let size = size.trim();
+    let split = size
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(size.len());
+    let (number, unit) = size.split_at(split);
+    let number = number.parse::<f64>().map_err(|_| format!("Invalid size: \"{}\"", size))?;
+    let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" => 1_000,
+        "m" | "mb" => 1_000_000,
+        "g" | "gb" => 1_000_000_000,
+        "t" | "tb" => 1_000_000_000_000,
+        "kib" => 1 << 10,
+        "mib" => 1 << 20,
+        "gib" => 1 << 30,
+        "tib" => 1 << 40,
+        unit => {
+            return Err(format!("Invalid size unit: \"{}\"", unit));
+        }
+    };
+    Ok((number * (multiplier as f64)).round() as u64)
+}
+
+/// Parses a point in time given as a local date (`2024-05-12`, `2024-05-12 09:30`, `2024-05-12T09:30:00`)
+/// or as a duration before now (`90m`, `12h`, `7d`, `2w`).
+pub fn parse_time(time: &str) -> Result<SystemTime, String> {
+    let time = time.trim();
+    let split = time.find(|c: char| !c.is_ascii_digit()).unwrap_or(time.len());
+    let (number, unit) = time.split_at(split);
+    if let Ok(number) = number.parse::<u64>() {
+        let seconds = match unit {
+            "s" => Some(1),
+            "m" => Some(60),
+            "h" => Some(60 * 60),
+            "d" => Some(24 * 60 * 60),
+            "w" => Some(7 * 24 * 60 * 60),
+            _ => None,
+        };
+        if let Some(seconds) = seconds {
+            return Ok(SystemTime::now() - Duration::from_secs(number * seconds));
+        }
+    }
+
+    let date_time = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M"]
+        .iter()
+        .find_map(|format| chrono::NaiveDateTime::parse_from_str(time, format).ok())
+        .or_else(|| {
+            chrono::NaiveDate::parse_from_str(time, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        })
+        .ok_or_else(|| format!("Invalid date or duration: \"{}\"", time))?;
+    date_time
+        .and_local_timezone(chrono::Local)
+        .earliest()
+        .map(SystemTime::from)
+        .ok_or_else(|| format!("Invalid local time: \"{}\"", time))
+}
+
+/// Returns `n` distinct indices below `len`, chosen at random from a seed and in ascending order. The same seed always
+/// picks the same indices, so a sample can be drawn again.
+pub fn sample_indices(len: usize, n: usize, seed: u64) -> Vec<usize> {
+    // splitmix64, which is plenty for picking items to spot-check.
+    let mut state = seed;
+    let mut next = move || {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    };
+    let mut indices = (0..len).collect::<Vec<_>>();
+    let n = n.min(len);
+    // a partial Fisher-Yates shuffle.
+    for i in 0..n {
+        let j = i + ((next() % ((len - i) as u64)) as usize);
+        indices.swap(i, j);
+    }
+    indices.truncate(n);
+    indices.sort_unstable();
+    indices
+}
+
+/// Returns the path of a file derived from `path`, next to it and named after its stem with `suffix` and `extension`,
+/// e.g. `records.csv` -> `records_populated.csv`. Only the file name changes, so a directory like `exports.csv/` is kept.
+pub fn derived_path(path: &str, suffix: &str, extension: &str) -> String {
+    let path = Path::new(path);
+    let stem = path.file_stem().map(|stem| stem.to_string_lossy()).unwrap_or_default();
+    path.with_file_name(format!("{}{}.{}", stem, suffix, extension)).to_string_lossy().to_string()
+}
+
 /// Checks if the target file exists, and if it does, prompts the user if they want to overwrite it.
-/// If they do not want to overwrite it, a new target file name is generated in a loop until a unique name is found.
+/// If they do not want to overwrite it, a new unique target file name is generated.
 pub fn safely_target_file(target: &str) -> String {
-    let mut target = target.to_string();
-    let mut target_path = PathBuf::from(target.as_str());
-    let mut target_exists = target_path.exists();
-    while target_exists {
-        let should_overwrite = prompt_bool(
-            &format!("The target file \"{}\" already exists. Do you want to overwrite it?", target)
+    let target_path = PathBuf::from(target);
+    if !target_path.exists() {
+        return target.to_string();
+    }
+    let should_overwrite = prompt_bool(&messages::overwrite_prompt(target));
+    if should_overwrite {
+        trash_file(&RealFilesystem, &target_path);
+        target.to_string()
+    } else {
+        unique_target(&RealFilesystem, &target_path).to_string_lossy().to_string()
+    }
+}
+
+/// Returns the first path of the form `stem_1.ext`, `stem_2.ext`, ... that does not exist yet.
+pub fn unique_target(fs: &dyn Filesystem, target: &Path) -> PathBuf {
+    let stem = target.file_stem().expect("Failed to get file stem.").to_string_lossy();
+    let ext = target.extension().map(|ext| ext.to_string_lossy());
+    let mut index = 1;
+    loop {
+        let name = match &ext {
+            Some(ext) => format!("{}_{}.{}", stem, index, ext),
+            None => format!("{}_{}", stem, index),
+        };
+        let candidate = target.with_file_name(name);
+        if !fs.exists(&candidate) {
+            return candidate;
+        }
+        index += 1;
+    }
+}
+
+/// How to handle a target file that already exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictChoice {
+    Overwrite,
+    Skip,
+    Rename,
+}
+
+/// Prompts for each conflicting target, remembering an "apply to all" answer for the rest of the run.
+#[derive(Debug, Default)]
+pub struct ConflictResolver {
+    remembered: Option<ConflictChoice>,
+}
+
+impl ConflictResolver {
+    /// Returns the path to write to, or `None` if the target should be skipped.
+    pub fn resolve(&mut self, fs: &dyn Filesystem, target: &Path) -> Option<PathBuf> {
+        if !fs.exists(target) {
+            return Some(target.to_path_buf());
+        }
+        let choice = match self.remembered {
+            Some(choice) => choice,
+            None if ASSUME_YES.load(Ordering::Relaxed) => ConflictChoice::Overwrite,
+            None => {
+                let (choice, apply_to_all) = prompt_conflict(target);
+                if apply_to_all {
+                    self.remembered = Some(choice);
+                }
+                choice
+            }
+        };
+        match choice {
+            ConflictChoice::Overwrite => {
+                trash_file(fs, target);
+                Some(target.to_path_buf())
+            }
+            ConflictChoice::Skip => None,
+            ConflictChoice::Rename => Some(unique_target(fs, target)),
+        }
+    }
+}
+
+/// Returns the chosen action, and whether it should apply to every remaining conflict.
+fn prompt_conflict(target: &Path) -> (ConflictChoice, bool) {
+    loop {
+        println!("{}", messages::target_exists(&target.to_string_lossy()));
+        println!("{}", messages::conflict_choices());
+        let mut input = String::new();
+        std::io::stdin().read_line(&mut input).expect("Failed to read input.");
+        match input.trim() {
+            "o" => {
+                return (ConflictChoice::Overwrite, false);
+            }
+            "s" => {
+                return (ConflictChoice::Skip, false);
+            }
+            "r" => {
+                return (ConflictChoice::Rename, false);
+            }
+            "O" => {
+                return (ConflictChoice::Overwrite, true);
+            }
+            "S" => {
+                return (ConflictChoice::Skip, true);
+            }
+            "R" => {
+                return (ConflictChoice::Rename, true);
+            }
+            _ => {
+                continue;
+            }
+        }
+    }
+}
+
+/// Returns the existing companion files of `file` that share its stem and have one of `extensions`, e.g. `tn12345.txt` for `tn12345.pdf`.
+/// The file's own extension is never treated as a companion.
+pub fn companion_files(fs: &dyn Filesystem, file: &Path, extensions: &[String]) -> Vec<PathBuf> {
+    let own_ext = file.extension().map(|ext| ext.to_string_lossy().to_lowercase());
+    extensions
+        .iter()
+        .map(|ext| ext.trim_start_matches('.'))
+        .filter(|ext| own_ext.as_deref() != Some(ext.to_lowercase().as_str()))
+        .map(|ext| file.with_extension(ext))
+        .filter(|companion| fs.is_file(companion))
+        .collect()
+}
+
+/// Returns true if a `/`-separated path matches a glob pattern: `*` matches within one path segment,
+/// `**` matches across segments (and `**/` also matches no directory), and `?` matches any one character but `/`.
+pub fn glob_match(pattern: &str, path: &str) -> bool {
+    fn matches(pattern: &[char], path: &[char]) -> bool {
+        match pattern {
+            [] => path.is_empty(),
+            ['*', '*', '/', rest @ ..] => {
+                matches(rest, path) || (0..path.len()).any(|i| path[i] == '/' && matches(rest, &path[i + 1..]))
+            }
+            ['*', '*', rest @ ..] => (0..=path.len()).any(|i| matches(rest, &path[i..])),
+            ['*', rest @ ..] => {
+                (0..=path.len()).take_while(|i| *i == 0 || path[i - 1] != '/').any(|i| matches(rest, &path[i..]))
+            }
+            ['?', rest @ ..] => path.first().is_some_and(|c| *c != '/') && matches(rest, &path[1..]),
+            [c, rest @ ..] => path.first() == Some(c) && matches(rest, &path[1..]),
+        }
+    }
+    matches(&pattern.chars().collect::<Vec<_>>(), &path.chars().collect::<Vec<_>>())
+}
+
+/// Files at least this large are copied in chunks, with a progress bar and a chance to cancel with Ctrl-C.
+const CHUNKED_COPY_BYTES: u64 = 256 << 20;
+const COPY_CHUNK_BYTES: usize = 8 << 20;
+
+/// Copies a file like `std::fs::copy`, but first tries to clone it copy-on-write (a reflink), which is near-instant
+/// and shares the data on disk, when both paths are on one btrfs, XFS, or APFS filesystem. Falls back to a full copy
+/// on other filesystems (and on Windows). Large files are copied in chunks with a progress bar, and a Ctrl-C during
+/// the copy removes the partial target and returns an `Interrupted` error. Returns the number of bytes copied.
+pub fn copy_file(source: &Path, target: &Path) -> std::io::Result<u64> {
+    if reflink(source, target).is_ok() {
+        summary::record_count("cloned", 1);
+        return Ok(source.metadata()?.len());
+    }
+    let bytes = source.metadata()?.len();
+    if bytes < CHUNKED_COPY_BYTES {
+        return std::fs::copy(source, target);
+    }
+    COPYING.store(true, Ordering::SeqCst);
+    let result = copy_chunked(source, target, COPY_CHUNK_BYTES);
+    COPYING.store(false, Ordering::SeqCst);
+    if result.is_err() {
+        let _ = std::fs::remove_file(target);
+    }
+    result
+}
+
+fn copy_chunked(source: &Path, target: &Path, chunk_bytes: usize) -> std::io::Result<u64> {
+    let mut reader = std::fs::File::open(source)?;
+    let mut writer = std::fs::File::create(target)?;
+    let bytes = reader.metadata()?.len();
+    let show_progress = !summary::is_quiet() && std::io::stderr().is_terminal();
+    let mut buffer = vec![0; chunk_bytes];
+    let mut copied = 0;
+    let result = loop {
+        if interrupted() {
+            break Err(std::io::ErrorKind::Interrupted.into());
+        }
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => {
+                break Ok(copied);
+            }
+            Ok(read) => read,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {
+                continue;
+            }
+            Err(e) => {
+                break Err(e);
+            }
+        };
+        if let Err(e) = writer.write_all(&buffer[..read]) {
+            break Err(e);
+        }
+        copied += read as u64;
+        if show_progress {
+            print_progress(copied, bytes);
+        }
+    };
+    if show_progress {
+        eprint!("\r{:width$}\r", "", width = PROGRESS_WIDTH);
+    }
+    writer.set_permissions(reader.metadata()?.permissions())?;
+    result
+}
+
+const PROGRESS_WIDTH: usize = 64;
+
+/// Overwrites the current stderr line with a copy's progress, e.g. `  42% [############                ] 4.2 GB / 10.0 GB`.
+fn print_progress(copied: u64, bytes: u64) {
+    let fraction = (copied as f64) / (bytes.max(1) as f64);
+    let filled = ((fraction * 28.0) as usize).min(28);
+    let line = format!(
+        "{:>5.0}% [{}{}] {} / {}",
+        fraction * 100.0,
+        "#".repeat(filled),
+        " ".repeat(28 - filled),
+        summary::format_size(copied),
+        summary::format_size(bytes)
+    );
+    eprint!("\r{:width$}", line, width = PROGRESS_WIDTH);
+    let _ = std::io::stderr().flush();
+}
+
+static COPYING: AtomicBool = AtomicBool::new(false);
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Makes a Ctrl-C during a chunked `copy_file` cancel the copy instead of exiting at once, so its partial output is
+/// removed. A Ctrl-C at any other time exits with status 130 as usual. Only on Unix; elsewhere Ctrl-C always exits.
+pub fn handle_interrupts() {
+    #[cfg(unix)]
+    // SAFETY: the handler only touches atomics and calls the async-signal-safe `_exit`.
+    unsafe {
+        libc::signal(libc::SIGINT, on_interrupt as *const () as libc::sighandler_t);
+    }
+}
+
+#[cfg(unix)]
+extern "C" fn on_interrupt(_signal: libc::c_int) {
+    if COPYING.load(Ordering::SeqCst) {
+        INTERRUPTED.store(true, Ordering::SeqCst);
+    } else {
+        // SAFETY: `_exit` is async-signal-safe.
+        unsafe { libc::_exit(130) }
+    }
+}
+
+/// Returns true if a Ctrl-C cancelled a copy, and the command should stop.
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn reflink(source: &Path, target: &Path) -> std::io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let source = std::fs::File::open(source)?;
+    let target = std::fs::File::create(target)?;
+    // SAFETY: both descriptors stay open for the duration of the call.
+    if unsafe { libc::ioctl(target.as_raw_fd(), libc::FICLONE, source.as_raw_fd()) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    target.set_permissions(source.metadata()?.permissions())
+}
+
+#[cfg(target_os = "macos")]
+fn reflink(source: &Path, target: &Path) -> std::io::Result<()> {
+    use std::{ ffi::CString, os::unix::ffi::OsStrExt };
+
+    let path = |path: &Path| {
+        CString::new(path.as_os_str().as_bytes()).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))
+    };
+    let (source, target) = (path(source)?, path(target)?);
+    // SAFETY: both paths are valid NUL-terminated strings. `clonefile` fails, without writing, if the target exists.
+    if unsafe { libc::clonefile(source.as_ptr(), target.as_ptr(), 0) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "macos")))]
+fn reflink(_source: &Path, _target: &Path) -> std::io::Result<()> {
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
+/// The directory, created next to an overwritten file, that holds its previous version.
+pub const TRASH_DIR: &str = ".bulk_format_trash";
+
+/// The directory, created in a directory a command moves files in, that holds snapshots of its inputs.
+pub const SNAPSHOT_DIR: &str = ".bulk_format/snapshots";
+
+/// Returns the local time the run started, e.g. `20240512-093000`. Shared by every trashed file and snapshot of a run.
+pub fn run_timestamp() -> &'static str {
+    static RUN_TIMESTAMP: OnceLock<String> = OnceLock::new();
+    RUN_TIMESTAMP.get_or_init(|| { chrono::Local::now().format("%Y%m%d-%H%M%S").to_string() })
+}
+
+/// Moves an existing file into `.bulk_format_trash/<timestamp>/` in its own directory, so an accidental overwrite can be recovered.
+/// Every file trashed during one run shares the same timestamp. Returns the trashed file's new path.
+pub fn trash_file(fs: &dyn Filesystem, path: &Path) -> PathBuf {
+    let trash_dir = path
+        .parent()
+        .expect("Failed to get parent directory.")
+        .join(TRASH_DIR)
+        .join(run_timestamp());
+    fs.create_dir_all(&trash_dir).expect("Failed to create trash directory.");
+
+    let mut trashed = trash_dir.join(path.file_name().expect("Failed to get file name."));
+    if fs.exists(&trashed) {
+        trashed = unique_target(fs, &trashed);
+    }
+    fs.rename(path, &trashed).expect("Failed to move file to trash.");
+    if !summary::is_quiet() {
+        println!("Moved \"{}\" to \"{}\"", path.to_string_lossy(), trashed.to_string_lossy());
+    }
+    trashed
+}
+
+/// The path that reads a CSV from stdin or writes it to stdout.
+pub const STDIO: &str = "-";
+
+static STDOUT_IS_DATA: AtomicBool = AtomicBool::new(false);
+
+/// Marks stdout as carrying CSV data, so status messages are written to stderr instead.
+pub fn set_stdout_is_data() {
+    STDOUT_IS_DATA.store(true, Ordering::Relaxed);
+}
+
+/// Returns true if stdout carries CSV data and status messages must go to stderr.
+pub fn stdout_is_data() -> bool {
+    STDOUT_IS_DATA.load(Ordering::Relaxed)
+}
+
+/// Opens a CSV file for reading, or stdin if `path` is `-`.
+pub fn csv_reader(path: &str) -> csv::Reader<Box<dyn Read>> {
+    let input: Box<dyn Read> = if path == STDIO {
+        Box::new(std::io::stdin().lock())
+    } else {
+        Box::new(std::fs::File::open(path).expect("Failed to read CSV file."))
+    };
+    csv::Reader::from_reader(input)
+}
+
+/// Creates a CSV file for writing, or writes to stdout if `path` is `-`.
+pub fn csv_writer(path: &str) -> csv::Writer<Box<dyn Write>> {
+    let output: Box<dyn Write> = if path == STDIO {
+        Box::new(std::io::stdout().lock())
+    } else {
+        Box::new(std::fs::File::create(path).expect("Failed to write CSV file."))
+    };
+    csv::Writer::from_writer(output)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::filesystem::MemoryFilesystem;
+
+    #[test]
+    fn match_globs() {
+        assert!(glob_match("*_draft*", "tn1_draft.pdf"));
+        assert!(!glob_match("*_draft*", "tn1.pdf"));
+        assert!(glob_match("tn?.pdf", "tn1.pdf"));
+        assert!(!glob_match("*.pdf", "backup/tn1.pdf"));
+        assert!(glob_match("backup/**", "backup/2024/tn1.pdf"));
+        assert!(glob_match("backup/**", "backup/"));
+        assert!(!glob_match("backup/**", "backups/tn1.pdf"));
+        assert!(glob_match("**/tn1.pdf", "tn1.pdf"));
+        assert!(glob_match("**/tn1.pdf", "a/b/tn1.pdf"));
+    }
+
+    #[test]
+    fn resolve_conflicts_with_remembered_choices() {
+        let fs = MemoryFilesystem::new();
+        fs.add_file("out/a.pdf", 1);
+        fs.add_file("out/a_1.pdf", 1);
+        let target = Path::new("out/a.pdf");
+        let mut resolver = ConflictResolver::default();
+        assert_eq!(resolver.resolve(&fs, Path::new("out/b.pdf")), Some(PathBuf::from("out/b.pdf")));
+        resolver.remembered = Some(ConflictChoice::Skip);
+        assert_eq!(resolver.resolve(&fs, target), None);
+        resolver.remembered = Some(ConflictChoice::Rename);
+        assert_eq!(resolver.resolve(&fs, target), Some(PathBuf::from("out/a_2.pdf")));
+        resolver.remembered = Some(ConflictChoice::Overwrite);
+        assert_eq!(resolver.resolve(&fs, target), Some(target.to_path_buf()));
+        assert!(!fs.exists(target));
+    }
+
+    #[test]
+    fn trash_files_by_run() {
+        let fs = MemoryFilesystem::new();
+        fs.add_file("out/a.pdf", 1);
+        let trash_dir = Path::new("out").join(TRASH_DIR).join(run_timestamp());
+        assert_eq!(trash_file(&fs, Path::new("out/a.pdf")), trash_dir.join("a.pdf"));
+        fs.add_file("out/a.pdf", 2);
+        assert_eq!(trash_file(&fs, Path::new("out/a.pdf")), trash_dir.join("a_1.pdf"));
+        assert_eq!(fs.len(&trash_dir.join("a_1.pdf")).unwrap(), 2);
+        assert!(!fs.exists(Path::new("out/a.pdf")));
+    }
+
+    #[test]
+    fn parse_sizes() {
+        assert_eq!(parse_size("1KB"), Ok(1_000));
+        assert_eq!(parse_size("2.5 gb"), Ok(2_500_000_000));
+        assert_eq!(parse_size("1MiB"), Ok(1 << 20));
+        assert_eq!(parse_size("512"), Ok(512));
+        assert!(parse_size("10 parsecs").is_err());
+        assert!(parse_size("MB").is_err());
+    }
+
+    #[test]
+    fn clone_or_copy_files() {
+        let dir = test_util::TestDir::new("clone_or_copy");
+        let source = dir.write("source.pdf", "abc");
+        let target = dir.join("target.pdf");
+        // a reflink where the filesystem supports one, and a full copy (over the target a failed clone created) elsewhere.
+        assert_eq!(copy_file(&source, &target).unwrap(), 3);
+        assert_eq!(std::fs::read(&target).unwrap(), b"abc");
+        assert_eq!(
+            std::fs::metadata(&target).unwrap().permissions(),
+            std::fs::metadata(&source).unwrap().permissions()
         );
-        if !should_overwrite {
-            let mut target_name = target_path
-                .file_stem()
-                .expect("Failed to get file stem.")
-                .to_string_lossy()
-                .to_string();
-            let target_ext = target_path
-                .extension()
-                .expect("Failed to get file extension.")
-                .to_string_lossy()
-                .to_string();
-            let target_dir = target_path
-                .parent()
-                .expect("Failed to get parent directory.")
-                .to_string_lossy()
-                .to_string();
-            let mut target_index = 1;
-            while target_exists {
-                target_name = format!("{}_{}", target_name, target_index);
-                target = format!("{}/{}.{}", target_dir, target_name, target_ext);
-                target_path = PathBuf::from(target.as_str());
-                target_exists = target_path.exists();
-                target_index += 1;
-            }
-        }
-    }
-    target
+        // the clone shares data copy-on-write, so writing the copy leaves the source as it was.
+        std::fs::write(&target, "abcd").unwrap();
+        assert_eq!(std::fs::read(&source).unwrap(), b"abc");
+    }
+
+    #[test]
+    fn remember_all_and_none_answers() {
+        let remembered = OnceLock::new();
+        assert!(!ask_bool("Overwrite?", &mut "maybe\nno\n".as_bytes(), &remembered));
+        assert!(ask_bool("Overwrite?", &mut "all\n".as_bytes(), &remembered));
+        // later questions are answered without reading any input.
+        assert!(ask_bool("Overwrite?", &mut "".as_bytes(), &remembered));
+
+        let remembered = OnceLock::new();
+        assert!(!ask_bool("Overwrite?", &mut "NONE\n".as_bytes(), &remembered));
+        assert!(!ask_bool("Overwrite?", &mut "".as_bytes(), &remembered));
+    }
+
+    #[test]
+    fn accept_answers_of_either_language() {
+        for (answer, expected) in [("sí", true), ("Si", true), ("yes", true), ("no", false)] {
+            assert_eq!(ask_bool("Overwrite?", &mut format!("{}\n", answer).as_bytes(), &OnceLock::new()), expected);
+        }
+        // a bare `s` is ambiguous with skip, so it is asked again.
+        assert!(!ask_bool("Overwrite?", &mut "s\nninguno\n".as_bytes(), &OnceLock::new()));
+        assert!(ask_bool("Overwrite?", &mut "todos\n".as_bytes(), &OnceLock::new()));
+    }
+
+    #[test]
+    fn copy_in_chunks() {
+        let dir = test_util::TestDir::new("chunked");
+        let data = (0..10_000u32).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+        let source = dir.write("source.bin", &data);
+        let target = dir.join("target.bin");
+        assert_eq!(copy_chunked(&source, &target, 4096).unwrap(), data.len() as u64);
+        assert_eq!(std::fs::read(&target).unwrap(), data);
+    }
+
+    #[test]
+    fn derive_paths() {
+        assert_eq!(derived_path("records.csv", "_populated", "csv"), "records_populated.csv");
+        assert_eq!(derived_path("exports.csv/records.csv", "_linked", "csv"), "exports.csv/records_linked.csv");
+        assert_eq!(derived_path("lookup.csv", "", "json"), "lookup.json");
+        assert_eq!(derived_path("records", "_populated", "csv"), "records_populated.csv");
+    }
+
+    #[test]
+    fn sample_with_seed() {
+        let sample = sample_indices(100, 10, 42);
+        assert_eq!(sample.len(), 10);
+        assert!(sample.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(sample.iter().all(|&i| i < 100));
+        assert_eq!(sample, sample_indices(100, 10, 42));
+        assert_ne!(sample, sample_indices(100, 10, 43));
+        assert_eq!(sample_indices(3, 10, 42), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn parse_times() {
+        let week_ago = parse_time("7d").unwrap();
+        let elapsed = SystemTime::now().duration_since(week_ago).unwrap();
+        assert!(elapsed >= Duration::from_secs(7 * 24 * 60 * 60) && elapsed < Duration::from_secs(7 * 24 * 60 * 60 + 60));
+
+        let midnight = chrono::NaiveDate::from_ymd_opt(2024, 5, 12).unwrap().and_hms_opt(0, 0, 0).unwrap();
+        let midnight = SystemTime::from(midnight.and_local_timezone(chrono::Local).unwrap());
+        assert_eq!(parse_time("2024-05-12"), Ok(midnight));
+        assert_eq!(parse_time("2024-05-12 09:30"), Ok(midnight + Duration::from_secs(9 * 60 * 60 + 30 * 60)));
+        assert!(parse_time("last week").is_err());
+        assert!(parse_time("7y").is_err());
+    }
 }
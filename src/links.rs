@@ -0,0 +1,529 @@
+use std::collections::{ BTreeMap, BTreeSet };
+use owo_colors::OwoColorize;
+
+use crate::{
+    archive_record::{ ArchiveRecord, ColumnOptions },
+    csv_reader,
+    csv_writer,
+    date::Date,
+    derived_path,
+    messages,
+    preview,
+    print_status,
+    print_warn,
+    safely_target_file,
+    summary,
+    warnings::Warning,
+    STDIO,
+};
+
+/// A problem with one `Previous Issue` or `Next Issue` value.
+#[derive(Debug, PartialEq, Eq)]
+enum LinkProblem {
+    /// The value is not the NODE_TITLE of any record.
+    Dangling,
+    /// The linked record does not link back.
+    Asymmetric,
+    /// The value differs from the neighbor in date order.
+    OutOfOrder,
+}
+
+impl LinkProblem {
+    /// Returns the warning for a record's `field` link to `value`.
+    fn describe(&self, title: &str, field: &str, value: &str) -> String {
+        match self {
+            LinkProblem::Dangling => messages::dangling_link(title, field, value),
+            LinkProblem::Asymmetric => messages::asymmetric_link(title, field, value),
+            LinkProblem::OutOfOrder => messages::out_of_order_link(title, field, value),
+        }
+    }
+}
+
+/// Checks every `Previous Issue`/`Next Issue` value of a CSV against the date order of each publication's records.
+/// With `fix`, writes `<target>_repaired.csv` with the expected links and returns its path.
+/// Without it, returns `Err` with the number of problems found.
+pub fn repair_links(target: &str, fix: bool, graph: Option<&str>) -> Result<Option<String>, usize> {
+    let mut reader = csv_reader(target);
+    let headers = reader.headers().expect("Failed to read target CSV headers.").clone();
+    let mut records: Vec<ArchiveRecord> = reader
+        .deserialize()
+        .map(|r| r.expect("Failed to parse record."))
+        .collect();
+
+    let titles = records.iter().map(|record| record.node_title.clone()).collect::<Vec<_>>();
+    let expected = expected_links(&titles);
+    let title_set = titles.iter().collect::<BTreeSet<_>>();
+    let by_title = records
+        .iter()
+        .map(|record| (record.node_title.as_str(), record))
+        .collect::<BTreeMap<_, _>>();
+
+    let mut problems = 0;
+    let mut fixes = vec![];
+    for (i, record) in records.iter().enumerate() {
+        let Some((expected_previous, expected_next)) = &expected[i] else {
+            print_warn!(Warning::UndatedIssue, "{}", messages::links_not_checked(&record.node_title));
+            continue;
+        };
+        let fields = [
+            ("Previous Issue", &record.previous_issue, expected_previous, false),
+            ("Next Issue", &record.next_issue, expected_next, true),
+        ];
+        for (field, value, expected, is_next) in fields {
+            let linked_back = by_title.get(value.as_str()).map(|other| {
+                let back = if is_next { &other.previous_issue } else { &other.next_issue };
+                *back == record.node_title
+            });
+            let problem = if !value.is_empty() && !title_set.contains(value) {
+                Some(LinkProblem::Dangling)
+            } else if linked_back == Some(false) {
+                Some(LinkProblem::Asymmetric)
+            } else if value != expected {
+                Some(LinkProblem::OutOfOrder)
+            } else {
+                None
+            };
+            if let Some(problem) = problem {
+                problems += 1;
+                print_warn!(Warning::BrokenLink, "{}", problem.describe(&record.node_title, field, value));
+                if value != expected {
+                    fixes.push((i, field, value.clone(), expected.clone()));
+                }
+            }
+        }
+    }
+    summary::record_count("problems", problems);
+    // graph the links as found, so the breaks are visible.
+    if let Some(graph) = graph {
+        write_graph(&records, &titles, graph);
+    }
+
+    if problems == 0 {
+        print_status!("{}", "All issue links are consistent.".green().bold());
+        return Ok(None);
+    }
+    if !fix {
+        print_status!("{} {}", problems.red().bold(), "link problems found.".red().bold());
+        return Err(problems);
+    }
+
+    let fixed = fixes.len();
+    for (i, field, old, new) in fixes {
+        let record = &mut records[i];
+        print_status!("{}", record.node_title);
+        preview::print_field_change(field, &old, &new);
+        if field == "Next Issue" {
+            record.next_issue = new;
+        } else {
+            record.previous_issue = new;
+        }
+    }
+
+    let output = safely_target_file(&derived_path(target, "_repaired", "csv"));
+    let mut writer = csv_writer(&output);
+    writer.write_record(&headers).expect("Failed to write headers.");
+    for record in &records {
+        writer.write_record(&record.to_row(&headers)).expect("Failed to write record.");
+    }
+    writer.flush().expect("Failed to write repaired CSV file.");
+    print_status!("Repaired {} links and saved to \"{}\".", fixed, output);
+    summary::record_output(&output);
+    Ok(Some(output))
+}
+
+/// Writes the previous/next chains of the records as a Graphviz DOT file, with one cluster per publication.
+/// `keys` are the values that identify each record in its links (usually its NODE_TITLE).
+/// Links between publications are drawn red, links to missing records dashed red, and one-way `Previous Issue` links dashed.
+pub fn write_graph(records: &[ArchiveRecord], keys: &[String], path: &str) {
+    let index = keys
+        .iter()
+        .enumerate()
+        .map(|(i, key)| (key.as_str(), i))
+        .collect::<BTreeMap<_, _>>();
+    let publication = |i: usize| {
+        title_parts(&records[i].node_title).map_or(records[i].node_title.as_str(), |(publication, _)| publication)
+    };
+
+    let mut dot = String::from("digraph issues {\n    rankdir=LR;\n    node [shape=box];\n");
+    let mut clusters: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+    for i in 0..records.len() {
+        clusters.entry(publication(i)).or_default().push(i);
+    }
+    for (n, (name, members)) in clusters.iter().enumerate() {
+        dot.push_str(&format!("    subgraph cluster_{} {{\n        label={};\n", n, dot_string(name)));
+        for i in members {
+            dot.push_str(&format!("        n{} [label={}];\n", i, dot_string(&records[*i].node_title)));
+        }
+        dot.push_str("    }\n");
+    }
+
+    let mut missing = 0;
+    for (i, record) in records.iter().enumerate() {
+        let links = [(&record.next_issue, true), (&record.previous_issue, false)];
+        for (value, is_next) in links {
+            if value.is_empty() {
+                continue;
+            }
+            match index.get(value.as_str()) {
+                Some(&other) => {
+                    // previous links are only drawn when the other record does not already link here.
+                    if !is_next && records[other].next_issue == keys[i] {
+                        continue;
+                    }
+                    let (from, to) = if is_next { (i, other) } else { (other, i) };
+                    let mut style = vec![];
+                    if !is_next {
+                        style.push("style=dashed");
+                    }
+                    if publication(from) != publication(to) {
+                        style.push("color=red");
+                    }
+                    let style = if style.is_empty() { String::new() } else { format!(" [{}]", style.join(", ")) };
+                    dot.push_str(&format!("    n{} -> n{}{};\n", from, to, style));
+                }
+                None => {
+                    dot.push_str(
+                        &format!(
+                            "    missing{} [label={}, style=dashed, color=red];\n    n{} -> missing{} [style=dashed, color=red];\n",
+                            missing,
+                            dot_string(value),
+                            i,
+                            missing
+                        )
+                    );
+                    missing += 1;
+                }
+            }
+        }
+    }
+    dot.push_str("}\n");
+
+    let path = safely_target_file(path);
+    std::fs::write(&path, dot).expect("Failed to write graph file.");
+    print_status!("Saved issue chains to \"{}\".", path);
+    summary::record_output(&path);
+}
+
+fn dot_string(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// The structure of the `Next Issue` links in a set of records, by record index.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct ChainReport {
+    /// Every chain, from a record nothing links to, following `Next Issue`.
+    chains: Vec<Vec<usize>>,
+    /// Loops of records that link back to themselves.
+    cycles: Vec<Vec<usize>>,
+    /// Records that more than one record names as its `Next Issue`, with those records.
+    forks: Vec<(usize, Vec<usize>)>,
+    /// Records whose `Next Issue` is not the NODE_TITLE of any record.
+    dangling: Vec<usize>,
+}
+
+/// Follows the `Next Issue` (`nexts`) of every record to find its chains, cycles, forks, and dangling links.
+fn find_chains(titles: &[String], nexts: &[String]) -> ChainReport {
+    let index = titles
+        .iter()
+        .enumerate()
+        .map(|(i, title)| (title.as_str(), i))
+        .collect::<BTreeMap<_, _>>();
+    let mut report = ChainReport::default();
+    let mut next = vec![None; titles.len()];
+    let mut incoming: Vec<Vec<usize>> = vec![vec![]; titles.len()];
+    for (i, value) in nexts.iter().enumerate() {
+        if value.is_empty() {
+            continue;
+        }
+        match index.get(value.as_str()) {
+            Some(&target) => {
+                next[i] = Some(target);
+                incoming[target].push(i);
+            }
+            None => report.dangling.push(i),
+        }
+    }
+    for (target, sources) in incoming.iter().enumerate() {
+        if sources.len() > 1 {
+            report.forks.push((target, sources.clone()));
+        }
+    }
+
+    let mut visited = vec![false; titles.len()];
+    let walk = |start: usize, visited: &mut Vec<bool>, report: &mut ChainReport, is_start: bool| {
+        let mut chain = vec![];
+        let mut current = Some(start);
+        while let Some(i) = current {
+            if visited[i] {
+                // a loop back into this walk is a cycle; reaching an earlier walk is a fork, reported above.
+                if let Some(position) = chain.iter().position(|j| *j == i) {
+                    report.cycles.push(chain.split_off(position));
+                }
+                break;
+            }
+            visited[i] = true;
+            chain.push(i);
+            current = next[i];
+        }
+        if is_start && !chain.is_empty() {
+            report.chains.push(chain);
+        }
+    };
+    for start in (0..titles.len()).filter(|i| incoming[*i].is_empty()) {
+        walk(start, &mut visited, &mut report, true);
+    }
+    // whatever was not reached from a chain start is part of a cycle.
+    for start in 0..titles.len() {
+        if !visited[start] {
+            walk(start, &mut visited, &mut report, false);
+        }
+    }
+    report
+}
+
+/// Reports the chains of each publication in a CSV, with their lengths, and any breaks, cycles, and forks.
+/// Returns the number of problems found.
+pub fn analyze_chains(target: &str) -> usize {
+    let mut reader = csv_reader(target);
+    let records: Vec<ArchiveRecord> = reader
+        .deserialize()
+        .map(|r| r.expect("Failed to parse record."))
+        .collect();
+    let titles = records.iter().map(|record| record.node_title.clone()).collect::<Vec<_>>();
+    let nexts = records.iter().map(|record| record.next_issue.clone()).collect::<Vec<_>>();
+    let report = find_chains(&titles, &nexts);
+    let publication = |i: usize| title_parts(&titles[i]).map_or(titles[i].as_str(), |(publication, _)| publication);
+
+    let mut publications: BTreeMap<&str, Vec<&Vec<usize>>> = BTreeMap::new();
+    for chain in &report.chains {
+        publications.entry(publication(chain[0])).or_default().push(chain);
+    }
+    for (name, chains) in &publications {
+        let lengths = chains.iter().map(|chain| chain.len().to_string()).collect::<Vec<_>>();
+        let status = if chains.len() == 1 { "1 chain".green().to_string() } else { format!("{} chains", chains.len()).yellow().to_string() };
+        let label = if lengths.len() == 1 { "length" } else { "lengths" };
+        print_status!("{} ({}, {} {})", name.bold(), status, label, lengths.join(", "));
+        for chain in chains.iter().filter(|chain| publication(*chain.last().unwrap_or(&chain[0])) != *name) {
+            print_warn!(Warning::BrokenLink, "{}", messages::chain_crosses_publications(&titles[chain[0]]));
+        }
+        if chains.len() > 1 {
+            for chain in chains {
+                print_status!("    {} {} {}", titles[chain[0]], "..".dimmed(), titles[chain[chain.len() - 1]]);
+            }
+        }
+    }
+
+    for i in &report.dangling {
+        print_warn!(Warning::BrokenLink, "{}", messages::missing_next_issue(&titles[*i], &nexts[*i]));
+    }
+    for cycle in &report.cycles {
+        let names = cycle.iter().map(|i| titles[*i].as_str()).collect::<Vec<_>>();
+        print_warn!(Warning::BrokenLink, "{}", messages::link_cycle(&names.join(" -> ")));
+    }
+    for (target, sources) in &report.forks {
+        let names = sources.iter().map(|i| format!("\"{}\"", titles[*i])).collect::<Vec<_>>();
+        print_warn!(Warning::BrokenLink, "{}", messages::link_fork(&names.join(", "), &titles[*target]));
+    }
+
+    let breaks = publications.values().map(|chains| chains.len() - 1).sum::<usize>();
+    summary::record_count("chains", report.chains.len());
+    summary::record_count("breaks", breaks);
+    summary::record_count("dangling", report.dangling.len());
+    summary::record_count("cycles", report.cycles.len());
+    summary::record_count("forks", report.forks.len());
+    breaks + report.dangling.len() + report.cycles.len() + report.forks.len()
+}
+
+/// Splits a NODE_TITLE such as `Arizona Sun, 1944-08-06` into its publication and date.
+pub fn title_parts(node_title: &str) -> Option<(&str, Date)> {
+    let (publication, date) = node_title.rsplit_once(", ")?;
+    Some((publication, Date::try_from(date).ok()?))
+}
+
+/// Returns the expected `(previous, next)` node titles of each title: its neighbors in date order within its publication.
+/// Titles without a date have no expected links.
+fn expected_links(titles: &[String]) -> Vec<Option<(String, String)>> {
+    let mut publications: BTreeMap<&str, Vec<(Date, usize)>> = BTreeMap::new();
+    for (i, title) in titles.iter().enumerate() {
+        if let Some((publication, date)) = title_parts(title) {
+            publications.entry(publication).or_default().push((date, i));
+        }
+    }
+
+    let mut expected = vec![None; titles.len()];
+    for chain in publications.values_mut() {
+        chain.sort_by(|a, b| a.0.cmp(&b.0));
+        for (position, (_, i)) in chain.iter().enumerate() {
+            let previous = position
+                .checked_sub(1)
+                .map(|previous| titles[chain[previous].1].clone())
+                .unwrap_or_default();
+            let next = chain
+                .get(position + 1)
+                .map(|(_, next)| titles[*next].clone())
+                .unwrap_or_default();
+            expected[*i] = Some((previous, next));
+        }
+    }
+    expected
+}
+
+/// Populate a CSV file with `previous` and `next` issue data, using the order of the records and their node titles.
+/// Returns the path of the linked CSV file.
+pub fn link_issues(
+    target: &str,
+    link_value_column: Option<&str>,
+    graph: Option<&str>,
+    columns: &ColumnOptions
+) -> String {
+    let mut reader = csv_reader(target);
+    let headers = reader.headers().expect("Failed to read target CSV headers.").clone();
+    let link_column = link_value_column.map(|column| {
+        headers
+            .iter()
+            .position(|header| header == column)
+            .unwrap_or_else(|| panic!("Column \"{}\" not found in the target CSV file.", column))
+    });
+    let output_headers = columns.project(&headers);
+    let target = if target == STDIO {
+        STDIO.to_string()
+    } else {
+        safely_target_file(&derived_path(target, "_linked", "csv"))
+    };
+
+    let mut writer = csv_writer(&target);
+    writer.write_record(&output_headers).expect("Failed to write headers.");
+
+    let mut records = vec![];
+    let mut link_values = vec![];
+    for result in reader.records() {
+        let row = result.expect("Failed to parse record.");
+        let record: ArchiveRecord = row.deserialize(Some(&headers)).expect("Failed to parse record.");
+        link_values.push(match link_column {
+            Some(column) => row.get(column).unwrap_or_default().to_string(),
+            None => record.node_title.clone(),
+        });
+        records.push(record);
+    }
+
+    link_records(&mut records, &link_values);
+    for record in &records {
+        writer.write_record(&record.to_row(&output_headers)).expect("Failed to write record.");
+    }
+    writer.flush().expect("Failed to write to target CSV file.");
+    if let Some(graph) = graph {
+        write_graph(&records, &link_values, graph);
+    }
+
+    print_status!("Linked issues and saved to \"{}\".", target);
+    if target != STDIO {
+        summary::record_output(&target);
+    }
+
+    target
+}
+
+/// Points each record's Previous Issue and Next Issue at the link values of its neighbours, in order.
+pub fn link_records(records: &mut [ArchiveRecord], link_values: &[String]) {
+    for (i, record) in records.iter_mut().enumerate() {
+        if i > 0 {
+            record.previous_issue = link_values[i - 1].clone();
+        }
+        if i < link_values.len() - 1 {
+            record.next_issue = link_values[i + 1].clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn expected_links_follow_date_order() {
+        let titles = [
+            "Arizona Sun, 1944-09-21",
+            "Arizona Sun, 1944-08-06",
+            "Catering News, 1944-08-06",
+            "Arizona Sun, undated",
+        ]
+            .map(String::from);
+        let expected = expected_links(&titles);
+        assert_eq!(expected[0], Some((titles[1].clone(), String::new())));
+        assert_eq!(expected[1], Some((String::new(), titles[0].clone())));
+        assert_eq!(expected[2], Some((String::new(), String::new())));
+        assert_eq!(expected[3], None);
+    }
+
+    #[test]
+    fn find_breaks_cycles_and_forks() {
+        let titles = ["a", "b", "c", "d", "e", "f", "g"].map(String::from);
+        // a -> b -> c, d -> c (fork), e -> missing, f <-> g (cycle).
+        let nexts = ["b", "c", "", "c", "missing", "g", "f"].map(String::from);
+        let report = find_chains(&titles, &nexts);
+        assert_eq!(report.chains, vec![vec![0, 1, 2], vec![3], vec![4]]);
+        assert_eq!(report.forks, vec![(2, vec![1, 3])]);
+        assert_eq!(report.dangling, vec![4]);
+        assert_eq!(report.cycles, vec![vec![5, 6]]);
+    }
+
+    #[test]
+    fn link_issues_by_value_column() {
+        let dir = crate::test_util::TestDir::new("link_value_column");
+        let target = dir.write(
+            "records.csv",
+            "NODE_TITLE,Local ID,Previous Issue,Next Issue\n\
+             \"Arizona Sun, 1944-05-12\",A-1,,\n\
+             \"Arizona Sun, 1944-05-19\",A-2,,\n\
+             \"Arizona Sun, 1944-05-26\",A-3,,\n"
+        );
+        let output = link_issues(&target.to_string_lossy(), Some("Local ID"), None, &ColumnOptions::default());
+        assert_eq!(
+            std::fs::read_to_string(output).unwrap(),
+            "NODE_TITLE,Local ID,Previous Issue,Next Issue\n\
+             \"Arizona Sun, 1944-05-12\",A-1,,A-2\n\
+             \"Arizona Sun, 1944-05-19\",A-2,A-1,A-3\n\
+             \"Arizona Sun, 1944-05-26\",A-3,A-2,\n"
+        );
+    }
+
+    #[test]
+    fn graph_issue_chains() {
+        let dir = crate::test_util::TestDir::new("issue_graph");
+        let record = |title: &str, previous: &str, next: &str| ArchiveRecord {
+            node_title: title.to_string(),
+            previous_issue: previous.to_string(),
+            next_issue: next.to_string(),
+            ..Default::default()
+        };
+        let records = [
+            record("Arizona Sun, 1944-05-12", "", "Arizona Sun, 1944-05-19"),
+            record("Arizona Sun, 1944-05-19", "Arizona Sun, 1944-05-12", "Catering News, 1944-05-26"),
+            record("Catering News, 1944-05-26", "Catering News, 1944-05-19", ""),
+        ];
+        let keys = records.iter().map(|record| record.node_title.clone()).collect::<Vec<_>>();
+        let graph = dir.join("issues.dot").to_string_lossy().to_string();
+        write_graph(&records, &keys, &graph);
+        assert_eq!(
+            std::fs::read_to_string(graph).unwrap(),
+            r#"digraph issues {
+    rankdir=LR;
+    node [shape=box];
+    subgraph cluster_0 {
+        label="Arizona Sun";
+        n0 [label="Arizona Sun, 1944-05-12"];
+        n1 [label="Arizona Sun, 1944-05-19"];
+    }
+    subgraph cluster_1 {
+        label="Catering News";
+        n2 [label="Catering News, 1944-05-26"];
+    }
+    n0 -> n1;
+    n1 -> n2 [color=red];
+    missing0 [label="Catering News, 1944-05-19", style=dashed, color=red];
+    n2 -> missing0 [style=dashed, color=red];
+}
+"#
+        );
+    }
+}
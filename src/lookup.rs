@@ -0,0 +1,562 @@
+use std::{ collections::BTreeMap, path::Path, sync::OnceLock };
+use owo_colors::OwoColorize;
+
+use crate::{
+    fetch,
+    filesystem::RealFilesystem,
+    issue_data::{ is_valid_tn, normalize_tn, IssueData, LookupRow, TitleParseError },
+    messages,
+    print_status,
+    print_warn,
+    summary,
+    trash_file,
+    warnings::Warning,
+};
+
+/// The columns of a lookup CSV file that the lookup table is read from.
+const TN_COLUMN: usize = 0;
+const TITLE_COLUMN: usize = 1;
+const DATE_LOADED_COLUMN: usize = 5;
+
+const BOM: &[u8] = b"\xef\xbb\xbf";
+
+/// An edit of a lookup CSV file.
+#[derive(clap::Subcommand, Debug)]
+pub enum LookupAction {
+    /// Add a row for a new tn.
+    Add {
+        tn: String,
+
+        /// The raw title, e.g. `Arizona Sun. v. 3, no. 12 (May 12, 1944)`.
+        title: String,
+
+        /// The date loaded, in the format `d/m/y h:m`.
+        #[arg(long, default_value = "")]
+        date_loaded: String,
+    },
+    /// Change the title or date loaded of a tn's row.
+    Update {
+        tn: String,
+
+        /// The new raw title.
+        #[arg(long)]
+        title: Option<String>,
+
+        /// The new date loaded.
+        #[arg(long)]
+        date_loaded: Option<String>,
+    },
+    /// Remove a tn's row.
+    Remove {
+        tn: String,
+    },
+}
+
+/// A lookup CSV file as read, with its encoding details kept so it is written back the same way.
+struct LookupFile {
+    has_bom: bool,
+    crlf: bool,
+    /// Whether the first row quotes every field, as some spreadsheet exports do.
+    quote_all: bool,
+    /// The header line as read.
+    raw_headers: Vec<u8>,
+    headers: csv::StringRecord,
+    rows: Vec<LookupLine>,
+}
+
+/// A row of a lookup CSV file, with the bytes it was read from until it is edited.
+struct LookupLine {
+    record: csv::StringRecord,
+    raw: Option<Vec<u8>>,
+}
+
+impl LookupLine {
+    fn edited(record: csv::StringRecord) -> Self {
+        LookupLine { record, raw: None }
+    }
+}
+
+impl LookupFile {
+    /// Reads a lookup CSV file, returning the first row that is not UTF-8 as an error.
+    fn read(path: &str) -> Result<Self, LookupError> {
+        let bytes = std::fs::read(path).expect("Failed to read lookup table.");
+        let has_bom = bytes.starts_with(BOM);
+        let bytes = bytes.strip_prefix(BOM).unwrap_or(&bytes);
+        let crlf = bytes.windows(2).any(|pair| pair == b"\r\n");
+        let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(bytes);
+        let headers = reader.byte_headers().expect("Failed to read lookup table headers.").clone();
+        let headers = csv::StringRecord::from_byte_record(headers).map_err(|_| LookupError::NotUtf8 { row: 1 })?;
+        let mut records = vec![];
+        let mut record = csv::ByteRecord::new();
+        while reader.read_byte_record(&mut record).expect("Failed to parse lookup table.") {
+            // a record's position is just after the previous record, before its line terminator.
+            let start = record.position().expect("Failed to get lookup row position.").byte() as usize;
+            let start = start + bytes[start..].iter().take_while(|byte| matches!(byte, b'\r' | b'\n')).count();
+            let row = records.len() + 2;
+            let string = csv::StringRecord::from_byte_record(record.clone()).map_err(|_| LookupError::NotUtf8 { row })?;
+            records.push((start, string));
+        }
+        // each row's raw bytes run up to the next row, so blank lines between rows are kept too.
+        let ends = records.iter().skip(1).map(|(start, _)| *start).chain(std::iter::once(bytes.len()));
+        let rows = records
+            .iter()
+            .zip(ends)
+            .map(|((start, record), end)| LookupLine { record: record.clone(), raw: Some(bytes[*start..end].to_vec()) })
+            .collect::<Vec<_>>();
+        let raw_headers = bytes[..records.first().map_or(bytes.len(), |(start, _)| *start)].to_vec();
+        let first_line = rows.first().and_then(|row| row.raw.as_deref()).unwrap_or(&raw_headers);
+        let quote_all = first_line.starts_with(b"\"");
+        Ok(LookupFile { has_bom, crlf, quote_all, raw_headers, headers, rows })
+    }
+
+    /// Replaces (and trashes) the lookup file with the edited rows. Unedited rows are written back as they were read.
+    fn write(&self, path: &str) {
+        trash_file(&RealFilesystem, Path::new(path));
+        std::fs::write(path, self.to_bytes()).expect("Failed to write lookup table.");
+        summary::record_output(path);
+    }
+
+    /// Returns the file's bytes, with edited rows quoted like the first row and ended like the others.
+    fn to_bytes(&self) -> Vec<u8> {
+        let terminator: &[u8] = if self.crlf { b"\r\n" } else { b"\n" };
+        let mut bytes = if self.has_bom { BOM.to_vec() } else { vec![] };
+        bytes.extend(&self.raw_headers);
+        for row in &self.rows {
+            if !bytes.is_empty() && !bytes.ends_with(b"\n") {
+                bytes.extend(terminator);
+            }
+            match &row.raw {
+                Some(raw) => bytes.extend(raw),
+                None => {
+                    let quote_style = if self.quote_all { csv::QuoteStyle::Always } else { csv::QuoteStyle::Necessary };
+                    let terminator = if self.crlf { csv::Terminator::CRLF } else { csv::Terminator::Any(b'\n') };
+                    let mut writer = csv::WriterBuilder::new()
+                        .flexible(true)
+                        .quote_style(quote_style)
+                        .terminator(terminator)
+                        .from_writer(vec![]);
+                    writer.write_record(&row.record).expect("Failed to write lookup table row.");
+                    bytes.extend(writer.into_inner().expect("Failed to write lookup table."));
+                }
+            }
+        }
+        bytes
+    }
+
+    /// Returns the index of a tn's row, matching tns as the lookup table does.
+    fn position(&self, tn: &str) -> Option<usize> {
+        let tn = normalize_tn(tn);
+        self.rows.iter().position(|row| row.record.get(TN_COLUMN).is_some_and(|other| normalize_tn(other) == tn))
+    }
+
+    /// Returns the width of a new row: every header, and at least up to the date loaded column.
+    fn width(&self) -> usize {
+        self.headers.len().max(DATE_LOADED_COLUMN + 1)
+    }
+}
+
+/// Returns a row with `values` set at their columns, and the rest copied from `row`.
+fn with_values(row: &csv::StringRecord, width: usize, values: &[(usize, &str)]) -> csv::StringRecord {
+    (0..width.max(row.len()))
+        .map(|i| {
+            values
+                .iter()
+                .find(|(column, _)| *column == i)
+                .map_or_else(|| row.get(i).unwrap_or_default(), |(_, value)| *value)
+        })
+        .collect()
+}
+
+/// Parses a row as the lookup table would read it.
+fn parse_row(tn: &str, title: &str, date_loaded: &str) -> Result<IssueData, TitleParseError> {
+    let row = LookupRow { tn: tn.to_string(), raw_title: title.to_string(), date_loaded: date_loaded.to_string() };
+    IssueData::try_from(row)
+}
+
+/// Panics if a row would not be read back into the lookup table.
+fn validate(tn: &str, title: &str, date_loaded: &str) {
+    if let Err(problem) = parse_row(tn, title, date_loaded) {
+        panic!("Invalid title \"{}\" for tn \"{}\": {}", title, tn, problem);
+    }
+}
+
+/// Applies an edit to a CSV lookup file in place. The previous version is moved to the trash.
+/// The BOM, line endings, and the other rows are kept as they were, and the edited row is quoted like the first row.
+/// Returns an error if the file is not UTF-8.
+pub fn edit_lookup(lookup: &str, action: LookupAction) -> Result<(), LookupError> {
+    assert!(lookup.ends_with(".csv"), "Only CSV lookup tables can be edited.");
+    let mut file = LookupFile::read(lookup)?;
+    match action {
+        LookupAction::Add { tn, title, date_loaded } => {
+            assert!(file.position(&tn).is_none(), "The lookup table already has a row for tn \"{}\".", tn);
+            validate(&tn, &title, &date_loaded);
+            let values = [(TN_COLUMN, tn.as_str()), (TITLE_COLUMN, title.as_str()), (DATE_LOADED_COLUMN, &date_loaded)];
+            let row = with_values(&csv::StringRecord::new(), file.width(), &values);
+            file.rows.push(LookupLine::edited(row));
+            print_status!("Added tn \"{}\": {}", tn, title.green());
+        }
+        LookupAction::Update { tn, title, date_loaded } => {
+            let i = file.position(&tn).unwrap_or_else(|| panic!("The lookup table has no row for tn \"{}\".", tn));
+            let row = &file.rows[i].record;
+            let old_title = row.get(TITLE_COLUMN).unwrap_or_default().to_string();
+            let title = title.unwrap_or_else(|| old_title.clone());
+            let date_loaded = date_loaded
+                .unwrap_or_else(|| row.get(DATE_LOADED_COLUMN).unwrap_or_default().to_string());
+            validate(&tn, &title, &date_loaded);
+            let values = [(TITLE_COLUMN, title.as_str()), (DATE_LOADED_COLUMN, &date_loaded)];
+            let updated = with_values(row, file.width(), &values);
+            file.rows[i] = LookupLine::edited(updated);
+            print_status!("Updated tn \"{}\": {} {} {}", tn, old_title.red(), "->".dimmed(), title.green());
+        }
+        LookupAction::Remove { tn } => {
+            let i = file.position(&tn).unwrap_or_else(|| panic!("The lookup table has no row for tn \"{}\".", tn));
+            let row = file.rows.remove(i);
+            print_status!("Removed tn \"{}\": {}", tn, row.record.get(TITLE_COLUMN).unwrap_or_default().red());
+        }
+    }
+    file.write(lookup);
+    Ok(())
+}
+
+/// Returns a suggested fix for a title that cannot be parsed.
+fn suggest_fix(problem: &TitleParseError) -> &'static str {
+    match problem {
+        TitleParseError::MissingTitlePeriod { .. } => {
+            "End the publication title with a period, e.g. `Arizona Sun. (May 12, 1944)`."
+        }
+        TitleParseError::MissingDate { .. } | TitleParseError::InvalidMonth { .. } => {
+            "Spell out or abbreviate the month (`May`, `Sep.`), or add the month name to `months` in the config."
+        }
+        TitleParseError::UnparseableVolume { .. } => "Write the volume and issue as `v. 3, no. 12`.",
+        TitleParseError::AmbiguousSplit { .. } | TitleParseError::UnparseableIssue { .. } => {
+            "Separate the volume and issue with a comma, e.g. `v. 3, no. 12`."
+        }
+        TitleParseError::InvalidDate { .. } => {
+            "End the title with the date in parentheses, e.g. `(May 12, 1944)`, `(1944)`, or `(n.d.)`."
+        }
+    }
+}
+
+/// Parses every row of a CSV lookup file without touching any files, reporting each title that cannot be parsed
+/// (with its row number and a suggested fix), duplicate tns, and tns that do not match the configured pattern.
+/// Returns the number of problems, or an error if the file is not UTF-8.
+pub fn check_lookup(lookup: &str) -> Result<usize, LookupError> {
+    let lookup = fetch::resolve_lookup(lookup);
+    assert!(lookup.ends_with(".csv"), "Only CSV lookup tables can be checked.");
+    let file = LookupFile::read(&lookup)?;
+    let mut problems = 0;
+    let mut rows_by_tn: BTreeMap<String, usize> = BTreeMap::new();
+    for (i, row) in file.rows.iter().map(|row| &row.record).enumerate() {
+        // the header is the first row.
+        let row_number = i + 2;
+        let tn = row.get(TN_COLUMN).unwrap_or_default().trim();
+        if tn.is_empty() {
+            continue;
+        }
+        let title = row.get(TITLE_COLUMN).unwrap_or_default();
+        let date_loaded = row.get(DATE_LOADED_COLUMN).unwrap_or_default();
+        let normalized = normalize_tn(tn);
+        if let Some(first) = rows_by_tn.insert(normalized.clone(), row_number) {
+            print_status!("{} Row {}: tn \"{}\" is also on row {}.", "DUPLICATE".red().bold(), row_number, tn, first);
+            problems += 1;
+        }
+        if !is_valid_tn(&normalized) {
+            print_status!(
+                "{} Row {}: tn \"{}\" does not match the configured tn pattern.",
+                "TN".red().bold(),
+                row_number,
+                tn
+            );
+            problems += 1;
+        }
+        if let Err(problem) = parse_row(tn, title, date_loaded) {
+            print_status!("{} Row {} (tn \"{}\"): \"{}\"", "INVALID".red().bold(), row_number, tn, title);
+            print_status!("    {}", problem);
+            print_status!("    {} {}", "Fix:".bold(), suggest_fix(&problem));
+            problems += 1;
+        }
+    }
+    print_status!("Checked {} rows, {} problems.", file.rows.len(), problems);
+    summary::record_count("problems", problems);
+    Ok(problems)
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DuplicateTns {
+    First,
+    #[default]
+    Last,
+    Fail,
+}
+
+/// The `--duplicate-tns` policy used by [`parse_lookup_table`].
+pub static DUPLICATE_TNS: OnceLock<DuplicateTns> = OnceLock::new();
+
+/// Why a lookup table could not be read.
+#[derive(Debug)]
+pub enum LookupError {
+    /// A CSV row's title could not be parsed.
+    Title { tn: String, problem: TitleParseError },
+    /// The lookup table repeats tns, and the `--duplicate-tns` policy is `fail`.
+    DuplicateTns { duplicates: usize },
+    /// A CSV row (the header is row 1) is not UTF-8.
+    NotUtf8 { row: usize },
+}
+
+impl std::fmt::Display for LookupError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LookupError::Title { tn, problem } => {
+                write!(f, "Failed to parse the title of tn \"{}\": {}. Run `check-lookup` for suggested fixes.", tn, problem)
+            }
+            LookupError::DuplicateTns { duplicates } => {
+                write!(
+                    f,
+                    "Found {} duplicate tns in the lookup table. Fix them, or pass --duplicate-tns first or last.",
+                    duplicates
+                )
+            }
+            LookupError::NotUtf8 { row } => {
+                write!(f, "Row {} of the lookup table is not UTF-8. Save the table as UTF-8 CSV and try again.", row)
+            }
+        }
+    }
+}
+
+impl std::error::Error for LookupError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LookupError::Title { problem, .. } => Some(problem),
+            LookupError::DuplicateTns { .. } | LookupError::NotUtf8 { .. } => None,
+        }
+    }
+}
+
+/// Parses a lookup CSV or exported JSON file, downloading it first if `lookup` is an `http(s)://` URL.
+/// Returns the first row whose title cannot be parsed (or that is not UTF-8) as an error, or the duplicate tns with
+/// `--duplicate-tns fail`.
+pub fn parse_lookup_table(lookup: &str) -> Result<BTreeMap<String, IssueData>, LookupError> {
+    let lookup = fetch::resolve_lookup(lookup);
+    let lookup = lookup.as_str();
+
+    // each issue with the title to report it by if its tn is a duplicate.
+    let (issues, kind) = if lookup.ends_with(".json") {
+        let file = std::fs::File::open(lookup).expect("Failed to read lookup table.");
+        let issues: Vec<IssueData> = serde_json
+            ::from_reader(std::io::BufReader::new(file))
+            .expect("Failed to parse JSON lookup table.");
+        let issues = issues
+            .into_iter()
+            .map(|issue_data| (issue_data.record_title(), issue_data))
+            .collect::<Vec<_>>();
+        (issues, "records from JSON lookup table.")
+    } else {
+        // assert the lookup is a csv file.
+        assert!(lookup.ends_with(".csv"), "Lookup table must be a CSV or JSON file.");
+
+        let mut issues = vec![];
+        let mut reader = csv::Reader::from_path(lookup).expect("Failed to read lookup table.");
+        for (i, result) in reader.records().enumerate() {
+            let record = match result {
+                // the header is row 1.
+                Err(e) if matches!(e.kind(), csv::ErrorKind::Utf8 { .. }) => {
+                    return Err(LookupError::NotUtf8 { row: i + 2 });
+                }
+                result => result.expect("Failed to parse record."),
+            };
+            let tn = record.get(0).expect("Failed to get tn.");
+            let title = record.get(1).expect("Failed to get title.");
+            let date_loaded = record.get(5).expect("Failed to get date loaded.");
+
+            if tn.trim().is_empty() {
+                continue;
+            }
+
+            let issue_data = parse_row(tn.trim(), title, date_loaded).map_err(|problem| LookupError::Title {
+                tn: tn.trim().to_string(),
+                problem,
+            })?;
+            issues.push((title.to_string(), issue_data));
+        }
+        (issues, "records from lookup table.")
+    };
+
+    // ordered map
+    let lookup_table = key_by_tn(issues, DUPLICATE_TNS.get().copied().unwrap_or_default())?;
+    print_status!("{} {} {}", "Parsed".italic().white(), lookup_table.len().bold().white(), kind.italic().white());
+    Ok(lookup_table)
+}
+
+/// Keys issues (each with the raw title to report it by) by normalized tn, keeping the first or last issue of a
+/// duplicate tn by `policy`.
+fn key_by_tn(
+    issues: Vec<(String, IssueData)>,
+    policy: DuplicateTns
+) -> Result<BTreeMap<String, IssueData>, LookupError> {
+    let mut lookup_table: BTreeMap<String, (String, IssueData)> = BTreeMap::new();
+    let mut duplicates = 0;
+    for (raw_title, issue_data) in issues {
+        let tn = lookup_tn(&issue_data.tn);
+        let Some((existing, _)) = lookup_table.get(&tn) else {
+            lookup_table.insert(tn, (raw_title, issue_data));
+            continue;
+        };
+        duplicates += 1;
+        let kept = match policy {
+            DuplicateTns::First | DuplicateTns::Fail => existing.clone(),
+            DuplicateTns::Last => raw_title.clone(),
+        };
+        print_warn!(Warning::Duplicate, "{}", messages::duplicate_tn(&tn, existing, &raw_title, &kept));
+        if policy == DuplicateTns::Last {
+            lookup_table.insert(tn, (raw_title, issue_data));
+        }
+    }
+    summary::record_count("duplicate tns", duplicates);
+    if policy == DuplicateTns::Fail && duplicates > 0 {
+        return Err(LookupError::DuplicateTns { duplicates });
+    }
+
+    Ok(
+        lookup_table
+            .into_iter()
+            .map(|(tn, (_, issue_data))| (tn, issue_data))
+            .collect()
+    )
+}
+
+/// Normalizes a lookup table tn, warning if it doesn't match the configured tn pattern.
+fn lookup_tn(tn: &str) -> String {
+    let normalized = normalize_tn(tn);
+    if !is_valid_tn(&normalized) {
+        print_warn!(Warning::TnPattern, "{}", messages::lookup_tn_pattern(tn));
+    }
+    normalized
+}
+
+pub fn export_lookup_table(lookup_table: &BTreeMap<String, IssueData>, output: &str) {
+    let file = std::fs::File::create(output).expect("Failed to create JSON file.");
+    let issues = lookup_table.values().collect::<Vec<_>>();
+    serde_json
+        ::to_writer_pretty(std::io::BufWriter::new(file), &issues)
+        .expect("Failed to write JSON file.");
+    print_status!("Exported {} records to \"{}\".", issues.len(), output);
+    summary::record_output(output);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::TestDir;
+
+    #[test]
+    fn suggest_fixes() {
+        let raw = || "x".to_string();
+        assert!(suggest_fix(&TitleParseError::InvalidMonth { raw: raw() }).contains("month"));
+        assert!(suggest_fix(&TitleParseError::MissingTitlePeriod { raw: raw() }).contains("period"));
+        assert!(suggest_fix(&TitleParseError::UnparseableIssue { raw: raw() }).contains("comma"));
+        assert!(suggest_fix(&TitleParseError::UnparseableVolume { raw: raw() }).contains("v. 3, no. 12"));
+    }
+
+    #[test]
+    fn bad_row_is_an_error() {
+        let dir = TestDir::new("bad_lookup_row");
+        let lookup = dir.write(
+            "lookup.csv",
+            "tn,title,a,b,c,date loaded\n\
+             tn1,\"Arizona Sun. v. 3, no. 12 (May 12, 1944)\",,,,\n\
+             tn2,Arizona Sun (May 19 1944),,,,\n"
+        );
+        let error = parse_lookup_table(&lookup.to_string_lossy()).unwrap_err();
+        let LookupError::Title { tn, problem } = error else {
+            panic!("Expected a title error, got {:?}.", error);
+        };
+        assert_eq!(tn, "tn2");
+        assert!(matches!(problem, TitleParseError::MissingTitlePeriod { .. }));
+    }
+
+    #[test]
+    fn export_and_import_json() {
+        let dir = TestDir::new("lookup_json");
+        let lookup = dir.write(
+            "lookup.csv",
+            "tn,title,a,b,c,date loaded\n\
+             tn1,\"Arizona Sun. v. 3, no. 12 (May 12, 1944)\",,,,6/8/2020 10:00\n\
+             tn2,\"Arizona Sun. (n.d.)\",,,,\n"
+        );
+        let lookup_table = parse_lookup_table(&lookup.to_string_lossy()).unwrap();
+        let json = dir.join("lookup.json").to_string_lossy().to_string();
+        export_lookup_table(&lookup_table, &json);
+        let imported = parse_lookup_table(&json).unwrap();
+        assert_eq!(format!("{:?}", imported), format!("{:?}", lookup_table));
+        assert_eq!(imported["tn1"].volume, Some(3));
+        assert_eq!(imported["tn1"].date_loaded, "6/8/2020 10:00");
+    }
+
+    #[test]
+    fn duplicate_tn_policies() {
+        let issues = || {
+            ["Arizona Sun. v. 3, no. 12 (May 12, 1944)", "Arizona Sun. v. 3, no. 13 (May 19, 1944)"]
+                .into_iter()
+                .map(|title| (title.to_string(), parse_row("tn1", title, "").unwrap()))
+                .collect::<Vec<_>>()
+        };
+        let first = key_by_tn(issues(), DuplicateTns::First).unwrap();
+        assert_eq!(first.len(), 1);
+        assert_eq!(first["tn1"].issue, Some(12));
+        let last = key_by_tn(issues(), DuplicateTns::Last).unwrap();
+        assert_eq!(last["tn1"].issue, Some(13));
+        assert!(
+            matches!(key_by_tn(issues(), DuplicateTns::Fail), Err(LookupError::DuplicateTns { duplicates: 1 }))
+        );
+    }
+
+    #[test]
+    fn edit_keeps_encoding_and_quoting() {
+        let dir = TestDir::new("edit_lookup");
+        let lookup = dir.write(
+            "lookup.csv",
+            "\u{feff}\"tn\",\"title\",\"a\",\"b\",\"c\",\"date loaded\"\r\n\
+             \"tn1\",\"Arizona Sun. (May 12, 1944)\",\"\",\"\",\"\",\"\"\r\n\
+             \"tn2\",\"Arizona Sun. (May 19, 1944)\",\"\",\"\",\"\",\"\""
+        );
+        let lookup = lookup.to_string_lossy();
+        let add = LookupAction::Add {
+            tn: "tn3".to_string(),
+            title: "Arizona Sun. (May 26, 1944)".to_string(),
+            date_loaded: String::new(),
+        };
+        edit_lookup(&lookup, add).unwrap();
+        edit_lookup(&lookup, LookupAction::Remove { tn: "tn1".to_string() }).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.join("lookup.csv")).unwrap(),
+            "\u{feff}\"tn\",\"title\",\"a\",\"b\",\"c\",\"date loaded\"\r\n\
+             \"tn2\",\"Arizona Sun. (May 19, 1944)\",\"\",\"\",\"\",\"\"\r\n\
+             \"tn3\",\"Arizona Sun. (May 26, 1944)\",\"\",\"\",\"\",\"\"\r\n"
+        );
+
+        let lookup = dir.write("unquoted.csv", "tn,title,a,b,c,date loaded\ntn1,\"Arizona Sun. (May 12, 1944)\",,,,\n");
+        let lookup = lookup.to_string_lossy();
+        let update = LookupAction::Update {
+            tn: "tn1".to_string(),
+            title: Some("Arizona Sun. (1944)".to_string()),
+            date_loaded: None,
+        };
+        edit_lookup(&lookup, update).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.join("unquoted.csv")).unwrap(),
+            "tn,title,a,b,c,date loaded\ntn1,Arizona Sun. (1944),,,,\n"
+        );
+    }
+
+    #[test]
+    fn non_utf8_lookup_is_an_error() {
+        let dir = TestDir::new("non_utf8_lookup");
+        let lookup = dir.join("lookup.csv");
+        std::fs::write(&lookup, b"tn,title,a,b,c,date loaded\ntn1,\"Arizona Sun. (Mai 12, 1944) \xe9\",,,,\n").unwrap();
+        let lookup = lookup.to_string_lossy();
+        assert!(matches!(check_lookup(&lookup), Err(LookupError::NotUtf8 { row: 2 })));
+        assert!(matches!(parse_lookup_table(&lookup), Err(LookupError::NotUtf8 { row: 2 })));
+        let remove = LookupAction::Remove { tn: "tn1".to_string() };
+        assert!(matches!(edit_lookup(&lookup, remove), Err(LookupError::NotUtf8 { row: 2 })));
+    }
+}
@@ -1,64 +1,294 @@
-use std::{ collections::BTreeMap, path::PathBuf };
-use bulk_format::{ prompt_bool, safely_target_file };
+use std::{ collections::BTreeMap, sync::OnceLock };
+use bulk_format::{
+    archive_record::ColumnOptions,
+    checksum,
+    collect::{ collect_directories, collect_files, read_file_list, FileFilters },
+    compare,
+    config,
+    coverage,
+    dedupe,
+    derivative,
+    derived_path,
+    diff,
+    filesystem::{ Filesystem, MemoryFilesystem, RealFilesystem },
+    filter,
+    format,
+    group,
+    hooks,
+    inspect,
+    issue_data::{ self, IssueData },
+    links::{ self, link_issues },
+    lookup::{ self, export_lookup_table, parse_lookup_table, DuplicateTns, DUPLICATE_TNS },
+    messages::{ self, Lang },
+    missing,
+    notify,
+    pdf,
+    plan,
+    populate::{ invert_lookup_table, populate_csv, PopulateOptions },
+    print_error,
+    print_status,
+    query,
+    reverse,
+    safely_target_file,
+    state,
+    titles,
+    validate,
+    warnings::{ Severity, Warning },
+    STDIO,
+};
 use owo_colors::OwoColorize;
-use clap::{ Parser, Subcommand };
-
-mod archive_record;
-mod issue_data;
-mod date;
-
-use issue_data::IssueData;
-use date::Date;
+use clap::{ builder::BoolishValueParser, CommandFactory, FromArgMatches, Parser, Subcommand };
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+
+    /// A path to a YAML config file. Defaults to `bulk_format.yaml` in the working directory, if it exists.
+    #[arg(long, global = true, env = "BULK_FORMAT_CONFIG")]
+    config: Option<String>,
+
+    /// A profile from the config file's `profiles`, e.g. `arizona-labor-journal`, whose settings (extensions,
+    /// name template, vocabularies, collection defaults, ...) override the rest of the config.
+    #[arg(long, global = true, env = "BULK_FORMAT_PROFILE")]
+    profile: Option<String>,
+
+    /// When to color output. `auto` colors only when writing to a terminal, and honors `NO_COLOR` and `CLICOLOR_FORCE`.
+    #[arg(long, global = true, value_enum, default_value_t = ColorMode::Auto)]
+    color: ColorMode,
+
+    /// Suppress all status output and print a single JSON summary line (outputs, counts, warnings) when the run ends.
+    #[arg(short, long, global = true, env = "BULK_FORMAT_QUIET", value_parser = BoolishValueParser::new())]
+    quiet: bool,
+
+    /// Hold back warnings until the end of the run, where they are summarized by category, and write every warning
+    /// to `bulk_format_warnings.csv`.
+    #[arg(long, global = true, env = "BULK_FORMAT_GROUP_WARNINGS", value_parser = BoolishValueParser::new())]
+    group_warnings: bool,
+
+    /// Warning categories to ignore, e.g. `--allow unparsed-volume`. Allowed warnings are not reported or counted.
+    #[arg(long, global = true, value_enum, value_delimiter = ',', env = "BULK_FORMAT_ALLOW")]
+    allow: Vec<Warning>,
+
+    /// Warning categories that stop the run with an error, e.g. `--deny unmatched-record`. Deny wins over `--allow`.
+    #[arg(long, global = true, value_enum, value_delimiter = ',', env = "BULK_FORMAT_DENY")]
+    deny: Vec<Warning>,
+
+    /// Answer every overwrite prompt with yes, and overwrite every conflicting output, without asking.
+    #[arg(long, global = true, env = "BULK_FORMAT_ASSUME_YES", value_parser = BoolishValueParser::new())]
+    yes: bool,
+
+    /// A webhook URL (e.g. a Slack incoming webhook) to post a JSON summary to when the run succeeds or fails.
+    #[arg(long, global = true, env = "BULK_FORMAT_NOTIFY")]
+    notify: Option<String>,
+
+    /// Which issue to keep when a tn appears more than once in a lookup table, or `fail` to stop.
+    #[arg(long, global = true, value_enum, default_value_t = DuplicateTns::Last, env = "BULK_FORMAT_DUPLICATE_TNS")]
+    duplicate_tns: DuplicateTns,
+
+    /// The language of prompts, run summaries, and Format's status messages.
+    #[arg(long, global = true, value_enum, default_value_t = Lang::En, env = "BULK_FORMAT_LANG")]
+    lang: Lang,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy)]
+enum ColorMode {
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorMode {
+    fn choice(self) -> anstream::ColorChoice {
+        match self {
+            ColorMode::Auto => anstream::ColorChoice::Auto,
+            ColorMode::Always => anstream::ColorChoice::Always,
+            ColorMode::Never => anstream::ColorChoice::Never,
+        }
+    }
 }
 
 #[derive(Subcommand)]
 enum Commands {
     /// Bulk reformat all files and produce a lookup table mapping `tn -> formatted title`.
     Format {
-        /// A path to the lookup CSV file. This csv is used to rename the input files with the corresponding `tn` to the formatted title.
+        /// A path (or `https://` URL, or `gsheet:<spreadsheet-id>/<tab>`) to the lookup CSV (or exported JSON) file. This csv is used to rename the input files with the corresponding `tn` to the formatted title.
         #[arg(short = 'L', long)]
         lookup: String,
 
-        /// A path to a directory containing all files to format.
-        #[arg(short, long = "dir")]
-        directory: String,
+        /// A path to a directory containing all files to format. Repeat it (or separate paths with commas) to format the
+        /// files of several directories in one run.
+        #[arg(short, long = "dir", required_unless_present = "files_from", value_delimiter = ',')]
+        directories: Vec<String>,
+
+        /// A file listing the paths of the files to format, one per line, or `-` to read the list from stdin.
+        /// The listed files are formatted as they are, instead of searching `--dir`, but the size and age filters
+        /// still apply.
+        #[arg(long, conflicts_with_all = ["directories", "extensions", "recursive", "exclude"])]
+        files_from: Option<String>,
 
-        /// The file extensions to include in the search.
-        #[arg(short, long = "ext", default_value = "pdf")]
+        /// The file extensions to include in the search. Defaults to `extensions` from the config file, or `pdf`.
+        #[arg(short, long = "ext")]
         extensions: Vec<String>,
 
         /// If true, the directory will be searched recursively.
         #[arg(short, long)]
         recursive: bool,
 
-        /// The output directory to save the newly named files. If not provided, the formatted files will be saved in the same directory as the input files.
-        /// If the directory does not exist, it will be created.
+        #[command(flatten)]
+        filters: FileFilters,
+
+        #[command(flatten)]
+        options: format::FormatOptions,
+    },
+
+    /// Check a `format` run's outputs against its inputs: every input with a lookup entry must have an output named as
+    /// `format` names it, equal in size and SHA-256 digest (or only present, if metadata was embedded). Pass the same
+    /// options as the `format` run. Exits with an error on any missing or mismatched output.
+    VerifyOutput {
+        /// A path (or `https://` URL, or `gsheet:<spreadsheet-id>/<tab>`) to the lookup CSV (or exported JSON) file.
+        #[arg(short = 'L', long)]
+        lookup: String,
+
+        /// A path to a directory containing the input files. Repeat it (or separate paths with commas) for several.
+        #[arg(short, long = "dir", required_unless_present = "files_from", value_delimiter = ',')]
+        directories: Vec<String>,
+
+        /// A file listing the paths of the input files, one per line, or `-` to read the list from stdin.
+        /// The size and age filters still apply.
+        #[arg(long, conflicts_with_all = ["directories", "extensions", "recursive", "exclude"])]
+        files_from: Option<String>,
+
+        /// The file extensions to include in the search. Defaults to `extensions` from the config file, or `pdf`.
+        #[arg(short, long = "ext")]
+        extensions: Vec<String>,
+
+        /// If true, the directory will be searched recursively.
         #[arg(short, long)]
-        output: Option<String>,
+        recursive: bool,
+
+        #[command(flatten)]
+        filters: FileFilters,
+
+        #[command(flatten)]
+        options: format::FormatOptions,
     },
 
     /// Modify a CSV file to include volume and issue numbers for each `tn` by its formatted title.
     Populate {
-        /// A path to the target CSV file to modify and populate with volume and issue numbers.
+        /// A path to the target CSV file to modify and populate with volume and issue numbers. `-` reads from stdin and writes to stdout.
         #[arg(short, long)]
         target: String,
 
-        /// A path to the lookup CSV file.
+        /// A path (or `https://` URL, or `gsheet:<spreadsheet-id>/<tab>`) to the lookup CSV (or exported JSON) file.
         #[arg(short = 'L', long)]
         lookup: String,
+
+        /// Print the changes to each record without writing the populated CSV file.
+        #[arg(long)]
+        dry_run: bool,
+
+        #[command(flatten)]
+        options: PopulateOptions,
     },
 
     /// Populate a CSV file with `previous` and `next` issue data, using the order of the records and their node titles.
     LinkIssues {
-        /// A path to the target CSV file to modify and populate with `previous` and `next` issue data.
+        /// A path to the target CSV file to modify and populate with `previous` and `next` issue data. `-` reads from stdin and writes to stdout.
         #[arg(short, long)]
         target: String,
+
+        /// The column whose value is written into `Previous Issue` and `Next Issue`, e.g. a node ID or handle column. Defaults to `NODE_TITLE`.
+        #[arg(long)]
+        link_value_column: Option<String>,
+
+        /// Also render each publication's previous/next chain as a Graphviz DOT file.
+        #[arg(long)]
+        graph: Option<String>,
+
+        #[command(flatten)]
+        columns: ColumnOptions,
+    },
+
+    /// Check that every `Previous Issue`/`Next Issue` refers to an existing NODE_TITLE, links back, and follows date order.
+    RepairLinks {
+        /// A path to the target CSV file to check.
+        #[arg(short, long)]
+        target: String,
+
+        /// Write `<target>_repaired.csv` with the links rebuilt from date order, instead of only reporting problems.
+        #[arg(long)]
+        fix: bool,
+
+        /// Also render each publication's previous/next chain (before repair) as a Graphviz DOT file.
+        #[arg(long)]
+        graph: Option<String>,
+    },
+
+    /// Remove rows with a duplicate NODE_TITLE from a CSV file, keeping the most complete row of each.
+    DedupeRows {
+        /// A path to the target CSV file. The deduped rows are saved to `<target>_deduped.csv`.
+        #[arg(short, long)]
+        target: String,
+
+        /// A path to save the dropped duplicate rows to, for review. Defaults to `<target>_duplicates.csv`.
+        #[arg(long)]
+        review: Option<String>,
+    },
+
+    /// Fix double spaces, stray trailing periods, and inconsistent publication casing in the NODE_TITLEs of a CSV file.
+    NormalizeTitles {
+        /// A path to the target CSV file. The normalized records are saved to `<target>_normalized.csv`.
+        #[arg(short, long)]
+        target: String,
+
+        /// Print the changes without writing the normalized CSV file.
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Analyze the `Previous Issue`/`Next Issue` chains of a CSV file: chain lengths per publication, breaks, cycles, and forks.
+    Chains {
+        /// A path to the target CSV file to analyze.
+        target: String,
+    },
+
+    /// Flag records whose Date Digitized precedes Date Original, whose Date Original falls outside Date Range, or whose
+    /// NODE_TITLE date disagrees with Date Original. Exits with an error if any are found.
+    CheckDates {
+        /// A path to the target CSV file to check.
+        #[arg(short, long)]
+        target: String,
+
+        /// Also export the problems as a CSV file (`node_title, problem`).
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+
+    /// Report target CSV values that are not in the config's `vocabularies` (e.g. Type, Language), with the closest
+    /// allowed value. Exits with an error if any are found.
+    Validate {
+        /// A path to the target CSV file to validate.
+        #[arg(short, long)]
+        target: String,
+    },
+
+    /// Show the rows added, removed, and changed (column by column) between two CSV files, matched by NODE_TITLE.
+    Diff {
+        /// A path to the original CSV file.
+        old: String,
+
+        /// A path to the changed CSV file, e.g. the output of Populate.
+        new: String,
+    },
+
+    /// Show which tns have been formatted, populated, grouped, and delivered, as recorded in `.bulk_format/state.csv`
+    /// in the working directory, with overall counts.
+    Status {
+        /// Show when this tn reached each stage.
+        #[arg(long)]
+        tn: Option<String>,
     },
 
     /// Compare a lookup table with a generated lookup table and identify missing entries.
@@ -70,394 +300,574 @@ enum Commands {
         /// A path to the generated lookup CSV file.
         #[arg(short, long)]
         generated: String,
+
+        #[command(flatten)]
+        options: compare::CompareOptions,
     },
 
-    /// Group files into directories where each directory contains at most `n` files.
+    /// Group files into directories where each directory contains at most `n` files and/or `--max-bytes` bytes.
     GroupFiles {
-        /// A path to the directory containing all files to group.
+        /// A path to the directory containing all files to group. Repeat it (or separate paths with commas) to group the
+        /// files of several directories together; the groups are created in the first directory.
+        #[arg(short, long = "dir", required_unless_present = "files_from", value_delimiter = ',')]
+        directories: Vec<String>,
+
+        /// A file listing the paths of the files to group, one per line, or `-` to read the list from stdin.
+        /// The listed files are grouped in order, instead of searching `--dir`, and the groups are created in the first
+        /// `--dir`, or else in the directory of the first listed file. The size and age filters still apply.
+        #[arg(long, conflicts_with_all = ["extensions", "recursive", "exclude"])]
+        files_from: Option<String>,
+
+        /// The file extensions to include in the search. Defaults to `extensions` from the config file, or `pdf`.
+        #[arg(short, long = "ext")]
+        extensions: Vec<String>,
+
+        /// If true, the directory will be searched recursively.
+        #[arg(short, long)]
+        recursive: bool,
+
+        #[command(flatten)]
+        filters: FileFilters,
+
+        #[command(flatten)]
+        options: group::GroupOptions,
+    },
+
+    /// Create derivatives (thumbnails, access JPEGs) of formatted files by running an external tool such as pdftoppm or ImageMagick.
+    Derivatives {
+        /// A path to the directory containing the formatted master files.
         #[arg(short, long = "dir")]
         directory: String,
 
-        /// The file extensions to include in the search.
-        #[arg(short, long = "ext", default_value = "pdf")]
+        /// The file extensions to include in the search. Defaults to `extensions` from the config file, or `pdf`.
+        #[arg(short, long = "ext")]
         extensions: Vec<String>,
 
         /// If true, the directory will be searched recursively.
         #[arg(short, long)]
         recursive: bool,
 
-        /// The number of files to include in each group. If the number of files in the directory is not divisible by `n`, the last group will contain the remainder.
-        #[arg(short)]
-        n: usize,
+        #[command(flatten)]
+        filters: FileFilters,
+
+        #[command(flatten)]
+        options: derivative::DerivativeOptions,
     },
-}
 
-macro_rules! print_warn {
-    ($($arg:tt)*) => {
-            eprintln!("{} {}", "[WARN]".yellow(), format_args!($($arg)*));
-    };
-}
-pub(crate) use print_warn;
+    /// Write a SHA-256 checksum manifest of a directory, or verify the directory against an existing manifest.
+    Checksum {
+        /// A path to the directory containing the files to hash.
+        #[arg(short, long = "dir")]
+        directory: String,
 
-macro_rules! print_warn_ok {
-    ($($arg:tt)*) => {
-            eprintln!("{} {}", "[OK]".yellow().dimmed().italic(), format_args!($($arg)*).dimmed());
-    };
-}
-pub(crate) use print_warn_ok;
+        /// The file extensions to include in the search. Defaults to `extensions` from the config file, or `pdf`.
+        #[arg(short, long = "ext")]
+        extensions: Vec<String>,
 
-fn main() {
-    let args = Cli::parse();
+        /// If true, the directory will be searched recursively.
+        #[arg(short, long)]
+        recursive: bool,
 
-    match args.command {
-        Commands::Format { lookup, directory, extensions, recursive, output } => {
-            let files = collect_files(&directory, &extensions, recursive);
-            let lookup_table = parse_lookup_table(&lookup);
-            copy_and_rename_files(files, lookup_table, output);
-        }
-        Commands::Populate { target, lookup } => {
-            let lookup_table = parse_lookup_table(&lookup);
-            let inverse_lookup_table = lookup_table
-                .into_iter()
-                .map(|(_, v)| (v.record_title(), v))
-                .collect::<BTreeMap<String, IssueData>>();
-            populate_csv(&target, inverse_lookup_table).unwrap();
-        }
-        Commands::LinkIssues { target } => {
-            link_issues(&target);
-        }
-        Commands::Compare { lookup, generated } => {
-            let lookup_table = parse_lookup_table(&lookup);
-            let generated_names = parse_generated_names(&generated);
-            compare_tables(lookup_table, generated_names);
-        }
-        Commands::GroupFiles { directory, extensions, recursive, n } => {
-            group_files(&directory, &extensions, recursive, n);
-        }
-    }
+        #[command(flatten)]
+        filters: FileFilters,
 
-    println!("{}", "Job done.".green().bold())
-}
+        /// A path to the manifest CSV file to write.
+        #[arg(short, long, default_value = "manifest.csv", conflicts_with = "verify")]
+        output: String,
 
-fn populate_csv(
-    target: &str,
-    inverse_lookup_table: BTreeMap<String, IssueData>
-) -> Result<(), csv::Error> {
-    use archive_record::ArchiveRecord;
+        /// A path to an existing manifest CSV file. Reports changed, missing, and new files, and exits with an error on any mismatch.
+        #[arg(long)]
+        verify: Option<String>,
 
-    let mut reader = csv::Reader::from_path(target).expect("Failed to read target CSV file.");
-    let target = target.replace(".csv", "_populated.csv");
+        /// The number of files to hash at once. Defaults to the number of CPUs.
+        #[arg(short, long)]
+        jobs: Option<usize>,
 
-    // if the target file already exists, prompt the user if they want to overwrite it.
-    if std::path::Path::new(&target).exists() {
-        let should_overwrite = prompt_bool(
-            &format!("The target file \"{}\" already exists. Do you want to overwrite it?", target)
-        );
-        if !should_overwrite {
-            print_warn_ok!("Exiting without overwriting target file.");
-            return Ok(());
-        }
-    }
+        /// Reuse the checksums of files unchanged (same size and modification time) since the last incremental run,
+        /// cached in the directory's `.bulk_format/cache.csv`.
+        #[arg(long, conflicts_with = "verify")]
+        incremental: bool,
+    },
 
-    let mut writer = csv::Writer::from_path(target).expect("Failed to write to target CSV file.");
+    /// Split a combined PDF (e.g. a reel scan) into per-issue PDFs from a CSV of `tn,pages,title` rows, named with each issue's formatted title.
+    SplitPdf {
+        /// A path to the combined PDF file.
+        #[arg(short, long)]
+        pdf: String,
 
-    for result in reader.deserialize() {
-        let mut record: ArchiveRecord = result?;
-        if let Some(issue) = inverse_lookup_table.get(&record.node_title) {
-            record.date_digitized = issue.date_loaded.to_string();
-            if let Some(volume) = issue.volume {
-                record.volume = volume.to_string();
-            }
-            if let Some(issue) = issue.issue {
-                record.issue = issue.to_string();
-            }
-        } else {
-            print_warn!("Failed to find issue data for \"{}\".", record.node_title);
-        }
-        writer.serialize(record)?;
-    }
+        /// A path to the CSV file of page ranges, with `tn`, `pages` (e.g. `1-4,6`), and optional `title` columns.
+        #[arg(long)]
+        ranges: String,
 
-    Ok(())
-}
+        /// A path (or `https://` URL, or `gsheet:<spreadsheet-id>/<tab>`) to the lookup CSV (or exported JSON) file. Issues without a lookup entry are named by their `title` column, or their `tn`.
+        #[arg(short = 'L', long)]
+        lookup: Option<String>,
 
-/// Populate a CSV file with `previous` and `next` issue data, using the order of the records and their node titles.
-fn link_issues(target: &str) {
-    use archive_record::ArchiveRecord;
+        /// The directory to write the split PDFs to.
+        #[arg(short, long, default_value = ".")]
+        output: String,
+    },
 
-    let mut reader = csv::Reader::from_path(target).expect("Failed to read target CSV file.");
-    let target = safely_target_file(&target.replace(".csv", "_linked.csv"));
+    /// Merge per-page PDFs (`tn12345_001.pdf`, `tn12345_002.pdf`, ...) into one PDF per issue (`tn12345.pdf`), ordered by page number.
+    MergePdf {
+        /// A path to the directory containing the per-page PDF files.
+        #[arg(short, long = "dir")]
+        directory: String,
 
-    let mut writer = csv::Writer
-        ::from_path(target.clone())
-        .expect("Failed to write to target CSV file.");
+        /// If true, the directory will be searched recursively.
+        #[arg(short, long)]
+        recursive: bool,
 
-    let mut records: Vec<ArchiveRecord> = reader
-        .deserialize()
-        .map(|r| r.expect("Failed to parse record."))
-        .collect();
-    let og_records = records.clone();
+        /// The directory to write the merged PDFs to.
+        #[arg(short, long, default_value = ".")]
+        output: String,
 
-    for (i, record) in records.iter_mut().enumerate() {
-        if i > 0 {
-            record.previous_issue = og_records[i - 1].node_title.clone();
-        }
-        if i < og_records.len() - 1 {
-            record.next_issue = og_records[i + 1].node_title.clone();
-        }
-        writer.serialize(record).expect("Failed to write record.");
-    }
+        /// Print the planned merges without writing any files.
+        #[arg(long)]
+        dry_run: bool,
+    },
 
-    println!("Linked issues and saved to \"{}\".", target);
-}
+    /// Export a parsed lookup table as a JSON array of issue data.
+    ExportLookup {
+        /// A path to the lookup CSV file.
+        #[arg(short = 'L', long)]
+        lookup: String,
 
-fn group_files(directory: &str, extensions: &[String], recursive: bool, n: usize) {
-    let files = collect_files(directory, extensions, recursive);
-    let groups = files.chunks(n);
-
-    for (i, group) in groups.enumerate() {
-        // if the files have dates at the end, find the min and max dates.
-        let mut dates: Vec<Date> = vec![];
-        for file in group {
-            // split on the last underscore, everything after is the date.
-            if
-                let Some(date) = file
-                    .file_name()
-                    .expect("Failed to get file name.")
-                    .to_string_lossy()
-                    .split("_")
-                    .last()
-            {
-                let date = date.split(".").next().expect("Failed to split date.");
-                let date = Date::try_from(date).expect("Failed to parse date.");
-                dates.push(date);
-            }
-        }
+        /// A path to the JSON file to write. Defaults to the lookup path with a `.json` extension.
+        #[arg(short, long)]
+        output: Option<String>,
+    },
 
-        #[allow(unused_parens)]
-        let group_dir = if
-            let Some((min_date, max_date)) = ({
-                dates
-                    .iter()
-                    .min()
-                    .map(|min_date| {
-                        dates
-                            .iter()
-                            .max()
-                            .map(|max_date| (min_date, max_date))
-                    })
-                    .flatten()
-            })
-        {
-            let min_date = min_date.year;
-            let max_date = max_date.year;
-            format!("{}/{i}_{min_date}-{max_date}", directory)
-        } else {
-            format!("{}/{i}", directory)
-        };
-        std::fs::create_dir_all(&group_dir).expect("Failed to create group directory.");
-
-        for file in group {
-            let target = PathBuf::from(group_dir.as_str()).join(
-                file.file_name().expect("Failed to get file name.")
-            );
-            println!(
-                "Moving file \"{}\" to \"{}\"",
-                file.to_string_lossy(),
-                target.to_string_lossy()
-            );
-            std::fs::rename(file, target).expect("Failed to move file.");
-        }
-    }
-}
+    /// Check that every row of a lookup CSV file parses, without touching any files. Reports each problem with its row
+    /// number and a suggested fix, and exits with an error if any are found.
+    CheckLookup {
+        /// A path (or `https://` URL, or `gsheet:<spreadsheet-id>/<tab>`) to the lookup CSV file.
+        #[arg(short = 'L', long)]
+        lookup: String,
+    },
 
-fn copy_and_rename_files(
-    files: Vec<PathBuf>,
-    lookup_table: BTreeMap<String, IssueData>,
-    output: Option<String>
-) {
-    let output_dir = match output {
-        Some(dir) => {
-            std::fs::create_dir_all(&dir).expect("Failed to create output directory.");
-            dir
-        }
-        None => String::new(),
-    };
+    /// Add, update, or remove a row of a lookup CSV file, checking that its title parses. The file keeps its encoding,
+    /// line endings, and quoting, and the previous version is moved to the trash.
+    Lookup {
+        /// A path to the lookup CSV file.
+        #[arg(short = 'L', long)]
+        lookup: String,
 
-    for file in files {
-        let file_name = file.file_name().expect("Failed to get file name.").to_string_lossy();
-        // break off the extension.
-        let (tn, ext) = file_name
-            .split_once(".")
-            .expect("Failed to split file name and extension.");
-
-        if let Some(issue) = lookup_table.get(tn) {
-            let target_file = format!("{}.{}", issue.formatted_title(), ext);
-            let target_path = if output_dir.is_empty() {
-                file.with_file_name(target_file)
-            } else {
-                PathBuf::from(output_dir.as_str()).join(target_file)
-            };
-            println!("Copying file \"{}\" to \"{}\"", file_name, target_path.to_string_lossy());
-            std::fs::copy(file, target_path).expect("Failed to copy file.");
-        }
-    }
-}
+        #[command(subcommand)]
+        action: lookup::LookupAction,
+    },
 
-fn collect_files(directory: &str, extensions: &[String], recursive: bool) -> Vec<PathBuf> {
-    let mut files = vec![];
-    for entry in std::fs
-        ::read_dir(directory)
-        .expect("Failed to read directory. Path does not exist or is not a directory.") {
-        let entry = entry.unwrap();
-        let path = entry.path();
-
-        if path.is_file() {
-            if let Some(ext) = path.extension() {
-                if extensions.contains(&ext.to_string_lossy().to_string()) {
-                    files.push(path.to_path_buf());
-                }
-            }
-        } else if path.is_dir() && recursive {
-            files.append(
-                &mut collect_files(&path.to_string_lossy().to_string(), extensions, recursive)
-            );
-        }
-    }
-    println!("Found {} files.", files.len());
+    /// Rebuild a lookup table (as JSON) from a populated target CSV, using its Call Number, Volume, Issue, and date columns.
+    ReverseLookup {
+        /// A path to the populated target CSV file.
+        #[arg(short, long)]
+        target: String,
 
-    files.sort_by(|a, b| a.file_name().cmp(&b.file_name()));
+        /// A path to the JSON file to write. Defaults to `<target>_lookup.json`.
+        #[arg(short, long)]
+        output: Option<String>,
+    },
 
-    files
-}
+    /// Print the number of issues per year (and month) of each publication, to show coverage and thin years.
+    Coverage {
+        #[command(flatten)]
+        source: coverage::DateSource,
 
-fn parse_lookup_table(lookup: &str) -> BTreeMap<String, IssueData> {
-    // ordered map
-    let mut lookup_table = BTreeMap::new();
+        /// Also print each year's issue count per month.
+        #[arg(long)]
+        months: bool,
 
-    // assert the lookup is a csv file.
-    assert!(lookup.ends_with(".csv"), "Lookup table must be a CSV file.");
+        /// Also export the table as a CSV file (`publication, year, issues, Jan, ..., Dec`).
+        #[arg(short, long)]
+        output: Option<String>,
+    },
 
-    let mut reader = csv::Reader::from_path(lookup).expect("Failed to read lookup table.");
-    for result in reader.records() {
-        let record = result.expect("Failed to parse record.");
-        let tn = record.get(0).expect("Failed to get tn.");
-        let title = record.get(1).expect("Failed to get title.");
-        let date_loaded = record.get(5).expect("Failed to get date loaded.");
+    /// Print the earliest and latest issue dates of each publication, with its normalized Date Range (e.g. `1944/1952`).
+    DateRanges {
+        #[command(flatten)]
+        source: coverage::DateSource,
 
-        if tn.is_empty() {
-            continue;
-        }
+        /// Also export the spans as a CSV file (`publication, first_issue, last_issue, date_range`).
+        #[arg(short, long)]
+        output: Option<String>,
+    },
 
-        let issue_data = IssueData::new(tn.to_string(), title.to_string(), date_loaded.to_string());
-        lookup_table.insert(tn.to_string(), issue_data);
-    }
+    /// List the expected issue dates of each publication that have no issue, given how often it was published.
+    MissingIssues {
+        #[command(flatten)]
+        source: coverage::DateSource,
 
-    println!(
-        "{} {} {}",
-        "Parsed".italic().white(),
-        lookup_table.len().bold().white(),
-        "records from lookup table.".italic().white()
-    );
+        /// How often the publication was issued. Inferred from the gaps between issue dates if not given.
+        #[arg(long, value_enum)]
+        frequency: Option<missing::Frequency>,
 
-    lookup_table
-}
+        /// Also export the missing issues as a CSV file (`publication, expected_date, previous_issue, next_issue`).
+        #[arg(short, long)]
+        output: Option<String>,
+    },
 
-fn parse_generated_names(generated: &str) -> Vec<String> {
-    let mut names = vec![];
+    /// Write the rows of a CSV file that match a predicate to a new CSV file.
+    Filter {
+        /// A path to the CSV file to filter. `-` reads from stdin.
+        csv: String,
 
-    // assert the lookup is a csv file.
-    assert!(generated.ends_with(".csv"), "Generated table must be a CSV file.");
+        /// The predicate each row must match, e.g. `Collection == "Arizona Labor Journal" && Volume != ""`.
+        /// Column names with spaces go in backticks, e.g. `` `Part Of` != "" ``.
+        #[arg(short = 'w', long = "where")]
+        predicate: String,
 
-    let mut reader = csv::Reader::from_path(generated).expect("Failed to read generated table.");
-    for result in reader.records() {
-        let record = result.expect("Failed to parse record.");
-        // Arizona Catering Employees, 1944-05-12
-        let node_title = record.get(0).expect("Failed to get node title.");
-        // // split on comma, extract the date.
-        // let date = node_title.split(", ").last().expect("Failed to split date from node title.");
-        names.push(node_title.to_string());
-    }
+        /// A path to the CSV file to write, or `-` for stdout. Defaults to `<csv>_filtered.csv` (stdout when reading stdin).
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+
+    /// Run a small SQL query (`SELECT`, `WHERE`, `ORDER BY`, `LIMIT`) over a CSV file and print the result as a table.
+    Query {
+        /// A path to the CSV file to query. `-` reads from stdin.
+        csv: String,
 
-    println!(
-        "{} {} {}",
-        "Parsed".italic().white(),
-        names.len().bold().white(),
-        "records from generated table.".italic().white()
-    );
+        /// The query, e.g. `SELECT NODE_TITLE, Volume WHERE Volume = '' ORDER BY NODE_TITLE LIMIT 20`.
+        /// `WHERE` takes the same predicates as `filter --where`.
+        sql: String,
 
-    names
+        /// Write the result as CSV to this path (or `-` for stdout) instead of printing a table.
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+
+    /// Print the headers, row count, empty-value percentages, and distinct value samples of a CSV file.
+    Inspect {
+        /// A path to the CSV file to inspect.
+        csv: String,
+
+        /// The number of distinct values to sample from each column.
+        #[arg(short, long, default_value_t = 5)]
+        samples: usize,
+    },
+
+    /// Run a sequence of steps (format, populate, link-issues, compare) from a YAML plan file.
+    Run {
+        /// A path to the YAML plan file.
+        plan: String,
+    },
 }
 
-fn compare_tables(lookup_table: BTreeMap<String, IssueData>, generated_names: Vec<String>) {
-    // check if any of the dates in the lookup table are the same.
-    {
-        let mut duplicate_dates = vec![];
-        for (tn, issue) in &lookup_table {
-            if
-                lookup_table
-                    .values()
-                    .filter(|i| i.date == issue.date)
-                    .count() > 1
-            {
-                duplicate_dates.push((tn, issue.date.clone()));
+fn main() {
+    bulk_format::summary::start_clock();
+    bulk_format::handle_interrupts();
+    let matches = Cli::command().get_matches();
+    let args = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let command_name = matches.subcommand_name().unwrap_or_default().to_string();
+    if let Some(url) = args.notify.clone() {
+        let command_name = command_name.clone();
+        let default_hook = std::panic::take_hook();
+        std::panic::set_hook(
+            Box::new(move |info| {
+                default_hook(info);
+                notify::notify(&url, &command_name, Some(info.payload_as_str().unwrap_or("panicked")));
+            })
+        );
+    }
+    if args.quiet {
+        bulk_format::summary::set_quiet();
+    }
+    if args.group_warnings {
+        bulk_format::summary::set_group_warnings();
+    }
+    for category in &args.allow {
+        bulk_format::summary::set_severity(*category, Severity::Allow);
+    }
+    for category in &args.deny {
+        bulk_format::summary::set_severity(*category, Severity::Deny);
+    }
+    bulk_format::summary::on_deny(stop_denied);
+    if args.yes {
+        bulk_format::assume_yes();
+    }
+    messages::set_lang(args.lang);
+    args.color.choice().write_global();
+    let target_is_stdio = match &args.command {
+        Commands::Populate { target, dry_run, .. } => target == STDIO && !dry_run,
+        Commands::LinkIssues { target, .. } => target == STDIO,
+        Commands::Filter { csv, output, .. } => output.as_deref().unwrap_or(csv) == STDIO,
+        Commands::Query { output, .. } => output.as_deref() == Some(STDIO),
+        _ => false,
+    };
+    if target_is_stdio {
+        bulk_format::set_stdout_is_data();
+    }
+    config::load_config(args.config.as_deref(), args.profile.as_deref());
+    DUPLICATE_TNS.set(args.duplicate_tns).expect("Duplicate tn policy was already set.");
+
+    let run_end = RunEnd { command_name: command_name.clone(), quiet: args.quiet, notify: args.notify.clone() };
+    RUN_END.set(run_end).unwrap_or_else(|_| panic!("The run was already started."));
+    hooks::before_run(&command_name);
+
+    // commands that find problems exit with an error after the summary is reported.
+    let mut found_problems = false;
+    match args.command {
+        Commands::Format { lookup, directories, files_from, extensions, recursive, filters, options } => {
+            let files = match &files_from {
+                Some(list) => read_file_list(&RealFilesystem, list, &filters),
+                None => {
+                    let extensions = config::extensions_or_default(&extensions);
+                    collect_directories(&RealFilesystem, &directories, &extensions, recursive, &filters)
+                }
+            };
+            let lookup_table = read_lookup_table(&lookup);
+            found_problems = !format::copy_and_rename_files(&RealFilesystem, &directories, files, &lookup_table, &options);
+        }
+        Commands::VerifyOutput { lookup, directories, files_from, extensions, recursive, filters, options } => {
+            let files = match &files_from {
+                Some(list) => read_file_list(&RealFilesystem, list, &filters),
+                None => {
+                    let extensions = config::extensions_or_default(&extensions);
+                    collect_directories(&RealFilesystem, &directories, &extensions, recursive, &filters)
+                }
+            };
+            let lookup_table = read_lookup_table(&lookup);
+            found_problems = !format::verify_output(&RealFilesystem, &files, &lookup_table, &options);
+        }
+        Commands::Populate { target, lookup, dry_run, options } => {
+            let lookup_table = read_lookup_table(&lookup);
+            populate_csv(&target, invert_lookup_table(lookup_table), dry_run, &options).unwrap();
+        }
+        Commands::LinkIssues { target, link_value_column, graph, columns } => {
+            link_issues(&target, link_value_column.as_deref(), graph.as_deref(), &columns);
+        }
+        Commands::RepairLinks { target, fix, graph } => {
+            found_problems = links::repair_links(&target, fix, graph.as_deref()).is_err();
+        }
+        Commands::DedupeRows { target, review } => {
+            dedupe::dedupe_rows(&target, review.as_deref());
+        }
+        Commands::NormalizeTitles { target, dry_run } => {
+            titles::normalize_titles(&target, dry_run);
+        }
+        Commands::Chains { target } => {
+            found_problems = links::analyze_chains(&target) > 0;
+        }
+        Commands::CheckDates { target, output } => {
+            found_problems = validate::check_dates(&target, output.as_deref()) > 0;
+        }
+        Commands::Validate { target } => {
+            found_problems = validate::validate_vocabularies(&target) > 0;
+        }
+        Commands::Diff { old, new } => {
+            diff::diff_csv(&old, &new);
+        }
+        Commands::Status { tn } => {
+            state::print_status(tn.as_deref().map(issue_data::normalize_tn).as_deref());
+        }
+        Commands::Compare { lookup, generated, options } => {
+            let lookup_table = read_lookup_table(&lookup);
+            let generated_names = compare::parse_generated_names(&generated);
+            found_problems = !compare::compare_tables(lookup_table, generated_names, &options);
+        }
+        Commands::GroupFiles { directories, files_from, extensions, recursive, filters, options } => {
+            let simulated = MemoryFilesystem::overlay();
+            let fs: &dyn Filesystem = if options.simulate { &simulated } else { &RealFilesystem };
+            match &files_from {
+                Some(list) => {
+                    let files = read_file_list(&RealFilesystem, list, &filters);
+                    group::group_listed_files(fs, directories.first(), files, &options)
+                },
+                None => {
+                    let extensions = config::extensions_or_default(&extensions);
+                    group::group_files(fs, &directories, &extensions, recursive, &filters, &options);
+                }
             }
         }
-        if duplicate_dates.is_empty() {
-            println!("{}", "No duplicate dates found.".green().bold());
-        } else {
-            println!("{}", "Duplicate dates:".red().bold());
-            for (tn, date) in duplicate_dates {
-                println!("{}: {}", tn, date);
+        Commands::Derivatives { directory, extensions, recursive, filters, options } => {
+            let files = collect_files(&RealFilesystem, &directory, &config::extensions_or_default(&extensions), recursive, &filters);
+            derivative::create_derivatives(files, &options);
+        }
+        Commands::Checksum { directory, extensions, recursive, filters, output, verify, jobs, incremental } => {
+            let files = collect_files(&RealFilesystem, &directory, &config::extensions_or_default(&extensions), recursive, &filters);
+            let jobs = jobs.unwrap_or_else(checksum::default_jobs);
+            match verify {
+                Some(manifest) => {
+                    found_problems = !checksum::verify_manifest(&directory, &files, &manifest, jobs);
+                }
+                None => checksum::write_manifest(&directory, &files, &output, jobs, incremental),
             }
         }
+        Commands::SplitPdf { pdf, ranges, lookup, output } => {
+            let lookup_table = lookup
+                .as_deref()
+                .map(read_lookup_table)
+                .unwrap_or_default();
+            pdf::split_pdf(&pdf, &ranges, &lookup_table, &output);
+        }
+        Commands::MergePdf { directory, recursive, output, dry_run } => {
+            let files = collect_files(&RealFilesystem, &directory, &["pdf".to_string()], recursive, &FileFilters::default());
+            pdf::merge_page_pdfs(files, &output, dry_run);
+        }
+        Commands::ExportLookup { lookup, output } => {
+            let lookup_table = read_lookup_table(&lookup);
+            let output = output.unwrap_or_else(|| derived_path(&lookup, "", "json"));
+            export_lookup_table(&lookup_table, &safely_target_file(&output));
+        }
+        Commands::CheckLookup { lookup } => {
+            found_problems = lookup::check_lookup(&lookup).unwrap_or_else(|e| stop_with_error(e)) > 0;
+        }
+        Commands::Lookup { lookup, action } => {
+            lookup::edit_lookup(&lookup, action).unwrap_or_else(|e| stop_with_error(e));
+        }
+        Commands::ReverseLookup { target, output } => {
+            let lookup_table = reverse::lookup_from_records(&target);
+            let output = output.unwrap_or_else(|| derived_path(&target, "_lookup", "json"));
+            export_lookup_table(&lookup_table, &safely_target_file(&output));
+        }
+        Commands::Coverage { source, months, output } => {
+            coverage::coverage_report(&source, months, output.as_deref()).unwrap_or_else(|e| stop_with_error(e));
+        }
+        Commands::DateRanges { source, output } => {
+            coverage::date_range_report(&source, output.as_deref()).unwrap_or_else(|e| stop_with_error(e));
+        }
+        Commands::MissingIssues { source, frequency, output } => {
+            missing::missing_issues(&source, frequency, output.as_deref()).unwrap_or_else(|e| stop_with_error(e));
+        }
+        Commands::Filter { csv, predicate, output } => {
+            filter::filter_csv(&csv, &predicate, output.as_deref());
+        }
+        Commands::Query { csv, sql, output } => {
+            query::query_csv(&csv, &sql, output.as_deref());
+        }
+        Commands::Inspect { csv, samples } => {
+            inspect::inspect_csv(&csv, samples);
+        }
+        Commands::Run { plan } => {
+            found_problems = !plan::run_plan(&plan);
+        }
     }
 
-    let mut missing = vec![];
-    let mut indexes = vec![];
-    // for each name in the lookup table, check if it exists in the generated names, and if it does not, add it to the missing list.
-    for (tn, issue) in lookup_table {
-        if !generated_names.contains(&issue.record_title()) {
-            missing.push((tn, issue.record_title()));
+    finish_run(found_problems);
+}
+
+/// What the end of a run reports, kept for a run stopped early by a denied warning.
+struct RunEnd {
+    command_name: String,
+    quiet: bool,
+    notify: Option<String>,
+}
+
+static RUN_END: OnceLock<RunEnd> = OnceLock::new();
+
+/// Reports the end of the run: the warning summary, metrics, `--quiet` JSON summary, and `--notify` notification.
+/// Exits with an error if problems were found.
+fn finish_run(mut found_problems: bool) {
+    let RunEnd { command_name, quiet, notify } = RUN_END.get().expect("The run was not started.");
+    hooks::after_run(command_name, !found_problems);
+    print_warning_summary();
+    let denied = bulk_format::summary::denied_warnings();
+    if denied > 0 {
+        print_status!("{}", format!("{} of denied categories.", messages::warning_count(denied)).red().bold());
+        found_problems = true;
+    }
+    print_status!("{}", bulk_format::summary::metrics_line().dimmed());
+    if !found_problems {
+        print_status!("{}", messages::job_done().green().bold());
+    }
+    if *quiet {
+        let summary = bulk_format::summary::summary_json(command_name);
+        if bulk_format::stdout_is_data() {
+            eprintln!("{}", summary);
         } else {
-            // get the index of the generated name.
-            let index = generated_names
-                .iter()
-                .position(|n| n.contains(&issue.record_title()))
-                .unwrap();
-            indexes.push(index);
+            println!("{}", summary);
         }
     }
+    if let Some(url) = notify {
+        notify::notify(url, command_name, found_problems.then_some("Problems were found."));
+    }
+    if found_problems {
+        std::process::exit(1);
+    }
+}
+
+/// Stops the run at the first warning of a denied category, before anything else is written.
+fn stop_denied(category: Warning) {
+    print_status!("{}", format!("Stopped at a warning of a denied category ({}).", category.name()).red().bold());
+    finish_run(true);
+}
 
-    // sort the indexes, verify they are sequential.
-    indexes.sort();
-    let mut last = -1;
-    let mut is_sequential = true;
-    for index in indexes.iter() {
-        let index = index.clone() as i32;
-        if index != last + 1 {
-            print_warn!("Index {} is not sequential.", index);
-            is_sequential = false;
-        }
-        last = index;
+/// Stops the run with an error in its input, reported like any run that found problems rather than as a panic.
+fn stop_with_error(error: impl std::fmt::Display) -> ! {
+    print_error!("{}", error);
+    finish_run(true);
+    unreachable!("A run that found problems exits.");
+}
+
+/// Parses a lookup table, stopping the run if a row cannot be parsed.
+fn read_lookup_table(lookup: &str) -> BTreeMap<String, IssueData> {
+    parse_lookup_table(lookup).unwrap_or_else(|e| stop_with_error(e))
+}
+
+/// Where `--group-warnings` writes every warning of the run.
+const WARNINGS_LOG: &str = "bulk_format_warnings.csv";
+
+/// Prints the number of warnings of each category, and with `--group-warnings`, writes them all to `WARNINGS_LOG`.
+fn print_warning_summary() {
+    let groups = bulk_format::summary::warning_groups();
+    if groups.is_empty() {
+        return;
     }
-    if is_sequential {
-        println!(
-            "{} {}..{}",
-            "Indexes are sequential.".green().bold(),
-            indexes[0],
-            indexes.last().unwrap()
-        );
-    } else {
-        println!("{}", "Indexes are not sequential.".red().bold());
+    print_status!("{}", "Warnings by category:".yellow().bold());
+    for (category, count) in groups {
+        print_status!("  {:>6}  {} {}", count, category.label(), format!("({})", category.name()).dimmed());
+    }
+    if bulk_format::summary::groups_warnings() {
+        bulk_format::summary::write_warnings(WARNINGS_LOG);
+        print_status!("Every warning is listed in \"{}\".", WARNINGS_LOG);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+    use super::*;
+
+    #[test]
+    fn strip_colors_by_color_mode() {
+        let colored = |mode: ColorMode| {
+            let mut stream = anstream::AutoStream::new(Vec::new(), mode.choice());
+            write!(stream, "{}", "Job done.".green()).unwrap();
+            String::from_utf8(stream.into_inner()).unwrap()
+        };
+        assert_eq!(colored(ColorMode::Never), "Job done.");
+        assert_eq!(colored(ColorMode::Always), "Job done.".green().to_string());
+        // `auto` never colors output that is not a terminal.
+        assert_eq!(colored(ColorMode::Auto), "Job done.");
     }
-    println!("{} Total verified files.", indexes.len());
-
-    if missing.is_empty() {
-        println!("{}", "No missing entries found.".green().bold());
-    } else {
-        println!("{}", "Missing entries:".red().bold());
-        for (tn, title) in missing {
-            println!("{}: {}", tn, title);
+
+    #[test]
+    fn read_options_from_environment() {
+        let vars = [
+            ("BULK_FORMAT_ASSUME_YES", "1"),
+            ("BULK_FORMAT_OUTPUT_DIR", "out"),
+            ("BULK_FORMAT_DENY", "unmatched-record"),
+        ];
+        for (key, value) in vars {
+            std::env::set_var(key, value);
+        }
+        let parse = |args: &[&str]| {
+            Cli::try_parse_from(["bulk_format", "format", "-L", "lookup.csv", "-d", "scans"].iter().chain(args))
+        };
+        let args = parse(&[]).unwrap();
+        assert!(args.yes);
+        assert_eq!(args.deny, [Warning::UnmatchedRecord]);
+        let Commands::Format { options, .. } = args.command else {
+            panic!("Expected the format command.");
+        };
+        assert_eq!(options.output.as_deref(), Some("out"));
+
+        // flags win over the environment.
+        let Commands::Format { options, .. } = parse(&["--output", "other"]).unwrap().command else {
+            panic!("Expected the format command.");
+        };
+        assert_eq!(options.output.as_deref(), Some("other"));
+        for (key, _) in vars {
+            std::env::remove_var(key);
         }
     }
 }
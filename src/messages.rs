@@ -0,0 +1,416 @@
+//! The catalog of user-facing messages (prompts, warnings, run summaries, and the status lines of Format) in each
+//! language of `--lang`. Messages not in the catalog yet are printed in English.
+
+use std::sync::OnceLock;
+
+/// A language of the message catalog.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Lang {
+    /// English.
+    #[default]
+    En,
+    /// Spanish.
+    Es,
+}
+
+static LANG: OnceLock<Lang> = OnceLock::new();
+
+/// Sets the language of every message for the rest of the run.
+pub fn set_lang(lang: Lang) {
+    LANG.set(lang).expect("The language was already set.");
+}
+
+pub fn lang() -> Lang {
+    #[cfg(test)]
+    if let Some(lang) = TEST_LANG.get() {
+        return lang;
+    }
+    LANG.get().copied().unwrap_or_default()
+}
+
+// each test thread can set its own language, as the run's language can only be set once.
+#[cfg(test)]
+thread_local! {
+    static TEST_LANG: std::cell::Cell<Option<Lang>> = const { std::cell::Cell::new(None) };
+}
+
+/// Returns e.g. `1 warning` or `2 warnings`.
+pub fn warning_count(count: usize) -> String {
+    let plural = if count == 1 { "" } else { "s" };
+    match lang() {
+        Lang::En => format!("{} warning{}", count, plural),
+        Lang::Es => format!("{} advertencia{}", count, plural),
+    }
+}
+
+/// Defines a function per message, formatting its English or Spanish text with its arguments by name.
+macro_rules! messages {
+    ($($(#[$doc:meta])* $name:ident($($arg:ident: $ty:ty),*) { en: $en:literal, es: $es:literal $(,)? })*) => {
+        $(
+            $(#[$doc])*
+            pub fn $name($($arg: $ty),*) -> String {
+                match lang() {
+                    Lang::En => format!($en $(, $arg = $arg)*),
+                    Lang::Es => format!($es $(, $arg = $arg)*),
+                }
+            }
+        )*
+    };
+}
+
+messages! {
+    overwrite_prompt(target: &str) {
+        en: "The target file \"{target}\" already exists. Do you want to overwrite it?",
+        es: "El archivo de destino \"{target}\" ya existe. ¿Desea sobrescribirlo?",
+    }
+    /// The answers of `prompt_bool`. Both languages' answers are accepted either way, and none of their letters is an
+    /// answer of a conflict prompt.
+    yes_no_choices() {
+        en: "[y/N/a(ll)/none]",
+        es: "[sí/N/t(odos)/ninguno]",
+    }
+    answered_all() {
+        en: "Yes (all).",
+        es: "Sí (todos).",
+    }
+    answered_none() {
+        en: "No (none).",
+        es: "No (ninguno).",
+    }
+    answered_assumed() {
+        en: "Yes (assumed).",
+        es: "Sí (asumido).",
+    }
+    target_exists(target: &str) {
+        en: "The target file \"{target}\" already exists.",
+        es: "El archivo de destino \"{target}\" ya existe.",
+    }
+    /// The answers of a conflict prompt. The letters are the same in every language.
+    conflict_choices() {
+        en: "[o]verwrite / [s]kip / [r]ename / [O]verwrite all / [S]kip all / [R]ename all",
+        es: "[o] sobrescribir / [s] saltar / [r] renombrar / [O] sobrescribir todos / [S] saltar todos / [R] renombrar todos",
+    }
+    exiting_without_overwriting() {
+        en: "Exiting without overwriting target file.",
+        es: "Saliendo sin sobrescribir el archivo de destino.",
+    }
+    job_done() {
+        en: "Job done.",
+        es: "Trabajo terminado.",
+    }
+    finished_in(seconds: f64, warnings: &str) {
+        en: "Finished in {seconds:.1}s, {warnings}.",
+        es: "Terminado en {seconds:.1}s, {warnings}.",
+    }
+    processed_files(files: usize, size: &str, seconds: f64, rate: &str, warnings: &str) {
+        en: "Processed {files} files ({size}) in {seconds:.1}s ({rate}/s), {warnings}.",
+        es: "Se procesaron {files} archivos ({size}) en {seconds:.1}s ({rate}/s), {warnings}.",
+    }
+    skipping(file: &str) {
+        en: "Skipping \"{file}\".",
+        es: "Saltando \"{file}\".",
+    }
+    unchanged(file: &str) {
+        en: "Unchanged \"{file}\"",
+        es: "Sin cambios \"{file}\"",
+    }
+    copying_file(file: &str, target: &str) {
+        en: "Copying file \"{file}\" to \"{target}\"",
+        es: "Copiando el archivo \"{file}\" a \"{target}\"",
+    }
+    copying_sidecar(file: &str, target: &str) {
+        en: "Copying sidecar \"{file}\" to \"{target}\"",
+        es: "Copiando el archivo complementario \"{file}\" a \"{target}\"",
+    }
+    interrupted_removed(target: &str) {
+        en: "Interrupted. Removed the partial \"{target}\".",
+        es: "Interrumpido. Se eliminó el archivo parcial \"{target}\".",
+    }
+    no_lookup_entry(file: &str) {
+        en: "No lookup entry for \"{file}\". Skipped.",
+        es: "No hay entrada en la tabla de búsqueda para \"{file}\". Se saltó.",
+    }
+    saved_mappings(count: usize, output: &str) {
+        en: "Saved {count} mappings to \"{output}\".",
+        es: "Se guardaron {count} correspondencias en \"{output}\".",
+    }
+    logged_files(count: usize, output: &str) {
+        en: "Logged {count} skipped, overwritten, or renamed files to \"{output}\".",
+        es: "Se registraron {count} archivos saltados, sobrescritos o renombrados en \"{output}\".",
+    }
+}
+
+// warnings, and the reasons given in them.
+messages! {
+    index_not_sequential(index: i32) {
+        en: "Index {index} is not sequential.",
+        es: "El índice {index} no es secuencial.",
+    }
+    no_date_found(name: &str) {
+        en: "No date found in \"{name}\".",
+        es: "No se encontró una fecha en \"{name}\".",
+    }
+    undated_not_counted(count: usize) {
+        en: "{count} issues have no date and are not counted.",
+        es: "{count} números no tienen fecha y no se cuentan.",
+    }
+    duplicate_set_aside(key: &str, row: usize, filled: usize) {
+        en: "Duplicate \"{key}\" (row {row}, {filled} filled values) set aside for review.",
+        es: "Duplicado \"{key}\" (fila {row}, {filled} valores completos) apartado para revisión.",
+    }
+    derivative_failed(file: &str, status: &str) {
+        en: "Failed to create a derivative of \"{file}\" ({status}).",
+        es: "No se pudo crear un derivado de \"{file}\" ({status}).",
+    }
+    derivatives_failed(count: usize) {
+        en: "{count} derivatives failed.",
+        es: "Fallaron {count} derivados.",
+    }
+    duplicate_key(column: &str, key: &str, path: &str) {
+        en: "Duplicate {column} \"{key}\" in \"{path}\". Only the first row is compared.",
+        es: "{column} duplicado \"{key}\" en \"{path}\". Solo se compara la primera fila.",
+    }
+    fetch_failed(lookup: &str, error: &str) {
+        en: "Failed to fetch \"{lookup}\": {error}",
+        es: "No se pudo descargar \"{lookup}\": {error}",
+    }
+    file_tn_pattern(file: &str) {
+        en: "File \"{file}\" does not match the configured tn pattern.",
+        es: "El archivo \"{file}\" no coincide con el patrón de tn configurado.",
+    }
+    lookup_tn_pattern(tn: &str) {
+        en: "Lookup tn \"{tn}\" does not match the configured tn pattern.",
+        es: "El tn \"{tn}\" de la tabla de búsqueda no coincide con el patrón de tn configurado.",
+    }
+    case_collision(name: &str, other: &str) {
+        en: "\"{name}\" and \"{other}\" differ only in case, and collide on case-insensitive filesystems.",
+        es: "\"{name}\" y \"{other}\" solo difieren en mayúsculas, y chocan en sistemas de archivos que no las distinguen.",
+    }
+    orphan_file(file: &str) {
+        en: "\"{file}\" in the output directory matches no lookup entry.",
+        es: "\"{file}\" en el directorio de salida no corresponde a ninguna entrada de la tabla de búsqueda.",
+    }
+    orphans_need_output() {
+        en: "--check-orphans needs an --output directory. No orphans were checked.",
+        es: "--check-orphans necesita un directorio --output. No se buscaron archivos huérfanos.",
+    }
+    missing_name_value(placeholder: &str, title: &str) {
+        en: "No \"{placeholder}\" value for \"{title}\" in the name template. Used the formatted title.",
+        es: "No hay un valor \"{placeholder}\" para \"{title}\" en la plantilla de nombres. Se usó el título formateado.",
+    }
+    upload_failed(url: &str, status: &str) {
+        en: "Upload to \"{url}\" failed ({status}).",
+        es: "Falló la subida a \"{url}\" ({status}).",
+    }
+    upload_not_run(program: &str, error: &str) {
+        en: "Failed to run {program}: {error}",
+        es: "No se pudo ejecutar {program}: {error}",
+    }
+    modified_time_not_set(tn: &str) {
+        en: "Cannot set the modification time of \"{tn}\" from its date.",
+        es: "No se puede establecer la hora de modificación de \"{tn}\" a partir de su fecha.",
+    }
+    xmp_failed(file: &str, error: &str) {
+        en: "Failed to embed XMP in \"{file}\": {error}",
+        es: "No se pudo incrustar XMP en \"{file}\": {error}",
+    }
+    pdf_info_failed(file: &str, error: &str) {
+        en: "Failed to embed PDF metadata in \"{file}\": {error}",
+        es: "No se pudieron incrustar los metadatos PDF en \"{file}\": {error}",
+    }
+    start_index_reused(index: usize) {
+        en: "--start-index {index} reuses the indices of existing groups.",
+        es: "--start-index {index} reutiliza los índices de grupos existentes.",
+    }
+    excluded_from_group_name(name: &str) {
+        en: "No date found in \"{name}\". Excluded from the group's name.",
+        es: "No se encontró una fecha en \"{name}\". Se excluyó del nombre del grupo.",
+    }
+    will_be_replaced(target: &str) {
+        en: "\"{target}\" already exists and will be replaced.",
+        es: "\"{target}\" ya existe y será reemplazado.",
+    }
+    grouped_alone(item: &str) {
+        en: "{item} is larger than --max-bytes and will be grouped alone.",
+        es: "{item} supera --max-bytes y se agrupará solo.",
+    }
+    hook_failed(name: &str, status: &str) {
+        en: "The {name} hook failed ({status}).",
+        es: "El hook {name} falló ({status}).",
+    }
+    hook_not_run(name: &str, error: &str) {
+        en: "Failed to run the {name} hook: {error}",
+        es: "No se pudo ejecutar el hook {name}: {error}",
+    }
+    volume_not_comma_separated(volume_issue: &str) {
+        en: "VOLUME AND ISSUE WERE NOT COMMA SEPARATED: \"{volume_issue}\"",
+        es: "EL VOLUMEN Y EL NÚMERO NO ESTABAN SEPARADOS POR COMA: \"{volume_issue}\"",
+    }
+    undated_issue(tn: &str) {
+        en: "Issue {tn} has no date. Its tn will be used in place of the date.",
+        es: "El número {tn} no tiene fecha. Se usará su tn en lugar de la fecha.",
+    }
+    date_parts(date: &str) {
+        en: "Parts is not 3 in length. Date: \"{date}\"",
+        es: "La fecha no tiene 3 partes: \"{date}\"",
+    }
+    expanded_year(year: &str, expanded: &str) {
+        en: "Expanded two-digit year \"{year}\" to {expanded}",
+        es: "Se expandió el año de dos dígitos \"{year}\" a {expanded}",
+    }
+    links_not_checked(title: &str) {
+        en: "\"{title}\" has no date in its NODE_TITLE. Its links were not checked.",
+        es: "\"{title}\" no tiene fecha en su NODE_TITLE. No se revisaron sus enlaces.",
+    }
+    dangling_link(title: &str, field: &str, value: &str) {
+        en: "\"{title}\" {field} \"{value}\" refers to a missing NODE_TITLE.",
+        es: "El {field} \"{value}\" de \"{title}\" se refiere a un NODE_TITLE inexistente.",
+    }
+    asymmetric_link(title: &str, field: &str, value: &str) {
+        en: "\"{title}\" {field} \"{value}\" is not linked back.",
+        es: "El {field} \"{value}\" de \"{title}\" no está enlazado de vuelta.",
+    }
+    out_of_order_link(title: &str, field: &str, value: &str) {
+        en: "\"{title}\" {field} \"{value}\" does not match date order.",
+        es: "El {field} \"{value}\" de \"{title}\" no coincide con el orden de fechas.",
+    }
+    chain_crosses_publications(title: &str) {
+        en: "A chain starting at \"{title}\" continues into another publication.",
+        es: "Una cadena que empieza en \"{title}\" continúa en otra publicación.",
+    }
+    missing_next_issue(title: &str, next: &str) {
+        en: "Broken link: \"{title}\" has a Next Issue \"{next}\" that does not exist.",
+        es: "Enlace roto: \"{title}\" tiene un Next Issue \"{next}\" que no existe.",
+    }
+    link_cycle(titles: &str) {
+        en: "Cycle: {titles} -> (back to start)",
+        es: "Ciclo: {titles} -> (vuelta al inicio)",
+    }
+    link_fork(titles: &str, target: &str) {
+        en: "Fork: {titles} all have \"{target}\" as their Next Issue.",
+        es: "Bifurcación: {titles} tienen todos a \"{target}\" como Next Issue.",
+    }
+    invalid_date_loaded(date_loaded: &str, title: &str) {
+        en: "Date loaded \"{date_loaded}\" of \"{title}\" is not a valid date. Copied as-is.",
+        es: "La fecha de carga \"{date_loaded}\" de \"{title}\" no es una fecha válida. Se copió tal cual.",
+    }
+    undated_record(title: &str) {
+        en: "\"{title}\" is undated (n.d.). Flagged in Admin Notes.",
+        es: "\"{title}\" no tiene fecha (n.d.). Se marcó en Admin Notes.",
+    }
+    no_issue_data(title: &str) {
+        en: "Failed to find issue data for \"{title}\".",
+        es: "No se encontraron datos del número para \"{title}\".",
+    }
+    listed_file_missing(file: &str) {
+        en: "Listed file \"{file}\" does not exist. Skipped.",
+        es: "El archivo listado \"{file}\" no existe. Se saltó.",
+    }
+    same_file_name(file: &str, other: &str) {
+        en: "\"{file}\" and \"{other}\" have the same file name.",
+        es: "\"{file}\" y \"{other}\" tienen el mismo nombre de archivo.",
+    }
+    skipped_because(name: &str, reason: &str) {
+        en: "Skipped \"{name}\": {reason}.",
+        es: "Se saltó \"{name}\": {reason}.",
+    }
+    under_min_size(size: &str, min: &str) {
+        en: "{size} is under the minimum size of {min}",
+        es: "{size} es menor que el tamaño mínimo de {min}",
+    }
+    over_max_size(size: &str, max: &str) {
+        en: "{size} is over the maximum size of {max}",
+        es: "{size} supera el tamaño máximo de {max}",
+    }
+    duplicate_tn(tn: &str, existing: &str, other: &str, kept: &str) {
+        en: "Duplicate tn \"{tn}\": \"{existing}\" and \"{other}\". Kept \"{kept}\".",
+        es: "tn duplicado \"{tn}\": \"{existing}\" y \"{other}\". Se conservó \"{kept}\".",
+    }
+    too_few_dated_issues(publication: &str) {
+        en: "Not enough dated issues of \"{publication}\" to infer its frequency. Skipped.",
+        es: "No hay suficientes números fechados de \"{publication}\" para inferir su frecuencia. Se saltó.",
+    }
+    uncertain_frequency(publication: &str) {
+        en: "The frequency of \"{publication}\" is uncertain. Pass --frequency to set it.",
+        es: "La frecuencia de \"{publication}\" es incierta. Use --frequency para fijarla.",
+    }
+    imprecise_dates(count: usize, publication: &str, frequency: &str) {
+        en: "{count} issues of \"{publication}\" are not dated precisely enough to check {frequency} issues.",
+        es: "{count} números de \"{publication}\" no tienen fechas lo bastante precisas para revisar la frecuencia {frequency}.",
+    }
+    no_page_number(name: &str) {
+        en: "No page number found in \"{name}\". Skipped.",
+        es: "No se encontró un número de página en \"{name}\". Se saltó.",
+    }
+    repeated_page(key: &str, page: u32) {
+        en: "\"{key}\" has more than one page {page}. Skipped.",
+        es: "\"{key}\" tiene más de una página {page}. Se saltó.",
+    }
+    duplicate_call_number(call_number: &str, existing: &str, other: &str) {
+        en: "Call Number \"{call_number}\" is used by both \"{existing}\" and \"{other}\". Kept the first.",
+        es: "El Call Number \"{call_number}\" lo usan \"{existing}\" y \"{other}\". Se conservó el primero.",
+    }
+    no_call_number() {
+        en: "no Call Number",
+        es: "no tiene Call Number",
+    }
+    not_a_number(column: &str, value: &str) {
+        en: "{column} \"{value}\" is not a number",
+        es: "{column} \"{value}\" no es un número",
+    }
+    date_problem(title: &str, problem: &str) {
+        en: "\"{title}\": {problem}",
+        es: "\"{title}\": {problem}",
+    }
+    invalid_date_field(field: &str, value: &str) {
+        en: "{field} \"{value}\" is not a valid date.",
+        es: "{field} \"{value}\" no es una fecha válida.",
+    }
+    invalid_date_range(value: &str) {
+        en: "Date Range \"{value}\" is not a valid range.",
+        es: "Date Range \"{value}\" no es un rango válido.",
+    }
+    digitized_before_original(digitized: &str, original: &str) {
+        en: "Date Digitized {digitized} precedes Date Original {original}.",
+        es: "Date Digitized {digitized} es anterior a Date Original {original}.",
+    }
+    original_outside_range(original: &str, range: &str) {
+        en: "Date Original {original} falls outside Date Range {range}.",
+        es: "Date Original {original} queda fuera de Date Range {range}.",
+    }
+    title_date_disagrees(title_date: &str, original: &str) {
+        en: "NODE_TITLE date {title_date} disagrees with Date Original {original}.",
+        es: "La fecha {title_date} del NODE_TITLE no coincide con Date Original {original}.",
+    }
+    no_vocabularies() {
+        en: "No vocabularies are set in the config. Nothing to validate.",
+        es: "No hay vocabularios en la configuración. No hay nada que validar.",
+    }
+    vocabulary_column_missing(column: &str) {
+        en: "Vocabulary column \"{column}\" not found in the target CSV file.",
+        es: "No se encontró la columna de vocabulario \"{column}\" en el archivo CSV de destino.",
+    }
+    invalid_value(row: usize, title: &str, column: &str, value: &str, suggestion: &str) {
+        en: "Row {row} (\"{title}\"): {column} \"{value}\" is not an allowed value.{suggestion}",
+        es: "Fila {row} (\"{title}\"): {column} \"{value}\" no es un valor permitido.{suggestion}",
+    }
+    did_you_mean(suggestion: &str) {
+        en: " Did you mean \"{suggestion}\"?",
+        es: " ¿Quiso decir \"{suggestion}\"?",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn messages_in_each_language() {
+        assert_eq!(warning_count(1), "1 warning");
+        assert_eq!(target_exists("out/a.pdf"), "The target file \"out/a.pdf\" already exists.");
+        TEST_LANG.set(Some(Lang::Es));
+        assert_eq!(warning_count(2), "2 advertencias");
+        assert_eq!(target_exists("out/a.pdf"), "El archivo de destino \"out/a.pdf\" ya existe.");
+        TEST_LANG.set(None);
+    }
+}
@@ -0,0 +1,247 @@
+use chrono::NaiveDate;
+use owo_colors::OwoColorize;
+
+use crate::{
+    coverage::DateSource,
+    csv_writer,
+    date::Date,
+    lookup::LookupError,
+    messages,
+    print_status,
+    print_warn,
+    safely_target_file,
+    summary,
+    warnings::Warning,
+};
+
+/// How often a publication was issued.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Frequency {
+    Weekly,
+    Biweekly,
+    Monthly,
+}
+
+impl Frequency {
+    /// The usual number of days between issues.
+    fn period_days(self) -> f64 {
+        match self {
+            Frequency::Weekly => 7.0,
+            Frequency::Biweekly => 14.0,
+            Frequency::Monthly => 365.25 / 12.0,
+        }
+    }
+
+    /// Returns true if a date is precise enough to check at this frequency.
+    fn can_check(self, date: &Date) -> bool {
+        match self {
+            Frequency::Weekly | Frequency::Biweekly => date.day.is_some(),
+            Frequency::Monthly => date.month.is_some(),
+        }
+    }
+}
+
+/// Returns the dates expected between two consecutive issues at `frequency`.
+/// Gaps are rounded to the nearest whole period, so an issue that came out a few days late is not reported.
+fn expected_between(previous: &Date, next: &Date, frequency: Frequency) -> Vec<Date> {
+    match frequency {
+        Frequency::Weekly | Frequency::Biweekly => {
+            let period = if frequency == Frequency::Weekly { 7 } else { 14 };
+            let (Some(previous), Some(next)) = (NaiveDate::try_from(previous).ok(), NaiveDate::try_from(next).ok()) else {
+                return vec![];
+            };
+            let periods = ((next - previous).num_days() as f64 / period as f64).round() as i64;
+            (1..periods)
+                .map(|k| Date::from(previous + chrono::Duration::days(k * period)))
+                .collect()
+        }
+        Frequency::Monthly => {
+            let (Some(previous_month), Some(next_month)) = (previous.month, next.month) else {
+                return vec![];
+            };
+            let previous = previous.year * 12 + previous_month - 1;
+            let next = next.year * 12 + next_month - 1;
+            (previous + 1..next)
+                .map(|month| Date {
+                    year: month.div_euclid(12),
+                    month: Some(month.rem_euclid(12) + 1),
+                    day: None,
+                    circa: false,
+                })
+                .collect()
+        }
+    }
+}
+
+/// Infers how often a publication was issued from the median gap between its (sorted) issue dates.
+/// Returns the frequency and its confidence: the share of gaps within a quarter period of one period.
+/// Dates without a day count only toward monthly gaps.
+fn infer_frequency(dates: &[Date]) -> Option<(Frequency, f64)> {
+    let days = dates
+        .iter()
+        .filter_map(|date| NaiveDate::try_from(date).ok())
+        .collect::<Vec<_>>();
+    let mut gaps = days
+        .windows(2)
+        .map(|pair| (pair[1] - pair[0]).num_days() as f64)
+        .filter(|gap| *gap > 0.0)
+        .collect::<Vec<_>>();
+    if gaps.is_empty() {
+        // fall back to the months between month-precise dates.
+        let months = dates
+            .iter()
+            .filter_map(|date| Some(date.year * 12 + date.month?))
+            .collect::<Vec<_>>();
+        gaps = months
+            .windows(2)
+            .map(|pair| ((pair[1] - pair[0]) as f64) * Frequency::Monthly.period_days())
+            .filter(|gap| *gap > 0.0)
+            .collect();
+    }
+    if gaps.is_empty() {
+        return None;
+    }
+    gaps.sort_by(|a, b| a.total_cmp(b));
+    let median = gaps[gaps.len() / 2];
+
+    let frequency = [Frequency::Weekly, Frequency::Biweekly, Frequency::Monthly]
+        .into_iter()
+        .min_by(|a, b| {
+            let distance = |frequency: &Frequency| (median / frequency.period_days()).ln().abs();
+            distance(a).total_cmp(&distance(b))
+        })?;
+    let period = frequency.period_days();
+    let regular = gaps
+        .iter()
+        .filter(|gap| (*gap - period).abs() <= period / 4.0)
+        .count();
+    Some((frequency, (regular as f64) / (gaps.len() as f64)))
+}
+
+/// Returns `(expected, previous, next)` for every expected issue date with no issue, given sorted issue dates.
+fn missing_dates(dates: &[&Date], frequency: Frequency) -> Vec<(Date, usize, usize)> {
+    dates
+        .windows(2)
+        .enumerate()
+        .flat_map(|(i, pair)| {
+            expected_between(pair[0], pair[1], frequency)
+                .into_iter()
+                .map(move |expected| (expected, i, i + 1))
+        })
+        .collect()
+}
+
+/// Reports the expected issue dates of each publication that have no issue, and optionally exports them as a CSV of
+/// `publication, expected_date, previous_issue, next_issue`. Returns the number of missing issues.
+/// Without a `frequency`, each publication's frequency is inferred from its issue dates.
+pub fn missing_issues(
+    source: &DateSource,
+    frequency: Option<Frequency>,
+    output: Option<&str>
+) -> Result<usize, LookupError> {
+    let (publications, _) = source.issue_dates()?;
+
+    let mut rows = vec![];
+    for (publication, dates) in &publications {
+        let (frequency, inferred) = match frequency {
+            Some(frequency) => (frequency, String::new()),
+            None => {
+                let Some((frequency, confidence)) = infer_frequency(dates) else {
+                    print_warn!(Warning::Other, "{}", messages::too_few_dated_issues(publication));
+                    continue;
+                };
+                if confidence < 0.5 {
+                    print_warn!(Warning::Other, "{}", messages::uncertain_frequency(publication));
+                }
+                let inferred = format!("looks {:?}, {:.0}% of gaps, ", frequency, confidence * 100.0);
+                (frequency, inferred.to_lowercase())
+            }
+        };
+        let (checked, skipped): (Vec<&Date>, Vec<&Date>) = dates
+            .iter()
+            .partition(|date| frequency.can_check(date));
+        if !skipped.is_empty() {
+            print_warn!(
+                Warning::OddDate,
+                "{}",
+                messages::imprecise_dates(skipped.len(), publication, &format!("{:?}", frequency).to_lowercase())
+            );
+        }
+        let missing = missing_dates(&checked, frequency);
+        let status = if missing.is_empty() {
+            "no missing issues".green().to_string()
+        } else {
+            format!("{} missing issues", missing.len()).red().to_string()
+        };
+        print_status!("{} ({} issues, {}{})", publication.bold(), checked.len(), inferred, status);
+        for (expected, previous, next) in missing {
+            print_status!(
+                "    {} {}",
+                expected,
+                format!("(between {} and {})", checked[previous], checked[next]).dimmed()
+            );
+            rows.push([
+                publication.clone(),
+                expected.to_string(),
+                checked[previous].to_string(),
+                checked[next].to_string(),
+            ]);
+        }
+    }
+    summary::record_count("missing", rows.len());
+
+    if let Some(output) = output {
+        let output = safely_target_file(output);
+        let mut writer = csv_writer(&output);
+        writer
+            .write_record(["publication", "expected_date", "previous_issue", "next_issue"])
+            .expect("Failed to write headers.");
+        for row in &rows {
+            writer.write_record(row).expect("Failed to write record.");
+        }
+        writer.flush().expect("Failed to write missing issues CSV file.");
+        print_status!("Saved {} missing issues to \"{}\".", rows.len(), output);
+        summary::record_output(&output);
+    }
+    Ok(rows.len())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn find_missing_issue_dates() {
+        let dates = ["1944-08-06", "1944-08-13", "1944-09-04", "1944-09-10"].map(|date| {
+            Date::try_from(date).unwrap()
+        });
+        let dates = dates.iter().collect::<Vec<_>>();
+        // 22 days from 08-13 to 09-04 rounds to three weeks, so two issues are missing; 6 days rounds to one week.
+        let missing = missing_dates(&dates, Frequency::Weekly)
+            .into_iter()
+            .map(|(expected, previous, _)| (expected.to_string(), previous))
+            .collect::<Vec<_>>();
+        assert_eq!(missing, vec![("1944-08-20".to_string(), 1), ("1944-08-27".to_string(), 1)]);
+
+        let months = ["1944-11", "1945-02-03"].map(|date| Date::try_from(date).unwrap());
+        let months = months.iter().collect::<Vec<_>>();
+        let missing = missing_dates(&months, Frequency::Monthly)
+            .into_iter()
+            .map(|(expected, _, _)| expected.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(missing, vec!["1944-12", "1945-01"]);
+    }
+
+    #[test]
+    fn infer_frequencies() {
+        let parse = |dates: &[&str]| dates.iter().map(|date| Date::try_from(*date).unwrap()).collect::<Vec<_>>();
+        let weekly = parse(&["1944-08-06", "1944-08-13", "1944-08-20", "1944-09-10", "1944-09-17"]);
+        let (frequency, confidence) = infer_frequency(&weekly).unwrap();
+        assert_eq!(frequency, Frequency::Weekly);
+        assert_eq!(confidence, 0.75);
+
+        let monthly = parse(&["1944-08", "1944-09", "1944-10", "1945-01"]);
+        assert_eq!(infer_frequency(&monthly).map(|(frequency, _)| frequency), Some(Frequency::Monthly));
+        assert_eq!(infer_frequency(&parse(&["1944"])), None);
+    }
+}
@@ -0,0 +1,79 @@
+/// Posts a JSON summary of the run to a webhook: the `--quiet` summary fields, plus `status` (`success` or `failure`),
+/// `error`, and a one-line `text` for chat webhooks such as Slack's.
+/// A failed post is reported on stderr rather than failing the run.
+pub fn notify(url: &str, command: &str, error: Option<&str>) {
+    let mut payload: serde_json::Value = serde_json
+        ::from_str(&crate::summary::summary_json(command))
+        .expect("Failed to read the run summary.");
+    let status = if error.is_none() { "success" } else { "failure" };
+    let duration = payload["elapsed_seconds"].as_f64().unwrap_or_default() as u64;
+    let outputs = payload["outputs"].as_array().map_or(0, Vec::len);
+    let warnings = payload["warnings"].as_array().map_or(0, Vec::len);
+    let text = match error {
+        None =>
+            format!(
+                "bulk_format {} finished in {}m {}s: {} outputs, {} warnings.",
+                command,
+                duration / 60,
+                duration % 60,
+                outputs,
+                warnings
+            ),
+        Some(error) => format!("bulk_format {} failed after {}m {}s: {}", command, duration / 60, duration % 60, error),
+    };
+    payload["status"] = status.into();
+    payload["error"] = error.into();
+    payload["text"] = text.into();
+
+    let result = ureq
+        ::post(url)
+        .header("Content-Type", "application/json")
+        .send(payload.to_string());
+    if let Err(e) = result {
+        eprintln!("Failed to send the completion notification to \"{}\": {}", url, e);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{ Read, Write };
+    use super::*;
+
+    /// Accepts one request, answers it with `200 OK`, and returns its body.
+    fn receive_one(listener: std::net::TcpListener) -> String {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = vec![];
+        let mut buffer = [0; 4096];
+        let body = loop {
+            let read = stream.read(&mut buffer).unwrap();
+            request.extend_from_slice(&buffer[..read]);
+            let text = String::from_utf8_lossy(&request).to_string();
+            let Some((head, body)) = text.split_once("\r\n\r\n") else {
+                assert!(read > 0, "The request ended before its headers.");
+                continue;
+            };
+            let length = head
+                .lines()
+                .find_map(|line| line.to_lowercase().strip_prefix("content-length:").map(|n| n.trim().parse().unwrap()))
+                .unwrap_or(0);
+            if body.len() >= length || read == 0 {
+                break body.to_string();
+            }
+        };
+        stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n").unwrap();
+        body
+    }
+
+    #[test]
+    fn post_failure_summary() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || receive_one(listener));
+        notify(&url, "format", Some("Failed to read lookup table."));
+        let payload: serde_json::Value = serde_json::from_str(&server.join().unwrap()).unwrap();
+        assert_eq!(payload["command"], "format");
+        assert_eq!(payload["status"], "failure");
+        assert_eq!(payload["error"], "Failed to read lookup table.");
+        assert!(payload["text"].as_str().unwrap().starts_with("bulk_format format failed after 0m"));
+    }
+}
@@ -0,0 +1,303 @@
+use std::{ collections::BTreeMap, path::{ Path, PathBuf } };
+use owo_colors::OwoColorize;
+use serde::Deserialize;
+
+use crate::{
+    filesystem::RealFilesystem,
+    issue_data::{ normalize_tn, IssueData },
+    messages,
+    preview,
+    print_status,
+    print_warn,
+    print_warn_ok,
+    summary,
+    warnings::Warning,
+    ConflictResolver,
+};
+use lopdf::{ dictionary, text_string, Dictionary, Document, Object };
+
+/// The document info fields written into a PDF.
+pub struct PdfInfo<'a> {
+    pub title: &'a str,
+    pub subject: &'a str,
+    /// The issue date as `(year, month, day)`; missing parts default to January and the first.
+    pub creation_date: Option<(i32, Option<i32>, Option<i32>)>,
+}
+
+/// Sets the Title, Subject, and CreationDate of a PDF's document info dictionary in place.
+pub fn embed_pdf_info(path: &Path, info: &PdfInfo) -> Result<(), String> {
+    let mut document = Document::load(path).map_err(|e| e.to_string())?;
+
+    let mut fields = Dictionary::new();
+    fields.set("Title", text_string(info.title));
+    fields.set("Subject", text_string(info.subject));
+    if let Some((year, month, day)) = info.creation_date {
+        let date = format!("D:{:04}{:02}{:02}000000", year, month.unwrap_or(1), day.unwrap_or(1));
+        fields.set("CreationDate", Object::string_literal(date));
+    }
+
+    // update the existing info dictionary, or add one to the trailer.
+    let existing = document.trailer
+        .get(b"Info")
+        .ok()
+        .and_then(|info| info.as_reference().ok());
+    match existing {
+        Some(id) => {
+            let dictionary = document
+                .get_object_mut(id)
+                .and_then(|info| info.as_dict_mut())
+                .map_err(|e| e.to_string())?;
+            for (key, value) in fields.into_iter() {
+                dictionary.set(key, value);
+            }
+        }
+        None => {
+            let id = document.add_object(fields);
+            document.trailer.set("Info", id);
+        }
+    }
+
+    document.save(path).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// One row of a split CSV: the pages of a combined PDF that belong to one issue.
+#[derive(Debug, Deserialize)]
+struct SplitRange {
+    tn: String,
+    /// 1-based pages, e.g. `1-4` or `5-8,10`.
+    pages: String,
+    /// Names the output file when the `tn` has no lookup entry.
+    #[serde(default)]
+    title: String,
+}
+
+/// Splits a combined (e.g. reel scan) PDF into one PDF per row of a `tn,pages,title` CSV, named with each issue's formatted title.
+pub fn split_pdf(pdf: &str, ranges: &str, lookup_table: &BTreeMap<String, IssueData>, output: &str) {
+    let document = Document::load(pdf).expect("Failed to read PDF file.");
+    let page_count = document.get_pages().len() as u32;
+    std::fs::create_dir_all(output).expect("Failed to create output directory.");
+
+    let mut reader = csv::Reader::from_path(ranges).expect("Failed to read page range CSV file.");
+    let mut resolver = ConflictResolver::default();
+    for range in reader.deserialize() {
+        let range: SplitRange = range.expect("Failed to parse page range.");
+        let pages = parse_page_range(&range.pages).unwrap_or_else(|e| {
+            panic!("Invalid page range for \"{}\": {}", range.tn, e)
+        });
+        if let Some(page) = pages.iter().find(|page| **page > page_count) {
+            panic!("Page {} of \"{}\" is past the end of the PDF ({} pages).", page, range.tn, page_count);
+        }
+
+        let name = match lookup_table.get(&normalize_tn(&range.tn)) {
+            Some(issue) => issue.formatted_title(),
+            None if !range.title.is_empty() => range.title.clone(),
+            None => range.tn.clone(),
+        };
+        let target = PathBuf::from(output).join(format!("{}.pdf", name));
+        let Some(target) = resolver.resolve(&RealFilesystem, &target) else {
+            print_warn_ok!("Skipping \"{}\".", range.tn);
+            summary::record_count("skipped", 1);
+            continue;
+        };
+
+        print_status!("Writing pages {} to \"{}\"", range.pages, target.to_string_lossy());
+        let mut part = document.clone();
+        let other_pages = (1..=page_count).filter(|page| !pages.contains(page)).collect::<Vec<_>>();
+        part.delete_pages(&other_pages);
+        part.prune_objects();
+        part.save(&target).expect("Failed to write PDF file.");
+        summary::record_output(target.to_string_lossy());
+    }
+}
+
+/// Merges per-page PDFs, e.g. `tn12345_001.pdf, tn12345_002.pdf` or `1944-08-06_p1.pdf`, into one PDF per issue named after
+/// the shared prefix (`tn12345.pdf`), ordered by page number.
+pub fn merge_page_pdfs(files: Vec<PathBuf>, output: &str, dry_run: bool) {
+    let mut issues: BTreeMap<String, Vec<(u32, PathBuf)>> = BTreeMap::new();
+    for file in files {
+        let stem = file.file_stem().expect("Failed to get file name.").to_string_lossy().to_string();
+        match page_key(&stem) {
+            Some((key, page)) => issues.entry(key).or_default().push((page, file)),
+            None => {
+                print_warn!(Warning::SkippedFile, "{}", messages::no_page_number(&stem));
+            }
+        }
+    }
+    if !dry_run {
+        std::fs::create_dir_all(output).expect("Failed to create output directory.");
+    }
+
+    let mut resolver = ConflictResolver::default();
+    for (key, mut pages) in issues {
+        pages.sort();
+        if let Some(window) = pages.windows(2).find(|window| window[0].0 == window[1].0) {
+            print_warn!(Warning::SkippedFile, "{}", messages::repeated_page(&key, window[0].0));
+            continue;
+        }
+        let target = PathBuf::from(output).join(format!("{}.pdf", key));
+        if dry_run {
+            for (_, page) in &pages {
+                preview::print_rename(&page.to_string_lossy(), &target.to_string_lossy());
+            }
+            continue;
+        }
+        let Some(target) = resolver.resolve(&RealFilesystem, &target) else {
+            print_warn_ok!("Skipping \"{}\".", key);
+            summary::record_count("skipped", 1);
+            continue;
+        };
+        print_status!("Merging {} pages into \"{}\"", pages.len(), target.to_string_lossy());
+        let inputs = pages.into_iter().map(|(_, page)| page).collect::<Vec<_>>();
+        merge_pdfs(&inputs, &target).expect("Failed to merge PDF files.");
+        summary::record_output(target.to_string_lossy());
+    }
+}
+
+/// Splits a per-page file stem into its issue prefix and page number: `tn12345_001`, `tn12345_p1`, or `tn12345_page1`.
+fn page_key(stem: &str) -> Option<(String, u32)> {
+    let (key, page) = stem.rsplit_once('_')?;
+    let page = page.to_lowercase();
+    let page = page.strip_prefix("page").or_else(|| page.strip_prefix('p')).unwrap_or(&page);
+    if key.is_empty() || page.is_empty() || !page.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    Some((key.to_string(), page.parse().ok()?))
+}
+
+/// The page attributes a page may inherit from its page tree, which are lost when the page is moved into a new tree.
+const INHERITABLE_PAGE_KEYS: [&[u8]; 4] = [b"Resources", b"MediaBox", b"CropBox", b"Rotate"];
+
+/// Appends the pages of every input PDF, in order, into a new PDF.
+fn merge_pdfs(inputs: &[PathBuf], output: &Path) -> Result<(), String> {
+    let mut merged = Document::with_version("1.5");
+    let pages_id = merged.new_object_id();
+    let mut kids: Vec<Object> = vec![];
+
+    for input in inputs {
+        let mut document = Document::load(input).map_err(|e| e.to_string())?;
+        document.renumber_objects_with(merged.max_id + 1);
+
+        for page_id in document.get_pages().into_values() {
+            let mut page = document.get_dictionary(page_id).map_err(|e| e.to_string())?.clone();
+            for key in INHERITABLE_PAGE_KEYS {
+                if !page.has(key) {
+                    if let Some(value) = inherited_attribute(&document, &page, key) {
+                        page.set(key, value);
+                    }
+                }
+            }
+            page.set("Parent", pages_id);
+            merged.objects.insert(page_id, Object::Dictionary(page));
+            kids.push(page_id.into());
+        }
+        // everything but the old catalog and page tree.
+        for (id, object) in document.objects {
+            let skip = matches!(object.type_name(), Ok(b"Catalog" | b"Pages" | b"Page"));
+            if !skip {
+                merged.objects.entry(id).or_insert(object);
+            }
+        }
+        merged.max_id = merged.objects.keys().map(|id| id.0).max().unwrap_or(merged.max_id);
+    }
+
+    let count = kids.len() as i64;
+    merged.objects.insert(
+        pages_id,
+        Object::Dictionary(dictionary! { "Type" => "Pages", "Kids" => kids, "Count" => count })
+    );
+    let catalog_id = merged.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+    merged.trailer.set("Root", catalog_id);
+    merged.prune_objects();
+    merged.save(output).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Looks up an attribute on the page's ancestors in the page tree.
+fn inherited_attribute(document: &Document, page: &Dictionary, key: &[u8]) -> Option<Object> {
+    let mut parent = page.get(b"Parent").and_then(Object::as_reference).ok();
+    while let Some(id) = parent {
+        let node = document.get_dictionary(id).ok()?;
+        if let Ok(value) = node.get(key) {
+            return Some(value.clone());
+        }
+        parent = node.get(b"Parent").and_then(Object::as_reference).ok();
+    }
+    None
+}
+
+/// Parses 1-based page ranges such as `3`, `1-4`, or `1-4,7,9-10` into a sorted list of pages.
+fn parse_page_range(pages: &str) -> Result<Vec<u32>, String> {
+    let parse = |page: &str| -> Result<u32, String> {
+        match page.trim().parse::<u32>() {
+            Ok(0) | Err(_) => Err(format!("\"{}\" is not a page number.", page.trim())),
+            Ok(page) => Ok(page),
+        }
+    };
+    let mut result = vec![];
+    for part in pages.split(',') {
+        match part.split_once('-') {
+            Some((start, end)) => {
+                let (start, end) = (parse(start)?, parse(end)?);
+                if start > end {
+                    return Err(format!("\"{}\" ends before it starts.", part.trim()));
+                }
+                result.extend(start..=end);
+            }
+            None => result.push(parse(part)?),
+        }
+    }
+    result.sort_unstable();
+    result.dedup();
+    Ok(result)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn embed_document_info() {
+        let dir = crate::test_util::TestDir::new("embed_pdf_info");
+        let path = dir.join("issue.pdf");
+        let mut document = Document::with_version("1.5");
+        let pages = document.new_object_id();
+        let page = document.add_object(dictionary! { "Type" => "Page", "Parent" => pages });
+        document.objects.insert(
+            pages,
+            Object::Dictionary(dictionary! { "Type" => "Pages", "Kids" => vec![page.into()], "Count" => 1 })
+        );
+        let catalog = document.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages });
+        document.trailer.set("Root", catalog);
+        document.save(&path).unwrap();
+
+        let info = PdfInfo { title: "Arizona Sun", subject: "v. 3, no. 12", creation_date: Some((1944, Some(5), None)) };
+        embed_pdf_info(&path, &info).unwrap();
+        let document = Document::load(&path).unwrap();
+        let id = document.trailer.get(b"Info").and_then(Object::as_reference).unwrap();
+        let info = document.get_dictionary(id).unwrap();
+        let text = |key: &[u8]| lopdf::decode_text_string(info.get(key).unwrap()).unwrap();
+        assert_eq!(text(b"Title"), "Arizona Sun");
+        assert_eq!(text(b"Subject"), "v. 3, no. 12");
+        assert_eq!(info.get(b"CreationDate").and_then(Object::as_str).unwrap(), b"D:19440501000000");
+    }
+
+    #[test]
+    fn parse_page_ranges() {
+        assert_eq!(parse_page_range("3"), Ok(vec![3]));
+        assert_eq!(parse_page_range("1-4"), Ok(vec![1, 2, 3, 4]));
+        assert_eq!(parse_page_range("9-10, 7,1-2"), Ok(vec![1, 2, 7, 9, 10]));
+        assert!(parse_page_range("0-2").is_err());
+        assert!(parse_page_range("4-1").is_err());
+        assert!(parse_page_range("a").is_err());
+    }
+
+    #[test]
+    fn parse_page_keys() {
+        assert_eq!(page_key("tn12345_001"), Some(("tn12345".to_string(), 1)));
+        assert_eq!(page_key("1944-08-06_p12"), Some(("1944-08-06".to_string(), 12)));
+        assert_eq!(page_key("Arizona_Sun_1944-08-06_Page3"), Some(("Arizona_Sun_1944-08-06".to_string(), 3)));
+        assert_eq!(page_key("Arizona_Sun_1944-08-06"), None);
+        assert_eq!(page_key("tn12345"), None);
+    }
+}
@@ -0,0 +1,212 @@
+use std::{ collections::BTreeMap, path::PathBuf };
+
+use crate::{
+    archive_record::{ ArchiveRecord, ColumnOptions },
+    csv_reader,
+    csv_writer,
+    derived_path,
+    filesystem::Filesystem,
+    format::{ copy_and_rename_files, FormatOptions },
+    issue_data::IssueData,
+    links::{ self, link_records },
+    populate::{ check_date_digitized_format, populate_records, PopulateOptions },
+    print_status,
+    safely_target_file,
+    summary,
+    STDIO,
+};
+
+/// Target CSV records passed from step to step in memory, so chained steps write only their final CSV.
+///
+/// ```no_run
+/// use bulk_format::{
+///     archive_record::ColumnOptions,
+///     collect::{ collect_files, FileFilters },
+///     filesystem::RealFilesystem,
+///     format::FormatOptions,
+///     lookup::parse_lookup_table,
+///     pipeline::Pipeline,
+///     populate::{ invert_lookup_table, PopulateOptions },
+/// };
+///
+/// let lookup_table = parse_lookup_table("lookup.csv")?;
+/// let files = collect_files(&RealFilesystem, "scans", &["tif".to_string()], false, &FileFilters::default());
+/// let linked = Pipeline::read("records.csv")
+///     .format(&RealFilesystem, &["scans".to_string()], files, &lookup_table, &FormatOptions::default())
+///     .populate(&invert_lookup_table(lookup_table), &PopulateOptions::default())
+///     .link(None, None, &ColumnOptions::default())
+///     .write(); // records_populated_linked.csv
+/// # Ok::<(), bulk_format::lookup::LookupError>(())
+/// ```
+#[derive(Debug)]
+pub struct Pipeline {
+    headers: csv::StringRecord,
+    records: Vec<ArchiveRecord>,
+    /// The CSV the steps so far would have written, e.g. `records_populated_linked.csv`.
+    output: String,
+}
+
+impl Pipeline {
+    /// Reads the records of a target CSV file (or stdin, if `target` is `-`).
+    pub fn read(target: &str) -> Self {
+        let mut reader = csv_reader(target);
+        let headers = reader.headers().expect("Failed to read target CSV headers.").clone();
+        let records = reader
+            .deserialize()
+            .map(|result| result.expect("Failed to parse record."))
+            .collect();
+        Pipeline { headers, records, output: target.to_string() }
+    }
+
+    /// Copies and renames the files of `directories` by the lookup table, like `format`. The records are unchanged, so
+    /// the same lookup table can go on to populate them. Panics if an upload to a remote output fails, as a failed copy
+    /// does.
+    pub fn format(
+        self,
+        fs: &dyn Filesystem,
+        directories: &[String],
+        files: Vec<PathBuf>,
+        lookup_table: &BTreeMap<String, IssueData>,
+        options: &FormatOptions
+    ) -> Self {
+        assert!(copy_and_rename_files(fs, directories, files, lookup_table, options), "Failed to upload every file.");
+        self
+    }
+
+    /// Populates the records from the issue data matching their node titles, like `populate`.
+    pub fn populate(mut self, inverse_lookup_table: &BTreeMap<String, IssueData>, options: &PopulateOptions) -> Self {
+        check_date_digitized_format(options);
+        self.headers = options.columns.project(&self.headers);
+        populate_records(&mut self.records, inverse_lookup_table, options);
+        self.output = self.output_for("_populated");
+        self
+    }
+
+    /// Links the records to their neighbours by node title (or `link_value_column`), like `link-issues`.
+    pub fn link(mut self, link_value_column: Option<&str>, graph: Option<&str>, columns: &ColumnOptions) -> Self {
+        let link_values = match link_value_column {
+            Some(column) => {
+                assert!(
+                    self.headers.iter().any(|header| header == column),
+                    "Column \"{}\" not found in the target CSV file.",
+                    column
+                );
+                let column = csv::StringRecord::from(vec![column]);
+                self.records
+                    .iter()
+                    .map(|record| record.to_row(&column)[0].to_string())
+                    .collect::<Vec<_>>()
+            }
+            None => self.records.iter().map(|record| record.node_title.clone()).collect(),
+        };
+        self.headers = columns.project(&self.headers);
+        link_records(&mut self.records, &link_values);
+        if let Some(graph) = graph {
+            links::write_graph(&self.records, &link_values, graph);
+        }
+        self.output = self.output_for("_linked");
+        self
+    }
+
+    /// Writes the records to the CSV the last step would have written, and returns its path.
+    pub fn write(self) -> String {
+        let output = if self.output == STDIO { STDIO.to_string() } else { safely_target_file(&self.output) };
+        let mut writer = csv_writer(&output);
+        writer.write_record(&self.headers).expect("Failed to write headers.");
+        for record in &self.records {
+            writer.write_record(&record.to_row(&self.headers)).expect("Failed to write record.");
+        }
+        writer.flush().expect("Failed to write target CSV file.");
+        print_status!("Saved {} records to \"{}\".", self.records.len(), output);
+        if output != STDIO {
+            summary::record_output(&output);
+        }
+        output
+    }
+
+    fn output_for(&self, suffix: &str) -> String {
+        if self.output == STDIO { STDIO.to_string() } else { derived_path(&self.output, suffix, "csv") }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{ filesystem::MemoryFilesystem, populate::invert_lookup_table, test_util::TestDir };
+
+    #[test]
+    fn chain_steps_without_intermediate_csvs() {
+        let dir = TestDir::new("pipeline_chain");
+        let lookup_table = || {
+            [("tn1", "Arizona Sun. v. 3, no. 12 (May 12, 1944)"), ("tn2", "Arizona Sun. v. 3, no. 13 (May 19, 1944)")]
+                .map(|(tn, title)| (tn.to_string(), IssueData::new(tn.to_string(), title.to_string(), String::new())))
+                .into_iter()
+                .collect::<BTreeMap<_, _>>()
+        };
+        let inverse_lookup_table = invert_lookup_table(lookup_table());
+        let headers = csv::StringRecord::from(vec!["NODE_TITLE", "Previous Issue", "Next Issue", "Volume", "Issue"]);
+        let mut contents = csv::Writer::from_writer(vec![]);
+        contents.write_record(&headers).unwrap();
+        for title in inverse_lookup_table.keys() {
+            contents.write_record(&ArchiveRecord { node_title: title.clone(), ..Default::default() }.to_row(&headers)).unwrap();
+        }
+        let target = dir.write("records.csv", contents.into_inner().unwrap());
+
+        let fs = MemoryFilesystem::new();
+        fs.add_file("scans/tn1.pdf", 10);
+        fs.add_file("scans/tn2.pdf", 10);
+        let options = FormatOptions { output: Some("out".to_string()), ..Default::default() };
+        let output = Pipeline::read(&target.to_string_lossy())
+            .format(&fs, &[], vec![PathBuf::from("scans/tn1.pdf"), PathBuf::from("scans/tn2.pdf")], &lookup_table(), &options)
+            .populate(&inverse_lookup_table, &PopulateOptions::default())
+            .link(None, None, &ColumnOptions::default())
+            .write();
+
+        assert!(fs.exists(std::path::Path::new("out/Arizona_Sun_1944-05-19.pdf")));
+        assert_eq!(PathBuf::from(&output), dir.join("records_populated_linked.csv"));
+        assert!(!dir.join("records_populated.csv").exists());
+        let records = csv_reader(&output)
+            .deserialize::<ArchiveRecord>()
+            .map(Result::unwrap)
+            .collect::<Vec<_>>();
+        let titles = inverse_lookup_table.keys().cloned().collect::<Vec<_>>();
+        assert_eq!(records.iter().map(|record| record.issue.as_str()).collect::<Vec<_>>(), ["12", "13"]);
+        assert_eq!(records[0].next_issue, titles[1]);
+        assert_eq!(records[1].previous_issue, titles[0]);
+    }
+
+    #[test]
+    fn link_by_column() {
+        let dir = TestDir::new("pipeline_link_column");
+        let target = dir.write("records.csv", "NODE_TITLE,Local ID,Previous Issue,Next Issue\na,A-1,,\nb,A-2,,\n");
+        let target = target.to_string_lossy();
+        let output = Pipeline::read(&target).link(Some("Local ID"), None, &ColumnOptions::default()).write();
+        let records = csv_reader(&output)
+            .deserialize::<ArchiveRecord>()
+            .map(Result::unwrap)
+            .collect::<Vec<_>>();
+        assert_eq!((records[0].next_issue.as_str(), records[1].previous_issue.as_str()), ("A-2", "A-1"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Column \"Call Number\" not found")]
+    fn link_by_missing_column() {
+        let dir = TestDir::new("pipeline_missing_column");
+        let target = dir.write("records.csv", "NODE_TITLE,Previous Issue,Next Issue\na,,\n");
+        Pipeline::read(&target.to_string_lossy()).link(Some("Call Number"), None, &ColumnOptions::default());
+    }
+
+    #[test]
+    fn stdin_pipelines_write_to_stdout() {
+        let headers = csv::StringRecord::from(vec!["NODE_TITLE", "Previous Issue", "Next Issue"]);
+        let pipeline = Pipeline { headers, records: vec![], output: STDIO.to_string() }
+            .populate(&BTreeMap::new(), &PopulateOptions::default())
+            .link(None, None, &ColumnOptions::default());
+        assert_eq!(pipeline.output, STDIO);
+        let pipeline = Pipeline { output: "exports/records.csv".to_string(), ..pipeline }.populate(
+            &BTreeMap::new(),
+            &PopulateOptions::default()
+        );
+        assert_eq!(pipeline.output, "exports/records_populated.csv");
+    }
+}
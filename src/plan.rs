@@ -0,0 +1,287 @@
+use serde::Deserialize;
+use owo_colors::OwoColorize;
+
+use crate::{
+    archive_record::ColumnOptions,
+    collect::{ collect_files, FileFilters },
+    compare::{ compare_tables, parse_generated_names, CompareOptions },
+    config,
+    filesystem::RealFilesystem,
+    format::{ copy_and_rename_files, FormatOptions },
+    links::link_issues,
+    lookup::parse_lookup_table,
+    pipeline::Pipeline,
+    populate::{ invert_lookup_table, populate_csv, PopulateOptions },
+    print_error,
+    print_status,
+};
+
+/// A declarative sequence of steps, read from a YAML plan file.
+///
+/// Example:
+/// ```yaml
+/// lookup: lookup.csv
+/// steps:
+///   - step: format
+///     dir: scans
+///     output: formatted
+///   - step: populate
+///     target: records.csv
+///   - step: link-issues
+///   - step: compare
+///     generated: generated.csv
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct Plan {
+    /// The lookup CSV shared by every step that does not set its own.
+    pub lookup: Option<String>,
+
+    /// Pass records from `populate` to `link-issues` in memory, writing only the last step's CSV
+    /// instead of an intermediate CSV per step.
+    #[serde(default, rename = "in-memory")]
+    pub in_memory: bool,
+
+    pub steps: Vec<Step>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "step", rename_all = "kebab-case")]
+pub enum Step {
+    Format {
+        lookup: Option<String>,
+        #[serde(rename = "dir")]
+        directory: String,
+        #[serde(rename = "ext", default = "default_extensions")]
+        extensions: Vec<String>,
+        #[serde(default)]
+        recursive: bool,
+        #[serde(flatten)]
+        filters: FileFilters,
+        #[serde(flatten)]
+        options: Box<FormatOptions>,
+    },
+    Populate {
+        /// Defaults to the CSV written by the previous step.
+        target: Option<String>,
+        lookup: Option<String>,
+        #[serde(flatten)]
+        options: PopulateOptions,
+    },
+    LinkIssues {
+        /// Defaults to the CSV written by the previous step.
+        target: Option<String>,
+        link_value_column: Option<String>,
+        graph: Option<String>,
+        #[serde(flatten)]
+        columns: ColumnOptions,
+    },
+    Compare {
+        lookup: Option<String>,
+        generated: String,
+        #[serde(flatten)]
+        options: CompareOptions,
+    },
+}
+
+fn default_extensions() -> Vec<String> {
+    config::extensions_or_default(&[])
+}
+
+impl Step {
+    fn name(&self) -> &'static str {
+        match self {
+            Step::Format { .. } => "format",
+            Step::Populate { .. } => "populate",
+            Step::LinkIssues { .. } => "link-issues",
+            Step::Compare { .. } => "compare",
+        }
+    }
+}
+
+pub fn parse_plan(path: &str) -> Plan {
+    let file = std::fs::File::open(path).expect("Failed to read plan file.");
+    serde_yaml::from_reader(file).expect("Failed to parse plan file.")
+}
+
+/// Runs every step of the plan in order, feeding the CSV written by one step into the next step that needs a target.
+/// Returns false if a step failed (e.g. a comparison below `--min-coverage`, a failed upload, or a lookup row that cannot be parsed) or
+/// the user declined to overwrite its output, either of which stops the plan.
+pub fn run_plan(path: &str) -> bool {
+    let plan = parse_plan(path);
+    let shared_lookup = plan.lookup.as_deref();
+    let lookup_for = |lookup: &Option<String>, step: &str| -> String {
+        lookup
+            .as_deref()
+            .or(shared_lookup)
+            .unwrap_or_else(|| panic!("The \"{}\" step requires a lookup table.", step))
+            .to_string()
+    };
+    // the step's lookup table, or nothing if a row cannot be parsed, which stops the plan.
+    let read_lookup = |lookup: &Option<String>, step: &Step| {
+        parse_lookup_table(&lookup_for(lookup, step.name()))
+            .inspect_err(|e| print_error!("{}", e))
+            .ok()
+    };
+
+    // The most recent CSV written by a step.
+    let mut last_csv: Option<String> = None;
+    // The records of the last in-memory step, not yet written.
+    let mut pipeline: Option<Pipeline> = None;
+    let total = plan.steps.len();
+    // The step the plan stopped at, if one failed or was declined.
+    let mut stopped_at = None;
+
+    for (i, step) in plan.steps.iter().enumerate() {
+        print_status!("{} {}", format!("[{}/{}]", i + 1, total).cyan().bold(), step.name().bold());
+
+        let target_for = |target: &Option<String>, last_csv: &Option<String>| -> String {
+            target
+                .clone()
+                .or_else(|| last_csv.clone())
+                .unwrap_or_else(|| {
+                    panic!(
+                        "The \"{}\" step requires a target, and no previous step produced one.",
+                        step.name()
+                    )
+                })
+        };
+
+        match step {
+            Step::Format { lookup, directory, extensions, recursive, filters, options } => {
+                let files = collect_files(&RealFilesystem, directory, extensions, *recursive, filters);
+                let Some(lookup_table) = read_lookup(lookup, step) else {
+                    stopped_at = Some((i, step));
+                    break;
+                };
+                let directories = std::slice::from_ref(directory);
+                if !copy_and_rename_files(&RealFilesystem, directories, files, &lookup_table, options) {
+                    stopped_at = Some((i, step));
+                    break;
+                }
+            }
+            Step::Populate { target, lookup, options } if plan.in_memory => {
+                let Some(lookup_table) = read_lookup(lookup, step) else {
+                    stopped_at = Some((i, step));
+                    break;
+                };
+                let records = take_records(&mut pipeline, target, &mut last_csv, step);
+                pipeline = Some(records.populate(&invert_lookup_table(lookup_table), options));
+            }
+            Step::LinkIssues { target, link_value_column, graph, columns } if plan.in_memory => {
+                let records = take_records(&mut pipeline, target, &mut last_csv, step);
+                pipeline = Some(records.link(link_value_column.as_deref(), graph.as_deref(), columns));
+            }
+            Step::Populate { target, lookup, options } => {
+                let target = target_for(target, &last_csv);
+                let Some(lookup_table) = read_lookup(lookup, step) else {
+                    stopped_at = Some((i, step));
+                    break;
+                };
+                match
+                    populate_csv(&target, invert_lookup_table(lookup_table), false, options).expect(
+                        "Failed to populate target CSV file."
+                    )
+                {
+                    Some(populated) => {
+                        last_csv = Some(populated);
+                    }
+                    None => {
+                        // The user declined to overwrite; stop rather than link a stale file.
+                        stopped_at = Some((i, step));
+                        break;
+                    }
+                }
+            }
+            Step::LinkIssues { target, link_value_column, graph, columns } => {
+                let target = target_for(target, &last_csv);
+                last_csv = Some(
+                    link_issues(&target, link_value_column.as_deref(), graph.as_deref(), columns)
+                );
+            }
+            Step::Compare { lookup, generated, options } => {
+                let Some(lookup_table) = read_lookup(lookup, step) else {
+                    stopped_at = Some((i, step));
+                    break;
+                };
+                let generated_names = parse_generated_names(generated);
+                if !compare_tables(lookup_table, generated_names, options) {
+                    stopped_at = Some((i, step));
+                    break;
+                }
+            }
+        }
+    }
+    if let Some(records) = pipeline {
+        records.write();
+    }
+    if let Some((i, step)) = stopped_at {
+        print_status!("{}", format!("The plan stopped at step {}: {}.", i + 1, step.name()).red().bold());
+    }
+    stopped_at.is_none()
+}
+
+/// Returns the records an in-memory step works on: the previous step's, unless the step sets its own target.
+/// Records left by an earlier step are written out before a new target is read.
+fn take_records(
+    pipeline: &mut Option<Pipeline>,
+    target: &Option<String>,
+    last_csv: &mut Option<String>,
+    step: &Step
+) -> Pipeline {
+    if target.is_none() {
+        if let Some(records) = pipeline.take() {
+            return records;
+        }
+    }
+    if let Some(records) = pipeline.take() {
+        *last_csv = Some(records.write());
+    }
+    let target = target
+        .clone()
+        .or_else(|| last_csv.clone())
+        .unwrap_or_else(|| {
+            panic!("The \"{}\" step requires a target, and no previous step produced one.", step.name())
+        });
+    Pipeline::read(&target)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_steps() {
+        let plan: Plan = serde_yaml
+            ::from_str(
+                r#"
+lookup: lookup.csv
+steps:
+  - step: format
+    dir: scans
+    recursive: true
+    output: formatted
+    preserve-metadata: true
+  - step: populate
+    target: records.csv
+  - step: link-issues
+  - step: compare
+    generated: generated.csv
+"#
+            )
+            .unwrap();
+        assert_eq!(plan.lookup.as_deref(), Some("lookup.csv"));
+        assert!(!plan.in_memory);
+        assert_eq!(plan.steps.len(), 4);
+        match &plan.steps[0] {
+            Step::Format { directory, extensions, recursive, options, .. } => {
+                assert_eq!(directory, "scans");
+                assert_eq!(extensions, &["pdf"]);
+                assert!(*recursive);
+                assert_eq!(options.output.as_deref(), Some("formatted"));
+                assert!(options.preserve_metadata);
+            }
+            _ => panic!("Expected a format step."),
+        }
+        assert!(matches!(plan.steps[2], Step::LinkIssues { target: None, .. }));
+    }
+}
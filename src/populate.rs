@@ -0,0 +1,295 @@
+use std::collections::BTreeMap;
+use owo_colors::OwoColorize;
+
+use crate::{
+    archive_record::{ ArchiveRecord, ColumnOptions },
+    collect::Selection,
+    config::{ config, CollectionDefaults },
+    coverage,
+    csv_reader,
+    csv_writer,
+    derived_path,
+    filesystem::RealFilesystem,
+    hooks,
+    issue_data::IssueData,
+    messages,
+    preview,
+    print_status,
+    print_warn,
+    print_warn_ok,
+    prompt_bool,
+    state,
+    summary,
+    trash_file,
+    warnings::Warning,
+    STDIO,
+};
+
+/// Written to the Admin Notes of populated records whose issue has no date.
+const UNDATED_NOTE: &str = "Undated issue (n.d.)";
+
+/// Re-keys a lookup table by each issue's record title (`title, date`).
+pub fn invert_lookup_table(lookup_table: BTreeMap<String, IssueData>) -> BTreeMap<String, IssueData> {
+    lookup_table
+        .into_values()
+        .map(|v| (v.record_title(), v))
+        .collect()
+}
+
+#[derive(clap::Args, Debug, Default, serde::Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct PopulateOptions {
+    /// A chrono format for Date Digitized, e.g. `%Y-%m-%d`. By default, the lookup's date loaded is copied as-is.
+    #[arg(long)]
+    pub date_digitized_format: Option<String>,
+
+    /// Write the span of each record's publication in the lookup table into Date Range, e.g. `1944/1952`.
+    #[arg(long)]
+    pub date_range: bool,
+
+    /// Print the changed fields of each modified record, e.g. `Volume: "" -> "9"`, to spot a wrong lookup match.
+    #[arg(long)]
+    pub show_diff: bool,
+
+    #[command(flatten)]
+    #[serde(flatten)]
+    pub selection: Selection,
+
+    #[command(flatten)]
+    #[serde(flatten)]
+    pub columns: ColumnOptions,
+}
+
+/// Returns the path of the populated CSV file, or `None` if the user declined to overwrite it or `dry_run` is set.
+pub fn populate_csv(
+    target: &str,
+    inverse_lookup_table: BTreeMap<String, IssueData>,
+    dry_run: bool,
+    options: &PopulateOptions
+) -> Result<Option<String>, csv::Error> {
+    check_date_digitized_format(options);
+
+    let mut reader = csv_reader(target);
+    let headers = reader.headers()?.clone();
+    let output_headers = options.columns.project(&headers);
+    let target = if target == STDIO { STDIO.to_string() } else { derived_path(target, "_populated", "csv") };
+
+    if dry_run {
+        let date_ranges = publication_date_ranges(&inverse_lookup_table, options);
+        let records = reader.deserialize().collect::<Result<Vec<ArchiveRecord>, _>>()?;
+        for record in options.selection.select(records, "records", |record| record.node_title.clone()) {
+            let mut populated = record.clone();
+            populate_record(&mut populated, &inverse_lookup_table, &date_ranges, options);
+            if !print_record_changes(&record, &populated) {
+                preview::print_unchanged(&record.node_title, "no changes");
+            }
+        }
+        return Ok(None);
+    }
+
+    // if the target file already exists, prompt the user if they want to overwrite it.
+    if target != STDIO && std::path::Path::new(&target).exists() {
+        let should_overwrite = prompt_bool(&messages::overwrite_prompt(&target));
+        if !should_overwrite {
+            print_warn_ok!("{}", messages::exiting_without_overwriting());
+            return Ok(None);
+        }
+        trash_file(&RealFilesystem, std::path::Path::new(&target));
+    }
+
+    let mut writer = csv_writer(&target);
+    writer.write_record(&output_headers)?;
+
+    let records = reader.deserialize().collect::<Result<Vec<ArchiveRecord>, _>>()?;
+    let mut records = options.selection.select(records, "records", |record| record.node_title.clone());
+    let originals = if options.show_diff { records.clone() } else { Vec::new() };
+    populate_records(&mut records, &inverse_lookup_table, options);
+    for (original, record) in originals.iter().zip(&records) {
+        print_record_changes(original, record);
+    }
+    for record in &records {
+        writer.write_record(&record.to_row(&output_headers))?;
+    }
+    writer.flush()?;
+
+    if target != STDIO {
+        summary::record_output(&target);
+        hooks::after_populate(&target);
+    }
+    Ok(Some(target).filter(|target| target != STDIO))
+}
+
+/// Prints a record's title and each field that populating it changed. Returns false if nothing changed.
+fn print_record_changes(record: &ArchiveRecord, populated: &ArchiveRecord) -> bool {
+    let changes = record.changes(populated);
+    if changes.is_empty() {
+        return false;
+    }
+    print_status!("{}", record.node_title);
+    for (column, old, new) in changes {
+        preview::print_field_change(&column, &old, &new);
+    }
+    true
+}
+
+/// Panics if the `--date-digitized-format` is not a valid chrono format.
+pub fn check_date_digitized_format(options: &PopulateOptions) {
+    if let Some(format) = &options.date_digitized_format {
+        let is_valid = chrono::format::StrftimeItems
+            ::new(format)
+            .all(|item| !matches!(item, chrono::format::Item::Error));
+        assert!(is_valid, "Invalid Date Digitized format \"{}\".", format);
+    }
+}
+
+/// Returns the `Date Range` of each publication in the lookup table if `--date-range` is set, or nothing.
+fn publication_date_ranges(
+    inverse_lookup_table: &BTreeMap<String, IssueData>,
+    options: &PopulateOptions
+) -> BTreeMap<String, String> {
+    if options.date_range {
+        coverage::publication_date_ranges(inverse_lookup_table.values())
+    } else {
+        BTreeMap::new()
+    }
+}
+
+/// Populates every record in memory (see [`populate_record`]) and records the matched tns as populated.
+pub fn populate_records(
+    records: &mut [ArchiveRecord],
+    inverse_lookup_table: &BTreeMap<String, IssueData>,
+    options: &PopulateOptions
+) {
+    let date_ranges = publication_date_ranges(inverse_lookup_table, options);
+    let mut populated = vec![];
+    for record in records {
+        if let Some(issue) = inverse_lookup_table.get(&record.node_title) {
+            populated.push((issue.tn.clone(), String::new()));
+        }
+        populate_record(record, inverse_lookup_table, &date_ranges, options);
+    }
+    state::mark_tns(state::Stage::Populated, &populated);
+}
+
+/// Fills in a record's volume, issue, and date digitized (and date range, from `date_ranges` by publication) from the
+/// issue data matching its node title, and any blank collection fields from the config's `collection-defaults`.
+fn populate_record(
+    record: &mut ArchiveRecord,
+    inverse_lookup_table: &BTreeMap<String, IssueData>,
+    date_ranges: &BTreeMap<String, String>,
+    options: &PopulateOptions
+) {
+    if let Some(issue) = inverse_lookup_table.get(&record.node_title) {
+        record.date_digitized = match (issue.parsed_date_loaded(), options.date_digitized_format.as_deref()) {
+            (Some(date_loaded), Some(format)) => date_loaded.format(format).to_string(),
+            (Some(_), None) => issue.date_loaded.to_string(),
+            (None, _) => {
+                if !issue.date_loaded.trim().is_empty() {
+                    print_warn!(
+                        Warning::OddDate,
+                        "{}",
+                        messages::invalid_date_loaded(&issue.date_loaded, &record.node_title)
+                    );
+                }
+                issue.date_loaded.to_string()
+            }
+        };
+        if let Some(volume) = issue.volume {
+            record.volume = volume.to_string();
+        }
+        if let Some(issue) = issue.issue {
+            record.issue = issue.to_string();
+        }
+        if let Some(date_range) = date_ranges.get(&issue.title.replace("_", " ")) {
+            record.date_range = date_range.clone();
+        }
+        if issue.is_undated() {
+            print_warn!(Warning::UndatedIssue, "{}", messages::undated_record(&record.node_title));
+            // a record populated before already has the note.
+            if !record.admin_notes.contains(UNDATED_NOTE) {
+                if !record.admin_notes.is_empty() {
+                    record.admin_notes.push_str("; ");
+                }
+                record.admin_notes.push_str(UNDATED_NOTE);
+            }
+        }
+    } else {
+        print_warn!(Warning::UnmatchedRecord, "{}", messages::no_issue_data(&record.node_title));
+    }
+    fill_collection_defaults(record, &config().collection_defaults);
+}
+
+/// Fills the blank collection fields of a record from `defaults`.
+fn fill_collection_defaults(record: &mut ArchiveRecord, defaults: &CollectionDefaults) {
+    for (field, default) in [
+        (&mut record.part_of, &defaults.part_of),
+        (&mut record.contributing_institution, &defaults.contributing_institution),
+        (&mut record.rights_statement, &defaults.rights_statement),
+        (&mut record.collection, &defaults.collection),
+    ] {
+        if let Some(default) = default {
+            if field.trim().is_empty() {
+                *field = default.clone();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn fill_blank_collection_fields() {
+        let config: crate::config::Config = serde_yaml
+            ::from_str("collection-defaults:\n  part-of: Arizona Sun\n  collection: Newspapers\n")
+            .unwrap();
+        let mut record = ArchiveRecord {
+            part_of: " ".to_string(),
+            collection: "Labor Newspapers".to_string(),
+            ..Default::default()
+        };
+        fill_collection_defaults(&mut record, &config.collection_defaults);
+        assert_eq!(record.part_of, "Arizona Sun");
+        assert_eq!(record.collection, "Labor Newspapers");
+        assert_eq!(record.rights_statement, "");
+    }
+
+    #[test]
+    fn show_diff_and_still_write_populated_records() {
+        let dir = crate::test_util::TestDir::new("populate_show_diff");
+        let issue = IssueData::new("tn1".to_string(), "Arizona Sun. v. 3, no. 12 (May 12, 1944)".to_string(), String::new());
+        let title = issue.record_title();
+        let target = dir.write("records.csv", format!("NODE_TITLE,Volume,Issue\n\"{}\",,\nUnknown,,\n", title));
+        let lookup_table = invert_lookup_table(BTreeMap::from([("tn1".to_string(), issue)]));
+        let options = PopulateOptions { show_diff: true, ..Default::default() };
+        let output = populate_csv(&target.to_string_lossy(), lookup_table, false, &options).unwrap().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(output).unwrap(),
+            format!("NODE_TITLE,Volume,Issue\n\"{}\",3,12\nUnknown,,\n", title)
+        );
+
+        let record = ArchiveRecord { node_title: title, ..Default::default() };
+        assert!(print_record_changes(&record, &ArchiveRecord { volume: "3".to_string(), ..record.clone() }));
+        assert!(!print_record_changes(&record, &record));
+    }
+
+    #[test]
+    fn note_undated_issues_once() {
+        let issue = IssueData::new(
+            "tn1".to_string(),
+            "Arizona Sun. v. 3, no. 12 (n.d.)".to_string(),
+            String::new()
+        );
+        let lookup_table = invert_lookup_table(BTreeMap::from([("tn1".to_string(), issue)]));
+        let mut record = ArchiveRecord {
+            node_title: lookup_table.keys().next().unwrap().clone(),
+            admin_notes: "Scanned from microfilm".to_string(),
+            ..Default::default()
+        };
+        for _ in 0..2 {
+            populate_record(&mut record, &lookup_table, &BTreeMap::new(), &PopulateOptions::default());
+        }
+        assert_eq!(record.admin_notes, "Scanned from microfilm; Undated issue (n.d.)");
+    }
+}
@@ -0,0 +1,26 @@
+use owo_colors::OwoColorize;
+
+use crate::{ print_status, summary };
+
+/// Prints a colored `old -> new` line for a planned rename or move.
+pub fn print_rename(from: &str, to: &str) {
+    print_status!("{} {} {}", from.red(), "->".dimmed(), to.green());
+    summary::record_planned(to);
+}
+
+/// Prints a colored `field: "old" -> "new"` line for a changed value.
+pub fn print_field_change(field: &str, old: &str, new: &str) {
+    print_status!(
+        "    {}: {} {} {}",
+        field.bold(),
+        format!("{:?}", old).red(),
+        "->".dimmed(),
+        format!("{:?}", new).green()
+    );
+}
+
+/// Prints a dimmed line for an input that will be left untouched.
+pub fn print_unchanged(name: &str, reason: &str) {
+    print_status!("{} {}", name.dimmed(), format!("({})", reason).dimmed().italic());
+    summary::record_count("unchanged", 1);
+}
@@ -0,0 +1,250 @@
+use std::cmp::Ordering;
+use owo_colors::OwoColorize;
+
+use crate::{ csv_reader, csv_writer, filter::{ self, Expr }, print_status, safely_target_file, summary, STDIO };
+
+/// A parsed `SELECT ... [FROM ...] [WHERE ...] [ORDER BY ...] [LIMIT n]` query.
+#[derive(Debug)]
+pub struct Query {
+    /// The selected columns, or empty for `*`.
+    pub columns: Vec<String>,
+    pub predicate: Option<Expr>,
+    /// `(column, descending)` pairs, in priority order.
+    pub order_by: Vec<(String, bool)>,
+    pub limit: Option<usize>,
+}
+
+const CLAUSES: [&str; 5] = ["SELECT", "FROM", "WHERE", "ORDER BY", "LIMIT"];
+
+/// Splits a query into its clauses, e.g. `[("SELECT", "a, b"), ("WHERE", "a = 1")]`.
+/// Keywords inside quotes or backticks are not clause boundaries.
+fn split_clauses(sql: &str) -> Result<Vec<(&'static str, String)>, String> {
+    let mut clauses: Vec<(&'static str, String)> = vec![];
+    let mut quote = None;
+    let mut i = 0;
+    while i < sql.len() {
+        let rest = &sql[i..];
+        let c = rest.chars().next().expect("Failed to read query character.");
+        match quote {
+            Some(q) if c == q => {
+                quote = None;
+            }
+            Some(_) => {}
+            None if matches!(c, '"' | '\'' | '`') => {
+                quote = Some(c);
+            }
+            None => {
+                let at_boundary = sql[..i].chars().last().is_none_or(|prev| prev.is_whitespace());
+                let keyword = CLAUSES.iter().find(|keyword| {
+                    rest.get(..keyword.len()).is_some_and(|word| word.eq_ignore_ascii_case(keyword)) &&
+                        rest[keyword.len()..].chars().next().is_none_or(|next| next.is_whitespace())
+                });
+                if let (true, Some(keyword)) = (at_boundary, keyword) {
+                    if clauses.iter().any(|(existing, _)| existing == keyword) {
+                        return Err(format!("{} appears more than once.", keyword));
+                    }
+                    clauses.push((keyword, String::new()));
+                    i += keyword.len();
+                    continue;
+                }
+            }
+        }
+        match clauses.last_mut() {
+            Some((_, text)) => text.push(c),
+            None if c.is_whitespace() => {}
+            None => {
+                return Err("A query must start with SELECT.".to_string());
+            }
+        }
+        i += c.len_utf8();
+    }
+    if quote.is_some() {
+        return Err("Unterminated quote.".to_string());
+    }
+    Ok(clauses)
+}
+
+/// Splits a comma-separated list, keeping commas inside quotes or backticks, and strips backticks from each item.
+fn split_list(list: &str) -> Vec<String> {
+    let mut items = vec![String::new()];
+    let mut quote = None;
+    for c in list.chars() {
+        match quote {
+            Some(q) if c == q => {
+                quote = None;
+            }
+            Some(_) => {}
+            None if matches!(c, '"' | '\'' | '`') => {
+                quote = Some(c);
+            }
+            None if c == ',' => {
+                items.push(String::new());
+                continue;
+            }
+            None => {}
+        }
+        items.last_mut().expect("Failed to split list.").push(c);
+    }
+    items
+        .iter()
+        .map(|item| item.trim().trim_matches('`').to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+/// Parses a small SQL subset: `SELECT a, `Part Of` FROM records WHERE a != '' ORDER BY b DESC, a LIMIT 10`.
+/// `FROM` is optional and ignored, since the CSV file is given separately. `WHERE` takes a [`filter`] predicate.
+pub fn parse_query(sql: &str) -> Result<Query, String> {
+    let clauses = split_clauses(sql)?;
+    let clause = |name: &str| clauses.iter().find(|(keyword, _)| *keyword == name).map(|(_, text)| text.trim());
+
+    let select = clause("SELECT").ok_or("A query must start with SELECT.")?;
+    if select.is_empty() {
+        return Err("SELECT needs at least one column, or `*`.".to_string());
+    }
+    let columns = if select == "*" { vec![] } else { split_list(select) };
+    let predicate = clause("WHERE").map(filter::parse_predicate).transpose()?;
+    let order_by = clause("ORDER BY")
+        .map(|order_by| {
+            split_list(order_by)
+                .into_iter()
+                .map(|item| {
+                    let (column, direction) = match item.rsplit_once(char::is_whitespace) {
+                        Some((column, direction)) if
+                            direction.eq_ignore_ascii_case("ASC") ||
+                            direction.eq_ignore_ascii_case("DESC")
+                        => (column.trim().trim_matches('`').to_string(), direction),
+                        _ => (item.clone(), "ASC"),
+                    };
+                    (column, direction.eq_ignore_ascii_case("DESC"))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    let limit = clause("LIMIT")
+        .map(|limit| limit.parse().map_err(|_| format!("LIMIT \"{}\" is not a number.", limit)))
+        .transpose()?;
+
+    Ok(Query { columns, predicate, order_by, limit })
+}
+
+/// Runs a query over a CSV file, printing the result as a table, or writing it as CSV to `output` (`-` for stdout).
+pub fn query_csv(path: &str, sql: &str, output: Option<&str>) {
+    let query = parse_query(sql).unwrap_or_else(|e| panic!("Invalid query: {}", e));
+    let mut reader = csv_reader(path);
+    let headers = reader.headers().expect("Failed to read CSV headers.").clone();
+    let position = |column: &str| -> usize {
+        headers
+            .iter()
+            .position(|header| header == column)
+            .unwrap_or_else(|| panic!("Column \"{}\" not found in \"{}\".", column, path))
+    };
+    let selected = if query.columns.is_empty() {
+        (0..headers.len()).collect::<Vec<_>>()
+    } else {
+        query.columns.iter().map(|column| position(column)).collect()
+    };
+    let order_by = query.order_by
+        .iter()
+        .map(|(column, descending)| (position(column), *descending))
+        .collect::<Vec<_>>();
+    if let Some(predicate) = &query.predicate {
+        predicate.columns().into_iter().for_each(|column| {
+            position(column);
+        });
+    }
+
+    let mut rows = reader
+        .records()
+        .map(|result| result.expect("Failed to parse record."))
+        .filter(|row| query.predicate.as_ref().is_none_or(|predicate| predicate.matches(&headers, row)))
+        .collect::<Vec<_>>();
+    // a stable sort keeps file order between equal rows.
+    rows.sort_by(|a, b| {
+        order_by
+            .iter()
+            .map(|(i, descending)| {
+                let ordering = filter
+                    ::order_values(a.get(*i).unwrap_or_default(), b.get(*i).unwrap_or_default())
+                    .unwrap_or(Ordering::Equal);
+                if *descending { ordering.reverse() } else { ordering }
+            })
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+    });
+    rows.truncate(query.limit.unwrap_or(rows.len()));
+
+    let header = selected.iter().map(|i| &headers[*i]).collect::<Vec<_>>();
+    let table = rows
+        .iter()
+        .map(|row| selected.iter().map(|i| row.get(*i).unwrap_or_default()).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    summary::record_count("rows", table.len());
+
+    if let Some(output) = output {
+        let output = if output == STDIO { STDIO.to_string() } else { safely_target_file(output) };
+        let mut writer = csv_writer(&output);
+        writer.write_record(&header).expect("Failed to write headers.");
+        for row in &table {
+            writer.write_record(row).expect("Failed to write record.");
+        }
+        writer.flush().expect("Failed to write query results.");
+        if output != STDIO {
+            print_status!("Saved {} rows to \"{}\".", table.len(), output);
+            summary::record_output(&output);
+        }
+        return;
+    }
+
+    let widths = header
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            table
+                .iter()
+                .map(|row| row[i].chars().count())
+                .chain(std::iter::once(name.chars().count()))
+                .max()
+                .unwrap_or_default()
+        })
+        .collect::<Vec<_>>();
+    let line = |values: &[&str]| -> String {
+        values
+            .iter()
+            .zip(&widths)
+            .map(|(value, width)| format!("{:width$}", value, width = width))
+            .collect::<Vec<_>>()
+            .join(" | ")
+            .trim_end()
+            .to_string()
+    };
+    print_status!("{}", line(&header).bold());
+    print_status!("{}", widths.iter().map(|width| "-".repeat(*width)).collect::<Vec<_>>().join("-+-").dimmed());
+    for row in &table {
+        print_status!("{}", line(row));
+    }
+    print_status!("{}", format!("({} rows)", table.len()).dimmed());
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_queries() {
+        let query = parse_query(
+            "select NODE_TITLE, `Part Of` from records where Volume = '' and Issue <> 'order by' order by Volume desc, NODE_TITLE limit 5"
+        ).unwrap();
+        assert_eq!(query.columns, vec!["NODE_TITLE", "Part Of"]);
+        assert!(query.predicate.is_some());
+        assert_eq!(query.order_by, vec![("Volume".to_string(), true), ("NODE_TITLE".to_string(), false)]);
+        assert_eq!(query.limit, Some(5));
+
+        let query = parse_query("SELECT *").unwrap();
+        assert!(query.columns.is_empty() && query.predicate.is_none() && query.limit.is_none());
+
+        assert!(parse_query("WHERE a = 1").is_err());
+        assert!(parse_query("SELECT a LIMIT ten").is_err());
+        assert!(parse_query("SELECT a WHERE a = 1 WHERE b = 2").is_err());
+    }
+}
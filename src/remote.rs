@@ -0,0 +1,102 @@
+use std::{ path::Path, process::Command };
+use crate::messages;
+
+/// A delivery endpoint that formatted files are uploaded to instead of copied into a local directory.
+/// Uploads use the `aws` CLI and `scp`, so their usual credentials and config (profiles, `~/.ssh/config`) apply.
+#[derive(Debug, PartialEq, Eq)]
+pub enum RemoteTarget {
+    S3 {
+        bucket: String,
+        prefix: String,
+    },
+    Sftp {
+        host: String,
+        path: String,
+    },
+}
+
+impl RemoteTarget {
+    /// Parses `s3://bucket/prefix` or `sftp://[user@]host/path`. Returns `None` for local paths.
+    /// The `sftp://` path is passed to `scp` as `host:path`, so it is relative to the login directory, as with `sftp`.
+    /// Use `sftp://host//srv/incoming` for an absolute path.
+    pub fn parse(output: &str) -> Option<Self> {
+        let split = |rest: &str| {
+            let (host, path) = rest.split_once('/').unwrap_or((rest, ""));
+            (host.to_string(), path.trim_end_matches('/').to_string())
+        };
+        if let Some(rest) = output.strip_prefix("s3://") {
+            let (bucket, prefix) = split(rest);
+            Some(RemoteTarget::S3 { bucket, prefix })
+        } else if let Some(rest) = output.strip_prefix("sftp://") {
+            let (host, path) = split(rest);
+            Some(RemoteTarget::Sftp { host, path })
+        } else {
+            None
+        }
+    }
+
+    /// Returns the URL of a file named `name` at this target.
+    pub fn url(&self, name: &str) -> String {
+        match self {
+            RemoteTarget::S3 { bucket, prefix } => format!("s3://{}/{}", bucket, join(prefix, name)),
+            RemoteTarget::Sftp { host, path } => format!("sftp://{}/{}", host, join(path, name)),
+        }
+    }
+
+    /// Uploads a local file to this target as `name`.
+    pub fn upload(&self, local: &Path, name: &str) -> Result<(), String> {
+        let mut command = match self {
+            RemoteTarget::S3 { .. } => {
+                let mut command = Command::new("aws");
+                command.args(["s3", "cp", "--only-show-errors"]).arg(local).arg(self.url(name));
+                command
+            }
+            RemoteTarget::Sftp { host, path } => {
+                // scp resolves relative paths from the login directory, like `sftp://host/path`.
+                let mut command = Command::new("scp");
+                command.args(["-q", "-p"]).arg(local).arg(format!("{}:{}", host, join(path, name)));
+                command
+            }
+        };
+        let program = format!("{:?}", command.get_program());
+        let status = command.status().map_err(|e| messages::upload_not_run(&program, &e.to_string()))?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(messages::upload_failed(&self.url(name), &status.to_string()))
+        }
+    }
+}
+
+fn join(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() { name.to_string() } else { format!("{}/{}", prefix, name) }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_remote_targets() {
+        let s3 = RemoteTarget::parse("s3://archive-bucket/deliveries/2024/").unwrap();
+        assert_eq!(s3.url("tn1.pdf"), "s3://archive-bucket/deliveries/2024/tn1.pdf");
+        let sftp = RemoteTarget::parse("sftp://ingest@example.org/incoming").unwrap();
+        assert_eq!(sftp, RemoteTarget::Sftp {
+            host: "ingest@example.org".to_string(),
+            path: "incoming".to_string(),
+        });
+        assert_eq!(RemoteTarget::parse("s3://bucket").unwrap().url("tn1.pdf"), "s3://bucket/tn1.pdf");
+        assert_eq!(RemoteTarget::parse("out/formatted"), None);
+    }
+
+    #[test]
+    fn remote_urls() {
+        let sftp = RemoteTarget::parse("sftp://example.org").unwrap();
+        assert_eq!(sftp, RemoteTarget::Sftp { host: "example.org".to_string(), path: String::new() });
+        assert_eq!(sftp.url("tn1.pdf"), "sftp://example.org/tn1.pdf");
+        let absolute = RemoteTarget::parse("sftp://example.org//srv/incoming/").unwrap();
+        assert_eq!(absolute.url("tn1.pdf"), "sftp://example.org//srv/incoming/tn1.pdf");
+        let s3 = RemoteTarget::parse("s3://bucket/a/b").unwrap();
+        assert_eq!(s3, RemoteTarget::S3 { bucket: "bucket".to_string(), prefix: "a/b".to_string() });
+    }
+}
@@ -0,0 +1,104 @@
+use std::collections::BTreeMap;
+use owo_colors::OwoColorize;
+
+use crate::{
+    archive_record::ArchiveRecord,
+    csv_reader,
+    date::Date,
+    issue_data::{ IssueData, UNDATED },
+    links,
+    messages,
+    print_status,
+    print_warn,
+    summary,
+    warnings::Warning,
+};
+
+/// Rebuilds an issue from a populated record: the tn from `Call Number`, the title and date from the NODE_TITLE
+/// (or `Date Original`), and the volume, issue, and date loaded from their columns.
+fn issue_from_record(record: &ArchiveRecord) -> Result<IssueData, String> {
+    let tn = record.call_number.trim();
+    if tn.is_empty() {
+        return Err(messages::no_call_number());
+    }
+    let (title, date) = match links::title_parts(&record.node_title) {
+        Some((publication, date)) => (publication.to_string(), Some(date)),
+        None => (record.node_title.trim().to_string(), Date::try_from(record.date_original.trim()).ok()),
+    };
+    let number = |column: &str, value: &str| -> Result<Option<u32>, String> {
+        let value = value.trim();
+        if value.is_empty() {
+            return Ok(None);
+        }
+        value
+            .parse()
+            .map(Some)
+            .map_err(|_| messages::not_a_number(column, value))
+    };
+    Ok(IssueData {
+        tn: tn.to_string(),
+        title: title.replace(" ", "_"),
+        volume: number("Volume", &record.volume)?,
+        issue: number("Issue", &record.issue)?,
+        date: date.map_or_else(|| UNDATED.to_string(), |date| date.to_string()),
+        date_loaded: record.date_digitized.clone(),
+    })
+}
+
+/// Builds a `tn -> IssueData` lookup table from a populated target CSV, for collections whose lookup spreadsheet
+/// has been lost. Records without a usable `Call Number` are skipped with a warning.
+pub fn lookup_from_records(target: &str) -> BTreeMap<String, IssueData> {
+    let mut lookup_table: BTreeMap<String, IssueData> = BTreeMap::new();
+    let mut skipped = 0;
+    for result in csv_reader(target).deserialize() {
+        let record: ArchiveRecord = result.expect("Failed to parse record.");
+        match issue_from_record(&record) {
+            Ok(issue) => {
+                if let Some(existing) = lookup_table.get(&issue.tn) {
+                    print_warn!(
+                        Warning::Duplicate,
+                        "{}",
+                        messages::duplicate_call_number(&issue.tn, &existing.record_title(), &record.node_title)
+                    );
+                    skipped += 1;
+                    continue;
+                }
+                lookup_table.insert(issue.tn.clone(), issue);
+            }
+            Err(reason) => {
+                print_warn!(Warning::SkippedFile, "{}", messages::skipped_because(&record.node_title, &reason));
+                skipped += 1;
+            }
+        }
+    }
+    summary::record_count("issues", lookup_table.len());
+    summary::record_count("skipped", skipped);
+    print_status!("Rebuilt {} lookup records from \"{}\".", lookup_table.len(), target);
+    lookup_table
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rebuild_issue_from_record() {
+        let record = ArchiveRecord {
+            node_title: "Arizona Catering Employees, 1952-07-11".to_string(),
+            call_number: " tn2 ".to_string(),
+            volume: "9".to_string(),
+            issue: "9".to_string(),
+            date_digitized: "7/11/1952 14:02".to_string(),
+            ..Default::default()
+        };
+        let issue = issue_from_record(&record).unwrap();
+        assert_eq!(issue.tn, "tn2");
+        assert_eq!(issue.title, "Arizona_Catering_Employees");
+        assert_eq!((issue.volume, issue.issue), (Some(9), Some(9)));
+        assert_eq!(issue.record_title(), record.node_title);
+
+        let undated = ArchiveRecord { node_title: "Arizona Sun".to_string(), call_number: "tn3".to_string(), ..record };
+        assert_eq!(issue_from_record(&undated).unwrap().date, UNDATED);
+        assert!(issue_from_record(&ArchiveRecord { call_number: String::new(), ..undated }).is_err());
+    }
+}
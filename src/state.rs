@@ -0,0 +1,224 @@
+use std::{ collections::BTreeMap, path::{ Path, PathBuf } };
+use owo_colors::OwoColorize;
+use serde::{ Deserialize, Serialize };
+
+use crate::{ print_status, summary };
+
+/// The processing state of every tn, in the working directory.
+pub const STATE_FILE: &str = ".bulk_format/state.csv";
+
+/// A step of the processing workflow that a tn can reach.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Formatted,
+    Populated,
+    Grouped,
+    Delivered,
+}
+
+impl Stage {
+    const ALL: [Stage; 4] = [Stage::Formatted, Stage::Populated, Stage::Grouped, Stage::Delivered];
+
+    fn name(self) -> &'static str {
+        match self {
+            Stage::Formatted => "formatted",
+            Stage::Populated => "populated",
+            Stage::Grouped => "grouped",
+            Stage::Delivered => "delivered",
+        }
+    }
+}
+
+/// One row of the state file: a tn, the stem of its formatted file, and when it last reached each stage.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct TnState {
+    tn: String,
+    file: String,
+    formatted: String,
+    populated: String,
+    grouped: String,
+    delivered: String,
+}
+
+impl TnState {
+    fn stage(&self, stage: Stage) -> &str {
+        match stage {
+            Stage::Formatted => &self.formatted,
+            Stage::Populated => &self.populated,
+            Stage::Grouped => &self.grouped,
+            Stage::Delivered => &self.delivered,
+        }
+    }
+
+    fn stage_mut(&mut self, stage: Stage) -> &mut String {
+        match stage {
+            Stage::Formatted => &mut self.formatted,
+            Stage::Populated => &mut self.populated,
+            Stage::Grouped => &mut self.grouped,
+            Stage::Delivered => &mut self.delivered,
+        }
+    }
+}
+
+fn read_state(path: &Path) -> BTreeMap<String, TnState> {
+    let Ok(mut reader) = csv::Reader::from_path(path) else {
+        return BTreeMap::new();
+    };
+    reader
+        .deserialize()
+        .map(|result| {
+            let state: TnState = result.expect("Failed to parse state entry.");
+            (state.tn.clone(), state)
+        })
+        .collect()
+}
+
+fn write_state(path: &Path, states: &BTreeMap<String, TnState>) {
+    std::fs::create_dir_all(path.parent().expect("Failed to get state directory.")).expect(
+        "Failed to create state directory."
+    );
+    // write beside the state file and rename over it, so an interrupted run never leaves a truncated state file.
+    let partial = PathBuf::from(format!("{}.partial", path.to_string_lossy()));
+    let mut writer = csv::Writer::from_path(&partial).expect("Failed to create state file.");
+    for state in states.values() {
+        writer.serialize(state).expect("Failed to write state entry.");
+    }
+    writer.flush().expect("Failed to write state file.");
+    std::fs::rename(&partial, path).expect("Failed to replace state file.");
+}
+
+/// Records that each `(tn, file stem)` reached `stage` now. An empty file stem keeps the one already recorded.
+pub fn mark_tns(stage: Stage, tns: &[(String, String)]) {
+    if tns.is_empty() {
+        return;
+    }
+    let path = &state_file();
+    let mut states = read_state(path);
+    let now = now();
+    for (tn, file) in tns {
+        let state = states.entry(tn.clone()).or_insert_with(|| TnState { tn: tn.clone(), ..Default::default() });
+        if !file.is_empty() {
+            state.file = file.clone();
+        }
+        *state.stage_mut(stage) = now.clone();
+    }
+    write_state(path, &states);
+}
+
+/// Records that the tns whose formatted files have the given stems reached `stage` now.
+/// Stems that no formatted tn is recorded with are ignored.
+pub fn mark_files(stage: Stage, stems: &[String]) {
+    let path = &state_file();
+    let mut states = read_state(path);
+    let now = now();
+    let mut marked = 0;
+    for state in states.values_mut().filter(|state| !state.file.is_empty() && stems.contains(&state.file)) {
+        *state.stage_mut(stage) = now.clone();
+        marked += 1;
+    }
+    if marked > 0 {
+        write_state(path, &states);
+    }
+}
+
+/// Returns the tn recorded for each formatted file stem.
+pub fn tns_by_file() -> BTreeMap<String, String> {
+    read_state(&state_file())
+        .into_values()
+        .filter(|state| !state.file.is_empty())
+        .map(|state| (state.file, state.tn))
+        .collect()
+}
+
+/// The state file. Each test thread keeps its own under the temp directory, so tests never write to the working
+/// directory.
+fn state_file() -> PathBuf {
+    if cfg!(test) {
+        std::env
+            ::temp_dir()
+            .join(format!("bulk_format_state_{}", std::process::id()))
+            .join(format!("{:?}.csv", std::thread::current().id()))
+    } else {
+        PathBuf::from(STATE_FILE)
+    }
+}
+
+fn now() -> String {
+    chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+/// Returns the number of tns that reached each stage.
+fn stage_counts(states: &BTreeMap<String, TnState>) -> Vec<(Stage, usize)> {
+    Stage::ALL.iter()
+        .map(|stage| (*stage, states.values().filter(|state| !state.stage(*stage).is_empty()).count()))
+        .collect()
+}
+
+/// Prints the stages each tn has reached (or only `tn`'s, with the times it reached them), and the overall counts.
+pub fn print_status(tn: Option<&str>) {
+    let states = read_state(&state_file());
+    if states.is_empty() {
+        print_status!("No processing state in \"{}\" yet.", STATE_FILE);
+        return;
+    }
+    if let Some(tn) = tn {
+        let state = states
+            .get(tn)
+            .unwrap_or_else(|| panic!("No processing state for tn \"{}\".", tn));
+        print_status!("{} {}", state.tn.bold(), state.file.dimmed());
+        for stage in Stage::ALL {
+            let time = state.stage(stage);
+            let time = if time.is_empty() { "-".dimmed().to_string() } else { time.green().to_string() };
+            print_status!("    {:<10} {}", stage.name(), time);
+        }
+        return;
+    }
+
+    for state in states.values() {
+        let stages = Stage::ALL.iter()
+            .map(|stage| {
+                if state.stage(*stage).is_empty() {
+                    format!("{:<10}", "-").dimmed().to_string()
+                } else {
+                    format!("{:<10}", stage.name()).green().to_string()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        print_status!("{:<12} {} {}", state.tn.bold(), stages, state.file.dimmed());
+    }
+    let counts = stage_counts(&states);
+    print_status!(
+        "{} tns: {}",
+        states.len(),
+        counts
+            .iter()
+            .map(|(stage, count)| format!("{} {}", count, stage.name()))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    summary::record_count("tns", states.len());
+    for (stage, count) in counts {
+        summary::record_count(stage.name(), count);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn count_stages() {
+        let formatted = TnState { tn: "tn1".to_string(), formatted: "2024-05-12 09:30:00".to_string(), ..Default::default() };
+        let populated = TnState { tn: "tn2".to_string(), populated: "2024-05-13 10:00:00".to_string(), ..formatted.clone() };
+        let states = [formatted, populated]
+            .into_iter()
+            .map(|state| (state.tn.clone(), state))
+            .collect();
+        let counts = stage_counts(&states)
+            .into_iter()
+            .map(|(stage, count)| (stage.name(), count))
+            .collect::<Vec<_>>();
+        assert_eq!(counts, vec![("formatted", 2), ("populated", 1), ("grouped", 0), ("delivered", 0)]);
+    }
+}
@@ -0,0 +1,285 @@
+use std::{ collections::BTreeMap, sync::{ atomic::{ AtomicBool, Ordering }, Mutex, OnceLock }, time::Instant };
+use serde::Serialize;
+
+use crate::{ csv_writer, filesystem::RealFilesystem, messages, trash_file, warnings::{ Severity, Warning } };
+
+/// What a run did, printed as one JSON line at the end of a `--quiet` run.
+#[derive(Debug, Serialize)]
+pub struct Summary {
+    pub command: String,
+    /// Files (or URLs) written by the run.
+    pub outputs: Vec<String>,
+    /// Files a dry run would have written.
+    pub planned: Vec<String>,
+    /// Command-specific counts, e.g. `skipped` or `missing`.
+    pub counts: BTreeMap<String, usize>,
+    pub warnings: Vec<String>,
+    /// The number of warnings of each category, e.g. `unmatched-record`.
+    pub warnings_by_category: BTreeMap<String, usize>,
+    /// Each warning with its category, in the order they were reported.
+    #[serde(skip)]
+    pub categorized_warnings: Vec<(Warning, String)>,
+    /// Files copied, moved, or hashed by the run, and their total size.
+    pub files: usize,
+    pub bytes: u64,
+    pub elapsed_seconds: f64,
+    pub bytes_per_second: f64,
+    pub warning_count: usize,
+}
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+static GROUP_WARNINGS: AtomicBool = AtomicBool::new(false);
+
+/// The categories whose severity is not the default `Warn`.
+static SEVERITIES: Mutex<BTreeMap<Warning, Severity>> = Mutex::new(BTreeMap::new());
+
+static SUMMARY: Mutex<Summary> = Mutex::new(Summary {
+    command: String::new(),
+    outputs: Vec::new(),
+    planned: Vec::new(),
+    counts: BTreeMap::new(),
+    warnings: Vec::new(),
+    warnings_by_category: BTreeMap::new(),
+    categorized_warnings: Vec::new(),
+    files: 0,
+    bytes: 0,
+    elapsed_seconds: 0.0,
+    bytes_per_second: 0.0,
+    warning_count: 0,
+});
+
+static STARTED: OnceLock<Instant> = OnceLock::new();
+
+/// Called with the category of the first warning of a denied category, to stop the run.
+static ON_DENY: OnceLock<fn(Warning)> = OnceLock::new();
+
+/// Starts the run's clock. Elapsed time is measured from the first call.
+pub fn start_clock() {
+    STARTED.get_or_init(Instant::now);
+}
+
+/// Suppresses status output for the rest of the run.
+pub fn set_quiet() {
+    QUIET.store(true, Ordering::Relaxed);
+}
+
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Holds back warnings until the end of the run, where they are summarized by category.
+pub fn set_group_warnings() {
+    GROUP_WARNINGS.store(true, Ordering::Relaxed);
+}
+
+pub fn groups_warnings() -> bool {
+    GROUP_WARNINGS.load(Ordering::Relaxed)
+}
+
+fn with_summary(f: impl FnOnce(&mut Summary)) {
+    f(&mut SUMMARY.lock().expect("Failed to lock the run summary."));
+}
+
+pub fn record_output(path: impl ToString) {
+    with_summary(|summary| summary.outputs.push(path.to_string()));
+}
+
+pub fn record_planned(path: impl ToString) {
+    with_summary(|summary| summary.planned.push(path.to_string()));
+}
+
+/// Sets the severity of a category for the rest of the run.
+pub fn set_severity(category: Warning, severity: Severity) {
+    SEVERITIES.lock().expect("Failed to lock warning severities.").insert(category, severity);
+}
+
+pub fn severity(category: Warning) -> Severity {
+    SEVERITIES.lock().expect("Failed to lock warning severities.").get(&category).copied().unwrap_or_default()
+}
+
+/// Records a warning, unless its category is allowed, and returns its category's severity.
+pub fn record_warning(category: Warning, message: impl ToString) -> Severity {
+    let severity = severity(category);
+    if severity == Severity::Allow {
+        record_count("allowed warnings", 1);
+        return severity;
+    }
+    if severity == Severity::Deny {
+        record_count("denied warnings", 1);
+    }
+    with_summary(|summary| {
+        summary.warnings.push(message.to_string());
+        *summary.warnings_by_category.entry(category.name()).or_default() += 1;
+        summary.categorized_warnings.push((category, message.to_string()));
+    });
+    severity
+}
+
+/// Sets how the run stops at a warning of a denied category.
+pub fn on_deny(stop: fn(Warning)) {
+    ON_DENY.set(stop).expect("The denied warning handler was already set.");
+}
+
+/// Stops the run at a warning of a denied category, once it is reported.
+pub fn deny(category: Warning) {
+    if let Some(stop) = ON_DENY.get() {
+        stop(category);
+    }
+}
+
+/// Returns the number of warnings of denied categories reported so far.
+pub fn denied_warnings() -> usize {
+    SUMMARY.lock().unwrap_or_else(|e| e.into_inner()).counts.get("denied warnings").copied().unwrap_or(0)
+}
+
+/// Returns the number of warnings of each category reported so far, most frequent first.
+pub fn warning_groups() -> Vec<(Warning, usize)> {
+    let mut groups = BTreeMap::new();
+    with_summary(|summary| {
+        for (category, _) in &summary.categorized_warnings {
+            *groups.entry(*category).or_insert(0) += 1;
+        }
+    });
+    let mut groups = groups.into_iter().collect::<Vec<_>>();
+    groups.sort_by(|(_, a), (_, b)| b.cmp(a));
+    groups
+}
+
+/// Writes every warning reported so far to a `category,message` CSV file, replacing (and trashing) an older one.
+pub fn write_warnings(output: &str) {
+    let output_path = std::path::Path::new(output);
+    if output_path.exists() {
+        trash_file(&RealFilesystem, output_path);
+    }
+    let mut writer = csv_writer(output);
+    writer.write_record(["category", "message"]).expect("Failed to write warnings header.");
+    with_summary(|summary| {
+        for (category, message) in &summary.categorized_warnings {
+            writer.write_record([category.name().as_str(), message]).expect("Failed to write warning row.");
+        }
+    });
+    writer.flush().expect("Failed to write warnings file.");
+    record_output(output);
+}
+
+/// Counts a file of `bytes` copied, moved, or hashed by the run.
+pub fn record_file(bytes: u64) {
+    with_summary(|summary| {
+        summary.files += 1;
+        summary.bytes += bytes;
+    });
+}
+
+/// Adds `n` to a named count.
+pub fn record_count(key: &str, n: usize) {
+    with_summary(|summary| {
+        *summary.counts.entry(key.to_string()).or_default() += n;
+    });
+}
+
+/// Updates the elapsed time, throughput, and warning count.
+fn finish(summary: &mut Summary) {
+    summary.elapsed_seconds = STARTED.get().map_or(0.0, |started| started.elapsed().as_secs_f64());
+    summary.bytes_per_second = if summary.elapsed_seconds > 0.0 {
+        (summary.bytes as f64) / summary.elapsed_seconds
+    } else {
+        0.0
+    };
+    summary.warning_count = summary.warnings.len();
+}
+
+/// Returns the summary of the run as a single line of JSON.
+pub fn summary_json(command: &str) -> String {
+    let mut summary = SUMMARY.lock().unwrap_or_else(|e| e.into_inner());
+    summary.command = command.to_string();
+    finish(&mut summary);
+    serde_json::to_string(&*summary).expect("Failed to serialize the run summary.")
+}
+
+/// Returns the run's metrics as a line of text, e.g.
+/// `Processed 120 files (4.2 GB) in 31.0s (135.5 MB/s), 2 warnings.`
+pub fn metrics_line() -> String {
+    let mut summary = SUMMARY.lock().unwrap_or_else(|e| e.into_inner());
+    finish(&mut summary);
+    let warnings = messages::warning_count(summary.warning_count);
+    if summary.files == 0 {
+        return messages::finished_in(summary.elapsed_seconds, &warnings);
+    }
+    messages::processed_files(
+        summary.files,
+        &format_size(summary.bytes),
+        summary.elapsed_seconds,
+        &format_size(summary.bytes_per_second as u64),
+        &warnings
+    )
+}
+
+/// Formats a byte size with a decimal unit, e.g. `4.2 GB`.
+pub fn format_size(bytes: u64) -> String {
+    let units = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1000.0 && unit < units.len() - 1 {
+        size /= 1000.0;
+        unit += 1;
+    }
+    if unit == 0 { format!("{} B", bytes) } else { format!("{:.1} {}", size, units[unit]) }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn format_sizes() {
+        assert_eq!(format_size(999), "999 B");
+        assert_eq!(format_size(4_200_000_000), "4.2 GB");
+        assert_eq!(format_size(135_500_000), "135.5 MB");
+    }
+
+    #[test]
+    fn summarize_run_as_json() {
+        // the summary is shared by every test, so only look at what this test recorded.
+        record_count("summarized test files", 2);
+        record_output("summarized_test_output.csv");
+        let summary: serde_json::Value = serde_json::from_str(&summary_json("populate")).unwrap();
+        assert_eq!(summary["command"], "populate");
+        assert_eq!(summary["counts"]["summarized test files"], 2);
+        assert!(summary["outputs"].as_array().unwrap().iter().any(|output| output == "summarized_test_output.csv"));
+        assert!(summary.get("categorized_warnings").is_none());
+    }
+
+    #[test]
+    fn list_warnings_by_category() {
+        assert_eq!(Warning::UnmatchedRecord.name(), "unmatched-record");
+        let dir = crate::test_util::TestDir::new("warnings_log");
+        record_warning(Warning::OrphanFile, "Orphan \"out/listed_warning.pdf\".");
+        record_warning(Warning::OrphanFile, "Orphan \"out/listed_warning_2.pdf\".");
+        let output = dir.join("warnings.csv").to_string_lossy().to_string();
+        write_warnings(&output);
+        let warnings = std::fs::read_to_string(output).unwrap();
+        assert!(warnings.starts_with("category,message\n"));
+        assert!(warnings.contains("orphan-file,\"Orphan \"\"out/listed_warning.pdf\"\".\"\n"));
+        // other tests report warnings too, so there are at least this test's.
+        assert!(warning_groups().iter().any(|&(category, count)| category == Warning::OrphanFile && count >= 2));
+    }
+
+    #[test]
+    fn allow_and_deny_warning_categories() {
+        // severities are shared by every test, so set them only for categories the other tests don't look at.
+        set_severity(Warning::TnPattern, Severity::Allow);
+        set_severity(Warning::InvalidValue, Severity::Deny);
+        assert_eq!(severity(Warning::UnparsedVolume), Severity::Warn);
+        assert_eq!(record_warning(Warning::TnPattern, "Allowed severity test warning."), Severity::Allow);
+        assert_eq!(record_warning(Warning::InvalidValue, "Denied severity test warning."), Severity::Deny);
+        assert!(denied_warnings() >= 1);
+
+        let dir = crate::test_util::TestDir::new("warning_severities");
+        let output = dir.join("warnings.csv").to_string_lossy().to_string();
+        write_warnings(&output);
+        let warnings = std::fs::read_to_string(output).unwrap();
+        assert!(!warnings.contains("Allowed severity test warning."));
+        assert!(warnings.contains("invalid-value,Denied severity test warning.\n"));
+    }
+}
@@ -0,0 +1,35 @@
+use std::path::PathBuf;
+
+/// A fresh directory under the system temp directory for a test's files, removed when it is dropped.
+pub struct TestDir(PathBuf);
+
+impl TestDir {
+    /// Creates `bulk_format_<name>_<pid>`, emptying it if an earlier run left it behind. `name` must be unique per test.
+    pub fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("bulk_format_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).expect("Failed to create test directory.");
+        TestDir(dir)
+    }
+
+    /// Returns the path of `name` inside the directory.
+    pub fn join(&self, name: &str) -> PathBuf {
+        self.0.join(name)
+    }
+
+    /// Writes `contents` to `name` inside the directory, creating its parent directories, and returns its path.
+    pub fn write(&self, name: &str, contents: impl AsRef<[u8]>) -> PathBuf {
+        let path = self.join(name);
+        std::fs::create_dir_all(path.parent().expect("Failed to get parent directory.")).expect(
+            "Failed to create parent directory."
+        );
+        std::fs::write(&path, contents).expect("Failed to write test file.");
+        path
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
@@ -0,0 +1,176 @@
+use std::collections::BTreeMap;
+use owo_colors::OwoColorize;
+
+use crate::{
+    archive_record::ArchiveRecord,
+    csv_reader,
+    csv_writer,
+    date::Date,
+    derived_path,
+    preview,
+    print_status,
+    safely_target_file,
+    summary,
+};
+
+/// Words left lowercase by [`title_case`] unless they start the title.
+const SMALL_WORDS: [&str; 16] = [
+    "a", "an", "and", "as", "at", "by", "de", "del", "el", "for", "in", "la", "of", "on", "the", "to",
+];
+
+/// Splits a NODE_TITLE into its publication and date, e.g. `("Arizona Sun", Some("1944-05-12"))`.
+fn split_title(title: &str) -> (&str, Option<&str>) {
+    match title.rsplit_once(',') {
+        Some((publication, date)) if Date::try_from(date.trim()).is_ok() => (publication, Some(date.trim())),
+        _ => (title, None),
+    }
+}
+
+/// Collapses runs of whitespace and strips stray trailing periods from the publication part of a NODE_TITLE.
+fn tidy_title(title: &str) -> String {
+    let title = title.split_whitespace().collect::<Vec<_>>().join(" ");
+    let (publication, date) = split_title(&title);
+    let publication = publication.trim_end_matches(|c: char| c == '.' || c.is_whitespace());
+    match date {
+        Some(date) => format!("{}, {}", publication, date),
+        None => publication.to_string(),
+    }
+}
+
+/// Capitalizes the first letter of each word, leaving [`SMALL_WORDS`] lowercase after the first word.
+fn title_case(publication: &str) -> String {
+    publication
+        .split(' ')
+        .enumerate()
+        .map(|(i, word)| {
+            let lower = word.to_lowercase();
+            if i > 0 && SMALL_WORDS.contains(&lower.as_str()) {
+                return lower;
+            }
+            let mut chars = lower.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => lower,
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Picks one casing for each publication name (compared case-insensitively): the most common one, the first on a tie.
+/// A name that is all lowercase or all uppercase is title-cased instead.
+fn canonical_casings<'a>(publications: impl IntoIterator<Item = &'a str>) -> BTreeMap<String, String> {
+    let mut variants: BTreeMap<String, Vec<(&str, usize)>> = BTreeMap::new();
+    for publication in publications {
+        let counts = variants.entry(publication.to_lowercase()).or_default();
+        match counts.iter_mut().find(|(variant, _)| *variant == publication) {
+            Some((_, count)) => {
+                *count += 1;
+            }
+            None => counts.push((publication, 1)),
+        }
+    }
+    variants
+        .into_iter()
+        .map(|(key, counts)| {
+            let mut best = counts[0];
+            for variant in &counts[1..] {
+                if variant.1 > best.1 {
+                    best = *variant;
+                }
+            }
+            let best = best.0;
+            let has_letters = best.chars().any(char::is_alphabetic);
+            let canonical = if has_letters && (best == best.to_lowercase() || best == best.to_uppercase()) {
+                title_case(best)
+            } else {
+                best.to_string()
+            };
+            (key, canonical)
+        })
+        .collect()
+}
+
+/// Fixes whitespace, trailing periods, and inconsistent publication casing in the NODE_TITLEs of a target CSV,
+/// updating `Previous Issue`/`Next Issue` values that refer to a changed title.
+/// Writes `<target>_normalized.csv`, unless `dry_run` is set. Returns the number of titles changed.
+pub fn normalize_titles(target: &str, dry_run: bool) -> usize {
+    let mut reader = csv_reader(target);
+    let headers = reader.headers().expect("Failed to read target CSV headers.").clone();
+    let mut records: Vec<ArchiveRecord> = reader
+        .deserialize()
+        .map(|r| r.expect("Failed to parse record."))
+        .collect();
+
+    let tidied = records
+        .iter()
+        .map(|record| tidy_title(&record.node_title))
+        .collect::<Vec<_>>();
+    let casings = canonical_casings(tidied.iter().map(|title| split_title(title).0));
+    let renames = records
+        .iter()
+        .zip(&tidied)
+        .filter_map(|(record, tidied)| {
+            let (publication, date) = split_title(tidied);
+            let publication = &casings[&publication.to_lowercase()];
+            let normalized = match date {
+                Some(date) => format!("{}, {}", publication, date),
+                None => publication.clone(),
+            };
+            (normalized != record.node_title).then(|| (record.node_title.clone(), normalized))
+        })
+        .collect::<BTreeMap<_, _>>();
+
+    for record in &mut records {
+        let old = record.clone();
+        for field in [&mut record.node_title, &mut record.previous_issue, &mut record.next_issue] {
+            if let Some(normalized) = renames.get(field.as_str()) {
+                *field = normalized.clone();
+            }
+        }
+        let changes = old.changes(record);
+        if changes.is_empty() {
+            continue;
+        }
+        print_status!("{}", old.node_title);
+        for (column, old, new) in changes {
+            preview::print_field_change(&column, &old, &new);
+        }
+    }
+    summary::record_count("normalized", renames.len());
+    if renames.is_empty() {
+        print_status!("{}", "All NODE_TITLEs are already normalized.".green().bold());
+        return 0;
+    }
+    if dry_run {
+        print_status!("{} NODE_TITLEs would be normalized.", renames.len());
+        return renames.len();
+    }
+
+    let output = safely_target_file(&derived_path(target, "_normalized", "csv"));
+    let mut writer = csv_writer(&output);
+    writer.write_record(&headers).expect("Failed to write headers.");
+    for record in &records {
+        writer.write_record(&record.to_row(&headers)).expect("Failed to write record.");
+    }
+    writer.flush().expect("Failed to write normalized CSV file.");
+    print_status!("Normalized {} NODE_TITLEs and saved to \"{}\".", renames.len(), output);
+    summary::record_output(&output);
+    renames.len()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn normalize_node_titles() {
+        assert_eq!(tidy_title("  Arizona  Sun. , 1944-05-12 "), "Arizona Sun, 1944-05-12");
+        assert_eq!(tidy_title("Arizona Sun."), "Arizona Sun");
+        assert_eq!(title_case("ARIZONA LABOR JOURNAL OF THE west"), "Arizona Labor Journal of the West");
+
+        let casings = canonical_casings(["Arizona Sun", "arizona sun", "Arizona Sun", "el sol de tucson"]);
+        assert_eq!(casings["arizona sun"], "Arizona Sun");
+        assert_eq!(casings["el sol de tucson"], "El Sol de Tucson");
+    }
+}
@@ -0,0 +1,227 @@
+use chrono::NaiveDate;
+use owo_colors::OwoColorize;
+
+use crate::{
+    archive_record::ArchiveRecord,
+    config::config,
+    csv_reader,
+    csv_writer,
+    date::Date,
+    issue_data::parse_date_loaded,
+    links,
+    messages,
+    print_status,
+    print_warn,
+    safely_target_file,
+    summary,
+    warnings::Warning,
+};
+
+/// The days a date may fall on, e.g. `1944-05` -> May 1 to May 31, 1944.
+fn date_span(date: &Date) -> (NaiveDate, NaiveDate) {
+    (date.first_day(), date.last_day())
+}
+
+fn non_empty(value: &str) -> Option<&str> {
+    Some(value.trim()).filter(|value| !value.is_empty())
+}
+
+/// Parses a `Date Range` such as `1944/1952`, `1944-05/1945`, or `1944`.
+fn parse_date_range(date_range: &str) -> Option<(NaiveDate, NaiveDate)> {
+    let (start, end) = date_range.split_once('/').unwrap_or((date_range, date_range));
+    let start = Date::try_from(start.trim()).ok()?;
+    let end = Date::try_from(end.trim()).ok()?;
+    Some((start.first_day(), end.last_day()))
+}
+
+/// Returns the date inconsistencies of a record, each as a sentence:
+/// Date Digitized before Date Original, Date Original outside Date Range,
+/// a NODE_TITLE date that disagrees with Date Original, or a date field that does not parse.
+fn date_problems(record: &ArchiveRecord) -> Vec<String> {
+    let mut problems = vec![];
+
+    let original = non_empty(&record.date_original).and_then(|value| {
+        let date = Date::try_from(value).ok();
+        if date.is_none() {
+            problems.push(messages::invalid_date_field("Date Original", value));
+        }
+        date
+    });
+    let digitized = non_empty(&record.date_digitized).and_then(|value| {
+        let date = parse_date_loaded(value)
+            .map(|date| date.date())
+            .or_else(|| Date::try_from(value).ok().map(|date| date.first_day()));
+        if date.is_none() {
+            problems.push(messages::invalid_date_field("Date Digitized", value));
+        }
+        date
+    });
+    let date_range = non_empty(&record.date_range).and_then(|value| {
+        let range = parse_date_range(value);
+        if range.is_none() {
+            problems.push(messages::invalid_date_range(value));
+        }
+        range
+    });
+
+    if let Some(original) = &original {
+        let (first, last) = date_span(original);
+        if let Some(digitized) = digitized.filter(|digitized| *digitized < first) {
+            problems.push(messages::digitized_before_original(&digitized.to_string(), &original.to_string()));
+        }
+        if date_range.is_some_and(|(start, end)| last < start || first > end) {
+            problems.push(messages::original_outside_range(&original.to_string(), record.date_range.trim()));
+        }
+        if let Some((_, title_date)) = links::title_parts(&record.node_title) {
+            let (title_first, title_last) = date_span(&title_date);
+            if title_last < first || title_first > last {
+                problems.push(messages::title_date_disagrees(&title_date.to_string(), &original.to_string()));
+            }
+        }
+    }
+    problems
+}
+
+/// Reports records whose dates are inconsistent with each other (see [`date_problems`]), optionally exporting them as a
+/// CSV of `node_title, problem`. Returns the number of problems.
+pub fn check_dates(target: &str, output: Option<&str>) -> usize {
+    let mut rows = vec![];
+    for result in csv_reader(target).deserialize() {
+        let record: ArchiveRecord = result.expect("Failed to parse record.");
+        for problem in date_problems(&record) {
+            print_warn!(Warning::OddDate, "{}", messages::date_problem(&record.node_title, &problem));
+            rows.push([record.node_title.clone(), problem]);
+        }
+    }
+    summary::record_count("problems", rows.len());
+    if rows.is_empty() {
+        print_status!("{}", "All record dates are consistent.".green().bold());
+    }
+
+    if let Some(output) = output {
+        let output = safely_target_file(output);
+        let mut writer = csv_writer(&output);
+        writer.write_record(["node_title", "problem"]).expect("Failed to write headers.");
+        for row in &rows {
+            writer.write_record(row).expect("Failed to write record.");
+        }
+        writer.flush().expect("Failed to write date problems CSV file.");
+        print_status!("Saved {} date problems to \"{}\".", rows.len(), output);
+        summary::record_output(&output);
+    }
+    rows.len()
+}
+
+/// The number of single-character insertions, deletions, and substitutions that turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Returns the allowed value closest to `value` (case-insensitively), if it is within a third of its length.
+fn suggest<'a>(value: &str, allowed: &'a [String]) -> Option<&'a str> {
+    let value = value.to_lowercase();
+    allowed
+        .iter()
+        .map(|candidate| (edit_distance(&value, &candidate.to_lowercase()), candidate))
+        .filter(|(distance, candidate)| *distance <= candidate.chars().count().max(3) / 3)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate.as_str())
+}
+
+/// Reports values of a target CSV that are not in the config's `vocabularies`, with the closest allowed value.
+/// Returns the number of off-vocabulary values.
+pub fn validate_vocabularies(target: &str) -> usize {
+    let vocabularies = &config().vocabularies;
+    if vocabularies.is_empty() {
+        print_warn!(Warning::Other, "{}", messages::no_vocabularies());
+        return 0;
+    }
+    let mut reader = csv_reader(target);
+    let headers = reader.headers().expect("Failed to read target CSV headers.").clone();
+    let columns = vocabularies
+        .iter()
+        .filter_map(|(column, allowed)| {
+            let position = headers.iter().position(|header| header == column);
+            if position.is_none() {
+                print_warn!(Warning::Other, "{}", messages::vocabulary_column_missing(column));
+            }
+            Some((position?, column, allowed))
+        })
+        .collect::<Vec<_>>();
+
+    let mut problems = 0;
+    for (row, result) in reader.records().enumerate() {
+        let record = result.expect("Failed to parse record.");
+        for (position, column, allowed) in &columns {
+            let values = record.get(*position).unwrap_or_default().split(';').map(str::trim);
+            for value in values.filter(|value| !value.is_empty() && !allowed.iter().any(|allowed| allowed == value)) {
+                problems += 1;
+                let suggestion = suggest(value, allowed)
+                    .map(messages::did_you_mean)
+                    .unwrap_or_default();
+                print_warn!(
+                    Warning::InvalidValue,
+                    "{}",
+                    messages::invalid_value(row + 2, record.get(0).unwrap_or_default(), column, value, &suggestion)
+                );
+            }
+        }
+    }
+    summary::record_count("off-vocabulary", problems);
+    if problems == 0 {
+        print_status!("{}", "All values are in their vocabularies.".green().bold());
+    }
+    problems
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn find_date_problems() {
+        let record = ArchiveRecord {
+            node_title: "Arizona Sun, 1944-05-12".to_string(),
+            date_original: "1944-05-12".to_string(),
+            date_range: "1944/1952".to_string(),
+            date_digitized: "6/8/2020 10:00".to_string(),
+            ..Default::default()
+        };
+        assert!(date_problems(&record).is_empty());
+
+        let record = ArchiveRecord {
+            node_title: "Arizona Sun, 1944-06-12".to_string(),
+            date_original: "1944-05".to_string(),
+            date_range: "1945/1952".to_string(),
+            date_digitized: "1940-01-01".to_string(),
+            ..record
+        };
+        assert_eq!(date_problems(&record).len(), 3);
+
+        let record = ArchiveRecord { date_range: "1944/later".to_string(), date_digitized: String::new(), ..record };
+        assert_eq!(date_problems(&record), vec![
+            "Date Range \"1944/later\" is not a valid range.".to_string(),
+            "NODE_TITLE date 1944-06-12 disagrees with Date Original 1944-05.".to_string(),
+        ]);
+    }
+
+    #[test]
+    fn suggest_vocabulary_values() {
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        let allowed = ["English", "Spanish", "Text"].map(str::to_string);
+        assert_eq!(suggest("english", &allowed), Some("English"));
+        assert_eq!(suggest("Spansh", &allowed), Some("Spanish"));
+        assert_eq!(suggest("Txt", &allowed), Some("Text"));
+        assert_eq!(suggest("French", &allowed), None);
+    }
+}
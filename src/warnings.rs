@@ -0,0 +1,69 @@
+/// What a warning of a category does, set per category with `--allow` and `--deny`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// Not reported or counted as a warning.
+    Allow,
+    /// Reported, and summarized at the end of the run.
+    #[default]
+    Warn,
+    /// Reported, and stops the run with an error before anything else is written.
+    Deny,
+}
+
+/// The category of a warning, used to group warnings in the end-of-run summary and to set their severity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, clap::ValueEnum)]
+pub enum Warning {
+    /// A volume/issue that was not in the expected `v.1, no.2` form.
+    UnparsedVolume,
+    /// A date that was guessed at, expanded, or copied as-is.
+    OddDate,
+    /// An issue or record without a date.
+    UndatedIssue,
+    /// A file or record with no lookup entry.
+    UnmatchedRecord,
+    /// A tn that does not match the configured tn pattern.
+    TnPattern,
+    /// A tn, key, or row that appears more than once.
+    Duplicate,
+    /// Output names that collide, or an output that will be replaced.
+    NameCollision,
+    /// An input file or row that was left out.
+    SkippedFile,
+    /// A broken, forked, or cyclic Previous/Next Issue link.
+    BrokenLink,
+    /// A value that is not allowed by the config's vocabularies.
+    InvalidValue,
+    /// A file in the output directory that no lookup entry is formatted to.
+    OrphanFile,
+    /// A hook, command, fetch, or write that failed.
+    Failure,
+    /// Anything else.
+    Other,
+}
+
+impl Warning {
+    /// Returns the category's kebab-case name, e.g. `unmatched-record`.
+    pub fn name(self) -> String {
+        use clap::ValueEnum;
+        self.to_possible_value().expect("Warning categories are never skipped.").get_name().to_string()
+    }
+
+    /// Returns a short description of the category for the end-of-run summary.
+    pub fn label(self) -> &'static str {
+        match self {
+            Warning::UnparsedVolume => "volume/issue not comma separated",
+            Warning::OddDate => "odd date",
+            Warning::UndatedIssue => "undated issue",
+            Warning::UnmatchedRecord => "unmatched file or record",
+            Warning::TnPattern => "tn does not match the pattern",
+            Warning::Duplicate => "duplicate",
+            Warning::NameCollision => "name collision",
+            Warning::SkippedFile => "skipped file or row",
+            Warning::BrokenLink => "broken link",
+            Warning::InvalidValue => "invalid value",
+            Warning::OrphanFile => "orphaned output file",
+            Warning::Failure => "failure",
+            Warning::Other => "other",
+        }
+    }
+}
@@ -0,0 +1,242 @@
+use std::{ io::{ Seek, SeekFrom, Write }, path::Path };
+
+/// The fields written into an image's XMP packet.
+pub struct XmpFields<'a> {
+    pub title: &'a str,
+    /// An ISO 8601 date, e.g. `1944-08-06`, `1944-08`, or `1944`.
+    pub date: Option<String>,
+    pub rights: Option<&'a str>,
+}
+
+/// The XMP namespace signature that starts a JPEG APP1 XMP segment.
+const JPEG_XMP_SIGNATURE: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+
+/// The TIFF tag holding an XMP packet.
+const TIFF_XMP_TAG: u16 = 700;
+
+/// Returns true if the file extension is one this module can embed XMP into.
+pub fn supports(path: &Path) -> bool {
+    matches!(
+        path.extension().map(|ext| ext.to_string_lossy().to_lowercase()).as_deref(),
+        Some("jpg" | "jpeg" | "tif" | "tiff")
+    )
+}
+
+/// Writes (or replaces) the XMP packet of a JPEG or TIFF file in place.
+pub fn embed_xmp(path: &Path, fields: &XmpFields) -> Result<(), String> {
+    let packet = xmp_packet(fields);
+    match path.extension().map(|ext| ext.to_string_lossy().to_lowercase()).as_deref() {
+        Some("jpg" | "jpeg") => embed_jpeg(path, packet.as_bytes()),
+        Some("tif" | "tiff") => embed_tiff(path, packet.as_bytes()),
+        _ => Err("Unsupported file type.".to_string()),
+    }
+}
+
+fn xmp_packet(fields: &XmpFields) -> String {
+    let mut properties = format!(
+        "   <dc:title><rdf:Alt><rdf:li xml:lang=\"x-default\">{}</rdf:li></rdf:Alt></dc:title>\n",
+        escape_xml(fields.title)
+    );
+    if let Some(date) = &fields.date {
+        properties.push_str(
+            &format!("   <dc:date><rdf:Seq><rdf:li>{}</rdf:li></rdf:Seq></dc:date>\n", escape_xml(date))
+        );
+        properties.push_str(
+            &format!("   <photoshop:DateCreated>{}</photoshop:DateCreated>\n", escape_xml(date))
+        );
+    }
+    if let Some(rights) = fields.rights {
+        properties.push_str(
+            &format!(
+                "   <dc:rights><rdf:Alt><rdf:li xml:lang=\"x-default\">{}</rdf:li></rdf:Alt></dc:rights>\n",
+                escape_xml(rights)
+            )
+        );
+    }
+
+    format!(
+        concat!(
+            "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n",
+            "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n",
+            " <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n",
+            "  <rdf:Description rdf:about=\"\"\n",
+            "    xmlns:dc=\"http://purl.org/dc/elements/1.1/\"\n",
+            "    xmlns:photoshop=\"http://ns.adobe.com/photoshop/1.0/\">\n",
+            "{}",
+            "  </rdf:Description>\n",
+            " </rdf:RDF>\n",
+            "</x:xmpmeta>\n",
+            "<?xpacket end=\"w\"?>"
+        ),
+        properties
+    )
+}
+
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Rewrites a JPEG with the XMP packet in an APP1 segment placed after any JFIF/Exif segments, dropping any existing XMP segment.
+fn embed_jpeg(path: &Path, packet: &[u8]) -> Result<(), String> {
+    let data = std::fs::read(path).map_err(|e| e.to_string())?;
+    if !data.starts_with(&[0xff, 0xd8]) {
+        return Err("Not a JPEG file.".to_string());
+    }
+
+    // walk the leading APPn/COM segments.
+    let mut segments: Vec<&[u8]> = vec![];
+    let mut insert_at = 0;
+    let mut pos = 2;
+    while pos + 4 <= data.len() && data[pos] == 0xff && matches!(data[pos + 1], 0xe0..=0xef | 0xfe) {
+        let len = u16::from_be_bytes([data[pos + 2], data[pos + 3]]) as usize;
+        let end = pos + 2 + len;
+        if len < 2 || end > data.len() {
+            return Err("Truncated JPEG segment.".to_string());
+        }
+        let segment = &data[pos..end];
+        let payload = &segment[4..];
+        let marker = data[pos + 1];
+        if marker == 0xe1 && payload.starts_with(JPEG_XMP_SIGNATURE) {
+            // drop the existing XMP packet.
+        } else {
+            segments.push(segment);
+            if marker == 0xe0 || (marker == 0xe1 && payload.starts_with(b"Exif\0")) {
+                insert_at = segments.len();
+            }
+        }
+        pos = end;
+    }
+
+    let len = 2 + JPEG_XMP_SIGNATURE.len() + packet.len();
+    if len > (u16::MAX as usize) {
+        return Err("XMP packet is too large for a JPEG segment.".to_string());
+    }
+    let mut xmp_segment = vec![0xff, 0xe1];
+    xmp_segment.extend_from_slice(&(len as u16).to_be_bytes());
+    xmp_segment.extend_from_slice(JPEG_XMP_SIGNATURE);
+    xmp_segment.extend_from_slice(packet);
+
+    let mut output = Vec::with_capacity(data.len() + xmp_segment.len());
+    output.extend_from_slice(&data[..2]);
+    for (i, segment) in segments.iter().enumerate() {
+        if i == insert_at {
+            output.extend_from_slice(&xmp_segment);
+        }
+        output.extend_from_slice(segment);
+    }
+    if insert_at == segments.len() {
+        output.extend_from_slice(&xmp_segment);
+    }
+    output.extend_from_slice(&data[pos..]);
+
+    std::fs::write(path, output).map_err(|e| e.to_string())
+}
+
+/// Appends the XMP packet and a copy of IFD0 with an XMP tag to the end of a TIFF, then points the header at the new IFD0.
+/// The image data is left untouched, so this is safe for very large files.
+fn embed_tiff(path: &Path, packet: &[u8]) -> Result<(), String> {
+    use std::io::Read;
+
+    let mut file = std::fs::File
+        ::options()
+        .read(true)
+        .write(true)
+        .open(path)
+        .map_err(|e| e.to_string())?;
+
+    let mut header = [0u8; 8];
+    file.read_exact(&mut header).map_err(|e| e.to_string())?;
+    let little_endian = match &header[..2] {
+        b"II" => true,
+        b"MM" => false,
+        _ => {
+            return Err("Not a TIFF file.".to_string());
+        }
+    };
+    let u16_from = |b: [u8; 2]| if little_endian { u16::from_le_bytes(b) } else { u16::from_be_bytes(b) };
+    let u32_from = |b: [u8; 4]| if little_endian { u32::from_le_bytes(b) } else { u32::from_be_bytes(b) };
+    let u16_to = |v: u16| if little_endian { v.to_le_bytes() } else { v.to_be_bytes() };
+    let u32_to = |v: u32| if little_endian { v.to_le_bytes() } else { v.to_be_bytes() };
+
+    if u16_from([header[2], header[3]]) != 42 {
+        return Err("Unsupported TIFF variant (BigTIFF is not supported).".to_string());
+    }
+    let ifd_offset = u32_from([header[4], header[5], header[6], header[7]]);
+
+    // read IFD0's entries and next-IFD pointer.
+    file.seek(SeekFrom::Start(ifd_offset as u64)).map_err(|e| e.to_string())?;
+    let mut count = [0u8; 2];
+    file.read_exact(&mut count).map_err(|e| e.to_string())?;
+    let count = u16_from(count) as usize;
+    let mut entries = vec![0u8; count * 12];
+    file.read_exact(&mut entries).map_err(|e| e.to_string())?;
+    let mut next_ifd = [0u8; 4];
+    file.read_exact(&mut next_ifd).map_err(|e| e.to_string())?;
+
+    let mut entries = entries
+        .chunks(12)
+        .map(|entry| entry.to_vec())
+        .filter(|entry| u16_from([entry[0], entry[1]]) != TIFF_XMP_TAG)
+        .collect::<Vec<_>>();
+
+    // TIFF offsets must be word-aligned and fit in 32 bits.
+    let mut end = file.seek(SeekFrom::End(0)).map_err(|e| e.to_string())?;
+    if end % 2 == 1 {
+        file.write_all(&[0]).map_err(|e| e.to_string())?;
+        end += 1;
+    }
+    let xmp_offset = end;
+    let mut new_ifd_offset = xmp_offset + (packet.len() as u64);
+    new_ifd_offset += new_ifd_offset % 2;
+    if new_ifd_offset + 2 + ((entries.len() as u64) + 1) * 12 + 4 > (u32::MAX as u64) {
+        return Err("TIFF file is too large to embed XMP.".to_string());
+    }
+
+    let mut xmp_entry = vec![];
+    xmp_entry.extend_from_slice(&u16_to(TIFF_XMP_TAG));
+    // type 7: UNDEFINED (bytes).
+    xmp_entry.extend_from_slice(&u16_to(7));
+    xmp_entry.extend_from_slice(&u32_to(packet.len() as u32));
+    xmp_entry.extend_from_slice(&u32_to(xmp_offset as u32));
+    entries.push(xmp_entry);
+    entries.sort_by_key(|entry| u16_from([entry[0], entry[1]]));
+
+    let mut appended = packet.to_vec();
+    if packet.len() % 2 == 1 {
+        appended.push(0);
+    }
+    appended.extend_from_slice(&u16_to(entries.len() as u16));
+    for entry in &entries {
+        appended.extend_from_slice(entry);
+    }
+    appended.extend_from_slice(&next_ifd);
+    file.write_all(&appended).map_err(|e| e.to_string())?;
+
+    file.seek(SeekFrom::Start(4)).map_err(|e| e.to_string())?;
+    file.write_all(&u32_to(new_ifd_offset as u32)).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn escapes_xmp_values() {
+        let packet = xmp_packet(
+            &(XmpFields {
+                title: "Arizona Sun <Extra> & More",
+                date: Some("1944-08-06".to_string()),
+                rights: None,
+            })
+        );
+        assert!(packet.contains("Arizona Sun &lt;Extra&gt; &amp; More"));
+        assert!(packet.contains("<photoshop:DateCreated>1944-08-06</photoshop:DateCreated>"));
+        assert!(!packet.contains("dc:rights"));
+    }
+}